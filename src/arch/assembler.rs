@@ -0,0 +1,920 @@
+/*
+ * assembler.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* Assembler for the GNU assembler syntax. Every statement is assembled to
+ * one or more 32-bit instructions, so label addresses are known after a
+ * first pass over the source. Directives that do not emit anything are
+ * ignored, data directives are not supported. */
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use super::decoder::{Instruction, FpRoundingMode, DecodeError};
+use super::disassembler::csr_number;
+use super::encoder::EncodeError;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum AsmErrorKind {
+    UnknownMnemonic(String),
+    UnknownDirective(String),
+    InvalidRegister(String),
+    InvalidOperand(String),
+    OperandCount {expected: usize, found: usize},
+    UndefinedSymbol(String),
+    DuplicateLabel(String),
+    OutOfRange(i64),
+    Encode(EncodeError),
+    Rv64Only,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct AsmError {
+    pub line : usize,
+    pub kind : AsmErrorKind,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match self.kind {
+            AsmErrorKind::UnknownMnemonic(ref m) => write!(f, "unknown instruction `{}`", m),
+            AsmErrorKind::UnknownDirective(ref d) => write!(f, "unsupported directive `{}`", d),
+            AsmErrorKind::InvalidRegister(ref r) => write!(f, "invalid register `{}`", r),
+            AsmErrorKind::InvalidOperand(ref o) => write!(f, "invalid operand `{}`", o),
+            AsmErrorKind::OperandCount {expected, found} =>
+                write!(f, "expected {} operand(s), found {}", expected, found),
+            AsmErrorKind::UndefinedSymbol(ref s) => write!(f, "undefined symbol `{}`", s),
+            AsmErrorKind::DuplicateLabel(ref l) => write!(f, "label `{}` is already defined", l),
+            AsmErrorKind::OutOfRange(value) => write!(f, "value {} out of range", value),
+            AsmErrorKind::Encode(ref e) => write!(f, "{}", e),
+            AsmErrorKind::Rv64Only => write!(f, "instruction is only valid in RV64"),
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+const ABI_NAMES : [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+    "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+    "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+const FP_ABI_NAMES : [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7",
+    "fs0", "fs1", "fa0", "fa1", "fa2", "fa3", "fa4", "fa5",
+    "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7",
+    "fs8", "fs9", "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+/* directives that do not change the layout of the code */
+const IGNORED_DIRECTIVES : [&str; 8] = [
+    ".text", ".globl", ".global", ".section", ".type", ".size", ".option", ".file",
+];
+
+fn parse_reg(name: &str, prefix: char, abi: &[&str; 32]) -> Option<u8> {
+    if let Some(pos) = abi.iter().position(|n| *n == name) {
+        return Some(pos as u8);
+    }
+    if name.starts_with(prefix) {
+        if let Ok(n) = name[1..].parse::<u8>() {
+            if n < 32 {
+                return Some(n);
+            }
+        }
+    }
+    None
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = if let Some(rest) = text.strip_prefix('-') {
+        (true, rest)
+    } else {
+        (false, text)
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()? as i64
+    } else if let Some(bin) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        u64::from_str_radix(bin, 2).ok()? as i64
+    } else {
+        digits.parse::<u64>().ok()? as i64
+    };
+    Some(if negative { value.wrapping_neg() } else { value })
+}
+
+fn is_symbol(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => (),
+        _ => return false
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$')
+}
+
+/* the argument of %hi(...) and friends */
+fn function<'a>(expr: &'a str, name: &str) -> Option<&'a str> {
+    if expr.starts_with(name) && expr.ends_with(')') {
+        let rest = expr[name.len()..].trim_start();
+        if let Some(inner) = rest.strip_prefix('(') {
+            return Some(&inner[..inner.len() - 1]);
+        }
+    }
+    None
+}
+
+fn strip_comment(line: &str) -> &str {
+    let end = [line.find('#'), line.find("//")].iter()
+        .filter_map(|p| *p)
+        .min()
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+fn split_operands(text: &str) -> Vec<&str> {
+    if text.trim().is_empty() {
+        Vec::new()
+    } else {
+        text.split(',').map(|op| op.trim()).collect()
+    }
+}
+
+fn sign_extend12(value: i64) -> i64 {
+    ((value & 0x0FFF) ^ 0x0800) - 0x0800
+}
+
+/* splits a pc-relative offset into the parts for AUIPC and a 12-bit
+ * immediate, the upper part is already shifted */
+fn split_pcrel(offset: i64) -> Result<(i32, i16), AsmErrorKind> {
+    if offset < i32::MIN as i64 || offset > i32::MAX as i64 {
+        return Err(AsmErrorKind::OutOfRange(offset));
+    }
+    let lo = sign_extend12(offset);
+    let hi = ((offset - lo) as u64 as u32) as i32;
+    Ok((hi, lo as i16))
+}
+
+/* the shortest sequence of LUI, ADDI(W) and SLLI that loads value */
+fn load_immediate(rd: u8, value: i64, rv64: bool, out: &mut Vec<Instruction>) {
+    if value >= i32::MIN as i64 && value <= i32::MAX as i64 {
+        let lo = sign_extend12(value);
+        let hi = ((value - lo) as u64 as u32) as i32;
+        if hi != 0 {
+            out.push(Instruction::LUI {rd: rd, imm: hi});
+            if lo != 0 && rv64 {
+                out.push(Instruction::ADDIW {rd: rd, rs1: rd, imm: lo as i16});
+            } else if lo != 0 {
+                out.push(Instruction::ADDI {rd: rd, rs1: rd, imm: lo as i16});
+            }
+        } else {
+            out.push(Instruction::ADDI {rd: rd, rs1: 0, imm: lo as i16});
+        }
+        return;
+    }
+
+    let lo = sign_extend12(value);
+    let hi = (((value as u64).wrapping_add(0x800) >> 12) << 12) as i64 >> 12;
+    let shift = hi.trailing_zeros();
+    load_immediate(rd, hi >> shift, rv64, out);
+    out.push(Instruction::SLLI {rd: rd, rs1: rd, shamt: (shift + 12) as u8});
+    if lo != 0 {
+        out.push(Instruction::ADDI {rd: rd, rs1: rd, imm: lo as i16});
+    }
+}
+
+struct Statement<'a> {
+    line     : usize,
+    mnemonic : String,
+    operands : Vec<&'a str>,
+    addr     : u64,
+}
+
+/* the operands of a single statement */
+struct Operands<'a> {
+    ops    : &'a [&'a str],
+    labels : &'a HashMap<String, u64>,
+    pc     : u64,
+}
+
+impl<'a> Operands<'a> {
+    fn count(&self, n: usize) -> Result<(), AsmErrorKind> {
+        if self.ops.len() == n {
+            Ok(())
+        } else {
+            Err(AsmErrorKind::OperandCount {expected: n, found: self.ops.len()})
+        }
+    }
+
+    /* the rounding mode is an optional last operand */
+    fn count_rm(&self, n: usize) -> Result<(), AsmErrorKind> {
+        if self.ops.len() == n + 1 {
+            Ok(())
+        } else {
+            self.count(n)
+        }
+    }
+
+    fn get(&self, i: usize) -> Result<&'a str, AsmErrorKind> {
+        self.ops.get(i).cloned()
+            .ok_or(AsmErrorKind::OperandCount {expected: i + 1, found: self.ops.len()})
+    }
+
+    fn x(&self, i: usize) -> Result<u8, AsmErrorKind> {
+        let op = self.get(i)?;
+        match op {
+            "fp" => Ok(8),
+            _ => parse_reg(op, 'x', &ABI_NAMES).ok_or_else(|| AsmErrorKind::InvalidRegister(op.to_string()))
+        }
+    }
+
+    fn f(&self, i: usize) -> Result<u8, AsmErrorKind> {
+        let op = self.get(i)?;
+        parse_reg(op, 'f', &FP_ABI_NAMES).ok_or_else(|| AsmErrorKind::InvalidRegister(op.to_string()))
+    }
+
+    fn eval(&self, expr: &str) -> Result<i64, AsmErrorKind> {
+        let expr = expr.trim();
+        if let Some(inner) = function(expr, "%hi") {
+            let value = self.eval(inner)?;
+            return Ok((value.wrapping_add(0x800) >> 12) & 0xFFFFF);
+        }
+        if let Some(inner) = function(expr, "%lo") {
+            return Ok(sign_extend12(self.eval(inner)?));
+        }
+
+        /* a sum of numbers and symbols */
+        let mut value: i64 = 0;
+        let mut start = 0;
+        let bytes = expr.as_bytes();
+        for i in 0..=bytes.len() {
+            if i == bytes.len() || (i > start && (bytes[i] == b'+' || bytes[i] == b'-')) {
+                value = value.wrapping_add(self.term(&expr[start..i])?);
+                start = i;
+                if i < bytes.len() && bytes[i] == b'+' {
+                    start += 1;
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    fn term(&self, term: &str) -> Result<i64, AsmErrorKind> {
+        let term = term.trim();
+        if let Some(value) = parse_number(term) {
+            return Ok(value);
+        }
+        let (negative, name) = match term.strip_prefix('-') {
+            Some(name) => (true, name.trim()),
+            None => (false, term)
+        };
+        let value = if name == "." {
+            self.pc as i64
+        } else if is_symbol(name) {
+            *self.labels.get(name).ok_or_else(|| AsmErrorKind::UndefinedSymbol(name.to_string()))? as i64
+        } else {
+            return Err(AsmErrorKind::InvalidOperand(term.to_string()));
+        };
+        Ok(if negative { value.wrapping_neg() } else { value })
+    }
+
+    fn value(&self, i: usize) -> Result<i64, AsmErrorKind> {
+        self.eval(self.get(i)?)
+    }
+
+    fn range(&self, i: usize, min: i64, max: i64) -> Result<i64, AsmErrorKind> {
+        let value = self.value(i)?;
+        if value < min || value > max {
+            Err(AsmErrorKind::OutOfRange(value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    fn imm12(&self, i: usize) -> Result<i16, AsmErrorKind> {
+        Ok(self.range(i, -2048, 2047)? as i16)
+    }
+
+    fn shamt(&self, i: usize) -> Result<u8, AsmErrorKind> {
+        Ok(self.range(i, 0, 63)? as u8)
+    }
+
+    fn uimm5(&self, i: usize) -> Result<u8, AsmErrorKind> {
+        Ok(self.range(i, 0, 31)? as u8)
+    }
+
+    /* LUI and AUIPC take the upper 20 bits */
+    fn upper(&self, i: usize) -> Result<i32, AsmErrorKind> {
+        Ok(((self.range(i, -0x80000, 0xFFFFF)? as u32) << 12) as i32)
+    }
+
+    /* offset(register) */
+    fn mem(&self, i: usize) -> Result<(i16, u8), AsmErrorKind> {
+        let op = self.get(i)?;
+        let invalid = || AsmErrorKind::InvalidOperand(op.to_string());
+        let open = op.rfind('(').ok_or_else(invalid)?;
+        if !op.ends_with(')') {
+            return Err(invalid());
+        }
+        let reg = op[open + 1..op.len() - 1].trim();
+        let reg = parse_reg(reg, 'x', &ABI_NAMES).ok_or_else(|| AsmErrorKind::InvalidRegister(reg.to_string()))?;
+        let offset = op[..open].trim();
+        if offset.is_empty() {
+            return Ok((0, reg));
+        }
+        let value = self.eval(offset)?;
+        if !(-2048..=2047).contains(&value) {
+            return Err(AsmErrorKind::OutOfRange(value));
+        }
+        Ok((value as i16, reg))
+    }
+
+    /* (register) of the atomic instructions */
+    fn addr(&self, i: usize) -> Result<u8, AsmErrorKind> {
+        match self.mem(i)? {
+            (0, reg) => Ok(reg),
+            _ => Err(AsmErrorKind::InvalidOperand(self.get(i)?.to_string()))
+        }
+    }
+
+    /* pc-relative offset of a branch or jump target */
+    fn offset(&self, i: usize) -> Result<i64, AsmErrorKind> {
+        Ok(self.value(i)?.wrapping_sub(self.pc as i64))
+    }
+
+    fn branch(&self, i: usize) -> Result<i16, AsmErrorKind> {
+        let offset = self.offset(i)?;
+        if offset < i16::MIN as i64 || offset > i16::MAX as i64 {
+            return Err(AsmErrorKind::OutOfRange(offset));
+        }
+        Ok(offset as i16)
+    }
+
+    fn jump(&self, i: usize) -> Result<i32, AsmErrorKind> {
+        let offset = self.offset(i)?;
+        if offset < i32::MIN as i64 || offset > i32::MAX as i64 {
+            return Err(AsmErrorKind::OutOfRange(offset));
+        }
+        Ok(offset as i32)
+    }
+
+    fn csr(&self, i: usize) -> Result<u16, AsmErrorKind> {
+        let op = self.get(i)?;
+        match csr_number(op) {
+            Some(number) => Ok(number),
+            None => Ok(self.range(i, 0, 0x0FFF)? as u16)
+        }
+    }
+
+    fn rm(&self, i: usize) -> Result<FpRoundingMode, AsmErrorKind> {
+        if i >= self.ops.len() {
+            return Ok(FpRoundingMode::DynamicRounding);
+        }
+        match self.ops[i] {
+            "rne" => Ok(FpRoundingMode::RoundToNearest),
+            "rtz" => Ok(FpRoundingMode::RoundTowardsZero),
+            "rdn" => Ok(FpRoundingMode::RoundDown),
+            "rup" => Ok(FpRoundingMode::RoundUp),
+            "rmm" => Ok(FpRoundingMode::RoundToNearestTieMaxMagnitude),
+            "dyn" => Ok(FpRoundingMode::DynamicRounding),
+            op => Err(AsmErrorKind::InvalidOperand(op.to_string()))
+        }
+    }
+
+    /* predecessor or successor set of FENCE */
+    fn fence_set(&self, i: usize) -> Result<u8, AsmErrorKind> {
+        let op = self.get(i)?;
+        if op == "0" {
+            return Ok(0);
+        }
+        let mut set = 0;
+        for c in op.chars() {
+            set |= match c {
+                'i' => 8,
+                'o' => 4,
+                'r' => 2,
+                'w' => 1,
+                _ => return Err(AsmErrorKind::InvalidOperand(op.to_string()))
+            };
+        }
+        Ok(set)
+    }
+}
+
+/* assembles source code that is placed at base */
+pub struct Assembler {
+    base : u64,
+    rv64 : bool,
+}
+
+impl Assembler {
+    pub fn new(base: u64, rv64: bool) -> Assembler {
+        Assembler {base: base, rv64: rv64}
+    }
+
+    pub fn assemble(&self, source: &str) -> Result<Vec<Instruction>, AsmError> {
+        let mut labels = HashMap::new();
+        let mut statements = Vec::new();
+        let mut addr = self.base;
+
+        for (i, line) in source.lines().enumerate() {
+            let lineno = i + 1;
+            let error = |kind| AsmError {line: lineno, kind: kind};
+            let mut text = strip_comment(line).trim();
+
+            while let Some(pos) = text.find(':') {
+                let label = text[..pos].trim();
+                if !is_symbol(label) {
+                    break;
+                }
+                if labels.insert(label.to_string(), addr).is_some() {
+                    return Err(error(AsmErrorKind::DuplicateLabel(label.to_string())));
+                }
+                text = text[pos + 1..].trim();
+            }
+            if text.is_empty() {
+                continue;
+            }
+
+            let (mnemonic, rest) = match text.find(char::is_whitespace) {
+                Some(pos) => (&text[..pos], &text[pos..]),
+                None => (text, "")
+            };
+            let mnemonic = mnemonic.to_lowercase();
+            if mnemonic.starts_with('.') {
+                if IGNORED_DIRECTIVES.contains(&mnemonic.as_str()) {
+                    continue;
+                }
+                return Err(error(AsmErrorKind::UnknownDirective(mnemonic)));
+            }
+
+            let statement = Statement {line: lineno, mnemonic: mnemonic, operands: split_operands(rest), addr: addr};
+            addr += 4 * self.size(&statement).map_err(error)? as u64;
+            statements.push(statement);
+        }
+
+        let mut program = Vec::new();
+        for statement in &statements {
+            let error = |kind| AsmError {line: statement.line, kind: kind};
+            let ops = Operands {ops: &statement.operands, labels: &labels, pc: statement.addr};
+            for instr in self.expand(&statement.mnemonic, &ops).map_err(error)? {
+                let word = instr.encode32().map_err(|e| error(AsmErrorKind::Encode(e)))?;
+                if let Err(DecodeError::Rv64Only {..}) = Instruction::decode32(word, self.rv64) {
+                    return Err(error(AsmErrorKind::Rv64Only));
+                }
+                program.push(instr);
+            }
+        }
+        Ok(program)
+    }
+
+    /* number of instructions a statement expands to */
+    fn size(&self, statement: &Statement) -> Result<usize, AsmErrorKind> {
+        match statement.mnemonic.as_str() {
+            "li" => {
+                let no_labels = HashMap::new();
+                let ops = Operands {ops: &statement.operands, labels: &no_labels, pc: statement.addr};
+                Ok(self.expand("li", &ops)?.len())
+            },
+            "la" | "lla" | "call" | "tail" => Ok(2),
+            _ => Ok(1)
+        }
+    }
+
+    fn expand(&self, mnemonic: &str, ops: &Operands) -> Result<Vec<Instruction>, AsmErrorKind> {
+        let instr = match mnemonic {
+            "nop" => { ops.count(0)?; Instruction::ADDI {rd: 0, rs1: 0, imm: 0} },
+            "li" => {
+                ops.count(2)?;
+                let value = if self.rv64 {
+                    ops.value(1)?
+                } else {
+                    ops.range(1, i32::MIN as i64, u32::MAX as i64)? as i32 as i64
+                };
+                let mut seq = Vec::new();
+                load_immediate(ops.x(0)?, value, self.rv64, &mut seq);
+                return Ok(seq);
+            },
+            "la" | "lla" => {
+                ops.count(2)?;
+                let rd = ops.x(0)?;
+                let (hi, lo) = split_pcrel(ops.offset(1)?)?;
+                return Ok(vec![Instruction::AUIPC {rd: rd, imm: hi},
+                               Instruction::ADDI {rd: rd, rs1: rd, imm: lo}]);
+            },
+            "call" | "tail" => {
+                ops.count(1)?;
+                /* call links to ra, tail uses t1 as scratch register */
+                let (rd, tmp) = if mnemonic == "call" { (1, 1) } else { (0, 6) };
+                let (hi, lo) = split_pcrel(ops.offset(0)?)?;
+                return Ok(vec![Instruction::AUIPC {rd: tmp, imm: hi},
+                               Instruction::JALR {rd: rd, rs1: tmp, imm: lo}]);
+            },
+            "mv" => { ops.count(2)?; Instruction::ADDI {rd: ops.x(0)?, rs1: ops.x(1)?, imm: 0} },
+            "not" => { ops.count(2)?; Instruction::XORI {rd: ops.x(0)?, rs1: ops.x(1)?, imm: -1} },
+            "neg" => { ops.count(2)?; Instruction::SUB {rd: ops.x(0)?, rs1: 0, rs2: ops.x(1)?} },
+            "negw" => { ops.count(2)?; Instruction::SUBW {rd: ops.x(0)?, rs1: 0, rs2: ops.x(1)?} },
+            "sext.w" => { ops.count(2)?; Instruction::ADDIW {rd: ops.x(0)?, rs1: ops.x(1)?, imm: 0} },
+            "seqz" => { ops.count(2)?; Instruction::SLTIU {rd: ops.x(0)?, rs1: ops.x(1)?, imm: 1} },
+            "snez" => { ops.count(2)?; Instruction::SLTU {rd: ops.x(0)?, rs1: 0, rs2: ops.x(1)?} },
+            "sltz" => { ops.count(2)?; Instruction::SLT {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: 0} },
+            "sgtz" => { ops.count(2)?; Instruction::SLT {rd: ops.x(0)?, rs1: 0, rs2: ops.x(1)?} },
+            "beqz" => { ops.count(2)?; Instruction::BEQ {rs1: ops.x(0)?, rs2: 0, imm: ops.branch(1)?} },
+            "bnez" => { ops.count(2)?; Instruction::BNE {rs1: ops.x(0)?, rs2: 0, imm: ops.branch(1)?} },
+            "blez" => { ops.count(2)?; Instruction::BGE {rs1: 0, rs2: ops.x(0)?, imm: ops.branch(1)?} },
+            "bgez" => { ops.count(2)?; Instruction::BGE {rs1: ops.x(0)?, rs2: 0, imm: ops.branch(1)?} },
+            "bltz" => { ops.count(2)?; Instruction::BLT {rs1: ops.x(0)?, rs2: 0, imm: ops.branch(1)?} },
+            "bgtz" => { ops.count(2)?; Instruction::BLT {rs1: 0, rs2: ops.x(0)?, imm: ops.branch(1)?} },
+            "bgt" => { ops.count(3)?; Instruction::BLT {rs1: ops.x(1)?, rs2: ops.x(0)?, imm: ops.branch(2)?} },
+            "ble" => { ops.count(3)?; Instruction::BGE {rs1: ops.x(1)?, rs2: ops.x(0)?, imm: ops.branch(2)?} },
+            "bgtu" => { ops.count(3)?; Instruction::BLTU {rs1: ops.x(1)?, rs2: ops.x(0)?, imm: ops.branch(2)?} },
+            "bleu" => { ops.count(3)?; Instruction::BGEU {rs1: ops.x(1)?, rs2: ops.x(0)?, imm: ops.branch(2)?} },
+            "j" => { ops.count(1)?; Instruction::JAL {rd: 0, imm: ops.jump(0)?} },
+            "jal" if ops.ops.len() == 1 => Instruction::JAL {rd: 1, imm: ops.jump(0)?},
+            "jal" => { ops.count(2)?; Instruction::JAL {rd: ops.x(0)?, imm: ops.jump(1)?} },
+            "jr" => { ops.count(1)?; Instruction::JALR {rd: 0, rs1: ops.x(0)?, imm: 0} },
+            "jalr" if ops.ops.len() == 1 => Instruction::JALR {rd: 1, rs1: ops.x(0)?, imm: 0},
+            "jalr" if ops.ops.len() == 3 => Instruction::JALR {rd: ops.x(0)?, rs1: ops.x(1)?, imm: ops.imm12(2)?},
+            "jalr" => {
+                ops.count(2)?;
+                let (imm, rs1) = ops.mem(1)?;
+                Instruction::JALR {rd: ops.x(0)?, rs1: rs1, imm: imm}
+            },
+            "ret" => { ops.count(0)?; Instruction::JALR {rd: 0, rs1: 1, imm: 0} },
+            "lui" => { ops.count(2)?; Instruction::LUI {rd: ops.x(0)?, imm: ops.upper(1)?} },
+            "auipc" => { ops.count(2)?; Instruction::AUIPC {rd: ops.x(0)?, imm: ops.upper(1)?} },
+            "fence" if ops.ops.is_empty() => Instruction::FENCE {succ: 0x0F, pred: 0x0F},
+            "fence" => { ops.count(2)?; Instruction::FENCE {pred: ops.fence_set(0)?, succ: ops.fence_set(1)?} },
+            "fence.i" => { ops.count(0)?; Instruction::FENCE_I },
+            "ecall" => { ops.count(0)?; Instruction::ECALL },
+            "ebreak" => { ops.count(0)?; Instruction::EBREAK },
+            "mret" => { ops.count(0)?; Instruction::MRET },
+            "sret" => { ops.count(0)?; Instruction::SRET },
+            "wfi" => { ops.count(0)?; Instruction::WFI },
+            "sfence.vma" if ops.ops.is_empty() => Instruction::SFENCE_VMA {rs1: 0, rs2: 0},
+            "sfence.vma" if ops.ops.len() == 1 => Instruction::SFENCE_VMA {rs1: ops.x(0)?, rs2: 0},
+            "sfence.vma" => { ops.count(2)?; Instruction::SFENCE_VMA {rs1: ops.x(0)?, rs2: ops.x(1)?} },
+            "csrr" => { ops.count(2)?; Instruction::CSRRS {rd: ops.x(0)?, rs1: 0, csr: ops.csr(1)?} },
+            "csrw" => { ops.count(2)?; Instruction::CSRRW {rd: 0, rs1: ops.x(1)?, csr: ops.csr(0)?} },
+            "csrs" => { ops.count(2)?; Instruction::CSRRS {rd: 0, rs1: ops.x(1)?, csr: ops.csr(0)?} },
+            "csrc" => { ops.count(2)?; Instruction::CSRRC {rd: 0, rs1: ops.x(1)?, csr: ops.csr(0)?} },
+            "csrwi" => { ops.count(2)?; Instruction::CSRRWI {rd: 0, zimm: ops.uimm5(1)?, csr: ops.csr(0)?} },
+            "csrsi" => { ops.count(2)?; Instruction::CSRRSI {rd: 0, zimm: ops.uimm5(1)?, csr: ops.csr(0)?} },
+            "csrci" => { ops.count(2)?; Instruction::CSRRCI {rd: 0, zimm: ops.uimm5(1)?, csr: ops.csr(0)?} },
+            "rdcycle" => { ops.count(1)?; Instruction::CSRRS {rd: ops.x(0)?, rs1: 0, csr: 0xC00} },
+            "rdtime" => { ops.count(1)?; Instruction::CSRRS {rd: ops.x(0)?, rs1: 0, csr: 0xC01} },
+            "rdinstret" => { ops.count(1)?; Instruction::CSRRS {rd: ops.x(0)?, rs1: 0, csr: 0xC02} },
+            "fmv.s" => { ops.count(2)?; Instruction::FSGNJ_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(1)?} },
+            "fabs.s" => { ops.count(2)?; Instruction::FSGNJX_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(1)?} },
+            "fneg.s" => { ops.count(2)?; Instruction::FSGNJN_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(1)?} },
+            "fmv.d" => { ops.count(2)?; Instruction::FSGNJ_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(1)?} },
+            "fabs.d" => { ops.count(2)?; Instruction::FSGNJX_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(1)?} },
+            "fneg.d" => { ops.count(2)?; Instruction::FSGNJN_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(1)?} },
+            _ => return self.expand_atomic(mnemonic, ops).map(|i| vec![i])
+        };
+        Ok(vec![instr])
+    }
+
+    /* strips the ordering suffixes of the atomic instructions */
+    fn expand_atomic(&self, mnemonic: &str, ops: &Operands) -> Result<Instruction, AsmErrorKind> {
+        let atomic = mnemonic.starts_with("amo") || mnemonic.starts_with("lr.") || mnemonic.starts_with("sc.");
+        let (base, aq, rl) = match mnemonic {
+            _ if !atomic => (mnemonic, false, false),
+            m if m.ends_with(".aqrl") => (&m[..m.len() - 5], true, true),
+            m if m.ends_with(".aq") => (&m[..m.len() - 3], true, false),
+            m if m.ends_with(".rl") => (&m[..m.len() - 3], false, true),
+            m => (m, false, false)
+        };
+        self.expand_base(base, aq, rl, ops)
+    }
+
+    fn expand_base(&self, mnemonic: &str, aq: bool, rl: bool, ops: &Operands) -> Result<Instruction, AsmErrorKind> {
+        let instr = match mnemonic {
+            "add" => { ops.count(3)?; Instruction::ADD {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "sub" => { ops.count(3)?; Instruction::SUB {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "sll" => { ops.count(3)?; Instruction::SLL {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "slt" => { ops.count(3)?; Instruction::SLT {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "sltu" => { ops.count(3)?; Instruction::SLTU {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "xor" => { ops.count(3)?; Instruction::XOR {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "srl" => { ops.count(3)?; Instruction::SRL {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "sra" => { ops.count(3)?; Instruction::SRA {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "or" => { ops.count(3)?; Instruction::OR {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "and" => { ops.count(3)?; Instruction::AND {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "addw" => { ops.count(3)?; Instruction::ADDW {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "subw" => { ops.count(3)?; Instruction::SUBW {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "sllw" => { ops.count(3)?; Instruction::SLLW {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "srlw" => { ops.count(3)?; Instruction::SRLW {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "sraw" => { ops.count(3)?; Instruction::SRAW {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "mul" => { ops.count(3)?; Instruction::MUL {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "mulh" => { ops.count(3)?; Instruction::MULH {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "mulhsu" => { ops.count(3)?; Instruction::MULHSU {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "mulhu" => { ops.count(3)?; Instruction::MULHU {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "div" => { ops.count(3)?; Instruction::DIV {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "divu" => { ops.count(3)?; Instruction::DIVU {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "rem" => { ops.count(3)?; Instruction::REM {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "remu" => { ops.count(3)?; Instruction::REMU {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "mulw" => { ops.count(3)?; Instruction::MULW {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "divw" => { ops.count(3)?; Instruction::DIVW {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "divuw" => { ops.count(3)?; Instruction::DIVUW {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "remw" => { ops.count(3)?; Instruction::REMW {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "remuw" => { ops.count(3)?; Instruction::REMUW {rd: ops.x(0)?, rs1: ops.x(1)?, rs2: ops.x(2)?} },
+            "addi" => { ops.count(3)?; Instruction::ADDI {rd: ops.x(0)?, rs1: ops.x(1)?, imm: ops.imm12(2)?} },
+            "slti" => { ops.count(3)?; Instruction::SLTI {rd: ops.x(0)?, rs1: ops.x(1)?, imm: ops.imm12(2)?} },
+            "sltiu" => { ops.count(3)?; Instruction::SLTIU {rd: ops.x(0)?, rs1: ops.x(1)?, imm: ops.imm12(2)?} },
+            "xori" => { ops.count(3)?; Instruction::XORI {rd: ops.x(0)?, rs1: ops.x(1)?, imm: ops.imm12(2)?} },
+            "ori" => { ops.count(3)?; Instruction::ORI {rd: ops.x(0)?, rs1: ops.x(1)?, imm: ops.imm12(2)?} },
+            "andi" => { ops.count(3)?; Instruction::ANDI {rd: ops.x(0)?, rs1: ops.x(1)?, imm: ops.imm12(2)?} },
+            "addiw" => { ops.count(3)?; Instruction::ADDIW {rd: ops.x(0)?, rs1: ops.x(1)?, imm: ops.imm12(2)?} },
+            "slli" => { ops.count(3)?; Instruction::SLLI {rd: ops.x(0)?, rs1: ops.x(1)?, shamt: ops.shamt(2)?} },
+            "srli" => { ops.count(3)?; Instruction::SRLI {rd: ops.x(0)?, rs1: ops.x(1)?, shamt: ops.shamt(2)?} },
+            "srai" => { ops.count(3)?; Instruction::SRAI {rd: ops.x(0)?, rs1: ops.x(1)?, shamt: ops.shamt(2)?} },
+            "slliw" => { ops.count(3)?; Instruction::SLLIW {rd: ops.x(0)?, rs1: ops.x(1)?, shamt: ops.shamt(2)?} },
+            "srliw" => { ops.count(3)?; Instruction::SRLIW {rd: ops.x(0)?, rs1: ops.x(1)?, shamt: ops.shamt(2)?} },
+            "sraiw" => { ops.count(3)?; Instruction::SRAIW {rd: ops.x(0)?, rs1: ops.x(1)?, shamt: ops.shamt(2)?} },
+            "lb" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::LB {rd: ops.x(0)?, rs1: rs1, imm: imm} },
+            "lh" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::LH {rd: ops.x(0)?, rs1: rs1, imm: imm} },
+            "lw" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::LW {rd: ops.x(0)?, rs1: rs1, imm: imm} },
+            "lbu" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::LBU {rd: ops.x(0)?, rs1: rs1, imm: imm} },
+            "lhu" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::LHU {rd: ops.x(0)?, rs1: rs1, imm: imm} },
+            "lwu" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::LWU {rd: ops.x(0)?, rs1: rs1, imm: imm} },
+            "ld" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::LD {rd: ops.x(0)?, rs1: rs1, imm: imm} },
+            "sb" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::SB {rs1: rs1, rs2: ops.x(0)?, imm: imm} },
+            "sh" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::SH {rs1: rs1, rs2: ops.x(0)?, imm: imm} },
+            "sw" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::SW {rs1: rs1, rs2: ops.x(0)?, imm: imm} },
+            "sd" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::SD {rs1: rs1, rs2: ops.x(0)?, imm: imm} },
+            "beq" => { ops.count(3)?; Instruction::BEQ {rs1: ops.x(0)?, rs2: ops.x(1)?, imm: ops.branch(2)?} },
+            "bne" => { ops.count(3)?; Instruction::BNE {rs1: ops.x(0)?, rs2: ops.x(1)?, imm: ops.branch(2)?} },
+            "blt" => { ops.count(3)?; Instruction::BLT {rs1: ops.x(0)?, rs2: ops.x(1)?, imm: ops.branch(2)?} },
+            "bge" => { ops.count(3)?; Instruction::BGE {rs1: ops.x(0)?, rs2: ops.x(1)?, imm: ops.branch(2)?} },
+            "bltu" => { ops.count(3)?; Instruction::BLTU {rs1: ops.x(0)?, rs2: ops.x(1)?, imm: ops.branch(2)?} },
+            "bgeu" => { ops.count(3)?; Instruction::BGEU {rs1: ops.x(0)?, rs2: ops.x(1)?, imm: ops.branch(2)?} },
+            "csrrw" => { ops.count(3)?; Instruction::CSRRW {rd: ops.x(0)?, csr: ops.csr(1)?, rs1: ops.x(2)?} },
+            "csrrs" => { ops.count(3)?; Instruction::CSRRS {rd: ops.x(0)?, csr: ops.csr(1)?, rs1: ops.x(2)?} },
+            "csrrc" => { ops.count(3)?; Instruction::CSRRC {rd: ops.x(0)?, csr: ops.csr(1)?, rs1: ops.x(2)?} },
+            "csrrwi" => { ops.count(3)?; Instruction::CSRRWI {rd: ops.x(0)?, csr: ops.csr(1)?, zimm: ops.uimm5(2)?} },
+            "csrrsi" => { ops.count(3)?; Instruction::CSRRSI {rd: ops.x(0)?, csr: ops.csr(1)?, zimm: ops.uimm5(2)?} },
+            "csrrci" => { ops.count(3)?; Instruction::CSRRCI {rd: ops.x(0)?, csr: ops.csr(1)?, zimm: ops.uimm5(2)?} },
+            "lr.w" => { ops.count(2)?; Instruction::LR_W {rd: ops.x(0)?, rs1: ops.addr(1)?, aq: aq, rl: rl} },
+            "sc.w" => { ops.count(3)?; Instruction::SC_W {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amoswap.w" => { ops.count(3)?; Instruction::AMOSWAP_W {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amoadd.w" => { ops.count(3)?; Instruction::AMOADD_W {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amoxor.w" => { ops.count(3)?; Instruction::AMOXOR_W {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amoand.w" => { ops.count(3)?; Instruction::AMOAND_W {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amoor.w" => { ops.count(3)?; Instruction::AMOOR_W {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amomin.w" => { ops.count(3)?; Instruction::AMOMIN_W {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amomax.w" => { ops.count(3)?; Instruction::AMOMAX_W {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amominu.w" => { ops.count(3)?; Instruction::AMOMINU_W {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amomaxu.w" => { ops.count(3)?; Instruction::AMOMAXU_W {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "lr.d" => { ops.count(2)?; Instruction::LR_D {rd: ops.x(0)?, rs1: ops.addr(1)?, aq: aq, rl: rl} },
+            "sc.d" => { ops.count(3)?; Instruction::SC_D {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amoswap.d" => { ops.count(3)?; Instruction::AMOSWAP_D {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amoadd.d" => { ops.count(3)?; Instruction::AMOADD_D {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amoxor.d" => { ops.count(3)?; Instruction::AMOXOR_D {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amoand.d" => { ops.count(3)?; Instruction::AMOAND_D {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amoor.d" => { ops.count(3)?; Instruction::AMOOR_D {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amomin.d" => { ops.count(3)?; Instruction::AMOMIN_D {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amomax.d" => { ops.count(3)?; Instruction::AMOMAX_D {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amominu.d" => { ops.count(3)?; Instruction::AMOMINU_D {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "amomaxu.d" => { ops.count(3)?; Instruction::AMOMAXU_D {rd: ops.x(0)?, rs2: ops.x(1)?, rs1: ops.addr(2)?, aq: aq, rl: rl} },
+            "flw" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::FLW {rd: ops.f(0)?, rs1: rs1, imm: imm} },
+            "fsw" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::FSW {rs1: rs1, rs2: ops.f(0)?, imm: imm} },
+            "fmadd.s" => { ops.count_rm(4)?; Instruction::FMADD_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rs3: ops.f(3)?, rm: ops.rm(4)?} },
+            "fmsub.s" => { ops.count_rm(4)?; Instruction::FMSUB_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rs3: ops.f(3)?, rm: ops.rm(4)?} },
+            "fnmsub.s" => { ops.count_rm(4)?; Instruction::FNMSUB_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rs3: ops.f(3)?, rm: ops.rm(4)?} },
+            "fnmadd.s" => { ops.count_rm(4)?; Instruction::FNMADD_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rs3: ops.f(3)?, rm: ops.rm(4)?} },
+            "fadd.s" => { ops.count_rm(3)?; Instruction::FADD_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rm: ops.rm(3)?} },
+            "fsub.s" => { ops.count_rm(3)?; Instruction::FSUB_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rm: ops.rm(3)?} },
+            "fmul.s" => { ops.count_rm(3)?; Instruction::FMUL_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rm: ops.rm(3)?} },
+            "fdiv.s" => { ops.count_rm(3)?; Instruction::FDIV_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rm: ops.rm(3)?} },
+            "fsqrt.s" => { ops.count_rm(2)?; Instruction::FSQRT_S {rd: ops.f(0)?, rs1: ops.f(1)?, rm: ops.rm(2)?} },
+            "fsgnj.s" => { ops.count(3)?; Instruction::FSGNJ_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "fsgnjn.s" => { ops.count(3)?; Instruction::FSGNJN_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "fsgnjx.s" => { ops.count(3)?; Instruction::FSGNJX_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "fmin.s" => { ops.count(3)?; Instruction::FMIN_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "fmax.s" => { ops.count(3)?; Instruction::FMAX_S {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "feq.s" => { ops.count(3)?; Instruction::FEQ_S {rd: ops.x(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "flt.s" => { ops.count(3)?; Instruction::FLT_S {rd: ops.x(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "fle.s" => { ops.count(3)?; Instruction::FLE_S {rd: ops.x(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "fcvt.w.s" => { ops.count_rm(2)?; Instruction::FCVT_W_S {rd: ops.x(0)?, rs1: ops.f(1)?, rm: ops.rm(2)?} },
+            "fcvt.s.w" => { ops.count_rm(2)?; Instruction::FCVT_S_W {rd: ops.f(0)?, rs1: ops.x(1)?, rm: ops.rm(2)?} },
+            "fcvt.wu.s" => { ops.count_rm(2)?; Instruction::FCVT_WU_S {rd: ops.x(0)?, rs1: ops.f(1)?, rm: ops.rm(2)?} },
+            "fcvt.s.wu" => { ops.count_rm(2)?; Instruction::FCVT_S_WU {rd: ops.f(0)?, rs1: ops.x(1)?, rm: ops.rm(2)?} },
+            "fcvt.l.s" => { ops.count_rm(2)?; Instruction::FCVT_L_S {rd: ops.x(0)?, rs1: ops.f(1)?, rm: ops.rm(2)?} },
+            "fcvt.s.l" => { ops.count_rm(2)?; Instruction::FCVT_S_L {rd: ops.f(0)?, rs1: ops.x(1)?, rm: ops.rm(2)?} },
+            "fcvt.lu.s" => { ops.count_rm(2)?; Instruction::FCVT_LU_S {rd: ops.x(0)?, rs1: ops.f(1)?, rm: ops.rm(2)?} },
+            "fcvt.s.lu" => { ops.count_rm(2)?; Instruction::FCVT_S_LU {rd: ops.f(0)?, rs1: ops.x(1)?, rm: ops.rm(2)?} },
+            "fmv.x.w" => { ops.count(2)?; Instruction::FMV_X_S {rd: ops.x(0)?, rs1: ops.f(1)?} },
+            "fmv.w.x" => { ops.count(2)?; Instruction::FMV_S_X {rd: ops.f(0)?, rs1: ops.x(1)?} },
+            "fclass.s" => { ops.count(2)?; Instruction::FCLASS_S {rd: ops.x(0)?, rs1: ops.f(1)?} },
+            "fld" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::FLD {rd: ops.f(0)?, rs1: rs1, imm: imm} },
+            "fsd" => { ops.count(2)?; let (imm, rs1) = ops.mem(1)?; Instruction::FSD {rs1: rs1, rs2: ops.f(0)?, imm: imm} },
+            "fmadd.d" => { ops.count_rm(4)?; Instruction::FMADD_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rs3: ops.f(3)?, rm: ops.rm(4)?} },
+            "fmsub.d" => { ops.count_rm(4)?; Instruction::FMSUB_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rs3: ops.f(3)?, rm: ops.rm(4)?} },
+            "fnmsub.d" => { ops.count_rm(4)?; Instruction::FNMSUB_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rs3: ops.f(3)?, rm: ops.rm(4)?} },
+            "fnmadd.d" => { ops.count_rm(4)?; Instruction::FNMADD_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rs3: ops.f(3)?, rm: ops.rm(4)?} },
+            "fadd.d" => { ops.count_rm(3)?; Instruction::FADD_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rm: ops.rm(3)?} },
+            "fsub.d" => { ops.count_rm(3)?; Instruction::FSUB_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rm: ops.rm(3)?} },
+            "fmul.d" => { ops.count_rm(3)?; Instruction::FMUL_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rm: ops.rm(3)?} },
+            "fdiv.d" => { ops.count_rm(3)?; Instruction::FDIV_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?, rm: ops.rm(3)?} },
+            "fsqrt.d" => { ops.count_rm(2)?; Instruction::FSQRT_D {rd: ops.f(0)?, rs1: ops.f(1)?, rm: ops.rm(2)?} },
+            "fsgnj.d" => { ops.count(3)?; Instruction::FSGNJ_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "fsgnjn.d" => { ops.count(3)?; Instruction::FSGNJN_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "fsgnjx.d" => { ops.count(3)?; Instruction::FSGNJX_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "fmin.d" => { ops.count(3)?; Instruction::FMIN_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "fmax.d" => { ops.count(3)?; Instruction::FMAX_D {rd: ops.f(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "feq.d" => { ops.count(3)?; Instruction::FEQ_D {rd: ops.x(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "flt.d" => { ops.count(3)?; Instruction::FLT_D {rd: ops.x(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "fle.d" => { ops.count(3)?; Instruction::FLE_D {rd: ops.x(0)?, rs1: ops.f(1)?, rs2: ops.f(2)?} },
+            "fcvt.w.d" => { ops.count_rm(2)?; Instruction::FCVT_W_D {rd: ops.x(0)?, rs1: ops.f(1)?, rm: ops.rm(2)?} },
+            "fcvt.d.w" => { ops.count_rm(2)?; Instruction::FCVT_D_W {rd: ops.f(0)?, rs1: ops.x(1)?, rm: ops.rm(2)?} },
+            "fcvt.wu.d" => { ops.count_rm(2)?; Instruction::FCVT_WU_D {rd: ops.x(0)?, rs1: ops.f(1)?, rm: ops.rm(2)?} },
+            "fcvt.d.wu" => { ops.count_rm(2)?; Instruction::FCVT_D_WU {rd: ops.f(0)?, rs1: ops.x(1)?, rm: ops.rm(2)?} },
+            "fcvt.l.d" => { ops.count_rm(2)?; Instruction::FCVT_L_D {rd: ops.x(0)?, rs1: ops.f(1)?, rm: ops.rm(2)?} },
+            "fcvt.d.l" => { ops.count_rm(2)?; Instruction::FCVT_D_L {rd: ops.f(0)?, rs1: ops.x(1)?, rm: ops.rm(2)?} },
+            "fcvt.lu.d" => { ops.count_rm(2)?; Instruction::FCVT_LU_D {rd: ops.x(0)?, rs1: ops.f(1)?, rm: ops.rm(2)?} },
+            "fcvt.d.lu" => { ops.count_rm(2)?; Instruction::FCVT_D_LU {rd: ops.f(0)?, rs1: ops.x(1)?, rm: ops.rm(2)?} },
+            "fmv.x.d" => { ops.count(2)?; Instruction::FMV_X_D {rd: ops.x(0)?, rs1: ops.f(1)?} },
+            "fmv.d.x" => { ops.count(2)?; Instruction::FMV_D_X {rd: ops.f(0)?, rs1: ops.x(1)?} },
+            "fclass.d" => { ops.count(2)?; Instruction::FCLASS_D {rd: ops.x(0)?, rs1: ops.f(1)?} },
+            "fcvt.s.d" => { ops.count_rm(2)?; Instruction::FCVT_S_D {rd: ops.f(0)?, rs1: ops.f(1)?, rm: ops.rm(2)?} },
+            "fcvt.d.s" => { ops.count_rm(2)?; Instruction::FCVT_D_S {rd: ops.f(0)?, rs1: ops.f(1)?, rm: ops.rm(2)?} },
+            _ => return Err(AsmErrorKind::UnknownMnemonic(mnemonic.to_string()))
+        };
+        Ok(instr)
+    }
+}
+
+/* assembles code that is placed at address 0 */
+pub fn assemble(source: &str, rv64: bool) -> Result<Vec<Instruction>, AsmError> {
+    Assembler::new(0, rv64).assemble(source)
+}
+
+/* assembles a test program and stores it at base, the labels are relative
+ * to the start of the program */
+#[cfg(test)]
+pub fn assemble_into<M: super::memory::Memory + ?Sized>(mem: &mut M, base: u64, source: &str, rv64: bool) {
+    for (i, instr) in assemble(source, rv64).unwrap().iter().enumerate() {
+        mem.store32(base + 4 * i as u64, instr.encode32().unwrap()).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{assemble, Assembler, AsmError, AsmErrorKind};
+    use super::super::decoder::{Instruction, FpRoundingMode};
+    use super::super::encoder::EncodeError;
+
+    fn one(source: &str) -> Instruction {
+        let mut program = assemble(source, true).unwrap();
+        assert_eq!(program.len(), 1);
+        program.remove(0)
+    }
+
+    #[test]
+    fn test_instructions() {
+        assert_eq!(one("addi x30, x20, -1036"), Instruction::ADDI{rd: 30, rs1: 20, imm: -1036});
+        assert_eq!(one("sw t0, 1951(s0)"), Instruction::SW{rs1: 8, rs2: 5, imm: 1951});
+        assert_eq!(one("lb a4,267(gp)"), Instruction::LB{rd: 14, rs1: 3, imm: 267});
+        assert_eq!(one("LW a0, (sp)"), Instruction::LW{rd: 10, rs1: 2, imm: 0});
+        assert_eq!(one("lui sp, 0x12345"), Instruction::LUI{rd: 2, imm: 0x12345000});
+        assert_eq!(one("slli a0, a0, 0x20"), Instruction::SLLI{rd: 10, rs1: 10, shamt: 32});
+        assert_eq!(one("mul a0, a0, a1"), Instruction::MUL{rd: 10, rs1: 10, rs2: 11});
+        assert_eq!(one("amoswap.w.aqrl t0, t1, (t2)"), Instruction::AMOSWAP_W{rd: 5, rs1: 7, rs2: 6, aq: true, rl: true});
+        assert_eq!(one("lr.d.aq a0, (a1)"), Instruction::LR_D{rd: 10, rs1: 11, aq: true, rl: false});
+        assert_eq!(one("fmadd.s ft1, ft2, ft3, ft4, rne"), Instruction::FMADD_S{rd: 1, rs1: 2, rs2: 3, rs3: 4, rm: FpRoundingMode::RoundToNearest});
+        assert_eq!(one("fadd.d f1, f2, f3"), Instruction::FADD_D{rd: 1, rs1: 2, rs2: 3, rm: FpRoundingMode::DynamicRounding});
+        assert_eq!(one("fcvt.w.s a0, fa0, rtz"), Instruction::FCVT_W_S{rd: 10, rs1: 10, rm: FpRoundingMode::RoundTowardsZero});
+        assert_eq!(one("fsw fs0, -4(sp)"), Instruction::FSW{rs1: 2, rs2: 8, imm: -4});
+        assert_eq!(one("fmv.x.w a0, fa0"), Instruction::FMV_X_S{rd: 10, rs1: 10});
+        assert_eq!(one("csrrw ra, mscratch, a0"), Instruction::CSRRW{rd: 1, rs1: 10, csr: 0x340});
+        assert_eq!(one("csrrsi zero, 0x7c0, 3"), Instruction::CSRRSI{rd: 0, zimm: 3, csr: 0x7C0});
+        assert_eq!(one("fence rw, w"), Instruction::FENCE{pred: 3, succ: 1});
+    }
+
+    #[test]
+    fn test_pseudo_instructions() {
+        assert_eq!(one("nop"), Instruction::ADDI{rd: 0, rs1: 0, imm: 0});
+        assert_eq!(one("mv a0, a1"), Instruction::ADDI{rd: 10, rs1: 11, imm: 0});
+        assert_eq!(one("ret"), Instruction::JALR{rd: 0, rs1: 1, imm: 0});
+        assert_eq!(one("jalr a5"), Instruction::JALR{rd: 1, rs1: 15, imm: 0});
+        assert_eq!(one("csrr a0, mcause"), Instruction::CSRRS{rd: 10, rs1: 0, csr: 0x342});
+        assert_eq!(one("csrw mtvec, t0"), Instruction::CSRRW{rd: 0, rs1: 5, csr: 0x305});
+        assert_eq!(one("fence"), Instruction::FENCE{pred: 0x0F, succ: 0x0F});
+        assert_eq!(one("bgt a0, a1, ."), Instruction::BLT{rs1: 11, rs2: 10, imm: 0});
+        assert_eq!(one("li a0, -1"), Instruction::ADDI{rd: 10, rs1: 0, imm: -1});
+        assert_eq!(one("li a0, 0x1000"), Instruction::LUI{rd: 10, imm: 0x1000});
+    }
+
+    #[test]
+    fn test_li() {
+        assert_eq!(assemble("li a0, 0x12345678", false).unwrap(), vec![
+            Instruction::LUI{rd: 10, imm: 0x12345000},
+            Instruction::ADDI{rd: 10, rs1: 10, imm: 0x678},
+        ]);
+        assert_eq!(assemble("li a0, 0xFFFFF800", false).unwrap(), vec![
+            Instruction::ADDI{rd: 10, rs1: 0, imm: -2048},
+        ]);
+        assert_eq!(assemble("li a0, 0x7FFFF800", true).unwrap(), vec![
+            Instruction::LUI{rd: 10, imm: i32::MIN},
+            Instruction::ADDIW{rd: 10, rs1: 10, imm: -2048},
+        ]);
+        assert_eq!(assemble("li t0, 0x80000000", true).unwrap(), vec![
+            Instruction::ADDI{rd: 5, rs1: 0, imm: 1},
+            Instruction::SLLI{rd: 5, rs1: 5, shamt: 31},
+        ]);
+        assert_eq!(assemble("li t0, 0x123456789", true).unwrap(), vec![
+            Instruction::LUI{rd: 5, imm: 0x92000},
+            Instruction::ADDIW{rd: 5, rs1: 5, imm: -1493},
+            Instruction::SLLI{rd: 5, rs1: 5, shamt: 13},
+            Instruction::ADDI{rd: 5, rs1: 5, imm: 0x789},
+        ]);
+        assert!(assemble("li a0, 0x100000000", false).is_err());
+    }
+
+    #[test]
+    fn test_labels() {
+        let source = "
+            # count down from 3
+            start:  li a0, 3
+            loop:   addi a0, a0, -1   // decrement
+                    bnez a0, loop
+                    j end
+                    call func
+                    tail start
+            end:    la a1, data
+            func:   ret
+            data:
+        ";
+        assert_eq!(Assembler::new(0x80000000, false).assemble(source).unwrap(), vec![
+            Instruction::ADDI{rd: 10, rs1: 0, imm: 3},
+            Instruction::ADDI{rd: 10, rs1: 10, imm: -1},
+            Instruction::BNE{rs1: 10, rs2: 0, imm: -4},
+            Instruction::JAL{rd: 0, imm: 20},
+            Instruction::AUIPC{rd: 1, imm: 0},
+            Instruction::JALR{rd: 1, rs1: 1, imm: 24},
+            Instruction::AUIPC{rd: 6, imm: 0},
+            Instruction::JALR{rd: 0, rs1: 6, imm: -24},
+            Instruction::AUIPC{rd: 11, imm: 0},
+            Instruction::ADDI{rd: 11, rs1: 11, imm: 12},
+            Instruction::JALR{rd: 0, rs1: 1, imm: 0},
+        ]);
+    }
+
+    #[test]
+    fn test_hi_lo() {
+        let source = "
+            lui a0, %hi(value)
+            addi a0, a0, %lo(value)
+            lw a1, %lo(value)(a0)
+            value:
+        ";
+        assert_eq!(Assembler::new(0x80000000, false).assemble(source).unwrap(), vec![
+            Instruction::LUI{rd: 10, imm: 0x80000000u32 as i32},
+            Instruction::ADDI{rd: 10, rs1: 10, imm: 12},
+            Instruction::LW{rd: 11, rs1: 10, imm: 12},
+        ]);
+
+        let source = "
+            .text
+            .globl _start
+            _start: lui a0, %hi(0x12345FFF)
+                    addi a0, a0, %lo(0x12345FFF)
+        ";
+        assert_eq!(assemble(source, false).unwrap(), vec![
+            Instruction::LUI{rd: 10, imm: 0x12346000},
+            Instruction::ADDI{rd: 10, rs1: 10, imm: -1},
+        ]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(assemble("foo a0", false), Err(AsmError{line: 1, kind: AsmErrorKind::UnknownMnemonic("foo".to_string())}));
+        assert_eq!(assemble("nop\nadd a0, a1", false), Err(AsmError{line: 2, kind: AsmErrorKind::OperandCount{expected: 3, found: 2}}));
+        assert_eq!(assemble("add a0, a1, x32", false), Err(AsmError{line: 1, kind: AsmErrorKind::InvalidRegister("x32".to_string())}));
+        assert_eq!(assemble("addi a0, a1, 2048", false), Err(AsmError{line: 1, kind: AsmErrorKind::OutOfRange(2048)}));
+        assert_eq!(assemble("j nowhere", false), Err(AsmError{line: 1, kind: AsmErrorKind::UndefinedSymbol("nowhere".to_string())}));
+        assert_eq!(assemble("a:\na:", false), Err(AsmError{line: 2, kind: AsmErrorKind::DuplicateLabel("a".to_string())}));
+        assert_eq!(assemble("beq a0, a1, 3", false), Err(AsmError{line: 1, kind: AsmErrorKind::Encode(EncodeError::MisalignedImmediate{imm: 3})}));
+        assert_eq!(assemble("ld a0, 0(a1)", false), Err(AsmError{line: 1, kind: AsmErrorKind::Rv64Only}));
+        assert_eq!(assemble(".word 0", false), Err(AsmError{line: 1, kind: AsmErrorKind::UnknownDirective(".word".to_string())}));
+    }
+}
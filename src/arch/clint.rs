@@ -0,0 +1,160 @@
+/*
+ * clint.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* The core-local interruptor in the SiFive layout. Each hart has a
+ * machine software interrupt pending bit msip, a 32-bit register of which
+ * only bit 0 is writable, so harts can interrupt each other, and a 64-bit
+ * timer compare register mtimecmp. The machine timer interrupt of a hart
+ * is pending while mtime is at least its mtimecmp. There is no real-time
+ * clock, the machine advances mtime by one for every instruction it
+ * executes. */
+
+use super::memory::{AccessFault, Memory};
+
+pub const CLINT_BASE : u64 = 0x0200_0000;
+pub const CLINT_SIZE : u64 = 0x1_0000;
+
+/* register offsets */
+const MSIP     : u64 = 0x0000;
+const MTIMECMP : u64 = 0x4000;
+const MTIME    : u64 = 0xBFF8;
+
+enum Register {
+    Msip(usize),
+    Mtimecmp(usize),
+    Mtime,
+}
+
+pub struct Clint {
+    msip     : Vec<bool>,
+    mtimecmp : Vec<u64>,
+    mtime    : u64,
+}
+
+impl Clint {
+    /* the compare registers start at the maximum, so no timer interrupt
+     * is pending until software sets them */
+    pub fn new(harts: usize) -> Clint {
+        Clint {msip: vec![false; harts], mtimecmp: vec![u64::MAX; harts], mtime: 0}
+    }
+
+    pub fn msip(&self, hart: usize) -> bool {
+        self.msip[hart]
+    }
+
+    pub fn mtip(&self, hart: usize) -> bool {
+        self.mtime >= self.mtimecmp[hart]
+    }
+
+    pub fn mtime(&self) -> u64 {
+        self.mtime
+    }
+
+    pub fn tick(&mut self) {
+        self.mtime = self.mtime.wrapping_add(1);
+    }
+
+    /* the register containing the byte at offset and the position of the
+     * byte in it */
+    fn register(&self, offset: u64) -> Option<(Register, u64)> {
+        let harts = self.msip.len() as u64;
+        if offset < MSIP + 4 * harts {
+            Some((Register::Msip(((offset - MSIP) / 4) as usize), offset & 3))
+        } else if (MTIMECMP..MTIMECMP + 8 * harts).contains(&offset) {
+            Some((Register::Mtimecmp(((offset - MTIMECMP) / 8) as usize), offset & 7))
+        } else if (MTIME..MTIME + 8).contains(&offset) {
+            Some((Register::Mtime, offset & 7))
+        } else {
+            None
+        }
+    }
+}
+
+fn set_byte(reg: &mut u64, byte: u64, value: u8) {
+    *reg = (*reg & !(0xFF << (8 * byte))) | ((value as u64) << (8 * byte));
+}
+
+/* the registers are accessed byte-wise, the upper bytes of msip read as
+ * zero and ignore writes */
+impl Memory for Clint {
+    fn load8(&mut self, addr: u64) -> Result<u8, AccessFault> {
+        Ok(match self.register(addr).ok_or(AccessFault)? {
+            (Register::Msip(hart), 0) => self.msip[hart] as u8,
+            (Register::Msip(_), _) => 0,
+            (Register::Mtimecmp(hart), byte) => (self.mtimecmp[hart] >> (8 * byte)) as u8,
+            (Register::Mtime, byte) => (self.mtime >> (8 * byte)) as u8,
+        })
+    }
+
+    fn store8(&mut self, addr: u64, value: u8) -> Result<(), AccessFault> {
+        match self.register(addr).ok_or(AccessFault)? {
+            (Register::Msip(hart), 0) => self.msip[hart] = (value & 1) != 0,
+            (Register::Msip(_), _) => (),
+            (Register::Mtimecmp(hart), byte) => set_byte(&mut self.mtimecmp[hart], byte, value),
+            (Register::Mtime, byte) => set_byte(&mut self.mtime, byte, value),
+        }
+        Ok(())
+    }
+
+    fn writable(&mut self, addr: u64, len: u64) -> bool {
+        (0..len).all(|i| self.register(addr.wrapping_add(i)).is_some())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Clint;
+    use super::super::memory::{AccessFault, Memory};
+
+    #[test]
+    fn test_msip() {
+        let mut clint = Clint::new(2);
+        assert_eq!(clint.store32(4, 0xFFFF_FFFF), Ok(()));
+        assert!(clint.msip(1) && !clint.msip(0));
+        assert_eq!(clint.load32(4), Ok(1));
+        assert_eq!(clint.load32(0), Ok(0));
+        clint.store8(4, 2).unwrap();
+        assert!(!clint.msip(1));
+        assert_eq!(clint.load32(8), Err(AccessFault));
+    }
+
+    #[test]
+    fn test_timer() {
+        let mut clint = Clint::new(2);
+        assert!(!clint.mtip(0) && !clint.mtip(1));
+        assert_eq!(clint.store64(0x4008, 2), Ok(()));
+        assert_eq!(clint.load64(0x4008), Ok(2));
+        assert_eq!(clint.load32(0x400C), Ok(0));
+        clint.tick();
+        assert!(!clint.mtip(1));
+        clint.tick();
+        assert!(clint.mtip(1) && !clint.mtip(0));
+        assert_eq!(clint.load64(0xBFF8), Ok(2));
+
+        /* RV32 software writes the halves of mtime separately */
+        clint.store32(0xBFFC, 1).unwrap();
+        clint.store32(0xBFF8, 0).unwrap();
+        assert_eq!(clint.mtime(), 1 << 32);
+        assert_eq!(clint.load32(0x4010), Err(AccessFault));
+        assert_eq!(clint.load8(0xC000), Err(AccessFault));
+    }
+}
@@ -107,6 +107,7 @@ pub mod funct3 {
     pub const FENCE_I : u8 = 0b001;
     pub const ECALL   : u8 = 0b000;
     pub const EBREAK  : u8 = 0b000;
+    pub const PRIV    : u8 = 0b000;
     pub const CSRRW   : u8 = 0b001;
     pub const CSRRS   : u8 = 0b010;
     pub const CSRRC   : u8 = 0b011;
@@ -187,6 +188,17 @@ pub mod amo {
         pub const AMOMAX_W  : u8 = 0b010;
         pub const AMOMINU_W : u8 = 0b010;
         pub const AMOMAXU_W : u8 = 0b010;
+        pub const LR_D      : u8 = 0b011;
+        pub const SC_D      : u8 = 0b011;
+        pub const AMOSWAP_D : u8 = 0b011;
+        pub const AMOADD_D  : u8 = 0b011;
+        pub const AMOXOR_D  : u8 = 0b011;
+        pub const AMOAND_D  : u8 = 0b011;
+        pub const AMOOR_D   : u8 = 0b011;
+        pub const AMOMIN_D  : u8 = 0b011;
+        pub const AMOMAX_D  : u8 = 0b011;
+        pub const AMOMINU_D : u8 = 0b011;
+        pub const AMOMAXU_D : u8 = 0b011;
     }
 
     pub mod funct5 {
@@ -201,6 +213,17 @@ pub mod amo {
         pub const AMOMAX_W  : u8 = 0b10100;
         pub const AMOMINU_W : u8 = 0b11000;
         pub const AMOMAXU_W : u8 = 0b11100;
+        pub const LR_D      : u8 = 0b00010;
+        pub const SC_D      : u8 = 0b00011;
+        pub const AMOSWAP_D : u8 = 0b00001;
+        pub const AMOADD_D  : u8 = 0b00000;
+        pub const AMOXOR_D  : u8 = 0b00100;
+        pub const AMOAND_D  : u8 = 0b01100;
+        pub const AMOOR_D   : u8 = 0b01000;
+        pub const AMOMIN_D  : u8 = 0b10000;
+        pub const AMOMAX_D  : u8 = 0b10100;
+        pub const AMOMINU_D : u8 = 0b11000;
+        pub const AMOMAXU_D : u8 = 0b11100;
     }
 
 }
@@ -231,6 +254,10 @@ pub mod single_fp {
         pub const FCVT_S_W  : u8 = 0b00000;
         pub const FCVT_S_WU : u8 = 0b00001;
         pub const FMV_S_X   : u8 = 0b00000;
+        pub const FCVT_L_S  : u8 = 0b00010;
+        pub const FCVT_LU_S : u8 = 0b00011;
+        pub const FCVT_S_L  : u8 = 0b00010;
+        pub const FCVT_S_LU : u8 = 0b00011;
     }
 
     pub mod funct7 {
@@ -254,6 +281,10 @@ pub mod single_fp {
         pub const FCVT_S_W  : u8 = 0b1101000;
         pub const FCVT_S_WU : u8 = 0b1101000;
         pub const FMV_S_X   : u8 = 0b1111000;
+        pub const FCVT_L_S  : u8 = 0b1100000;
+        pub const FCVT_LU_S : u8 = 0b1100000;
+        pub const FCVT_S_L  : u8 = 0b1101000;
+        pub const FCVT_S_LU : u8 = 0b1101000;
     }
 
 }
@@ -271,6 +302,8 @@ pub mod double_fp {
         pub const FLT_D    : u8 = 0b001;
         pub const FLE_D    : u8 = 0b000;
         pub const FCLASS_D : u8 = 0b001;
+        pub const FMV_X_D  : u8 = 0b000;
+        pub const FMV_D_X  : u8 = 0b000;
     }
 
     pub mod rs2 {
@@ -282,6 +315,12 @@ pub mod double_fp {
         pub const FCVT_WU_D : u8 = 0b00001;
         pub const FCVT_D_W  : u8 = 0b00000;
         pub const FCVT_D_WU : u8 = 0b00001;
+        pub const FCVT_L_D  : u8 = 0b00010;
+        pub const FCVT_LU_D : u8 = 0b00011;
+        pub const FMV_X_D   : u8 = 0b00000;
+        pub const FCVT_D_L  : u8 = 0b00010;
+        pub const FCVT_D_LU : u8 = 0b00011;
+        pub const FMV_D_X   : u8 = 0b00000;
     }
 
     pub mod funct7 {
@@ -305,5 +344,90 @@ pub mod double_fp {
         pub const FCVT_WU_D : u8 = 0b1100001;
         pub const FCVT_D_W  : u8 = 0b1101001;
         pub const FCVT_D_WU : u8 = 0b1101001;
+        pub const FCVT_L_D  : u8 = 0b1100001;
+        pub const FCVT_LU_D : u8 = 0b1100001;
+        pub const FMV_X_D   : u8 = 0b1110001;
+        pub const FCVT_D_L  : u8 = 0b1101001;
+        pub const FCVT_D_LU : u8 = 0b1101001;
+        pub const FMV_D_X   : u8 = 0b1111001;
+    }
+}
+
+/* the fmt field of the fused multiply-add instructions (R4-type) */
+pub mod fp_fmt {
+    pub const S : u8 = 0b00;
+    pub const D : u8 = 0b01;
+}
+
+pub mod rounding_mode {
+    pub const RNE : u8 = 0b000;
+    pub const RTZ : u8 = 0b001;
+    pub const RDN : u8 = 0b010;
+    pub const RUP : u8 = 0b011;
+    pub const RMM : u8 = 0b100;
+    pub const DYN : u8 = 0b111;
+}
+
+/* compressed instructions (RVC), the quadrant is given by the lower two bits */
+pub mod compressed {
+    pub mod quadrant {
+        pub const C0 : u8 = 0b00;
+        pub const C1 : u8 = 0b01;
+        pub const C2 : u8 = 0b10;
+    }
+
+    pub mod funct3 {
+        /* quadrant 0 */
+        pub const C_ADDI4SPN : u8 = 0b000;
+        pub const C_FLD      : u8 = 0b001;
+        pub const C_LW       : u8 = 0b010;
+        pub const C_FLW      : u8 = 0b011;
+        pub const C_LD       : u8 = 0b011;
+        pub const C_FSD      : u8 = 0b101;
+        pub const C_SW       : u8 = 0b110;
+        pub const C_FSW      : u8 = 0b111;
+        pub const C_SD       : u8 = 0b111;
+
+        /* quadrant 1 */
+        pub const C_ADDI     : u8 = 0b000;
+        pub const C_JAL      : u8 = 0b001;
+        pub const C_ADDIW    : u8 = 0b001;
+        pub const C_LI       : u8 = 0b010;
+        pub const C_LUI      : u8 = 0b011;
+        pub const C_ADDI16SP : u8 = 0b011;
+        pub const C_MISC_ALU : u8 = 0b100;
+        pub const C_J        : u8 = 0b101;
+        pub const C_BEQZ     : u8 = 0b110;
+        pub const C_BNEZ     : u8 = 0b111;
+
+        /* quadrant 2 */
+        pub const C_SLLI     : u8 = 0b000;
+        pub const C_FLDSP    : u8 = 0b001;
+        pub const C_LWSP     : u8 = 0b010;
+        pub const C_FLWSP    : u8 = 0b011;
+        pub const C_LDSP     : u8 = 0b011;
+        pub const C_JR_MV_ADD : u8 = 0b100;
+        pub const C_FSDSP    : u8 = 0b101;
+        pub const C_SWSP     : u8 = 0b110;
+        pub const C_FSWSP    : u8 = 0b111;
+        pub const C_SDSP     : u8 = 0b111;
+    }
+
+    /* bits [11:10] of C_MISC_ALU */
+    pub mod funct2 {
+        pub const C_SRLI : u8 = 0b00;
+        pub const C_SRAI : u8 = 0b01;
+        pub const C_ANDI : u8 = 0b10;
+        pub const C_ARITH : u8 = 0b11;
+    }
+
+    /* bits [6:5] of C_ARITH, bit 12 selects the word variants of RV64 */
+    pub mod arith {
+        pub const C_SUB  : u8 = 0b00;
+        pub const C_XOR  : u8 = 0b01;
+        pub const C_OR   : u8 = 0b10;
+        pub const C_AND  : u8 = 0b11;
+        pub const C_SUBW : u8 = 0b00;
+        pub const C_ADDW : u8 = 0b01;
     }
 }
@@ -22,7 +22,1811 @@
 
 #![allow(dead_code)]
 
+use super::csr::{CsrFile, Privilege, MIP, SATP, SIP, misa, mstatus};
+use super::decoder::{Instruction, FpRoundingMode};
+use super::memory::Memory;
+use super::mmu::{AccessType, Mmu};
+use super::softfloat::{Env, Format, Rounding, F32, F64};
+
+/* synchronous exceptions, the payload is the faulting address or the
+ * faulting instruction word */
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Exception {
+    InstructionAddressMisaligned(u64),
+    InstructionAccessFault(u64),
+    IllegalInstruction(u32),
+    Breakpoint(u64),
+    LoadAddressMisaligned(u64),
+    LoadAccessFault(u64),
+    StoreAddressMisaligned(u64), // also for AMOs
+    StoreAccessFault(u64),
+    EnvironmentCall(Privilege),
+    InstructionPageFault(u64),
+    LoadPageFault(u64),
+    StorePageFault(u64),
+}
+
+impl Exception {
+    /* the exception code written to mcause */
+    pub fn cause(&self) -> u64 {
+        match *self {
+            Exception::InstructionAddressMisaligned(_) => 0,
+            Exception::InstructionAccessFault(_) => 1,
+            Exception::IllegalInstruction(_) => 2,
+            Exception::Breakpoint(_) => 3,
+            Exception::LoadAddressMisaligned(_) => 4,
+            Exception::LoadAccessFault(_) => 5,
+            Exception::StoreAddressMisaligned(_) => 6,
+            Exception::StoreAccessFault(_) => 7,
+            Exception::EnvironmentCall(privilege) => 8 + privilege as u64,
+            Exception::InstructionPageFault(_) => 12,
+            Exception::LoadPageFault(_) => 13,
+            Exception::StorePageFault(_) => 15,
+        }
+    }
+
+    /* the value written to mtval */
+    pub fn tval(&self) -> u64 {
+        match *self {
+            Exception::InstructionAddressMisaligned(addr) |
+            Exception::InstructionAccessFault(addr) |
+            Exception::Breakpoint(addr) |
+            Exception::LoadAddressMisaligned(addr) |
+            Exception::LoadAccessFault(addr) |
+            Exception::StoreAddressMisaligned(addr) |
+            Exception::StoreAccessFault(addr) |
+            Exception::InstructionPageFault(addr) |
+            Exception::LoadPageFault(addr) |
+            Exception::StorePageFault(addr) => addr,
+            Exception::IllegalInstruction(word) => word as u64,
+            Exception::EnvironmentCall(_) => 0,
+        }
+    }
+}
+
+/* The effects of the last instruction, recorded for tracing if the log of
+ * the core is enabled. Only the integer and floating-point registers and
+ * memory accesses of retired instructions are recorded, addresses are
+ * virtual. */
+#[derive(PartialEq, Debug, Clone)]
+pub struct Commit {
+    pub retired   : bool,
+    pub pc        : u64,
+    pub privilege : Privilege,
+    pub word      : u32,
+    pub len       : u64,
+    pub writes    : Vec<(u8, u64)>,        // register and value
+    pub fwrites   : Vec<(u8, u64)>,        // fp register and value
+    pub loads     : Vec<(u64, u64)>,       // address and size
+    pub stores    : Vec<(u64, u64, u64)>,  // address, size and value
+}
+
+impl Commit {
+    pub fn new() -> Commit {
+        Commit {retired: false, pc: 0, privilege: Privilege::Machine, word: 0, len: 0,
+                writes: Vec::new(), fwrites: Vec::new(), loads: Vec::new(), stores: Vec::new()}
+    }
+
+    fn clear(&mut self, pc: u64, privilege: Privilege) {
+        self.retired = false;
+        self.pc = pc;
+        self.privilege = privilege;
+        self.writes.clear();
+        self.fwrites.clear();
+        self.loads.clear();
+        self.stores.clear();
+    }
+}
+
+impl Default for Commit {
+    fn default() -> Commit {
+        Commit::new()
+    }
+}
+
+/* the read-modify-write operation of the CSR instructions */
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum CsrOp {
+    Write(u64),
+    Set(u64),
+    Clear(u64),
+}
+
+/* the sign bit of the result of the sign-injection instructions */
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Injection {
+    Copy,
+    Negate,
+    Xor,
+}
+
+/* the reservation granule of LR/SC if not configured otherwise, a cache
+ * line */
+pub const DEFAULT_GRANULE : u64 = 64;
+
+/* sign-extends the lower 32 bits, the result of every *W instruction */
+fn sext32(value: u64) -> u64 {
+    value as i32 as i64 as u64
+}
+
+/* A RV32I or RV64I hart. The registers are always 64 bits wide, with RV32
+ * the upper half is kept zero so the values read the same as on a 32-bit
+ * machine. The floating-point registers are FLEN=64 wide and hold singles
+ * NaN-boxed, i.e. with the upper 32 bits set. */
 pub struct Core {
-    pub reg : [u32; 32], // reg[0] is always zero
-    pub pc  : u32,
+    pub reg : [u64; 32], // reg[0] is always zero
+    pub freg: [u64; 32],
+    pub pc  : u64,
+    pub csr : CsrFile,
+    pub mmu : Mmu,
+    pub log : Option<Commit>, // disabled if None
+    /* the reservation set of LR as physical address aligned to the
+     * granule, a power of two of at least eight bytes */
+    pub reservation : Option<u64>,
+    pub granule     : u64,
+}
+
+impl Core {
+    pub fn new(pc: u64, rv64: bool) -> Core {
+        let mut core = Core {reg: [0; 32], freg: [0; 32], pc: 0, csr: CsrFile::new(rv64, 0), mmu: Mmu::new(), log: None,
+                             reservation: None, granule: DEFAULT_GRANULE};
+        core.pc = pc & core.xlen_mask();
+        core
+    }
+
+    /* XLEN is fixed by misa.MXL */
+    pub fn rv64(&self) -> bool {
+        self.csr.rv64()
+    }
+
+    fn xlen(&self) -> u32 {
+        if self.rv64() { 64 } else { 32 }
+    }
+
+    fn xlen_mask(&self) -> u64 {
+        self.csr.xlen_mask()
+    }
+
+    fn shamt_mask(&self) -> u64 {
+        (self.xlen() - 1) as u64
+    }
+
+    /* interprets an XLEN-wide value as signed */
+    fn signed(&self, value: u64) -> i64 {
+        if self.rv64() { value as i64 } else { value as i32 as i64 }
+    }
+
+    fn get_reg(&self, rs: u8) -> u64 {
+        self.reg[rs as usize]
+    }
+
+    fn set_reg(&mut self, rd: u8, value: u64) {
+        if rd != 0 {
+            self.reg[rd as usize] = value & self.xlen_mask();
+            if let Some(ref mut log) = self.log {
+                log.writes.push((rd, self.reg[rd as usize]));
+            }
+        }
+    }
+
+    /* reads an operand, a single that is not properly NaN-boxed is the
+     * canonical NaN */
+    fn get_freg(&self, fmt: Format, rs: u8) -> u64 {
+        let value = self.freg[rs as usize];
+        if fmt == F64 {
+            value
+        } else if (value >> 32) == 0xFFFF_FFFF {
+            value & 0xFFFF_FFFF
+        } else {
+            F32.canonical_nan()
+        }
+    }
+
+    fn set_freg(&mut self, fmt: Format, rd: u8, value: u64) {
+        let value = if fmt == F64 { value } else { value | 0xFFFF_FFFF_0000_0000 };
+        self.freg[rd as usize] = value;
+        self.csr.fp_dirty();
+        if let Some(ref mut log) = self.log {
+            log.fwrites.push((rd, value));
+        }
+    }
+
+    /* Takes a pending interrupt or executes a single instruction. An
+     * exception enters the trap handler and is returned to the caller for
+     * information. */
+    pub fn step<M: Memory + ?Sized>(&mut self, mem: &mut M) -> Result<(), Exception> {
+        if self.take_interrupt() {
+            return Ok(());
+        }
+        self.try_step(mem).inspect_err(|e| self.trap(*e))
+    }
+
+    /* enters the trap handler of the pending interrupt with the highest
+     * priority, returns false if no interrupt is pending and enabled */
+    pub fn take_interrupt(&mut self) -> bool {
+        match self.csr.pending_interrupt() {
+            Some((irq, target)) => {
+                let cause = (1 << (self.xlen() - 1)) | irq;
+                self.enter_trap(cause, 0, target);
+                if let Some(ref mut log) = self.log {
+                    log.retired = false;
+                }
+                true
+            },
+            None => false
+        }
+    }
+
+    /* fetches, decodes and executes a single instruction. If an exception
+     * is raised no trap is taken and the pc, the registers and the memory
+     * written by the instruction are left untouched, so the caller can
+     * handle it e.g. by emulating an environment call. The cycle counter,
+     * the TLB and the A/D bits set by the page walk may still change. */
+    pub fn try_step<M: Memory + ?Sized>(&mut self, mem: &mut M) -> Result<(), Exception> {
+        self.csr.cycle = self.csr.cycle.wrapping_add(1);
+        if let Some(ref mut log) = self.log {
+            log.clear(self.pc, self.csr.privilege);
+        }
+
+        let (instr, word, len) = self.fetch(mem)?;
+        self.pc = self.execute(instr, word, len, mem)?;
+        self.csr.instret = self.csr.instret.wrapping_add(1);
+        if let Some(ref mut log) = self.log {
+            log.retired = true;
+            log.word = word;
+            log.len = len;
+        }
+        Ok(())
+    }
+
+    /* Completes the instruction at pc after the caller emulated the
+     * exception it raised, e.g. an ECALL serviced by a system call proxy.
+     * The pc moves past it, it counts as retired and the registers in
+     * writes are logged with their current values. */
+    pub fn retire_emulated(&mut self, word: u32, len: u64, writes: &[u8]) {
+        self.pc = self.pc.wrapping_add(len) & self.xlen_mask();
+        self.csr.instret = self.csr.instret.wrapping_add(1);
+        if let Some(ref mut log) = self.log {
+            log.retired = true;
+            log.word = word;
+            log.len = len;
+            let reg = &self.reg;
+            log.writes.extend(writes.iter().filter(|&&rd| rd != 0).map(|&rd| (rd, reg[rd as usize])));
+        }
+    }
+
+    /* enters the trap handler for an exception raised at pc, exceptions in
+     * S and U-mode are handled in S-mode if they are delegated by medeleg */
+    pub fn trap(&mut self, e: Exception) {
+        let delegated = (self.csr.medeleg >> e.cause()) & 1 == 1;
+        let target = if delegated && self.csr.privilege != Privilege::Machine {
+            Privilege::Supervisor
+        } else {
+            Privilege::Machine
+        };
+        self.enter_trap(e.cause(), e.tval(), target);
+    }
+
+    /* A store of another hart or device to physical memory, which
+     * invalidates an overlapping reservation. Stores of the hart itself
+     * keep it, as the ISA allows. */
+    pub fn snoop(&mut self, paddr: u64, size: u64) {
+        if let Some(set) = self.reservation {
+            if paddr < set.wrapping_add(self.granule) && set < paddr.wrapping_add(size) {
+                self.reservation = None;
+            }
+        }
+    }
+
+    /* Traps and returns from traps invalidate the reservation, so an LR/SC
+     * sequence cannot succeed across a context switch. */
+    fn enter_trap(&mut self, cause: u64, tval: u64, target: Privilege) {
+        self.reservation = None;
+        let interrupt = (cause >> (self.xlen() - 1)) != 0;
+        let privilege = self.csr.privilege as u64;
+
+        let tvec = if target == Privilege::Machine {
+            self.csr.mepc = self.pc;
+            self.csr.mcause = cause;
+            self.csr.mtval = tval;
+            let mie = self.csr.mstatus & mstatus::MIE;
+            self.csr.mstatus &= !(mstatus::MIE | mstatus::MPIE | mstatus::MPP);
+            self.csr.mstatus |= (mie << 4) | (privilege << mstatus::MPP_SHIFT);
+            self.csr.mtvec
+        } else {
+            self.csr.sepc = self.pc;
+            self.csr.scause = cause;
+            self.csr.stval = tval;
+            let sie = self.csr.mstatus & mstatus::SIE;
+            self.csr.mstatus &= !(mstatus::SIE | mstatus::SPIE | mstatus::SPP);
+            self.csr.mstatus |= (sie << 4) | (privilege << 8);
+            self.csr.stvec
+        };
+        self.csr.privilege = target;
+
+        /* in vectored mode interrupts jump to base + 4 * cause */
+        let base = tvec & !3;
+        self.pc = if interrupt && (tvec & 1) == 1 {
+            base.wrapping_add(4 * (cause & 0xFF)) & self.xlen_mask()
+        } else {
+            base
+        };
+    }
+
+    /* fetches and decodes the instruction at pc, returns the instruction,
+     * the raw instruction word and the length in bytes. Apart from the TLB
+     * the state of the core is not changed. */
+    pub fn fetch<M: Memory + ?Sized>(&mut self, mem: &mut M) -> Result<(Instruction, u32, u64), Exception> {
+        let pc = self.pc;
+        let paddr = self.translate(mem, pc, 2, AccessType::Fetch)?;
+        let low = mem.load16(paddr)
+            .map_err(|_| Exception::InstructionAccessFault(pc))?;
+
+        if (low & 0x03) != 0x03 {
+            let word = low as u32;
+            if !self.csr.has(misa::C) {
+                return Err(Exception::IllegalInstruction(word));
+            }
+            let instr = Instruction::decode16(low, self.rv64())
+                .map_err(|_| Exception::IllegalInstruction(word))?;
+            return Ok((instr, word, 2));
+        }
+
+        /* the upper half may be on the next page */
+        let addr = pc.wrapping_add(2) & self.xlen_mask();
+        let paddr = self.translate(mem, addr, 2, AccessType::Fetch)?;
+        let high = mem.load16(paddr)
+            .map_err(|_| Exception::InstructionAccessFault(addr))?;
+        let word = (low as u32) | ((high as u32) << 16);
+        let instr = Instruction::decode32(word, self.rv64())
+            .map_err(|_| Exception::IllegalInstruction(word))?;
+        Ok((instr, word, 4))
+    }
+
+    /* executes an already decoded instruction of len bytes and returns the
+     * next pc */
+    fn execute<M: Memory + ?Sized>(&mut self, instr: Instruction, word: u32, len: u64, mem: &mut M)
+        -> Result<u64, Exception> {
+        let pc = self.pc;
+        let next_pc = pc.wrapping_add(len) & self.xlen_mask();
+        let m = self.csr.has(misa::M);
+        let f = self.csr.fp_enabled();
+        let d = f && self.csr.has(misa::D);
+        let a = self.csr.has(misa::A);
+
+        match instr {
+            Instruction::LUI {rd, imm} => self.set_reg(rd, imm as i64 as u64),
+            Instruction::AUIPC {rd, imm} => self.set_reg(rd, pc.wrapping_add(imm as i64 as u64)),
+            Instruction::JAL {rd, imm} => {
+                let target = self.jump_target(pc.wrapping_add(imm as i64 as u64))?;
+                self.set_reg(rd, next_pc);
+                return Ok(target);
+            },
+            Instruction::JALR {rd, rs1, imm} => {
+                let base = self.get_reg(rs1).wrapping_add(imm as i64 as u64);
+                let target = self.jump_target(base & !1)?;
+                self.set_reg(rd, next_pc);
+                return Ok(target);
+            },
+            Instruction::BEQ {rs1, rs2, imm} => return self.branch(self.get_reg(rs1) == self.get_reg(rs2), imm, next_pc),
+            Instruction::BNE {rs1, rs2, imm} => return self.branch(self.get_reg(rs1) != self.get_reg(rs2), imm, next_pc),
+            Instruction::BLT {rs1, rs2, imm} => return self.branch(self.signed(self.get_reg(rs1)) < self.signed(self.get_reg(rs2)), imm, next_pc),
+            Instruction::BGE {rs1, rs2, imm} => return self.branch(self.signed(self.get_reg(rs1)) >= self.signed(self.get_reg(rs2)), imm, next_pc),
+            Instruction::BLTU {rs1, rs2, imm} => return self.branch(self.get_reg(rs1) < self.get_reg(rs2), imm, next_pc),
+            Instruction::BGEU {rs1, rs2, imm} => return self.branch(self.get_reg(rs1) >= self.get_reg(rs2), imm, next_pc),
+            Instruction::LB {rd, rs1, imm} => {
+                let addr = self.addr(rs1, imm);
+                let value = self.load(mem, addr, 1)?;
+                self.set_reg(rd, value as i8 as u64);
+            },
+            Instruction::LH {rd, rs1, imm} => {
+                let addr = self.addr(rs1, imm);
+                let value = self.load(mem, addr, 2)?;
+                self.set_reg(rd, value as i16 as u64);
+            },
+            Instruction::LW {rd, rs1, imm} => {
+                let addr = self.addr(rs1, imm);
+                let value = self.load(mem, addr, 4)?;
+                self.set_reg(rd, value as i32 as u64);
+            },
+            Instruction::LD {rd, rs1, imm} => {
+                let addr = self.addr(rs1, imm);
+                let value = self.load(mem, addr, 8)?;
+                self.set_reg(rd, value);
+            },
+            Instruction::LBU {rd, rs1, imm} => {
+                let addr = self.addr(rs1, imm);
+                let value = self.load(mem, addr, 1)?;
+                self.set_reg(rd, value);
+            },
+            Instruction::LHU {rd, rs1, imm} => {
+                let addr = self.addr(rs1, imm);
+                let value = self.load(mem, addr, 2)?;
+                self.set_reg(rd, value);
+            },
+            Instruction::LWU {rd, rs1, imm} => {
+                let addr = self.addr(rs1, imm);
+                let value = self.load(mem, addr, 4)?;
+                self.set_reg(rd, value);
+            },
+            Instruction::SB {rs1, rs2, imm} => {
+                let addr = self.addr(rs1, imm);
+                let value = self.get_reg(rs2);
+                self.store(mem, addr, 1, value)?;
+            },
+            Instruction::SH {rs1, rs2, imm} => {
+                let addr = self.addr(rs1, imm);
+                let value = self.get_reg(rs2);
+                self.store(mem, addr, 2, value)?;
+            },
+            Instruction::SW {rs1, rs2, imm} => {
+                let addr = self.addr(rs1, imm);
+                let value = self.get_reg(rs2);
+                self.store(mem, addr, 4, value)?;
+            },
+            Instruction::SD {rs1, rs2, imm} => {
+                let addr = self.addr(rs1, imm);
+                let value = self.get_reg(rs2);
+                self.store(mem, addr, 8, value)?;
+            },
+            Instruction::ADDI {rd, rs1, imm} => {
+                let value = self.get_reg(rs1).wrapping_add(imm as i64 as u64);
+                self.set_reg(rd, value);
+            },
+            Instruction::SLTI {rd, rs1, imm} => {
+                let value = (self.signed(self.get_reg(rs1)) < (imm as i64)) as u64;
+                self.set_reg(rd, value);
+            },
+            Instruction::SLTIU {rd, rs1, imm} => {
+                /* the immediate is sign-extended and then compared unsigned */
+                let value = (self.get_reg(rs1) < (imm as i64 as u64 & self.xlen_mask())) as u64;
+                self.set_reg(rd, value);
+            },
+            Instruction::XORI {rd, rs1, imm} => {
+                let value = self.get_reg(rs1) ^ (imm as i64 as u64);
+                self.set_reg(rd, value);
+            },
+            Instruction::ORI {rd, rs1, imm} => {
+                let value = self.get_reg(rs1) | (imm as i64 as u64);
+                self.set_reg(rd, value);
+            },
+            Instruction::ANDI {rd, rs1, imm} => {
+                let value = self.get_reg(rs1) & (imm as i64 as u64);
+                self.set_reg(rd, value);
+            },
+            Instruction::SLLI {rd, rs1, shamt} => {
+                let value = self.get_reg(rs1) << (shamt as u64 & self.shamt_mask());
+                self.set_reg(rd, value);
+            },
+            Instruction::SRLI {rd, rs1, shamt} => {
+                let value = self.get_reg(rs1) >> (shamt as u64 & self.shamt_mask());
+                self.set_reg(rd, value);
+            },
+            Instruction::SRAI {rd, rs1, shamt} => {
+                let value = (self.signed(self.get_reg(rs1)) >> (shamt as u64 & self.shamt_mask())) as u64;
+                self.set_reg(rd, value);
+            },
+            Instruction::ADD {rd, rs1, rs2} => {
+                let value = self.get_reg(rs1).wrapping_add(self.get_reg(rs2));
+                self.set_reg(rd, value);
+            },
+            Instruction::SUB {rd, rs1, rs2} => {
+                let value = self.get_reg(rs1).wrapping_sub(self.get_reg(rs2));
+                self.set_reg(rd, value);
+            },
+            Instruction::SLL {rd, rs1, rs2} => {
+                let value = self.get_reg(rs1) << (self.get_reg(rs2) & self.shamt_mask());
+                self.set_reg(rd, value);
+            },
+            Instruction::SLT {rd, rs1, rs2} => {
+                let value = (self.signed(self.get_reg(rs1)) < self.signed(self.get_reg(rs2))) as u64;
+                self.set_reg(rd, value);
+            },
+            Instruction::SLTU {rd, rs1, rs2} => {
+                let value = (self.get_reg(rs1) < self.get_reg(rs2)) as u64;
+                self.set_reg(rd, value);
+            },
+            Instruction::XOR {rd, rs1, rs2} => {
+                let value = self.get_reg(rs1) ^ self.get_reg(rs2);
+                self.set_reg(rd, value);
+            },
+            Instruction::SRL {rd, rs1, rs2} => {
+                let value = self.get_reg(rs1) >> (self.get_reg(rs2) & self.shamt_mask());
+                self.set_reg(rd, value);
+            },
+            Instruction::SRA {rd, rs1, rs2} => {
+                let value = (self.signed(self.get_reg(rs1)) >> (self.get_reg(rs2) & self.shamt_mask())) as u64;
+                self.set_reg(rd, value);
+            },
+            Instruction::OR {rd, rs1, rs2} => {
+                let value = self.get_reg(rs1) | self.get_reg(rs2);
+                self.set_reg(rd, value);
+            },
+            Instruction::AND {rd, rs1, rs2} => {
+                let value = self.get_reg(rs1) & self.get_reg(rs2);
+                self.set_reg(rd, value);
+            },
+            /* RV64I only, the decoder rejects them for RV32 */
+            Instruction::ADDIW {rd, rs1, imm} => {
+                let value = self.get_reg(rs1).wrapping_add(imm as i64 as u64);
+                self.set_reg(rd, sext32(value));
+            },
+            Instruction::SLLIW {rd, rs1, shamt} => {
+                let value = (self.get_reg(rs1) as u32) << (shamt & 0x1F);
+                self.set_reg(rd, sext32(value as u64));
+            },
+            Instruction::SRLIW {rd, rs1, shamt} => {
+                let value = (self.get_reg(rs1) as u32) >> (shamt & 0x1F);
+                self.set_reg(rd, sext32(value as u64));
+            },
+            Instruction::SRAIW {rd, rs1, shamt} => {
+                let value = (self.get_reg(rs1) as i32) >> (shamt & 0x1F);
+                self.set_reg(rd, value as i64 as u64);
+            },
+            Instruction::ADDW {rd, rs1, rs2} => {
+                let value = self.get_reg(rs1).wrapping_add(self.get_reg(rs2));
+                self.set_reg(rd, sext32(value));
+            },
+            Instruction::SUBW {rd, rs1, rs2} => {
+                let value = self.get_reg(rs1).wrapping_sub(self.get_reg(rs2));
+                self.set_reg(rd, sext32(value));
+            },
+            Instruction::SLLW {rd, rs1, rs2} => {
+                let value = (self.get_reg(rs1) as u32) << (self.get_reg(rs2) & 0x1F);
+                self.set_reg(rd, sext32(value as u64));
+            },
+            Instruction::SRLW {rd, rs1, rs2} => {
+                let value = (self.get_reg(rs1) as u32) >> (self.get_reg(rs2) & 0x1F);
+                self.set_reg(rd, sext32(value as u64));
+            },
+            Instruction::SRAW {rd, rs1, rs2} => {
+                let value = (self.get_reg(rs1) as i32) >> (self.get_reg(rs2) & 0x1F);
+                self.set_reg(rd, value as i64 as u64);
+            },
+            Instruction::MUL {rd, rs1, rs2} if m => {
+                let value = self.get_reg(rs1).wrapping_mul(self.get_reg(rs2));
+                self.set_reg(rd, value);
+            },
+            Instruction::MULH {rd, rs1, rs2} if m => {
+                let value = (self.signed(self.get_reg(rs1)) as i128) * (self.signed(self.get_reg(rs2)) as i128);
+                self.set_reg(rd, (value >> self.xlen()) as u64);
+            },
+            Instruction::MULHSU {rd, rs1, rs2} if m => {
+                let value = (self.signed(self.get_reg(rs1)) as i128) * (self.get_reg(rs2) as i128);
+                self.set_reg(rd, (value >> self.xlen()) as u64);
+            },
+            Instruction::MULHU {rd, rs1, rs2} if m => {
+                let value = (self.get_reg(rs1) as u128) * (self.get_reg(rs2) as u128);
+                self.set_reg(rd, (value >> self.xlen()) as u64);
+            },
+            /* division by zero and overflow do not trap, the results are
+             * defined by the spec */
+            Instruction::DIV {rd, rs1, rs2} if m => {
+                let (a, b) = (self.signed(self.get_reg(rs1)), self.signed(self.get_reg(rs2)));
+                let value = if b == 0 { -1 } else { a.wrapping_div(b) };
+                self.set_reg(rd, value as u64);
+            },
+            Instruction::DIVU {rd, rs1, rs2} if m => {
+                let (a, b) = (self.get_reg(rs1), self.get_reg(rs2));
+                let value = a.checked_div(b).unwrap_or(!0);
+                self.set_reg(rd, value);
+            },
+            Instruction::REM {rd, rs1, rs2} if m => {
+                let (a, b) = (self.signed(self.get_reg(rs1)), self.signed(self.get_reg(rs2)));
+                let value = if b == 0 { a } else { a.wrapping_rem(b) };
+                self.set_reg(rd, value as u64);
+            },
+            Instruction::REMU {rd, rs1, rs2} if m => {
+                let (a, b) = (self.get_reg(rs1), self.get_reg(rs2));
+                let value = if b == 0 { a } else { a % b };
+                self.set_reg(rd, value);
+            },
+            Instruction::MULW {rd, rs1, rs2} if m => {
+                let value = self.get_reg(rs1).wrapping_mul(self.get_reg(rs2));
+                self.set_reg(rd, sext32(value));
+            },
+            Instruction::DIVW {rd, rs1, rs2} if m => {
+                let (a, b) = (self.get_reg(rs1) as i32, self.get_reg(rs2) as i32);
+                let value = if b == 0 { -1 } else { a.wrapping_div(b) };
+                self.set_reg(rd, value as i64 as u64);
+            },
+            Instruction::DIVUW {rd, rs1, rs2} if m => {
+                let (a, b) = (self.get_reg(rs1) as u32, self.get_reg(rs2) as u32);
+                let value = a.checked_div(b).unwrap_or(!0);
+                self.set_reg(rd, sext32(value as u64));
+            },
+            Instruction::REMW {rd, rs1, rs2} if m => {
+                let (a, b) = (self.get_reg(rs1) as i32, self.get_reg(rs2) as i32);
+                let value = if b == 0 { a } else { a.wrapping_rem(b) };
+                self.set_reg(rd, value as i64 as u64);
+            },
+            Instruction::REMUW {rd, rs1, rs2} if m => {
+                let (a, b) = (self.get_reg(rs1) as u32, self.get_reg(rs2) as u32);
+                let value = if b == 0 { a } else { a % b };
+                self.set_reg(rd, sext32(value as u64));
+            },
+            Instruction::LR_W {rd, rs1, ..} if a => self.load_reserved(mem, rd, rs1, 4)?,
+            Instruction::SC_W {rd, rs1, rs2, ..} if a => self.store_conditional(mem, rd, rs1, rs2, 4)?,
+            Instruction::AMOSWAP_W {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 4, |_, b| b)?,
+            Instruction::AMOADD_W {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 4, u64::wrapping_add)?,
+            Instruction::AMOXOR_W {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 4, |a, b| a ^ b)?,
+            Instruction::AMOAND_W {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 4, |a, b| a & b)?,
+            Instruction::AMOOR_W {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 4, |a, b| a | b)?,
+            Instruction::AMOMIN_W {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 4, |a, b| (a as i64).min(b as i64) as u64)?,
+            Instruction::AMOMAX_W {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 4, |a, b| (a as i64).max(b as i64) as u64)?,
+            Instruction::AMOMINU_W {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 4, u64::min)?,
+            Instruction::AMOMAXU_W {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 4, u64::max)?,
+            Instruction::LR_D {rd, rs1, ..} if a => self.load_reserved(mem, rd, rs1, 8)?,
+            Instruction::SC_D {rd, rs1, rs2, ..} if a => self.store_conditional(mem, rd, rs1, rs2, 8)?,
+            Instruction::AMOSWAP_D {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 8, |_, b| b)?,
+            Instruction::AMOADD_D {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 8, u64::wrapping_add)?,
+            Instruction::AMOXOR_D {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 8, |a, b| a ^ b)?,
+            Instruction::AMOAND_D {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 8, |a, b| a & b)?,
+            Instruction::AMOOR_D {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 8, |a, b| a | b)?,
+            Instruction::AMOMIN_D {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 8, |a, b| (a as i64).min(b as i64) as u64)?,
+            Instruction::AMOMAX_D {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 8, |a, b| (a as i64).max(b as i64) as u64)?,
+            Instruction::AMOMINU_D {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 8, u64::min)?,
+            Instruction::AMOMAXU_D {rd, rs1, rs2, ..} if a => self.amo(mem, rd, rs1, rs2, 8, u64::max)?,
+            Instruction::FLW {rd, rs1, imm} if f => {
+                let addr = self.addr(rs1, imm);
+                let value = self.load(mem, addr, 4)?;
+                self.set_freg(F32, rd, value);
+            },
+            Instruction::FSW {rs1, rs2, imm} if f => {
+                let addr = self.addr(rs1, imm);
+                self.store(mem, addr, 4, self.freg[rs2 as usize])?;
+            },
+            Instruction::FMADD_S {rd, rs1, rs2, rs3, rm} if f => self.fp_fused(F32, rd, rs1, rs2, rs3, self.rounding(rm, word)?, false, false),
+            Instruction::FMSUB_S {rd, rs1, rs2, rs3, rm} if f => self.fp_fused(F32, rd, rs1, rs2, rs3, self.rounding(rm, word)?, false, true),
+            Instruction::FNMSUB_S {rd, rs1, rs2, rs3, rm} if f => self.fp_fused(F32, rd, rs1, rs2, rs3, self.rounding(rm, word)?, true, false),
+            Instruction::FNMADD_S {rd, rs1, rs2, rs3, rm} if f => self.fp_fused(F32, rd, rs1, rs2, rs3, self.rounding(rm, word)?, true, true),
+            Instruction::FADD_S {rd, rs1, rs2, rm} if f => self.fp_binary(F32, rd, rs1, rs2, self.rounding(rm, word)?, Env::add),
+            Instruction::FSUB_S {rd, rs1, rs2, rm} if f => self.fp_binary(F32, rd, rs1, rs2, self.rounding(rm, word)?, Env::sub),
+            Instruction::FMUL_S {rd, rs1, rs2, rm} if f => self.fp_binary(F32, rd, rs1, rs2, self.rounding(rm, word)?, Env::mul),
+            Instruction::FDIV_S {rd, rs1, rs2, rm} if f => self.fp_binary(F32, rd, rs1, rs2, self.rounding(rm, word)?, Env::div),
+            Instruction::FSQRT_S {rd, rs1, rm} if f => {
+                let mut env = Env::new(self.rounding(rm, word)?);
+                let value = env.sqrt(F32, self.get_freg(F32, rs1));
+                self.fp_result(F32, rd, value, env);
+            },
+            Instruction::FSGNJ_S {rd, rs1, rs2} if f => self.fp_sign_inject(F32, rd, rs1, rs2, Injection::Copy),
+            Instruction::FSGNJN_S {rd, rs1, rs2} if f => self.fp_sign_inject(F32, rd, rs1, rs2, Injection::Negate),
+            Instruction::FSGNJX_S {rd, rs1, rs2} if f => self.fp_sign_inject(F32, rd, rs1, rs2, Injection::Xor),
+            Instruction::FMIN_S {rd, rs1, rs2} if f => self.fp_binary(F32, rd, rs1, rs2, Rounding::NearestEven, Env::min),
+            Instruction::FMAX_S {rd, rs1, rs2} if f => self.fp_binary(F32, rd, rs1, rs2, Rounding::NearestEven, Env::max),
+            Instruction::FCVT_W_S {rd, rs1, rm} if f => self.fp_to_int(F32, rd, rs1, self.rounding(rm, word)?, true, 32),
+            Instruction::FCVT_WU_S {rd, rs1, rm} if f => self.fp_to_int(F32, rd, rs1, self.rounding(rm, word)?, false, 32),
+            Instruction::FCVT_L_S {rd, rs1, rm} if f => self.fp_to_int(F32, rd, rs1, self.rounding(rm, word)?, true, 64),
+            Instruction::FCVT_LU_S {rd, rs1, rm} if f => self.fp_to_int(F32, rd, rs1, self.rounding(rm, word)?, false, 64),
+            Instruction::FMV_X_S {rd, rs1} if f => self.set_reg(rd, sext32(self.freg[rs1 as usize])),
+            Instruction::FEQ_S {rd, rs1, rs2} if f => self.fp_compare(F32, rd, rs1, rs2, Env::equal),
+            Instruction::FLT_S {rd, rs1, rs2} if f => self.fp_compare(F32, rd, rs1, rs2, Env::less),
+            Instruction::FLE_S {rd, rs1, rs2} if f => self.fp_compare(F32, rd, rs1, rs2, Env::less_equal),
+            Instruction::FCLASS_S {rd, rs1} if f => self.set_reg(rd, F32.classify(self.get_freg(F32, rs1))),
+            Instruction::FCVT_S_W {rd, rs1, rm} if f => self.int_to_fp(F32, rd, rs1, self.rounding(rm, word)?, true, 32),
+            Instruction::FCVT_S_WU {rd, rs1, rm} if f => self.int_to_fp(F32, rd, rs1, self.rounding(rm, word)?, false, 32),
+            Instruction::FCVT_S_L {rd, rs1, rm} if f => self.int_to_fp(F32, rd, rs1, self.rounding(rm, word)?, true, 64),
+            Instruction::FCVT_S_LU {rd, rs1, rm} if f => self.int_to_fp(F32, rd, rs1, self.rounding(rm, word)?, false, 64),
+            Instruction::FMV_S_X {rd, rs1} if f => self.set_freg(F32, rd, self.get_reg(rs1) & 0xFFFF_FFFF),
+            Instruction::FLD {rd, rs1, imm} if d => {
+                let addr = self.addr(rs1, imm);
+                let value = self.load(mem, addr, 8)?;
+                self.set_freg(F64, rd, value);
+            },
+            Instruction::FSD {rs1, rs2, imm} if d => {
+                let addr = self.addr(rs1, imm);
+                self.store(mem, addr, 8, self.freg[rs2 as usize])?;
+            },
+            Instruction::FMADD_D {rd, rs1, rs2, rs3, rm} if d => self.fp_fused(F64, rd, rs1, rs2, rs3, self.rounding(rm, word)?, false, false),
+            Instruction::FMSUB_D {rd, rs1, rs2, rs3, rm} if d => self.fp_fused(F64, rd, rs1, rs2, rs3, self.rounding(rm, word)?, false, true),
+            Instruction::FNMSUB_D {rd, rs1, rs2, rs3, rm} if d => self.fp_fused(F64, rd, rs1, rs2, rs3, self.rounding(rm, word)?, true, false),
+            Instruction::FNMADD_D {rd, rs1, rs2, rs3, rm} if d => self.fp_fused(F64, rd, rs1, rs2, rs3, self.rounding(rm, word)?, true, true),
+            Instruction::FADD_D {rd, rs1, rs2, rm} if d => self.fp_binary(F64, rd, rs1, rs2, self.rounding(rm, word)?, Env::add),
+            Instruction::FSUB_D {rd, rs1, rs2, rm} if d => self.fp_binary(F64, rd, rs1, rs2, self.rounding(rm, word)?, Env::sub),
+            Instruction::FMUL_D {rd, rs1, rs2, rm} if d => self.fp_binary(F64, rd, rs1, rs2, self.rounding(rm, word)?, Env::mul),
+            Instruction::FDIV_D {rd, rs1, rs2, rm} if d => self.fp_binary(F64, rd, rs1, rs2, self.rounding(rm, word)?, Env::div),
+            Instruction::FSQRT_D {rd, rs1, rm} if d => {
+                let mut env = Env::new(self.rounding(rm, word)?);
+                let value = env.sqrt(F64, self.get_freg(F64, rs1));
+                self.fp_result(F64, rd, value, env);
+            },
+            Instruction::FSGNJ_D {rd, rs1, rs2} if d => self.fp_sign_inject(F64, rd, rs1, rs2, Injection::Copy),
+            Instruction::FSGNJN_D {rd, rs1, rs2} if d => self.fp_sign_inject(F64, rd, rs1, rs2, Injection::Negate),
+            Instruction::FSGNJX_D {rd, rs1, rs2} if d => self.fp_sign_inject(F64, rd, rs1, rs2, Injection::Xor),
+            Instruction::FMIN_D {rd, rs1, rs2} if d => self.fp_binary(F64, rd, rs1, rs2, Rounding::NearestEven, Env::min),
+            Instruction::FMAX_D {rd, rs1, rs2} if d => self.fp_binary(F64, rd, rs1, rs2, Rounding::NearestEven, Env::max),
+            Instruction::FCVT_S_D {rd, rs1, rm} if d => {
+                let mut env = Env::new(self.rounding(rm, word)?);
+                let value = env.convert(F64, F32, self.get_freg(F64, rs1));
+                self.fp_result(F32, rd, value, env);
+            },
+            Instruction::FCVT_D_S {rd, rs1, rm} if d => {
+                let mut env = Env::new(self.rounding(rm, word)?);
+                let value = env.convert(F32, F64, self.get_freg(F32, rs1));
+                self.fp_result(F64, rd, value, env);
+            },
+            Instruction::FEQ_D {rd, rs1, rs2} if d => self.fp_compare(F64, rd, rs1, rs2, Env::equal),
+            Instruction::FLT_D {rd, rs1, rs2} if d => self.fp_compare(F64, rd, rs1, rs2, Env::less),
+            Instruction::FLE_D {rd, rs1, rs2} if d => self.fp_compare(F64, rd, rs1, rs2, Env::less_equal),
+            Instruction::FCLASS_D {rd, rs1} if d => self.set_reg(rd, F64.classify(self.get_freg(F64, rs1))),
+            Instruction::FCVT_W_D {rd, rs1, rm} if d => self.fp_to_int(F64, rd, rs1, self.rounding(rm, word)?, true, 32),
+            Instruction::FCVT_WU_D {rd, rs1, rm} if d => self.fp_to_int(F64, rd, rs1, self.rounding(rm, word)?, false, 32),
+            Instruction::FCVT_L_D {rd, rs1, rm} if d => self.fp_to_int(F64, rd, rs1, self.rounding(rm, word)?, true, 64),
+            Instruction::FCVT_LU_D {rd, rs1, rm} if d => self.fp_to_int(F64, rd, rs1, self.rounding(rm, word)?, false, 64),
+            Instruction::FCVT_D_W {rd, rs1, rm} if d => self.int_to_fp(F64, rd, rs1, self.rounding(rm, word)?, true, 32),
+            Instruction::FCVT_D_WU {rd, rs1, rm} if d => self.int_to_fp(F64, rd, rs1, self.rounding(rm, word)?, false, 32),
+            Instruction::FCVT_D_L {rd, rs1, rm} if d => self.int_to_fp(F64, rd, rs1, self.rounding(rm, word)?, true, 64),
+            Instruction::FCVT_D_LU {rd, rs1, rm} if d => self.int_to_fp(F64, rd, rs1, self.rounding(rm, word)?, false, 64),
+            Instruction::FMV_X_D {rd, rs1} if d => self.set_reg(rd, self.freg[rs1 as usize]),
+            Instruction::FMV_D_X {rd, rs1} if d => self.set_freg(F64, rd, self.get_reg(rs1)),
+            /* single hart without caches, so both fences are no-ops */
+            Instruction::FENCE {..} | Instruction::FENCE_I => (),
+            Instruction::ECALL => return Err(Exception::EnvironmentCall(self.csr.privilege)),
+            Instruction::EBREAK => return Err(Exception::Breakpoint(pc)),
+            /* the privileged instructions fall through to an illegal
+             * instruction if the guard does not allow them */
+            Instruction::MRET if self.csr.privilege == Privilege::Machine => {
+                let status = self.csr.mstatus;
+                let mpp = Privilege::from_bits((status & mstatus::MPP) >> mstatus::MPP_SHIFT).unwrap_or(Privilege::User);
+                self.csr.mstatus &= !(mstatus::MIE | mstatus::MPP);
+                self.csr.mstatus |= ((status & mstatus::MPIE) >> 4) | mstatus::MPIE;
+                if mpp != Privilege::Machine {
+                    self.csr.mstatus &= !mstatus::MPRV;
+                }
+                self.csr.privilege = mpp;
+                self.reservation = None;
+                return Ok(self.csr.mepc());
+            },
+            Instruction::SRET if self.permitted(mstatus::TSR) => {
+                let status = self.csr.mstatus;
+                let spp = if (status & mstatus::SPP) != 0 { Privilege::Supervisor } else { Privilege::User };
+                self.csr.mstatus &= !(mstatus::SIE | mstatus::SPP | mstatus::MPRV);
+                self.csr.mstatus |= ((status & mstatus::SPIE) >> 4) | mstatus::SPIE;
+                self.csr.privilege = spp;
+                self.reservation = None;
+                return Ok(self.csr.sepc());
+            },
+            /* there is nothing to wait for, so WFI is a no-op */
+            Instruction::WFI if self.permitted(mstatus::TW) => (),
+            Instruction::SFENCE_VMA {rs1, rs2} if self.permitted(mstatus::TVM) => {
+                let vaddr = if rs1 != 0 { Some(self.get_reg(rs1)) } else { None };
+                let asid = if rs2 != 0 { Some(self.get_reg(rs2)) } else { None };
+                self.mmu.flush(vaddr, asid);
+            },
+            Instruction::CSRRW {rd, rs1, csr} => self.csr_access(word, rd, csr, CsrOp::Write(self.get_reg(rs1)), true)?,
+            Instruction::CSRRS {rd, rs1, csr} => self.csr_access(word, rd, csr, CsrOp::Set(self.get_reg(rs1)), rs1 != 0)?,
+            Instruction::CSRRC {rd, rs1, csr} => self.csr_access(word, rd, csr, CsrOp::Clear(self.get_reg(rs1)), rs1 != 0)?,
+            Instruction::CSRRWI {rd, zimm, csr} => self.csr_access(word, rd, csr, CsrOp::Write(zimm as u64), true)?,
+            Instruction::CSRRSI {rd, zimm, csr} => self.csr_access(word, rd, csr, CsrOp::Set(zimm as u64), zimm != 0)?,
+            Instruction::CSRRCI {rd, zimm, csr} => self.csr_access(word, rd, csr, CsrOp::Clear(zimm as u64), zimm != 0)?,
+            _ => return Err(Exception::IllegalInstruction(word))
+        }
+
+        Ok(next_pc)
+    }
+
+    /* The address of an atomic access, which has to be naturally aligned.
+     * It is translated as a store except for LR, so the faults of an AMO
+     * are store faults. The read of an AMO has to be allowed by the PMP
+     * as well. */
+    fn atomic_addr<M: Memory + ?Sized>(&mut self, mem: &mut M, rs1: u8, size: u64, access: AccessType)
+        -> Result<u64, Exception> {
+        let addr = self.get_reg(rs1);
+        if (addr & (size - 1)) != 0 {
+            return Err(match access {
+                AccessType::Load => Exception::LoadAddressMisaligned(addr),
+                _ => Exception::StoreAddressMisaligned(addr),
+            });
+        }
+        let paddr = self.translate(mem, addr, size, access)?;
+        if access == AccessType::Store && !self.csr.pmp.check(paddr, size, AccessType::Load, self.csr.data_privilege()) {
+            return Err(Exception::StoreAccessFault(addr));
+        }
+        Ok(paddr)
+    }
+
+    /* reads an aligned value of an atomic access, words are sign-extended */
+    fn atomic_read<M: Memory + ?Sized>(&mut self, mem: &mut M, addr: u64, paddr: u64, size: u64, fault: Exception)
+        -> Result<u64, Exception> {
+        let value = match size {
+            4 => mem.load32(paddr).map(|v| v as i32 as u64),
+            _ => mem.load64(paddr),
+        }.map_err(|_| fault)?;
+        if let Some(ref mut log) = self.log {
+            log.loads.push((addr, size));
+        }
+        Ok(value)
+    }
+
+    fn atomic_write<M: Memory + ?Sized>(&mut self, mem: &mut M, addr: u64, paddr: u64, size: u64, value: u64)
+        -> Result<(), Exception> {
+        match size {
+            4 => mem.store32(paddr, value as u32),
+            _ => mem.store64(paddr, value),
+        }.map_err(|_| Exception::StoreAccessFault(addr))?;
+        if let Some(ref mut log) = self.log {
+            let mask = if size == 8 { !0 } else { 0xFFFF_FFFF };
+            log.stores.push((addr, size, value & mask));
+        }
+        Ok(())
+    }
+
+    fn load_reserved<M: Memory + ?Sized>(&mut self, mem: &mut M, rd: u8, rs1: u8, size: u64) -> Result<(), Exception> {
+        let addr = self.get_reg(rs1);
+        let paddr = self.atomic_addr(mem, rs1, size, AccessType::Load)?;
+        let value = self.atomic_read(mem, addr, paddr, size, Exception::LoadAccessFault(addr))?;
+        self.reservation = Some(paddr & !(self.granule - 1));
+        self.set_reg(rd, value);
+        Ok(())
+    }
+
+    /* writes 0 to rd on success and 1 on failure, either way the
+     * reservation is gone afterwards */
+    fn store_conditional<M: Memory + ?Sized>(&mut self, mem: &mut M, rd: u8, rs1: u8, rs2: u8, size: u64)
+        -> Result<(), Exception> {
+        let addr = self.get_reg(rs1);
+        let paddr = self.atomic_addr(mem, rs1, size, AccessType::Store)?;
+        let success = self.reservation == Some(paddr & !(self.granule - 1));
+        if success {
+            self.atomic_write(mem, addr, paddr, size, self.get_reg(rs2))?;
+        }
+        self.reservation = None;
+        self.set_reg(rd, !success as u64);
+        Ok(())
+    }
+
+    /* Reads the old value into rd and writes op(old, rs2). For words both
+     * operands are sign-extended, which keeps the signed and the unsigned
+     * order of the 32-bit values. */
+    fn amo<M: Memory + ?Sized>(&mut self, mem: &mut M, rd: u8, rs1: u8, rs2: u8, size: u64, op: fn(u64, u64) -> u64)
+        -> Result<(), Exception> {
+        let addr = self.get_reg(rs1);
+        let paddr = self.atomic_addr(mem, rs1, size, AccessType::Store)?;
+        let old = self.atomic_read(mem, addr, paddr, size, Exception::StoreAccessFault(addr))?;
+        let src = if size == 4 { sext32(self.get_reg(rs2)) } else { self.get_reg(rs2) };
+        self.atomic_write(mem, addr, paddr, size, op(old, src))?;
+        self.set_reg(rd, old);
+        Ok(())
+    }
+
+    /* the rounding mode of a floating-point instruction, DYN reads frm and
+     * the reserved modes are illegal instructions */
+    fn rounding(&self, rm: FpRoundingMode, word: u32) -> Result<Rounding, Exception> {
+        let bits = match rm {
+            FpRoundingMode::RoundToNearest => 0,
+            FpRoundingMode::RoundTowardsZero => 1,
+            FpRoundingMode::RoundDown => 2,
+            FpRoundingMode::RoundUp => 3,
+            FpRoundingMode::RoundToNearestTieMaxMagnitude => 4,
+            FpRoundingMode::DynamicRounding => self.csr.frm(),
+            FpRoundingMode::Invalid => 7,
+        };
+        Rounding::from_bits(bits).ok_or(Exception::IllegalInstruction(word))
+    }
+
+    /* accrues the exception flags of an operation in fflags */
+    fn accrue(&mut self, env: Env) {
+        if env.flags != 0 {
+            self.csr.fcsr |= env.flags as u64;
+            self.csr.fp_dirty();
+        }
+    }
+
+    fn fp_result(&mut self, fmt: Format, rd: u8, value: u64, env: Env) {
+        self.accrue(env);
+        self.set_freg(fmt, rd, value);
+    }
+
+    fn fp_binary(&mut self, fmt: Format, rd: u8, rs1: u8, rs2: u8, rm: Rounding,
+                 op: fn(&mut Env, Format, u64, u64) -> u64) {
+        let mut env = Env::new(rm);
+        let value = op(&mut env, fmt, self.get_freg(fmt, rs1), self.get_freg(fmt, rs2));
+        self.fp_result(fmt, rd, value, env);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn fp_fused(&mut self, fmt: Format, rd: u8, rs1: u8, rs2: u8, rs3: u8, rm: Rounding,
+                negate_product: bool, negate_addend: bool) {
+        let mut env = Env::new(rm);
+        let (a, b, c) = (self.get_freg(fmt, rs1), self.get_freg(fmt, rs2), self.get_freg(fmt, rs3));
+        let value = env.mul_add(fmt, a, b, c, negate_product, negate_addend);
+        self.fp_result(fmt, rd, value, env);
+    }
+
+    fn fp_sign_inject(&mut self, fmt: Format, rd: u8, rs1: u8, rs2: u8, op: Injection) {
+        let (a, b) = (self.get_freg(fmt, rs1), self.get_freg(fmt, rs2));
+        let sign = fmt.sign_bit();
+        let value = match op {
+            Injection::Copy => (a & !sign) | (b & sign),
+            Injection::Negate => (a & !sign) | (!b & sign),
+            Injection::Xor => a ^ (b & sign),
+        };
+        self.set_freg(fmt, rd, value);
+    }
+
+    fn fp_compare(&mut self, fmt: Format, rd: u8, rs1: u8, rs2: u8, op: fn(&mut Env, Format, u64, u64) -> bool) {
+        let mut env = Env::new(Rounding::NearestEven);
+        let value = op(&mut env, fmt, self.get_freg(fmt, rs1), self.get_freg(fmt, rs2));
+        self.accrue(env);
+        self.set_reg(rd, value as u64);
+    }
+
+    /* the 32-bit results are sign-extended, even the unsigned ones */
+    fn fp_to_int(&mut self, fmt: Format, rd: u8, rs1: u8, rm: Rounding, signed: bool, bits: u32) {
+        let mut env = Env::new(rm);
+        let value = env.to_int(fmt, self.get_freg(fmt, rs1), signed, bits);
+        self.accrue(env);
+        self.set_reg(rd, if bits == 32 { sext32(value) } else { value });
+    }
+
+    fn int_to_fp(&mut self, fmt: Format, rd: u8, rs1: u8, rm: Rounding, signed: bool, bits: u32) {
+        let value = match (bits, signed) {
+            (32, true) => sext32(self.get_reg(rs1)),
+            (32, false) => self.get_reg(rs1) & 0xFFFF_FFFF,
+            _ => self.get_reg(rs1),
+        };
+        let mut env = Env::new(rm);
+        let value = env.from_int(fmt, value, signed);
+        self.fp_result(fmt, rd, value, env);
+    }
+
+    fn branch(&self, taken: bool, imm: i16, next_pc: u64) -> Result<u64, Exception> {
+        if taken {
+            self.jump_target(self.pc.wrapping_add(imm as i64 as u64))
+        } else {
+            Ok(next_pc)
+        }
+    }
+
+    /* whether an instruction that mstatus can trap for S-mode (TSR, TW or
+     * TVM) may execute, in U-mode these instructions are always illegal */
+    fn permitted(&self, trap: u64) -> bool {
+        match self.csr.privilege {
+            Privilege::Machine => true,
+            Privilege::Supervisor => (self.csr.mstatus & trap) == 0,
+            Privilege::User => false,
+        }
+    }
+
+    /* instructions are aligned to 16 bit with RVC and to 32 bit without */
+    fn jump_target(&self, target: u64) -> Result<u64, Exception> {
+        let target = target & self.xlen_mask();
+        let mask = if self.csr.has(misa::C) { 0x01 } else { 0x03 };
+        if (target & mask) != 0 {
+            Err(Exception::InstructionAddressMisaligned(target))
+        } else {
+            Ok(target)
+        }
+    }
+
+    /* effective address of loads and stores */
+    fn addr(&self, rs1: u8, imm: i16) -> u64 {
+        self.get_reg(rs1).wrapping_add(imm as i64 as u64) & self.xlen_mask()
+    }
+
+    /* translates a virtual address and checks the access against the PMP */
+    fn translate<M: Memory + ?Sized>(&mut self, mem: &mut M, addr: u64, size: u64, access: AccessType)
+        -> Result<u64, Exception> {
+        let paddr = self.mmu.translate(addr, access, &self.csr, mem)?;
+        let privilege = if access == AccessType::Fetch { self.csr.privilege } else { self.csr.data_privilege() };
+        if !self.csr.pmp.check(paddr, size, access, privilege) {
+            return Err(access.access_fault(addr));
+        }
+        Ok(paddr)
+    }
+
+    /* loads size bytes from a virtual address */
+    fn load<M: Memory + ?Sized>(&mut self, mem: &mut M, addr: u64, size: u64) -> Result<u64, Exception> {
+        let value = self.read(mem, addr, size)?;
+        if let Some(ref mut log) = self.log {
+            log.loads.push((addr, size));
+        }
+        Ok(value)
+    }
+
+    /* accesses that cross a page boundary are split into bytes as each page
+     * is translated separately */
+    fn read<M: Memory + ?Sized>(&mut self, mem: &mut M, addr: u64, size: u64) -> Result<u64, Exception> {
+        if (addr & 0xFFF) + size > 0x1000 {
+            let mut value = 0;
+            for i in 0..size {
+                let byte = self.read(mem, addr.wrapping_add(i) & self.xlen_mask(), 1)?;
+                value |= byte << (8 * i);
+            }
+            return Ok(value);
+        }
+        let paddr = self.translate(mem, addr, size, AccessType::Load)?;
+        match size {
+            1 => mem.load8(paddr).map(|v| v as u64),
+            2 => mem.load16(paddr).map(|v| v as u64),
+            4 => mem.load32(paddr).map(|v| v as u64),
+            _ => mem.load64(paddr),
+        }.map_err(|_| Exception::LoadAccessFault(addr))
+    }
+
+    /* stores the lower size bytes of value to a virtual address */
+    fn store<M: Memory + ?Sized>(&mut self, mem: &mut M, addr: u64, size: u64, value: u64) -> Result<(), Exception> {
+        self.write(mem, addr, size, value)?;
+        if let Some(ref mut log) = self.log {
+            let mask = if size == 8 { !0 } else { (1 << (8 * size)) - 1 };
+            log.stores.push((addr, size, value & mask));
+        }
+        Ok(())
+    }
+
+    /* both pages of a page crossing store are translated and all bytes are
+     * checked before memory is written, so a fault leaves memory as it was */
+    fn write<M: Memory + ?Sized>(&mut self, mem: &mut M, addr: u64, size: u64, value: u64) -> Result<(), Exception> {
+        let fault = Exception::StoreAccessFault(addr);
+        if (addr & 0xFFF) + size > 0x1000 {
+            let mut paddr = [0; 8];
+            for i in 0..size {
+                let vaddr = addr.wrapping_add(i) & self.xlen_mask();
+                paddr[i as usize] = self.translate(mem, vaddr, 1, AccessType::Store)?;
+            }
+            if !paddr[..size as usize].iter().all(|&paddr| mem.writable(paddr, 1)) {
+                return Err(fault);
+            }
+            for i in 0..size {
+                mem.store8(paddr[i as usize], (value >> (8 * i)) as u8).map_err(|_| fault)?;
+            }
+            return Ok(());
+        }
+        let paddr = self.translate(mem, addr, size, AccessType::Store)?;
+        match size {
+            1 => mem.store8(paddr, value as u8),
+            2 => mem.store16(paddr, value as u16),
+            4 => mem.store32(paddr, value as u32),
+            _ => mem.store64(paddr, value),
+        }.map_err(|_| fault)
+    }
+
+    /* Reads and optionally writes a CSR. Accessing an unknown CSR, a CSR of
+     * a higher privilege level or writing a read-only one is an illegal
+     * instruction. */
+    fn csr_access(&mut self, word: u32, rd: u8, csr: u16, op: CsrOp, write: bool) -> Result<(), Exception> {
+        if !self.csr.accessible(csr) {
+            return Err(Exception::IllegalInstruction(word));
+        }
+        let old = self.csr.read(csr).ok_or(Exception::IllegalInstruction(word))?;
+        if write {
+            /* the read-only CSRs have both upper bits of the number set */
+            if (csr >> 10) == 0b11 {
+                return Err(Exception::IllegalInstruction(word));
+            }
+            /* the lines of the interrupt controllers must not be latched
+             * into the software-writable SEIP bit */
+            let current = match csr {
+                MIP | SIP => old & !self.csr.external,
+                _ => old,
+            };
+            let value = match op {
+                CsrOp::Write(value) => value,
+                CsrOp::Set(mask) => current | mask,
+                CsrOp::Clear(mask) => current & !mask,
+            };
+            self.csr.write(csr, value);
+            /* cached translations of the old address space must not be used */
+            if csr == SATP {
+                self.mmu.flush(None, None);
+            }
+        }
+        self.set_reg(rd, old);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Core, Exception};
+    use super::super::csr::{Privilege, misa, mstatus, irq};
+    use super::super::assembler::assemble_into;
+    use super::super::memory::{Bus, Memory, Ram};
+
+    fn run(program: &[u32], steps: usize) -> (Core, Ram) {
+        let mut ram = Ram::new(0, 0x1000);
+        for (i, word) in program.iter().enumerate() {
+            ram.store32(4 * i as u64, *word).unwrap();
+        }
+        let mut core = Core::new(0, false);
+        for _ in 0..steps {
+            core.step(&mut ram).unwrap();
+        }
+        (core, ram)
+    }
+
+    #[test]
+    fn test_arith() {
+        let (core, _) = run(&[
+            0xBF400093, // addi x1, x0, -1036
+            0x12345137, // lui x2, 0x12345
+            0x00208033, // add x0, x1, x2
+            0x401101B3, // sub x3, x2, x1
+            0xFFF0A213, // slti x4, x1, -1
+            0xFFF0B293, // sltiu x5, x1, -1
+            0x4040D313, // srai x6, x1, 4
+            0x0040D393, // srli x7, x1, 4
+            0x00000417, // auipc x8, 0
+        ], 9);
+        assert_eq!(core.reg[0], 0);
+        assert_eq!(core.reg[1], -1036i32 as u32 as u64);
+        assert_eq!(core.reg[2], 0x12345000);
+        assert_eq!(core.reg[3], 0x1234540C);
+        assert_eq!(core.reg[4], 1);
+        assert_eq!(core.reg[5], 1);
+        assert_eq!(core.reg[6], -65i32 as u32 as u64);
+        assert_eq!(core.reg[7], 0x0FFFFFBF);
+        assert_eq!(core.reg[8], 32);
+        assert_eq!(core.pc, 36);
+        assert_eq!(core.csr.instret, 9);
+    }
+
+    fn load_asm(source: &str, rv64: bool) -> Ram {
+        let mut ram = Ram::new(0, 0x1000);
+        assemble_into(&mut ram, 0, source, rv64);
+        ram
+    }
+
+    /* assembles and runs a program until the first exception */
+    fn run_asm(source: &str, rv64: bool) -> (Core, Exception) {
+        let mut ram = load_asm(source, rv64);
+        let mut core = Core::new(0, rv64);
+        loop {
+            if let Err(e) = core.try_step(&mut ram) {
+                return (core, e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rv64() {
+        let (core, e) = run_asm("
+            li a0, -1
+            srli a1, a0, 33
+            slli a2, a0, 63
+            srai a3, a2, 40
+            addiw a4, a1, 1
+            slliw a5, a1, 1
+            srliw a6, a0, 4
+            li t1, 36
+            sraw t0, a4, t1
+            sll t2, a1, t1
+            subw s0, zero, a1
+            sd a2, 0x100(zero)
+            sw a0, 0x100(zero)
+            lwu s1, 0x100(zero)
+            lw s2, 0x100(zero)
+            ld s3, 0x100(zero)
+            slt s4, a2, a1
+            sltu s5, a2, a1
+            li s6, 0x123456789
+            ebreak
+        ", true);
+        assert_eq!(e, Exception::Breakpoint(core.pc));
+        assert_eq!(core.reg[10], 0xFFFFFFFFFFFFFFFF);
+        assert_eq!(core.reg[11], 0x7FFFFFFF);
+        assert_eq!(core.reg[12], 0x8000000000000000);
+        assert_eq!(core.reg[13], 0xFFFFFFFFFF800000);
+        assert_eq!(core.reg[14], 0xFFFFFFFF80000000);
+        assert_eq!(core.reg[15], 0xFFFFFFFFFFFFFFFE);
+        assert_eq!(core.reg[16], 0x0FFFFFFF);
+        assert_eq!(core.reg[5], 0xFFFFFFFFF8000000);
+        assert_eq!(core.reg[7], 0xFFFFFFF000000000);
+        assert_eq!(core.reg[8], 0xFFFFFFFF80000001);
+        assert_eq!(core.reg[9], 0xFFFFFFFF);
+        assert_eq!(core.reg[18], 0xFFFFFFFFFFFFFFFF);
+        assert_eq!(core.reg[19], 0x80000000FFFFFFFF);
+        assert_eq!(core.reg[20], 1);
+        assert_eq!(core.reg[21], 0);
+        assert_eq!(core.reg[22], 0x123456789);
+    }
+
+    #[test]
+    fn test_mul_div() {
+        let source = "
+            li a0, -7
+            li a1, 2
+            mul s0, a0, a1
+            mulh s1, a0, a1
+            mulhu s2, a0, a1
+            mulhsu s3, a0, a1
+            div s4, a0, a1
+            divu s5, a0, zero
+            rem s6, a0, a1
+            remu s7, a0, zero
+            ebreak
+        ";
+        let (core, _) = run_asm(source, false);
+        assert_eq!(core.reg[8], -14i32 as u32 as u64);
+        assert_eq!(core.reg[9], 0xFFFFFFFF);
+        assert_eq!(core.reg[18], 1);
+        assert_eq!(core.reg[19], 0xFFFFFFFF);
+        assert_eq!(core.reg[20], -3i32 as u32 as u64);
+        assert_eq!(core.reg[21], 0xFFFFFFFF);
+        assert_eq!(core.reg[22], -1i32 as u32 as u64);
+        assert_eq!(core.reg[23], -7i32 as u32 as u64);
+
+        let (core, _) = run_asm(source, true);
+        assert_eq!(core.reg[8], -14i64 as u64);
+        assert_eq!(core.reg[9], !0);
+        assert_eq!(core.reg[18], 1);
+        assert_eq!(core.reg[19], !0);
+
+        /* signed overflow */
+        let (core, _) = run_asm("
+            li a0, 1
+            slli a0, a0, 31
+            li a1, -1
+            div a2, a0, a1
+            rem a3, a0, a1
+            divw a4, a0, a1
+            remw a5, a0, a1
+            divuw a6, a0, zero
+            remuw a7, a0, zero
+            mulw t0, a0, a1
+            ebreak
+        ", true);
+        assert_eq!(core.reg[12], 0xFFFFFFFF80000000);
+        assert_eq!(core.reg[13], 0);
+        assert_eq!(core.reg[14], 0xFFFFFFFF80000000);
+        assert_eq!(core.reg[15], 0);
+        assert_eq!(core.reg[16], !0);
+        assert_eq!(core.reg[17], 0xFFFFFFFF80000000);
+        assert_eq!(core.reg[5], 0xFFFFFFFF80000000);
+
+        let mut ram = load_asm("mul a0, a0, a0", false);
+        let mut core = Core::new(0, false);
+        core.csr.misa &= !misa::M;
+        assert_eq!(core.try_step(&mut ram), Err(Exception::IllegalInstruction(0x02A50533)));
+    }
+
+    #[test]
+    fn test_traps() {
+        let mut ram = load_asm("
+                    la t0, handler
+                    csrw mtvec, t0
+                    li a0, 5
+                    ecall
+                    addi a0, a0, 1
+                    ebreak
+            handler:
+                    csrr t1, mcause
+                    csrr t2, mepc
+                    addi t2, t2, 4
+                    csrw mepc, t2
+                    csrr t3, mstatus
+                    mret
+        ", false);
+        let mut core = Core::new(0, false);
+        for _ in 0..4 {
+            assert_eq!(core.step(&mut ram), Ok(()));
+        }
+        assert_eq!(core.step(&mut ram), Err(Exception::EnvironmentCall(Privilege::Machine)));
+        assert_eq!(core.pc, 28);
+        for _ in 0..7 {
+            assert_eq!(core.step(&mut ram), Ok(()));
+        }
+        assert_eq!(core.pc, 24);
+        assert_eq!(core.reg[10], 6);
+        assert_eq!(core.reg[6], 11);
+        assert_eq!(core.reg[7], 20);
+        assert_eq!(core.reg[28], 0x3800);
+
+        assert_eq!(core.step(&mut ram), Err(Exception::Breakpoint(24)));
+        assert_eq!((core.pc, core.csr.mepc, core.csr.mcause, core.csr.mtval), (28, 24, 3, 24));
+
+        /* illegal instructions, including writes to read-only CSRs */
+        core.pc = 0x100;
+        assert_eq!(core.step(&mut ram), Err(Exception::IllegalInstruction(0)));
+        assert_eq!((core.pc, core.csr.mepc, core.csr.mcause, core.csr.mtval), (28, 0x100, 2, 0));
+        ram.store32(0x100, 0xF1401073).unwrap(); // csrw mhartid, x0
+        core.pc = 0x100;
+        assert_eq!(core.step(&mut ram), Err(Exception::IllegalInstruction(0xF1401073)));
+        ram.store32(0x100, 0xF1402573).unwrap(); // csrr a0, mhartid
+        core.pc = 0x100;
+        core.csr.mhartid = 5;
+        assert_eq!(core.step(&mut ram), Ok(()));
+        assert_eq!(core.reg[10], 5);
+    }
+
+    #[test]
+    fn test_interrupts() {
+        let mut ram = load_asm("
+            csrsi mstatus, 8
+            nop
+        ", true);
+        let mut core = Core::new(0, true);
+        core.csr.mtvec = 0x801;
+        core.csr.mie = 1 << irq::MTI;
+        core.csr.mip = 1 << irq::MTI;
+        assert_eq!(core.step(&mut ram), Ok(()));
+        assert_eq!(core.pc, 4);
+        assert_eq!(core.step(&mut ram), Ok(()));
+        assert_eq!(core.pc, 0x800 + 4 * irq::MTI);
+        assert_eq!(core.csr.mepc, 4);
+        assert_eq!(core.csr.mcause, (1 << 63) | irq::MTI);
+        assert_eq!(core.csr.mstatus & (mstatus::MIE | mstatus::MPIE), mstatus::MPIE);
+        assert_eq!(core.csr.instret, 1);
+
+        /* MRET enables the interrupts again, exceptions use the base */
+        ram.store32(0x81C, 0x30200073).unwrap(); // mret
+        core.csr.mip = 0;
+        assert_eq!(core.step(&mut ram), Ok(()));
+        assert_eq!(core.pc, 4);
+        assert_eq!(core.csr.mstatus & (mstatus::MIE | mstatus::MPIE), mstatus::MIE | mstatus::MPIE);
+        ram.store32(4, 0).unwrap();
+        assert_eq!(core.step(&mut ram), Err(Exception::IllegalInstruction(0)));
+        assert_eq!(core.pc, 0x800);
+    }
+
+    #[test]
+    fn test_privilege() {
+        let mut ram = load_asm("
+                    la t0, mhandler
+                    csrw mtvec, t0
+                    la t0, shandler
+                    csrw stvec, t0
+                    li t0, 0x100
+                    csrw medeleg, t0
+                    li t0, 0x1000
+                    csrc mstatus, t0
+                    la t0, supervisor
+                    csrw mepc, t0
+                    mret
+            supervisor:
+                    la t0, user
+                    csrw sepc, t0
+                    sret
+            user:
+                    ecall
+            shandler:
+                    csrr s0, scause
+                    csrr s1, sepc
+                    csrr s2, sstatus
+                    ecall
+            mhandler:
+                    csrr s3, mcause
+                    csrr s4, mstatus
+                    ebreak
+        ", true);
+        let mut core = Core::new(0, true);
+        for _ in 0..100 {
+            let pc = core.pc;
+            if core.step(&mut ram) == Err(Exception::Breakpoint(pc)) {
+                break;
+            }
+        }
+        assert_eq!(core.csr.privilege, Privilege::Machine);
+        assert_eq!(core.reg[8], 8);
+        assert_eq!(core.reg[9], 72);
+        assert_eq!(core.reg[18] & mstatus::SPP, 0);
+        assert_eq!(core.reg[18] & mstatus::MPP, 0);
+        assert_eq!(core.reg[19], 9);
+        assert_eq!((core.reg[20] & mstatus::MPP) >> mstatus::MPP_SHIFT, Privilege::Supervisor as u64);
+        assert_eq!(core.reg[20] >> 32, 0xA);
+    }
+
+    #[test]
+    fn test_paging() {
+        let source = "
+                    la t0, mhandler
+                    csrw mtvec, t0
+                    li t0, 0x8000000000000001
+                    csrw satp, t0
+                    li t0, 0x1000
+                    csrc mstatus, t0
+                    la t0, supervisor
+                    csrw mepc, t0
+                    mret
+            supervisor:
+                    li t0, 0x40000000
+                    li t1, 42
+                    sd t1, 8(t0)
+                    ld s0, 8(t0)
+                    li t0, 0x40001000
+                    ld s1, 0(t0)
+            mhandler:
+                    csrr s2, mcause
+                    csrr s3, mtval
+                    ebreak
+        ";
+        /* the first GiB is identity mapped, 0x40000000 maps to 0x4000 */
+        let mut ram = Ram::new(0, 0x5000);
+        assemble_into(&mut ram, 0, source, true);
+        ram.store64(0x1000, 0x0F).unwrap();
+        ram.store64(0x1008, (0x2 << 10) | 0x01).unwrap();
+        ram.store64(0x2000, (0x3 << 10) | 0x01).unwrap();
+        ram.store64(0x3000, (0x4 << 10) | 0x07).unwrap();
+
+        let mut core = Core::new(0, true);
+        for _ in 0..100 {
+            let pc = core.pc;
+            if core.step(&mut ram) == Err(Exception::Breakpoint(pc)) {
+                break;
+            }
+        }
+        assert_eq!(ram.load64(0x4008), Ok(42));
+        assert_eq!(core.reg[8], 42);
+        assert_eq!(core.reg[18], 13);
+        assert_eq!(core.reg[19], 0x40001000);
+        assert_eq!(ram.load64(0x1000), Ok(0x4F));
+        assert_eq!(ram.load64(0x3000), Ok((0x4 << 10) | 0xC7));
+    }
+
+    #[test]
+    fn test_pmp() {
+        let mut ram = load_asm("
+                    la t0, mhandler
+                    csrw mtvec, t0
+                    li t0, 0x200
+                    csrw pmpaddr0, t0
+                    li t0, 0x0F
+                    csrw pmpcfg0, t0
+                    li t0, 0x1800
+                    csrc mstatus, t0
+                    la t0, user
+                    csrw mepc, t0
+                    mret
+            user:
+                    lw s0, 0x7FC(zero)
+                    li t0, 0x800
+                    lw s1, 0(t0)
+            mhandler:
+                    csrr s2, mcause
+                    csrr s3, mtval
+                    lw s4, 0(t0)
+                    ebreak
+        ", false);
+        ram.store32(0x7FC, 0x12345678).unwrap();
+        ram.store32(0x800, 0x9ABCDEF0).unwrap();
+        let mut core = Core::new(0, false);
+        for _ in 0..100 {
+            let pc = core.pc;
+            if core.step(&mut ram) == Err(Exception::Breakpoint(pc)) {
+                break;
+            }
+        }
+        assert_eq!(core.reg[8], 0x12345678);
+        assert_eq!(core.reg[9], 0);
+        assert_eq!(core.reg[18], 5);
+        assert_eq!(core.reg[19], 0x800);
+        assert_eq!(core.reg[20], 0x9ABCDEF0);
+    }
+
+    #[test]
+    fn test_privileged_instructions() {
+        let mut ram = load_asm("
+            csrr a0, mstatus
+            rdcycle a0
+            sfence.vma
+            wfi
+            sret
+            mret
+        ", false);
+        let mut core = Core::new(0, false);
+        let illegal = |core: &mut Core, ram: &mut Ram, pc: u64| {
+            core.pc = pc;
+            matches!(core.try_step(ram), Err(Exception::IllegalInstruction(_)))
+        };
+
+        core.csr.privilege = Privilege::User;
+        for pc in [0, 4, 8, 12, 16, 20].iter() {
+            assert!(illegal(&mut core, &mut ram, *pc));
+        }
+        core.csr.mcounteren = 1;
+        core.csr.scounteren = 1;
+        assert!(!illegal(&mut core, &mut ram, 4));
+
+        core.csr.privilege = Privilege::Supervisor;
+        assert!(illegal(&mut core, &mut ram, 0));
+        assert!(!illegal(&mut core, &mut ram, 8));
+        assert!(!illegal(&mut core, &mut ram, 12));
+        assert!(illegal(&mut core, &mut ram, 20));
+        core.csr.mstatus |= mstatus::TVM | mstatus::TW | mstatus::TSR;
+        assert!(illegal(&mut core, &mut ram, 8));
+        assert!(illegal(&mut core, &mut ram, 12));
+        assert!(illegal(&mut core, &mut ram, 16));
+
+        core.csr.privilege = Privilege::Machine;
+        assert!(!illegal(&mut core, &mut ram, 12));
+        assert!(!illegal(&mut core, &mut ram, 16));
+        assert_eq!(core.csr.privilege, Privilege::User);
+    }
+
+    #[test]
+    fn test_interrupt_delegation() {
+        let mut ram = load_asm("nop", true);
+        let mut core = Core::new(0, true);
+        core.csr.stvec = 0x400;
+        core.csr.mtvec = 0x800;
+        core.csr.mideleg = 1 << irq::STI;
+        core.csr.mie = (1 << irq::STI) | (1 << irq::MTI);
+        core.csr.mip = 1 << irq::STI;
+
+        /* S-mode interrupts are masked by SIE in S-mode, but not in U-mode */
+        core.csr.privilege = Privilege::Supervisor;
+        assert_eq!(core.step(&mut ram), Ok(()));
+        assert_eq!(core.pc, 4);
+        core.pc = 0;
+        core.csr.privilege = Privilege::User;
+        assert_eq!(core.step(&mut ram), Ok(()));
+        assert_eq!(core.pc, 0x400);
+        assert_eq!(core.csr.scause, (1 << 63) | irq::STI);
+        assert_eq!(core.csr.privilege, Privilege::Supervisor);
+
+        /* M-mode interrupts preempt S-mode regardless of MIE */
+        core.csr.mip |= 1 << irq::MTI;
+        assert_eq!(core.step(&mut ram), Ok(()));
+        assert_eq!(core.pc, 0x800);
+        assert_eq!(core.csr.mcause, (1 << 63) | irq::MTI);
+        assert_eq!(core.csr.mepc, 0x400);
+        assert_eq!(core.csr.privilege, Privilege::Machine);
+    }
+
+    #[test]
+    fn test_rv32_wrap() {
+        let (core, _) = run(&[
+            0xFFF00093, // addi x1, x0, -1
+            0x00108113, // addi x2, x1, 1
+            0x01F09193, // slli x3, x1, 31
+            0x4011D213, // srai x4, x3, 1
+        ], 4);
+        assert_eq!(core.reg[1], 0xFFFFFFFF);
+        assert_eq!(core.reg[2], 0);
+        assert_eq!(core.reg[3], 0x80000000);
+        assert_eq!(core.reg[4], 0xC0000000);
+
+        let mut ram = Ram::new(0, 0x100);
+        ram.store32(0, 0x0000B083).unwrap(); // ld x1, 0(x1)
+        let mut core = Core::new(0, false);
+        assert_eq!(core.step(&mut ram), Err(Exception::IllegalInstruction(0x0000B083)));
+    }
+
+    #[test]
+    fn test_load_store() {
+        let (core, mut ram) = run(&[
+            0x40000093, // addi x1, x0, 1024
+            0xF8000113, // addi x2, x0, -128
+            0x0020A023, // sw x2, 0(x1)
+            0x00008183, // lb x3, 0(x1)
+            0x0000C203, // lbu x4, 0(x1)
+            0x0020D283, // lhu x5, 2(x1)
+            0x00209323, // sh x2, 6(x1)
+        ], 7);
+        assert_eq!(core.reg[3], -128i32 as u32 as u64);
+        assert_eq!(core.reg[4], 0x80);
+        assert_eq!(core.reg[5], 0xFFFF);
+        assert_eq!(ram.load32(1024), Ok(0xFFFFFF80));
+        assert_eq!(ram.load32(1028), Ok(0xFF800000));
+    }
+
+    #[test]
+    fn test_page_crossing_store() {
+        let mut bus = Bus::new();
+        bus.map_ram(0, 0x1000).unwrap();
+        bus.map_rom(0x1000, vec![0; 0x1000]).unwrap();
+        bus.store32(0, 0xFE112F23).unwrap(); // sw x1, -2(x2)
+        let mut core = Core::new(0, false);
+        core.reg[1] = 0x11223344;
+        core.reg[2] = 0x1000;
+
+        /* the upper half goes to ROM, so nothing is written */
+        bus.store16(0xFFE, 0xAAAA).unwrap();
+        assert_eq!(core.try_step(&mut bus), Err(Exception::StoreAccessFault(0xFFE)));
+        assert_eq!(bus.load16(0xFFE), Ok(0xAAAA));
+
+        core.reg[2] = 0xFFE;
+        assert_eq!(core.try_step(&mut bus), Ok(()));
+        assert_eq!(bus.load32(0xFFC), Ok(0x11223344));
+    }
+
+    #[test]
+    fn test_jumps() {
+        let (core, _) = run(&[
+            0x00C000EF, // jal x1, 12
+            0x00000013, // nop
+            0x00000013, // nop
+            0x00100113, // addi x2, x0, 1
+            0xFE208EE3, // beq x1, x2, -4
+            0x00208463, // beq x1, x2, 8
+            0x01C08167, // jalr x2, 28(x1)
+        ], 5);
+        assert_eq!(core.reg[1], 4);
+        assert_eq!(core.reg[2], 28);
+        assert_eq!(core.pc, 32);
+    }
+
+    #[test]
+    fn test_exceptions() {
+        let mut ram = Ram::new(0, 0x100);
+        ram.store32(0, 0x00200067).unwrap(); // jalr x0, 2(x0)
+        ram.store32(4, 0x00100073).unwrap(); // ebreak
+        ram.store32(8, 0xC0009073).unwrap(); // csrw cycle, x1
+        ram.store32(12, 0xFFFFFFFF).unwrap();
+        let mut core = Core::new(0, false);
+        core.csr.misa &= !misa::C;
+        assert_eq!(core.try_step(&mut ram), Err(Exception::InstructionAddressMisaligned(2)));
+        assert_eq!(core.pc, 0);
+        core.pc = 4;
+        assert_eq!(core.try_step(&mut ram), Err(Exception::Breakpoint(4)));
+        core.pc = 8;
+        assert_eq!(core.try_step(&mut ram), Err(Exception::IllegalInstruction(0xC0009073)));
+        core.pc = 12;
+        assert_eq!(core.try_step(&mut ram), Err(Exception::IllegalInstruction(0xFFFFFFFF)));
+        core.pc = 0x100;
+        assert_eq!(core.try_step(&mut ram), Err(Exception::InstructionAccessFault(0x100)));
+        assert_eq!(core.csr.instret, 0);
+    }
+
+    #[test]
+    fn test_atomics() {
+        let source = "
+            li a0, 0x100
+            li a1, 5
+            sw a1, 0(a0)
+            amoadd.w a2, a1, (a0)
+            li a3, -1
+            amomaxu.w a4, a3, (a0)
+            amomin.w a5, a1, (a0)
+            lr.w a6, (a0)
+            sc.w a7, a1, (a0)
+            sc.w s2, a3, (a0)
+            lr.d s3, (a0)
+            sd zero, 8(a0)
+            sc.d.rl s4, a3, (a0)
+            amoor.d s5, a1, (a0)
+            addi t0, a0, 2
+            amoswap.w s6, a1, (t0)
+        ";
+        let mut ram = load_asm(source, true);
+        let mut core = Core::new(0, true);
+        let e = loop {
+            if let Err(e) = core.try_step(&mut ram) {
+                break e;
+            }
+        };
+        assert_eq!(e, Exception::StoreAddressMisaligned(0x102));
+        assert_eq!(e.cause(), 6);
+        assert_eq!(core.reg[12], 5);
+        assert_eq!(core.reg[14], 10);
+        assert_eq!(core.reg[15], !0);
+        assert_eq!(core.reg[16], !0);
+        assert_eq!((core.reg[17], core.reg[18]), (0, 1));
+        assert_eq!(core.reg[19], 5);
+        /* a store of the hart itself keeps the reservation */
+        assert_eq!(core.reg[20], 0);
+        assert_eq!(core.reg[21], !0);
+        assert_eq!(ram.load64(0x100), Ok(!0));
+        assert_eq!(core.reservation, None);
+
+        /* stores of other harts and traps invalidate the reservation, the
+         * granule is configurable */
+        let mut ram = load_asm("lr.w a0, (a1)\nsc.w a0, a0, (a1)\nlr.w.aq a2, (a3)", false);
+        let mut core = Core::new(0, false);
+        core.reg[11] = 0x100;
+        core.reg[13] = 0x101;
+        core.granule = 8;
+        core.step(&mut ram).unwrap();
+        assert_eq!(core.reservation, Some(0x100));
+        core.snoop(0x108, 8);
+        assert_eq!(core.reservation, Some(0x100));
+        core.snoop(0xFF, 2);
+        core.step(&mut ram).unwrap();
+        assert_eq!(core.reg[10], 1);
+        core.pc = 0;
+        core.step(&mut ram).unwrap();
+        assert!(core.reservation.is_some());
+        core.pc = 8;
+        assert_eq!(core.step(&mut ram), Err(Exception::LoadAddressMisaligned(0x101)));
+        assert_eq!(core.reservation, None);
+
+        core.csr.misa &= !misa::A;
+        core.pc = 0;
+        assert_eq!(core.try_step(&mut ram), Err(Exception::IllegalInstruction(0x1005a52f)));
+    }
+
+    #[test]
+    fn test_float() {
+        let source = "
+            li a0, 3
+            fcvt.s.w ft0, a0
+            li a1, 1
+            fcvt.s.w ft1, a1
+            fdiv.s ft2, ft1, ft0
+            fmv.x.w a2, ft2
+            csrr a3, fflags
+            csrwi frm, 1
+            fdiv.s ft3, ft1, ft0
+            fmv.x.w a4, ft3
+            fcvt.d.s fa0, ft2
+            fsd fa0, 0x100(zero)
+            fld fa1, 0x100(zero)
+            feq.d a5, fa0, fa1
+            fmv.x.d a6, fa1
+            fneg.s ft4, ft0
+            fcvt.wu.s a7, ft4, rne
+            csrr s2, fcsr
+            li t0, 1
+            fmv.d.x ft5, t0
+            fadd.s ft6, ft5, ft1
+            fmv.x.w s3, ft6
+            fmv.x.w s4, ft5
+            csrwi frm, 5
+            fadd.s ft7, ft1, ft1, rne
+            fadd.s ft7, ft1, ft1
+        ";
+        let mut ram = load_asm(source, true);
+        let mut core = Core::new(0, true);
+        core.csr.misa |= misa::F | misa::D;
+        let e = loop {
+            if let Err(e) = core.try_step(&mut ram) {
+                break e;
+            }
+        };
+        assert_eq!(e, Exception::IllegalInstruction(0x0010F3D3));
+        assert_eq!(core.reg[12], 0x3EAA_AAAB);
+        assert_eq!(core.reg[13], 1);
+        assert_eq!(core.reg[14], 0x3EAA_AAAA);
+        assert_eq!(core.reg[15], 1);
+        assert_eq!(core.reg[16], 0x3FD5_5555_6000_0000);
+        assert_eq!(ram.load64(0x100), Ok(0x3FD5_5555_6000_0000));
+        assert_eq!(core.freg[4], 0xFFFF_FFFF_C040_0000);
+        /* a negative value is invalid for an unsigned conversion */
+        assert_eq!(core.reg[17], 0);
+        assert_eq!(core.reg[18], 0x31);
+        /* an improperly boxed single is the canonical NaN */
+        assert_eq!(core.reg[19], 0x7FC0_0000);
+        assert_eq!(core.reg[20], 1);
+        assert_eq!(core.freg[7], 0xFFFF_FFFF_4000_0000);
+        assert_eq!(core.csr.mstatus & mstatus::FS, mstatus::FS);
+
+        /* without F or with FS off the instructions and CSRs are illegal */
+        let mut ram = load_asm("fadd.s ft0, ft1, ft2\ncsrr a0, fflags", false);
+        let mut core = Core::new(0, false);
+        assert_eq!(core.try_step(&mut ram), Err(Exception::IllegalInstruction(0x0020F053)));
+        core.csr.misa |= misa::F;
+        core.csr.mstatus &= !mstatus::FS;
+        assert_eq!(core.try_step(&mut ram), Err(Exception::IllegalInstruction(0x0020F053)));
+        core.pc = 4;
+        assert_eq!(core.try_step(&mut ram), Err(Exception::IllegalInstruction(0x00102573)));
+        core.csr.mstatus |= mstatus::FS_INITIAL;
+        core.pc = 0;
+        assert_eq!(core.try_step(&mut ram), Ok(()));
+        assert_eq!(core.freg[0], 0xFFFF_FFFF_7FC0_0000);
+    }
+
+    #[test]
+    fn test_compressed() {
+        let mut ram = Ram::new(0, 0x100);
+        ram.write_bytes(0, &[
+            0x85, 0x45,             // c.li x11, 1
+            0x13, 0x06, 0x20, 0x00, // addi x12, x0, 2
+            0x2E, 0x95,             // c.add x10, x11
+            0xEF, 0x00, 0x60, 0x00, // jal x1, 6
+            0x01, 0x00,             // c.nop
+            0x82, 0x80,             // c.jr x1
+        ]).unwrap();
+        let mut core = Core::new(0, false);
+        for _ in 0..5 {
+            core.step(&mut ram).unwrap();
+        }
+        assert_eq!(core.reg[10], 1);
+        assert_eq!(core.reg[12], 2);
+        assert_eq!(core.reg[1], 12);
+        assert_eq!(core.pc, 12);
+
+        core.csr.misa &= !misa::C;
+        assert_eq!(core.try_step(&mut ram), Err(Exception::IllegalInstruction(0x0001)));
+    }
+
+    #[test]
+    fn test_counters() {
+        let (core, _) = run(&[
+            0x00000013, // nop
+            0xC00020F3, // rdcycle x1
+            0xC0202173, // rdinstret x2
+        ], 3);
+        assert_eq!(core.reg[1], 2);
+        assert_eq!(core.reg[2], 2);
+    }
 }
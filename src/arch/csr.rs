@@ -0,0 +1,632 @@
+/*
+ * csr.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::pmp::Pmp;
+
+/* Control and status registers of a hart with M, S and U-mode. Every CSR
+ * is stored XLEN wide, the read and write functions apply the WARL rules.
+ * The supervisor CSRs sstatus, sie and sip are views of their machine-mode
+ * counterparts. */
+
+/* CSR numbers */
+pub const FFLAGS     : u16 = 0x001;
+pub const FRM        : u16 = 0x002;
+pub const FCSR       : u16 = 0x003;
+pub const SSTATUS    : u16 = 0x100;
+pub const SIE        : u16 = 0x104;
+pub const STVEC      : u16 = 0x105;
+pub const SCOUNTEREN : u16 = 0x106;
+pub const SSCRATCH   : u16 = 0x140;
+pub const SEPC       : u16 = 0x141;
+pub const SCAUSE     : u16 = 0x142;
+pub const STVAL      : u16 = 0x143;
+pub const SIP        : u16 = 0x144;
+pub const SATP       : u16 = 0x180;
+pub const MSTATUS    : u16 = 0x300;
+pub const MISA       : u16 = 0x301;
+pub const MEDELEG    : u16 = 0x302;
+pub const MIDELEG    : u16 = 0x303;
+pub const MIE        : u16 = 0x304;
+pub const MTVEC      : u16 = 0x305;
+pub const MCOUNTEREN : u16 = 0x306;
+pub const MSCRATCH   : u16 = 0x340;
+pub const MEPC       : u16 = 0x341;
+pub const MCAUSE     : u16 = 0x342;
+pub const MTVAL      : u16 = 0x343;
+pub const MIP        : u16 = 0x344;
+pub const PMPCFG0    : u16 = 0x3A0;
+pub const PMPCFG15   : u16 = 0x3AF;
+pub const PMPADDR0   : u16 = 0x3B0;
+pub const PMPADDR63  : u16 = 0x3EF;
+pub const MCYCLE     : u16 = 0xB00;
+pub const MINSTRET   : u16 = 0xB02;
+pub const MCYCLEH    : u16 = 0xB80;
+pub const MINSTRETH  : u16 = 0xB82;
+pub const CYCLE      : u16 = 0xC00;
+pub const TIME       : u16 = 0xC01;
+pub const INSTRET    : u16 = 0xC02;
+pub const CYCLEH     : u16 = 0xC80;
+pub const TIMEH      : u16 = 0xC81;
+pub const INSTRETH   : u16 = 0xC82;
+pub const MVENDORID  : u16 = 0xF11;
+pub const MARCHID    : u16 = 0xF12;
+pub const MIMPID     : u16 = 0xF13;
+pub const MHARTID    : u16 = 0xF14;
+
+/* extension bits of misa */
+pub mod misa {
+    pub const A : u64 = 1 << 0;
+    pub const C : u64 = 1 << 2;
+    pub const D : u64 = 1 << 3;
+    pub const F : u64 = 1 << 5;
+    pub const I : u64 = 1 << 8;
+    pub const M : u64 = 1 << 12;
+    pub const S : u64 = 1 << 18;
+    pub const U : u64 = 1 << 20;
+
+    /* Parses an ISA string like rv64imafdc or rv32i2p1_m2p0_zicsr2p0 from
+     * the ELF attributes into misa including MXL. The version numbers and
+     * multi-letter extensions are ignored, S and U are not part of the ISA
+     * string and have to be added by the caller. */
+    pub fn parse(isa: &str) -> Option<u64> {
+        let isa = isa.to_ascii_lowercase();
+        let (mxl, rest) = if let Some(rest) = isa.strip_prefix("rv32") {
+            (1 << 30, rest)
+        } else {
+            (2 << 62, isa.strip_prefix("rv64")?)
+        };
+
+        let mut misa = mxl;
+        for (i, part) in rest.split('_').enumerate() {
+            if i > 0 && part.len() > 1 && !part[1..].starts_with(|c: char| c.is_ascii_digit()) {
+                continue;
+            }
+            for c in part.chars().filter(|c| c.is_ascii_lowercase() && *c != 'p') {
+                misa |= match c {
+                    'g' => I | M | A | F | D,
+                    _ => 1 << (c as u8 - b'a'),
+                };
+            }
+        }
+        if (misa & I) == 0 {
+            return None;
+        }
+        Some(misa)
+    }
+}
+
+/* fields of mstatus */
+pub mod mstatus {
+    pub const SIE       : u64 = 1 << 1;
+    pub const MIE       : u64 = 1 << 3;
+    pub const SPIE      : u64 = 1 << 5;
+    pub const MPIE      : u64 = 1 << 7;
+    pub const SPP       : u64 = 1 << 8;
+    pub const MPP_SHIFT : u64 = 11;
+    pub const MPP       : u64 = 0b11 << MPP_SHIFT;
+    pub const FS_SHIFT  : u64 = 13;
+    pub const FS        : u64 = 0b11 << FS_SHIFT;
+    pub const MPRV      : u64 = 1 << 17;
+    pub const SUM       : u64 = 1 << 18;
+    pub const MXR       : u64 = 1 << 19;
+    pub const TVM       : u64 = 1 << 20;
+    pub const TW        : u64 = 1 << 21;
+    pub const TSR       : u64 = 1 << 22;
+    pub const UXL       : u64 = 0b11 << 32;
+    pub const SXL       : u64 = 0b11 << 34;
+
+    /* states of FS, SD is set in the top bit if FS is dirty */
+    pub const FS_OFF     : u64 = 0;
+    pub const FS_INITIAL : u64 = 1 << FS_SHIFT;
+    pub const FS_DIRTY   : u64 = 3 << FS_SHIFT;
+}
+
+/* interrupt numbers, they are also the bit positions in mie and mip */
+pub mod irq {
+    pub const SSI : u64 = 1;
+    pub const MSI : u64 = 3;
+    pub const STI : u64 = 5;
+    pub const MTI : u64 = 7;
+    pub const SEI : u64 = 9;
+    pub const MEI : u64 = 11;
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub enum Privilege {
+    User = 0,
+    Supervisor = 1,
+    Machine = 3,
+}
+
+impl Privilege {
+    /* decodes the MPP and SPP fields, the reserved value 2 is None */
+    pub fn from_bits(bits: u64) -> Option<Privilege> {
+        match bits {
+            0 => Some(Privilege::User),
+            1 => Some(Privilege::Supervisor),
+            3 => Some(Privilege::Machine),
+            _ => None
+        }
+    }
+}
+
+/* the interrupts this hart implements and those that can be delegated */
+const MIP_MASK : u64 = 0xAAA;
+const MIDELEG_MASK : u64 = (1 << irq::SSI) | (1 << irq::STI) | (1 << irq::SEI);
+/* the M-mode interrupts are raised by devices, the S-mode ones by M-mode */
+const MIP_WRITABLE : u64 = MIDELEG_MASK;
+const SIP_WRITABLE : u64 = 1 << irq::SSI;
+/* every exception except the environment call from M-mode */
+const MEDELEG_MASK : u64 = 0xB3FF;
+const COUNTEREN_MASK : u64 = 0x07;
+const SSTATUS_MASK : u64 = mstatus::SIE | mstatus::SPIE | mstatus::SPP | mstatus::FS |
+    mstatus::SUM | mstatus::MXR | mstatus::UXL;
+const MSTATUS_WRITABLE : u64 = mstatus::SIE | mstatus::MIE | mstatus::SPIE | mstatus::MPIE |
+    mstatus::SPP | mstatus::FS | mstatus::MPRV | mstatus::SUM | mstatus::MXR | mstatus::TVM | mstatus::TW |
+    mstatus::TSR;
+
+/* interrupts in the order they are taken if several are pending */
+const IRQ_PRIORITY : [u64; 6] = [irq::MEI, irq::MSI, irq::MTI, irq::SEI, irq::SSI, irq::STI];
+
+pub struct CsrFile {
+    pub privilege  : Privilege,
+    pub misa       : u64,
+    pub mstatus    : u64,
+    pub medeleg    : u64,
+    pub mideleg    : u64,
+    pub mtvec      : u64,
+    pub mcounteren : u64,
+    pub mepc       : u64,
+    pub mcause     : u64,
+    pub mtval      : u64,
+    pub mie        : u64,
+    pub mip        : u64,
+    pub external   : u64, // interrupt lines of the controllers, ORed into mip
+    pub mscratch   : u64,
+    pub mhartid    : u64,
+    pub stvec      : u64,
+    pub scounteren : u64,
+    pub sscratch   : u64,
+    pub sepc       : u64,
+    pub scause     : u64,
+    pub stval      : u64,
+    pub satp       : u64,
+    pub cycle      : u64,
+    pub time       : Option<u64>, // mtime of the CLINT if there is one
+    pub instret    : u64,
+    pub fcsr       : u64, // frm and fflags
+    pub pmp        : Pmp,
+}
+
+impl CsrFile {
+    /* A hart in M-mode with the I, M, A, C, S and U extensions. FS starts in
+     * the initial state, so programs without a kernel that enables it can
+     * use the FPU once F or D are added to misa. */
+    pub fn new(rv64: bool, hartid: u64) -> CsrFile {
+        /* XLEN is 64 in every mode of a RV64 hart */
+        let (mxl, xl) = if rv64 { (2 << 62, (2 << 32) | (2 << 34)) } else { (1 << 30, 0) };
+        CsrFile {
+            privilege: Privilege::Machine,
+            misa: mxl | misa::I | misa::M | misa::A | misa::C | misa::S | misa::U,
+            mstatus: xl | mstatus::MPP | mstatus::FS_INITIAL,
+            medeleg: 0,
+            mideleg: 0,
+            mtvec: 0,
+            mcounteren: 0,
+            mepc: 0,
+            mcause: 0,
+            mtval: 0,
+            mie: 0,
+            mip: 0,
+            external: 0,
+            mscratch: 0,
+            mhartid: hartid,
+            stvec: 0,
+            scounteren: 0,
+            sscratch: 0,
+            sepc: 0,
+            scause: 0,
+            stval: 0,
+            satp: 0,
+            cycle: 0,
+            time: None,
+            instret: 0,
+            fcsr: 0,
+            pmp: Pmp::new(),
+        }
+    }
+
+    pub fn rv64(&self) -> bool {
+        (self.misa >> 62) == 2
+    }
+
+    /* true if all extension bits in ext are set in misa */
+    pub fn has(&self, ext: u64) -> bool {
+        (self.misa & ext) == ext
+    }
+
+    pub fn xlen_mask(&self) -> u64 {
+        if self.rv64() { !0 } else { 0xFFFF_FFFF }
+    }
+
+    /* floating-point instructions and CSRs are illegal without F or if
+     * mstatus.FS is off */
+    pub fn fp_enabled(&self) -> bool {
+        self.has(misa::F) && (self.mstatus & mstatus::FS) != mstatus::FS_OFF
+    }
+
+    /* the dynamic rounding mode */
+    pub fn frm(&self) -> u64 {
+        (self.fcsr >> 5) & 0x07
+    }
+
+    /* the floating-point state was changed, which is tracked by FS so the
+     * kernel knows it has to be saved */
+    pub fn fp_dirty(&mut self) {
+        self.mstatus |= mstatus::FS_DIRTY;
+    }
+
+    /* mstatus with SD summarizing FS */
+    fn status(&self) -> u64 {
+        if (self.mstatus & mstatus::FS) == mstatus::FS_DIRTY {
+            self.mstatus | (1 << (self.xlen_mask().count_ones() - 1))
+        } else {
+            self.mstatus
+        }
+    }
+
+    /* the privilege level of loads and stores, which is MPP if MPRV is set */
+    pub fn data_privilege(&self) -> Privilege {
+        if (self.mstatus & mstatus::MPRV) != 0 {
+            Privilege::from_bits((self.mstatus & mstatus::MPP) >> mstatus::MPP_SHIFT).unwrap_or(Privilege::User)
+        } else {
+            self.privilege
+        }
+    }
+
+    /* with IALIGN=32 the second bit of xepc reads as zero */
+    fn epc_mask(&self) -> u64 {
+        if self.has(misa::C) { !0 } else { !3 }
+    }
+
+    pub fn mepc(&self) -> u64 {
+        self.mepc & self.epc_mask()
+    }
+
+    pub fn sepc(&self) -> u64 {
+        self.sepc & self.epc_mask()
+    }
+
+    /* Whether the current privilege level may access a CSR. The level is
+     * encoded in the CSR number, the counters can additionally be hidden
+     * from lower levels and satp is trapped by mstatus.TVM. */
+    pub fn accessible(&self, csr: u16) -> bool {
+        let level = (csr >> 8) & 0b11;
+        if (self.privilege as u16) < level {
+            return false;
+        }
+        match csr {
+            CYCLE ..= 0xC1F | CYCLEH ..= 0xC9F => {
+                let bit = 1 << (csr & 0x1F);
+                match self.privilege {
+                    Privilege::Machine => true,
+                    Privilege::Supervisor => (self.mcounteren & bit) != 0,
+                    Privilege::User => (self.mcounteren & self.scounteren & bit) != 0,
+                }
+            },
+            SATP => self.privilege == Privilege::Machine || (self.mstatus & mstatus::TVM) == 0,
+            FFLAGS ..= FCSR => self.fp_enabled(),
+            _ => true
+        }
+    }
+
+    /* returns None if the CSR does not exist */
+    pub fn read(&self, csr: u16) -> Option<u64> {
+        let rv32 = !self.rv64();
+        let value = match csr {
+            FFLAGS if self.has(misa::F) => self.fcsr & 0x1F,
+            FRM if self.has(misa::F) => self.frm(),
+            FCSR if self.has(misa::F) => self.fcsr,
+            SSTATUS => self.status() & (SSTATUS_MASK | 1 << 63 | 1 << 31),
+            SIE => self.mie & self.mideleg,
+            STVEC => self.stvec,
+            SCOUNTEREN => self.scounteren,
+            SSCRATCH => self.sscratch,
+            SEPC => self.sepc(),
+            SCAUSE => self.scause,
+            STVAL => self.stval,
+            SIP => self.ip() & self.mideleg,
+            SATP => self.satp,
+            MSTATUS => self.status(),
+            MISA => self.misa,
+            MEDELEG => self.medeleg,
+            MIDELEG => self.mideleg,
+            MIE => self.mie,
+            MTVEC => self.mtvec,
+            MCOUNTEREN => self.mcounteren,
+            MSCRATCH => self.mscratch,
+            MEPC => self.mepc(),
+            MCAUSE => self.mcause,
+            MTVAL => self.mtval,
+            MIP => self.ip(),
+            /* the odd pmpcfg registers only exist in RV32 */
+            PMPCFG0 ..= PMPCFG15 if rv32 || (csr & 1) == 0 => self.pmp.read_cfg((csr - PMPCFG0) as usize, !rv32),
+            PMPADDR0 ..= PMPADDR63 => self.pmp.read_addr((csr - PMPADDR0) as usize),
+            MCYCLE | CYCLE => self.cycle,
+            /* without a CLINT time follows the cycle counter */
+            TIME => self.time.unwrap_or(self.cycle),
+            MINSTRET | INSTRET => self.instret,
+            /* the upper halves only exist in RV32 */
+            MCYCLEH | CYCLEH if rv32 => self.cycle >> 32,
+            TIMEH if rv32 => self.time.unwrap_or(self.cycle) >> 32,
+            MINSTRETH | INSTRETH if rv32 => self.instret >> 32,
+            MVENDORID | MARCHID | MIMPID => 0,
+            MHARTID => self.mhartid,
+            _ => return None
+        };
+        Some(value & self.xlen_mask())
+    }
+
+    /* Writes a CSR that exists and is not read-only. Fields that are not
+     * implemented are hardwired to zero, illegal values are ignored. */
+    pub fn write(&mut self, csr: u16, value: u64) {
+        let value = value & self.xlen_mask();
+        match csr {
+            FFLAGS => {
+                self.fcsr = (self.fcsr & !0x1F) | (value & 0x1F);
+                self.fp_dirty();
+            },
+            FRM => {
+                self.fcsr = (self.fcsr & 0x1F) | ((value & 0x07) << 5);
+                self.fp_dirty();
+            },
+            FCSR => {
+                self.fcsr = value & 0xFF;
+                self.fp_dirty();
+            },
+            SSTATUS => {
+                let mask = SSTATUS_MASK & MSTATUS_WRITABLE;
+                self.mstatus = (self.mstatus & !mask) | (value & mask);
+            },
+            SIE => self.mie = (self.mie & !self.mideleg) | (value & self.mideleg),
+            STVEC => self.stvec = value & !0b10,
+            SCOUNTEREN => self.scounteren = value & COUNTEREN_MASK,
+            SSCRATCH => self.sscratch = value,
+            SEPC => self.sepc = value & !1,
+            SCAUSE => self.scause = value,
+            STVAL => self.stval = value,
+            SIP => {
+                let mask = SIP_WRITABLE & self.mideleg;
+                self.mip = (self.mip & !mask) | (value & mask);
+            },
+            /* Bare and Sv32 on RV32, Bare, Sv39 and Sv48 on RV64. Writing an
+             * unsupported mode has no effect at all. */
+            SATP => {
+                let legal = if self.rv64() {
+                    matches!(value >> 60, 0 | 8 | 9)
+                } else {
+                    true
+                };
+                if legal {
+                    self.satp = value & self.xlen_mask();
+                }
+            },
+            MSTATUS => {
+                let mut mstatus = (self.mstatus & !MSTATUS_WRITABLE) | (value & MSTATUS_WRITABLE);
+                /* MPP keeps its value if a reserved level is written */
+                if Privilege::from_bits((value & mstatus::MPP) >> mstatus::MPP_SHIFT).is_some() {
+                    mstatus = (mstatus & !mstatus::MPP) | (value & mstatus::MPP);
+                }
+                self.mstatus = mstatus;
+            },
+            /* the extensions cannot be switched off */
+            MISA => (),
+            MEDELEG => self.medeleg = value & MEDELEG_MASK,
+            MIDELEG => self.mideleg = value & MIDELEG_MASK,
+            MIE => self.mie = value & MIP_MASK,
+            /* direct and vectored mode are supported */
+            MTVEC => self.mtvec = value & !0b10,
+            MCOUNTEREN => self.mcounteren = value & COUNTEREN_MASK,
+            MSCRATCH => self.mscratch = value,
+            MEPC => self.mepc = value & !1,
+            MCAUSE => self.mcause = value,
+            MTVAL => self.mtval = value,
+            MIP => self.mip = (self.mip & !MIP_WRITABLE) | (value & MIP_WRITABLE),
+            PMPCFG0 ..= PMPCFG15 if !self.rv64() || (csr & 1) == 0 => self.pmp.write_cfg((csr - PMPCFG0) as usize, value, self.rv64()),
+            PMPADDR0 ..= PMPADDR63 => self.pmp.write_addr((csr - PMPADDR0) as usize, value, self.rv64()),
+            MCYCLE if self.rv64() => self.cycle = value,
+            MCYCLE => self.cycle = (self.cycle & !0xFFFF_FFFF) | value,
+            MCYCLEH => self.cycle = (self.cycle & 0xFFFF_FFFF) | (value << 32),
+            MINSTRET if self.rv64() => self.instret = value,
+            MINSTRET => self.instret = (self.instret & !0xFFFF_FFFF) | value,
+            MINSTRETH => self.instret = (self.instret & 0xFFFF_FFFF) | (value << 32),
+            _ => ()
+        }
+    }
+
+    /* the pending interrupts, SEIP is pending if either software or the
+     * PLIC sets it */
+    fn ip(&self) -> u64 {
+        self.mip | self.external
+    }
+
+    /* The highest priority interrupt that is pending and enabled together
+     * with the privilege level that handles it. Interrupts for a higher
+     * level are always enabled, those for the current level only if the
+     * global enable bit is set and those for a lower level never. */
+    pub fn pending_interrupt(&self) -> Option<(u64, Privilege)> {
+        let pending = self.ip() & self.mie;
+        let enabled = |target: Privilege, ie: u64| {
+            self.privilege < target || (self.privilege == target && (self.mstatus & ie) != 0)
+        };
+        IRQ_PRIORITY.iter()
+            .filter(|&&i| (pending & (1 << i)) != 0)
+            .map(|&i| if (self.mideleg & (1 << i)) != 0 { (i, Privilege::Supervisor) } else { (i, Privilege::Machine) })
+            .find(|&(_, target)| match target {
+                Privilege::Machine => enabled(Privilege::Machine, mstatus::MIE),
+                _ => enabled(Privilege::Supervisor, mstatus::SIE),
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CsrFile, Privilege, misa, mstatus, irq, MISA, MHARTID, MSTATUS, SSTATUS, MTVEC, MEPC,
+                MIE, MIP, SIE, SIP, MIDELEG, MEDELEG, SATP, MCYCLE, MCYCLEH, CYCLE, CYCLEH,
+                PMPCFG0, PMPADDR63, FFLAGS, FRM, FCSR};
+
+    #[test]
+    fn test_warl() {
+        let mut csr = CsrFile::new(false, 3);
+        assert_eq!(csr.read(MISA), Some(0x40141105));
+        assert_eq!(csr.read(MHARTID), Some(3));
+        assert_eq!(csr.read(0x7C0), None);
+
+        csr.write(MSTATUS, 0xFFFFFFFF);
+        assert_eq!(csr.read(MSTATUS), Some(0x807E79AA));
+        assert_eq!(csr.read(SSTATUS), Some(0x800C6122));
+        csr.write(MSTATUS, 0);
+        assert_eq!(csr.read(MSTATUS), Some(0));
+        csr.write(MSTATUS, 0x1000);
+        assert_eq!(csr.read(MSTATUS), Some(0));
+        csr.write(SSTATUS, 0xFFFFFFFF);
+        assert_eq!(csr.read(MSTATUS), Some(0x800C6122));
+
+        /* the FP CSRs only exist with F and dirty FS */
+        assert_eq!(csr.read(FCSR), None);
+        csr.misa |= misa::F;
+        csr.mstatus &= !mstatus::FS;
+        assert!(!csr.accessible(FFLAGS));
+        csr.mstatus |= mstatus::FS_INITIAL;
+        csr.write(FCSR, 0xFFF);
+        assert_eq!((csr.read(FFLAGS), csr.read(FRM), csr.read(FCSR)), (Some(0x1F), Some(7), Some(0xFF)));
+        csr.write(FRM, 0x0A);
+        csr.write(FFLAGS, 0x21);
+        assert_eq!(csr.read(FCSR), Some(0x41));
+        assert_eq!(csr.read(MSTATUS).unwrap() & 0x80006000, 0x80006000);
+
+        csr.write(MTVEC, 0x80000003);
+        assert_eq!(csr.read(MTVEC), Some(0x80000001));
+        csr.write(MEPC, 0x80000003);
+        assert_eq!(csr.read(MEPC), Some(0x80000002));
+        csr.misa &= !misa::C;
+        assert_eq!(csr.read(MEPC), Some(0x80000000));
+
+        csr.write(MEDELEG, 0xFFFFFFFF);
+        assert_eq!(csr.read(MEDELEG), Some(0xB3FF));
+        csr.write(MIDELEG, 0xFFFFFFFF);
+        assert_eq!(csr.read(MIDELEG), Some(0x222));
+        csr.write(MIE, 0xFFFFFFFF);
+        assert_eq!(csr.read(MIE), Some(0xAAA));
+        csr.write(SIE, 0);
+        assert_eq!(csr.read(MIE), Some(0x888));
+        csr.write(MIP, 0xFFFFFFFF);
+        assert_eq!(csr.read(MIP), Some(0x222));
+        csr.write(SIP, 0);
+        assert_eq!(csr.read(SIP), Some(0x220));
+
+        csr.write(SATP, 0x80000001);
+        assert_eq!(csr.read(SATP), Some(0x80000001));
+
+        csr.write(MCYCLEH, 1);
+        csr.write(MCYCLE, 2);
+        assert_eq!(csr.cycle, 0x100000002);
+        assert_eq!(csr.read(CYCLEH), Some(1));
+
+        let mut csr = CsrFile::new(true, 0);
+        assert_eq!(csr.read(MISA), Some(0x8000000000141105));
+        assert_eq!(csr.read(MSTATUS), Some(0xA00003800));
+        assert_eq!(csr.read(SSTATUS), Some(0x200002000));
+        assert_eq!(csr.read(CYCLEH), None);
+        csr.write(SATP, 0x9000000000000001);
+        assert_eq!(csr.read(SATP), Some(0x9000000000000001));
+        csr.write(SATP, 0xA000000000000001);
+        assert_eq!(csr.read(SATP), Some(0x9000000000000001));
+        csr.write(PMPADDR63, !0);
+        assert_eq!(csr.read(PMPADDR63), Some(0x3FFFFFFFFFFFFF));
+        assert_eq!(csr.read(PMPCFG0 + 1), None);
+        csr.write(PMPCFG0 + 15, !0);
+        assert_eq!(csr.read(PMPCFG0 + 14), Some(0));
+    }
+
+    #[test]
+    fn test_parse_isa() {
+        assert_eq!(misa::parse("rv32imafdc"), Some((1 << 30) | 0x112D));
+        assert_eq!(misa::parse("RV64GC"), Some((2 << 62) | 0x112D));
+        assert_eq!(misa::parse("rv32i2p1_m2p0_a2p1_c2p0_zicsr2p0_zifencei2p0"), Some((1 << 30) | 0x1105));
+        assert_eq!(misa::parse("rv64e"), None);
+        assert_eq!(misa::parse("x86"), None);
+    }
+
+    #[test]
+    fn test_accessible() {
+        let mut csr = CsrFile::new(true, 0);
+        assert!(csr.accessible(MSTATUS));
+        csr.privilege = Privilege::Supervisor;
+        assert!(!csr.accessible(MSTATUS));
+        assert!(csr.accessible(SSTATUS));
+        assert!(!csr.accessible(CYCLE));
+        csr.mcounteren = 1;
+        assert!(csr.accessible(CYCLE));
+        assert!(csr.accessible(SATP));
+        csr.mstatus |= mstatus::TVM;
+        assert!(!csr.accessible(SATP));
+        csr.privilege = Privilege::User;
+        assert!(!csr.accessible(SSTATUS));
+        assert!(!csr.accessible(CYCLE));
+        csr.scounteren = 1;
+        assert!(csr.accessible(CYCLE));
+    }
+
+    #[test]
+    fn test_pending_interrupt() {
+        let mut csr = CsrFile::new(true, 0);
+        csr.mip = (1 << irq::MTI) | (1 << irq::MSI);
+        csr.mie = 1 << irq::MTI;
+        assert_eq!(csr.pending_interrupt(), None);
+        csr.mstatus |= mstatus::MIE;
+        assert_eq!(csr.pending_interrupt(), Some((irq::MTI, Privilege::Machine)));
+        csr.mie |= 1 << irq::MSI;
+        assert_eq!(csr.pending_interrupt(), Some((irq::MSI, Privilege::Machine)));
+
+        csr.mstatus = 0;
+        csr.mip = 1 << irq::SEI;
+        csr.mie = 1 << irq::SEI;
+        csr.mideleg = 1 << irq::SEI;
+        assert_eq!(csr.pending_interrupt(), None);
+        csr.privilege = Privilege::Supervisor;
+        assert_eq!(csr.pending_interrupt(), None);
+        csr.mstatus = mstatus::SIE;
+        assert_eq!(csr.pending_interrupt(), Some((irq::SEI, Privilege::Supervisor)));
+        csr.mstatus = 0;
+        csr.privilege = Privilege::User;
+        assert_eq!(csr.pending_interrupt(), Some((irq::SEI, Privilege::Supervisor)));
+
+        /* the lines of the controllers are ORed into mip */
+        csr.mip = 0;
+        assert_eq!(csr.pending_interrupt(), None);
+        csr.external = 1 << irq::SEI;
+        assert_eq!(csr.pending_interrupt(), Some((irq::SEI, Privilege::Supervisor)));
+        assert_eq!(csr.read(MIP), Some(1 << irq::SEI));
+        assert_eq!(csr.mip, 0);
+    }
+}
@@ -22,6 +22,9 @@
 
 #![allow(dead_code,non_camel_case_types)]
 
+use std::error::Error;
+use std::fmt;
+
 use super::constants::*;
 
 #[derive(PartialEq, Debug)]
@@ -35,6 +38,75 @@ pub enum FpRoundingMode {
     Invalid
 }
 
+/* the part of the instruction word that made it undecodable */
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Field {
+    Funct2,
+    Funct3,
+    Funct5,
+    Funct7,
+    Funct12,
+    Fmt,
+    Rd,
+    Rs2,
+    Imm,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DecodeError {
+    /* the lower two bits are not 11, so this is a compressed instruction */
+    Compressed {word: u32},
+    /* the lower two bits are 11, so this is not a compressed instruction */
+    NotCompressed {word: u32},
+    /* not enough bytes for the whole instruction */
+    Truncated {len: usize},
+    IllegalOpcode {word: u32, opcode: u8},
+    Reserved {word: u32, field: Field, value: u32},
+    /* valid instruction that only exists in RV64 */
+    Rv64Only {word: u32},
+    MalformedFence {word: u32},
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Field::Funct2 => "funct2",
+            Field::Funct3 => "funct3",
+            Field::Funct5 => "funct5",
+            Field::Funct7 => "funct7",
+            Field::Funct12 => "funct12",
+            Field::Fmt => "fmt",
+            Field::Rd => "rd",
+            Field::Rs2 => "rs2",
+            Field::Imm => "immediate",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::Compressed {word} =>
+                write!(f, "0x{:04x} is a compressed instruction", word),
+            DecodeError::NotCompressed {word} =>
+                write!(f, "0x{:04x} is not a compressed instruction", word),
+            DecodeError::Truncated {len} =>
+                write!(f, "instruction truncated after {} byte(s)", len),
+            DecodeError::IllegalOpcode {word, opcode} =>
+                write!(f, "illegal opcode 0b{:05b} in 0x{:08x}", opcode, word),
+            DecodeError::Reserved {word, field, value} =>
+                write!(f, "reserved {} 0x{:x} in 0x{:08x}", field, value, word),
+            DecodeError::Rv64Only {word} =>
+                write!(f, "0x{:08x} is only valid in RV64", word),
+            DecodeError::MalformedFence {word} =>
+                write!(f, "malformed FENCE 0x{:08x}", word),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
 /* all immeadiates are already shifted */
 #[derive(PartialEq, Debug)]
 pub enum Instruction {
@@ -80,6 +152,10 @@ pub enum Instruction {
     FENCE_I,
     ECALL,
     EBREAK,
+    MRET,
+    SRET,
+    WFI,
+    SFENCE_VMA {rs1: u8, rs2: u8},
     CSRRW {rd: u8, rs1: u8, csr: u16},
     CSRRS {rd: u8, rs1: u8, csr: u16},
     CSRRC {rd: u8, rs1: u8, csr: u16},
@@ -120,7 +196,7 @@ pub enum Instruction {
 
     /* RV32A */
     LR_W {rd: u8, rs1: u8, aq: bool, rl: bool},
-    SC_W {rd: u8, rs1: u8, aq: bool, rl: bool},
+    SC_W {rd: u8, rs1: u8, rs2: u8, aq: bool, rl: bool},
     AMOSWAP_W {rd: u8, rs1: u8, rs2: u8, aq: bool, rl: bool},
     AMOADD_W {rd: u8, rs1: u8, rs2: u8, aq: bool, rl: bool},
     AMOXOR_W {rd: u8, rs1: u8, rs2: u8, aq: bool, rl: bool},
@@ -133,7 +209,7 @@ pub enum Instruction {
 
     /* RV64A */
     LR_D {rd: u8, rs1: u8, aq: bool, rl: bool},
-    SC_D {rd: u8, rs1: u8, aq: bool, rl: bool},
+    SC_D {rd: u8, rs1: u8, rs2: u8, aq: bool, rl: bool},
     AMOSWAP_D {rd: u8, rs1: u8, rs2: u8, aq: bool, rl: bool},
     AMOADD_D {rd: u8, rs1: u8, rs2: u8, aq: bool, rl: bool},
     AMOXOR_D {rd: u8, rs1: u8, rs2: u8, aq: bool, rl: bool},
@@ -146,7 +222,7 @@ pub enum Instruction {
 
     /* RV32F */
     FLW {rd: u8, rs1: u8, imm: i16},
-    FSW {rd: u8, rs1: u8, imm: i16},
+    FSW {rs1: u8, rs2: u8, imm: i16},
     FMADD_S {rd: u8, rs1: u8, rs2: u8, rs3: u8, rm: FpRoundingMode},
     FMSUB_S {rd: u8, rs1: u8, rs2: u8, rs3: u8, rm: FpRoundingMode},
     FNMSUB_S {rd: u8, rs1: u8, rs2: u8, rs3: u8, rm: FpRoundingMode},
@@ -180,7 +256,7 @@ pub enum Instruction {
 
     /* RV32D */
     FLD {rd: u8, rs1: u8, imm: i16},
-    FSD {rd: u8, rs1: u8, imm: i16},
+    FSD {rs1: u8, rs2: u8, imm: i16},
     FMADD_D {rd: u8, rs1: u8, rs2: u8, rs3: u8, rm: FpRoundingMode},
     FMSUB_D {rd: u8, rs1: u8, rs2: u8, rs3: u8, rm: FpRoundingMode},
     FNMSUB_D {rd: u8, rs1: u8, rs2: u8, rs3: u8, rm: FpRoundingMode},
@@ -206,7 +282,7 @@ pub enum Instruction {
     FCVT_D_W {rd: u8, rs1: u8, rm: FpRoundingMode},
     FCVT_D_WU {rd: u8, rs1: u8, rm: FpRoundingMode},
 
-    /* RV64F */
+    /* RV64D */
     FCVT_L_D {rd: u8, rs1: u8, rm: FpRoundingMode},
     FCVT_LU_D {rd: u8, rs1: u8, rm: FpRoundingMode},
     FMV_X_D {rd: u8, rs1: u8},
@@ -269,117 +345,521 @@ fn get_jal_imm20(word: u32) -> i32 {
     }
 }
 
+fn decode_amo(instr: u32, rv64: bool) -> Result<Instruction, DecodeError> {
+    let rd     : u8 = ((instr >> 7) & 0x1F) as u8;
+    let rs1    : u8 = ((instr >> 15) & 0x1F) as u8;
+    let rs2    : u8 = ((instr >> 20) & 0x1F) as u8;
+    let funct3 : u8 = ((instr >> 12) & 0x07) as u8;
+    let funct5 : u8 = ((instr >> 27) & 0x1F) as u8;
+    let aq     : bool = ((instr >> 26) & 0x01) != 0;
+    let rl     : bool = ((instr >> 25) & 0x01) != 0;
+
+    match (rv64, funct3, funct5) {
+        (_, amo::funct3::LR_W, amo::funct5::LR_W) if rs2 == 0 => Ok(Instruction::LR_W {rd: rd, rs1: rs1, aq: aq, rl: rl}),
+        (_, amo::funct3::SC_W, amo::funct5::SC_W) => Ok(Instruction::SC_W {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (_, amo::funct3::AMOSWAP_W, amo::funct5::AMOSWAP_W) => Ok(Instruction::AMOSWAP_W {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (_, amo::funct3::AMOADD_W, amo::funct5::AMOADD_W) => Ok(Instruction::AMOADD_W {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (_, amo::funct3::AMOXOR_W, amo::funct5::AMOXOR_W) => Ok(Instruction::AMOXOR_W {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (_, amo::funct3::AMOAND_W, amo::funct5::AMOAND_W) => Ok(Instruction::AMOAND_W {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (_, amo::funct3::AMOOR_W, amo::funct5::AMOOR_W) => Ok(Instruction::AMOOR_W {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (_, amo::funct3::AMOMIN_W, amo::funct5::AMOMIN_W) => Ok(Instruction::AMOMIN_W {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (_, amo::funct3::AMOMAX_W, amo::funct5::AMOMAX_W) => Ok(Instruction::AMOMAX_W {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (_, amo::funct3::AMOMINU_W, amo::funct5::AMOMINU_W) => Ok(Instruction::AMOMINU_W {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (_, amo::funct3::AMOMAXU_W, amo::funct5::AMOMAXU_W) => Ok(Instruction::AMOMAXU_W {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (true, amo::funct3::LR_D, amo::funct5::LR_D) if rs2 == 0 => Ok(Instruction::LR_D {rd: rd, rs1: rs1, aq: aq, rl: rl}),
+        (true, amo::funct3::SC_D, amo::funct5::SC_D) => Ok(Instruction::SC_D {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (true, amo::funct3::AMOSWAP_D, amo::funct5::AMOSWAP_D) => Ok(Instruction::AMOSWAP_D {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (true, amo::funct3::AMOADD_D, amo::funct5::AMOADD_D) => Ok(Instruction::AMOADD_D {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (true, amo::funct3::AMOXOR_D, amo::funct5::AMOXOR_D) => Ok(Instruction::AMOXOR_D {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (true, amo::funct3::AMOAND_D, amo::funct5::AMOAND_D) => Ok(Instruction::AMOAND_D {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (true, amo::funct3::AMOOR_D, amo::funct5::AMOOR_D) => Ok(Instruction::AMOOR_D {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (true, amo::funct3::AMOMIN_D, amo::funct5::AMOMIN_D) => Ok(Instruction::AMOMIN_D {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (true, amo::funct3::AMOMAX_D, amo::funct5::AMOMAX_D) => Ok(Instruction::AMOMAX_D {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (true, amo::funct3::AMOMINU_D, amo::funct5::AMOMINU_D) => Ok(Instruction::AMOMINU_D {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (true, amo::funct3::AMOMAXU_D, amo::funct5::AMOMAXU_D) => Ok(Instruction::AMOMAXU_D {rd: rd, rs1: rs1, rs2: rs2, aq: aq, rl: rl}),
+        (_, amo::funct3::LR_W, amo::funct5::LR_W) | (_, amo::funct3::LR_D, amo::funct5::LR_D) =>
+            Err(DecodeError::Reserved {word: instr, field: Field::Rs2, value: rs2 as u32}),
+        (_, amo::funct3::AMOADD_W, _) | (_, amo::funct3::AMOADD_D, _) =>
+            Err(DecodeError::Reserved {word: instr, field: Field::Funct5, value: funct5 as u32}),
+        _ => Err(DecodeError::Reserved {word: instr, field: Field::Funct3, value: funct3 as u32})
+    }
+}
+
+fn get_rounding_mode(funct3: u8) -> FpRoundingMode {
+    match funct3 {
+        rounding_mode::RNE => FpRoundingMode::RoundToNearest,
+        rounding_mode::RTZ => FpRoundingMode::RoundTowardsZero,
+        rounding_mode::RDN => FpRoundingMode::RoundDown,
+        rounding_mode::RUP => FpRoundingMode::RoundUp,
+        rounding_mode::RMM => FpRoundingMode::RoundToNearestTieMaxMagnitude,
+        rounding_mode::DYN => FpRoundingMode::DynamicRounding,
+        _ => FpRoundingMode::Invalid
+    }
+}
+
+/* decodes the fused multiply-add instructions (R4-type) */
+fn decode_fma(instr: u32) -> Result<Instruction, DecodeError> {
+    let op  : u8 = ((instr >> 2) & 0x1F) as u8;
+    let rd  : u8 = ((instr >> 7) & 0x1F) as u8;
+    let rs1 : u8 = ((instr >> 15) & 0x1F) as u8;
+    let rs2 : u8 = ((instr >> 20) & 0x1F) as u8;
+    let rs3 : u8 = ((instr >> 27) & 0x1F) as u8;
+    let fmt : u8 = ((instr >> 25) & 0x03) as u8;
+    let rm  : FpRoundingMode = get_rounding_mode(((instr >> 12) & 0x07) as u8);
+
+    match (op, fmt) {
+        (opcode::MADD, fp_fmt::S) => Ok(Instruction::FMADD_S {rd: rd, rs1: rs1, rs2: rs2, rs3: rs3, rm: rm}),
+        (opcode::MSUB, fp_fmt::S) => Ok(Instruction::FMSUB_S {rd: rd, rs1: rs1, rs2: rs2, rs3: rs3, rm: rm}),
+        (opcode::NMSUB, fp_fmt::S) => Ok(Instruction::FNMSUB_S {rd: rd, rs1: rs1, rs2: rs2, rs3: rs3, rm: rm}),
+        (opcode::NMADD, fp_fmt::S) => Ok(Instruction::FNMADD_S {rd: rd, rs1: rs1, rs2: rs2, rs3: rs3, rm: rm}),
+        (opcode::MADD, fp_fmt::D) => Ok(Instruction::FMADD_D {rd: rd, rs1: rs1, rs2: rs2, rs3: rs3, rm: rm}),
+        (opcode::MSUB, fp_fmt::D) => Ok(Instruction::FMSUB_D {rd: rd, rs1: rs1, rs2: rs2, rs3: rs3, rm: rm}),
+        (opcode::NMSUB, fp_fmt::D) => Ok(Instruction::FNMSUB_D {rd: rd, rs1: rs1, rs2: rs2, rs3: rs3, rm: rm}),
+        (opcode::NMADD, fp_fmt::D) => Ok(Instruction::FNMADD_D {rd: rd, rs1: rs1, rs2: rs2, rs3: rs3, rm: rm}),
+        _ => Err(DecodeError::Reserved {word: instr, field: Field::Fmt, value: fmt as u32})
+    }
+}
+
+/* decodes the OP-FP major opcode of the F and D extension */
+fn decode_op_fp(instr: u32, rv64: bool) -> Result<Instruction, DecodeError> {
+    let rd     : u8 = ((instr >> 7) & 0x1F) as u8;
+    let rs1    : u8 = ((instr >> 15) & 0x1F) as u8;
+    let rs2    : u8 = ((instr >> 20) & 0x1F) as u8;
+    let funct3 : u8 = ((instr >> 12) & 0x07) as u8;
+    let funct7 : u8 = ((instr >> 25) & 0x7F) as u8;
+    let rm     : FpRoundingMode = get_rounding_mode(funct3);
+
+    match (rv64, funct7, rs2, funct3) {
+        (_, single_fp::funct7::FADD_S, _, _) => Ok(Instruction::FADD_S {rd: rd, rs1: rs1, rs2: rs2, rm: rm}),
+        (_, single_fp::funct7::FSUB_S, _, _) => Ok(Instruction::FSUB_S {rd: rd, rs1: rs1, rs2: rs2, rm: rm}),
+        (_, single_fp::funct7::FMUL_S, _, _) => Ok(Instruction::FMUL_S {rd: rd, rs1: rs1, rs2: rs2, rm: rm}),
+        (_, single_fp::funct7::FDIV_S, _, _) => Ok(Instruction::FDIV_S {rd: rd, rs1: rs1, rs2: rs2, rm: rm}),
+        (_, single_fp::funct7::FSQRT_S, single_fp::rs2::FSQRT_S, _) => Ok(Instruction::FSQRT_S {rd: rd, rs1: rs1, rm: rm}),
+        (_, single_fp::funct7::FSGNJ_S, _, single_fp::funct3::FSGNJ_S) => Ok(Instruction::FSGNJ_S {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, single_fp::funct7::FSGNJN_S, _, single_fp::funct3::FSGNJN_S) => Ok(Instruction::FSGNJN_S {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, single_fp::funct7::FSGNJX_S, _, single_fp::funct3::FSGNJX_S) => Ok(Instruction::FSGNJX_S {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, single_fp::funct7::FMIN_S, _, single_fp::funct3::FMIN_S) => Ok(Instruction::FMIN_S {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, single_fp::funct7::FMAX_S, _, single_fp::funct3::FMAX_S) => Ok(Instruction::FMAX_S {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, single_fp::funct7::FEQ_S, _, single_fp::funct3::FEQ_S) => Ok(Instruction::FEQ_S {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, single_fp::funct7::FLT_S, _, single_fp::funct3::FLT_S) => Ok(Instruction::FLT_S {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, single_fp::funct7::FLE_S, _, single_fp::funct3::FLE_S) => Ok(Instruction::FLE_S {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, single_fp::funct7::FCVT_W_S, single_fp::rs2::FCVT_W_S, _) => Ok(Instruction::FCVT_W_S {rd: rd, rs1: rs1, rm: rm}),
+        (_, single_fp::funct7::FCVT_S_W, single_fp::rs2::FCVT_S_W, _) => Ok(Instruction::FCVT_S_W {rd: rd, rs1: rs1, rm: rm}),
+        (_, single_fp::funct7::FCVT_WU_S, single_fp::rs2::FCVT_WU_S, _) => Ok(Instruction::FCVT_WU_S {rd: rd, rs1: rs1, rm: rm}),
+        (_, single_fp::funct7::FCVT_S_WU, single_fp::rs2::FCVT_S_WU, _) => Ok(Instruction::FCVT_S_WU {rd: rd, rs1: rs1, rm: rm}),
+        (true, single_fp::funct7::FCVT_L_S, single_fp::rs2::FCVT_L_S, _) => Ok(Instruction::FCVT_L_S {rd: rd, rs1: rs1, rm: rm}),
+        (true, single_fp::funct7::FCVT_S_L, single_fp::rs2::FCVT_S_L, _) => Ok(Instruction::FCVT_S_L {rd: rd, rs1: rs1, rm: rm}),
+        (true, single_fp::funct7::FCVT_LU_S, single_fp::rs2::FCVT_LU_S, _) => Ok(Instruction::FCVT_LU_S {rd: rd, rs1: rs1, rm: rm}),
+        (true, single_fp::funct7::FCVT_S_LU, single_fp::rs2::FCVT_S_LU, _) => Ok(Instruction::FCVT_S_LU {rd: rd, rs1: rs1, rm: rm}),
+        (_, single_fp::funct7::FCLASS_S, single_fp::rs2::FCLASS_S, single_fp::funct3::FCLASS_S) => Ok(Instruction::FCLASS_S {rd: rd, rs1: rs1}),
+        (_, double_fp::funct7::FADD_D, _, _) => Ok(Instruction::FADD_D {rd: rd, rs1: rs1, rs2: rs2, rm: rm}),
+        (_, double_fp::funct7::FSUB_D, _, _) => Ok(Instruction::FSUB_D {rd: rd, rs1: rs1, rs2: rs2, rm: rm}),
+        (_, double_fp::funct7::FMUL_D, _, _) => Ok(Instruction::FMUL_D {rd: rd, rs1: rs1, rs2: rs2, rm: rm}),
+        (_, double_fp::funct7::FDIV_D, _, _) => Ok(Instruction::FDIV_D {rd: rd, rs1: rs1, rs2: rs2, rm: rm}),
+        (_, double_fp::funct7::FSQRT_D, double_fp::rs2::FSQRT_D, _) => Ok(Instruction::FSQRT_D {rd: rd, rs1: rs1, rm: rm}),
+        (_, double_fp::funct7::FSGNJ_D, _, double_fp::funct3::FSGNJ_D) => Ok(Instruction::FSGNJ_D {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, double_fp::funct7::FSGNJN_D, _, double_fp::funct3::FSGNJN_D) => Ok(Instruction::FSGNJN_D {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, double_fp::funct7::FSGNJX_D, _, double_fp::funct3::FSGNJX_D) => Ok(Instruction::FSGNJX_D {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, double_fp::funct7::FMIN_D, _, double_fp::funct3::FMIN_D) => Ok(Instruction::FMIN_D {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, double_fp::funct7::FMAX_D, _, double_fp::funct3::FMAX_D) => Ok(Instruction::FMAX_D {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, double_fp::funct7::FEQ_D, _, double_fp::funct3::FEQ_D) => Ok(Instruction::FEQ_D {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, double_fp::funct7::FLT_D, _, double_fp::funct3::FLT_D) => Ok(Instruction::FLT_D {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, double_fp::funct7::FLE_D, _, double_fp::funct3::FLE_D) => Ok(Instruction::FLE_D {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, double_fp::funct7::FCVT_W_D, double_fp::rs2::FCVT_W_D, _) => Ok(Instruction::FCVT_W_D {rd: rd, rs1: rs1, rm: rm}),
+        (_, double_fp::funct7::FCVT_D_W, double_fp::rs2::FCVT_D_W, _) => Ok(Instruction::FCVT_D_W {rd: rd, rs1: rs1, rm: rm}),
+        (_, double_fp::funct7::FCVT_WU_D, double_fp::rs2::FCVT_WU_D, _) => Ok(Instruction::FCVT_WU_D {rd: rd, rs1: rs1, rm: rm}),
+        (_, double_fp::funct7::FCVT_D_WU, double_fp::rs2::FCVT_D_WU, _) => Ok(Instruction::FCVT_D_WU {rd: rd, rs1: rs1, rm: rm}),
+        (true, double_fp::funct7::FCVT_L_D, double_fp::rs2::FCVT_L_D, _) => Ok(Instruction::FCVT_L_D {rd: rd, rs1: rs1, rm: rm}),
+        (true, double_fp::funct7::FCVT_D_L, double_fp::rs2::FCVT_D_L, _) => Ok(Instruction::FCVT_D_L {rd: rd, rs1: rs1, rm: rm}),
+        (true, double_fp::funct7::FCVT_LU_D, double_fp::rs2::FCVT_LU_D, _) => Ok(Instruction::FCVT_LU_D {rd: rd, rs1: rs1, rm: rm}),
+        (true, double_fp::funct7::FCVT_D_LU, double_fp::rs2::FCVT_D_LU, _) => Ok(Instruction::FCVT_D_LU {rd: rd, rs1: rs1, rm: rm}),
+        (_, double_fp::funct7::FCLASS_D, double_fp::rs2::FCLASS_D, double_fp::funct3::FCLASS_D) => Ok(Instruction::FCLASS_D {rd: rd, rs1: rs1}),
+        (_, single_fp::funct7::FMV_X_S, single_fp::rs2::FMV_X_S, single_fp::funct3::FMV_X_S) => Ok(Instruction::FMV_X_S {rd: rd, rs1: rs1}),
+        (_, single_fp::funct7::FMV_S_X, single_fp::rs2::FMV_S_X, single_fp::funct3::FMV_S_X) => Ok(Instruction::FMV_S_X {rd: rd, rs1: rs1}),
+        (true, double_fp::funct7::FMV_X_D, double_fp::rs2::FMV_X_D, double_fp::funct3::FMV_X_D) => Ok(Instruction::FMV_X_D {rd: rd, rs1: rs1}),
+        (true, double_fp::funct7::FMV_D_X, double_fp::rs2::FMV_D_X, double_fp::funct3::FMV_D_X) => Ok(Instruction::FMV_D_X {rd: rd, rs1: rs1}),
+        (_, double_fp::funct7::FCVT_S_D, double_fp::rs2::FCVT_S_D, _) => Ok(Instruction::FCVT_S_D {rd: rd, rs1: rs1, rm: rm}),
+        (_, double_fp::funct7::FCVT_D_S, double_fp::rs2::FCVT_D_S, _) => Ok(Instruction::FCVT_D_S {rd: rd, rs1: rs1, rm: rm}),
+        (_, single_fp::funct7::FSGNJ_S, _, _) | (_, double_fp::funct7::FSGNJ_D, _, _) |
+        (_, single_fp::funct7::FMIN_S, _, _) | (_, double_fp::funct7::FMIN_D, _, _) |
+        (_, single_fp::funct7::FEQ_S, _, _) | (_, double_fp::funct7::FEQ_D, _, _) |
+        (_, single_fp::funct7::FMV_X_S, 0, _) | (_, double_fp::funct7::FMV_X_D, 0, _) |
+        (_, single_fp::funct7::FMV_S_X, 0, _) | (_, double_fp::funct7::FMV_D_X, 0, _) =>
+            Err(DecodeError::Reserved {word: instr, field: Field::Funct3, value: funct3 as u32}),
+        (_, single_fp::funct7::FSQRT_S, _, _) | (_, double_fp::funct7::FSQRT_D, _, _) |
+        (_, single_fp::funct7::FCVT_W_S, _, _) | (_, double_fp::funct7::FCVT_W_D, _, _) |
+        (_, single_fp::funct7::FCVT_S_W, _, _) | (_, double_fp::funct7::FCVT_D_W, _, _) |
+        (_, double_fp::funct7::FCVT_S_D, _, _) | (_, double_fp::funct7::FCVT_D_S, _, _) |
+        (_, single_fp::funct7::FMV_X_S, _, _) | (_, double_fp::funct7::FMV_X_D, _, _) |
+        (_, single_fp::funct7::FMV_S_X, _, _) | (_, double_fp::funct7::FMV_D_X, _, _) =>
+            Err(DecodeError::Reserved {word: instr, field: Field::Rs2, value: rs2 as u32}),
+        _ => Err(DecodeError::Reserved {word: instr, field: Field::Funct7, value: funct7 as u32})
+    }
+}
+
+/* extracts bits [hi:lo] of a compressed instruction and moves them to pos */
+fn c_bits(word: u16, hi: u32, lo: u32, pos: u32) -> u32 {
+    let mask = (1u32 << (hi - lo + 1)) - 1;
+    (((word as u32) >> lo) & mask) << pos
+}
+
+fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
+
+/* the registers x8-x15 encoded in three bits */
+fn c_reg(word: u16, lo: u32) -> u8 {
+    (((word >> lo) & 0x07) + 8) as u8
+}
+
+fn get_ci_imm6(word: u16) -> i32 {
+    sign_extend(c_bits(word, 12, 12, 5) | c_bits(word, 6, 2, 0), 6)
+}
+
+fn get_cj_imm12(word: u16) -> i32 {
+    let imm = c_bits(word, 12, 12, 11) | c_bits(word, 11, 11, 4) |
+        c_bits(word, 10, 9, 8) | c_bits(word, 8, 8, 10) |
+        c_bits(word, 7, 7, 6) | c_bits(word, 6, 6, 7) |
+        c_bits(word, 5, 3, 1) | c_bits(word, 2, 2, 5);
+    sign_extend(imm, 12)
+}
+
+fn get_cb_imm9(word: u16) -> i16 {
+    let imm = c_bits(word, 12, 12, 8) | c_bits(word, 11, 10, 3) |
+        c_bits(word, 6, 5, 6) | c_bits(word, 4, 3, 1) | c_bits(word, 2, 2, 5);
+    sign_extend(imm, 9) as i16
+}
+
+/* offsets of the word and double word loads and stores */
+fn get_clw_uimm(word: u16) -> i16 {
+    (c_bits(word, 12, 10, 3) | c_bits(word, 6, 6, 2) | c_bits(word, 5, 5, 6)) as i16
+}
+
+fn get_cld_uimm(word: u16) -> i16 {
+    (c_bits(word, 12, 10, 3) | c_bits(word, 6, 5, 6)) as i16
+}
+
+fn get_lwsp_uimm(word: u16) -> i16 {
+    (c_bits(word, 12, 12, 5) | c_bits(word, 6, 4, 2) | c_bits(word, 3, 2, 6)) as i16
+}
+
+fn get_ldsp_uimm(word: u16) -> i16 {
+    (c_bits(word, 12, 12, 5) | c_bits(word, 6, 5, 3) | c_bits(word, 4, 2, 6)) as i16
+}
+
+fn get_swsp_uimm(word: u16) -> i16 {
+    (c_bits(word, 12, 9, 2) | c_bits(word, 8, 7, 6)) as i16
+}
+
+fn get_sdsp_uimm(word: u16) -> i16 {
+    (c_bits(word, 12, 10, 3) | c_bits(word, 9, 7, 6)) as i16
+}
+
+/* finds out why an instruction of the base ISA could not be decoded */
+fn base_error(instr: u32, op: u8, funct3: u8, funct7: u8) -> DecodeError {
+    match op {
+        /* the upper bits of the immediate select the kind of shift */
+        opcode::OP_IMM | opcode::OP_IMM_32 if funct3 == funct3::SLLI || funct3 == funct3::SRLI =>
+            DecodeError::Reserved {word: instr, field: Field::Funct7, value: funct7 as u32},
+        opcode::OP | opcode::OP_32 if funct7 == funct7::ADD || funct7 == funct7::SUB || funct7 == mul_div::funct7::MUL =>
+            DecodeError::Reserved {word: instr, field: Field::Funct3, value: funct3 as u32},
+        opcode::OP | opcode::OP_32 =>
+            DecodeError::Reserved {word: instr, field: Field::Funct7, value: funct7 as u32},
+        opcode::LOAD | opcode::STORE | opcode::BRANCH | opcode::JALR | opcode::LOAD_FP |
+        opcode::STORE_FP | opcode::OP_IMM_32 | opcode::SYSTEM =>
+            DecodeError::Reserved {word: instr, field: Field::Funct3, value: funct3 as u32},
+        _ => DecodeError::IllegalOpcode {word: instr, opcode: op}
+    }
+}
+
+/* decodes an 32-bit sized instruction for the given XLEN */
+fn decode_word(instr : u32, rv64: bool) -> Result<Instruction, DecodeError> {
+    if (instr & 0x03) != 0x03 {
+        return Err(DecodeError::Compressed {word: instr});
+    }
+
+    let op        : u8 = ((instr >> 2) & 0x1F) as u8;
+    let rd        : u8 = ((instr >> 7) & 0x1F) as u8;
+    let rs1       : u8 = ((instr >> 15) & 0x1F) as u8;
+    let rs2       : u8 = ((instr >> 20) & 0x1F) as u8;
+    let shamt     : u8 = ((instr >> 20) & 0x1F) as u8;
+    let shamt64   : u8 = ((instr >> 20) & 0x3F) as u8;
+    let funct3    : u8 = ((instr >> 12) & 0x07) as u8;
+    let funct7    : u8 = ((instr >> 25) & 0x7F) as u8;
+    let csr       : u16 = ((instr >> 20) & 0x0FFF) as u16;
+    let i_imm12   : i16 = get_i_imm12(instr);
+    let s_imm12   : i16 = get_s_imm12(instr);
+    let sb_imm12  : i16 = get_sb_imm12(instr);
+    let jal_imm20 : i32 = get_jal_imm20(instr);
+
+    /* TODO separate decode32 to decode_rv32i, decode_rv64i, decode_rv32m, 
+     * and so on. decode32 first tries to decode the extensions and then
+     * the base ISA, first 64 bit and then 32 bit. */
+
+    match (rv64, op, funct3, funct7) {
+        (_, opcode::LUI, _, _) => Ok(Instruction::LUI {rd: rd, imm: (instr & 0xFFFFF000) as i32}), 
+        (_, opcode::AUIPC, _, _) => Ok(Instruction::AUIPC {rd: rd, imm: (instr & 0xFFFFF000) as i32}), 
+        (_, opcode::JAL, _, _) => Ok(Instruction::JAL {rd: rd, imm: jal_imm20}), 
+        (_, opcode::JALR, funct3::JALR, _) => Ok(Instruction::JALR {rd: rd, rs1: rs1, imm: i_imm12}), 
+        (_, opcode::BRANCH, funct3::BEQ, _) => Ok(Instruction::BEQ {rs1: rs1, rs2: rs2, imm: sb_imm12}),
+        (_, opcode::BRANCH, funct3::BNE, _) => Ok(Instruction::BNE {rs1: rs1, rs2: rs2, imm: sb_imm12}),
+        (_, opcode::BRANCH, funct3::BLT, _) => Ok(Instruction::BLT {rs1: rs1, rs2: rs2, imm: sb_imm12}),
+        (_, opcode::BRANCH, funct3::BGE, _) => Ok(Instruction::BGE {rs1: rs1, rs2: rs2, imm: sb_imm12}),
+        (_, opcode::BRANCH, funct3::BLTU, _) => Ok(Instruction::BLTU {rs1: rs1, rs2: rs2, imm: sb_imm12}),
+        (_, opcode::BRANCH, funct3::BGEU, _) => Ok(Instruction::BGEU {rs1: rs1, rs2: rs2, imm: sb_imm12}),
+        (_, opcode::LOAD, funct3::LB, _) => Ok(Instruction::LB {rd: rd, rs1: rs1, imm: i_imm12}), 
+        (_, opcode::LOAD, funct3::LH, _) => Ok(Instruction::LH {rd: rd, rs1: rs1, imm: i_imm12}), 
+        (_, opcode::LOAD, funct3::LW, _) => Ok(Instruction::LW {rd: rd, rs1: rs1, imm: i_imm12}), 
+        (true, opcode::LOAD, funct3::LD, _) => Ok(Instruction::LD {rd: rd, rs1: rs1, imm: i_imm12}), 
+        (_, opcode::LOAD, funct3::LBU, _) => Ok(Instruction::LBU {rd: rd, rs1: rs1, imm: i_imm12}), 
+        (_, opcode::LOAD, funct3::LHU, _) => Ok(Instruction::LHU {rd: rd, rs1: rs1, imm: i_imm12}), 
+        (true, opcode::LOAD, funct3::LWU, _) => Ok(Instruction::LWU {rd: rd, rs1: rs1, imm: i_imm12}), 
+        (_, opcode::STORE, funct3::SB, _) => Ok(Instruction::SB {rs1: rs1, rs2: rs2, imm: s_imm12}),
+        (_, opcode::STORE, funct3::SH, _) => Ok(Instruction::SH {rs1: rs1, rs2: rs2, imm: s_imm12}),
+        (_, opcode::STORE, funct3::SW, _) => Ok(Instruction::SW {rs1: rs1, rs2: rs2, imm: s_imm12}),
+        (true, opcode::STORE, funct3::SD, _) => Ok(Instruction::SD {rs1: rs1, rs2: rs2, imm: s_imm12}),
+        (_, opcode::OP_IMM, funct3::ADDI, _) => Ok(Instruction::ADDI {rd: rd, rs1: rs1, imm: i_imm12}),
+        (true, opcode::OP_IMM_32, funct3::ADDIW, _) => Ok(Instruction::ADDIW {rd: rd, rs1: rs1, imm: i_imm12}),
+        (_, opcode::OP_IMM, funct3::SLTI, _) => Ok(Instruction::SLTI {rd: rd, rs1: rs1, imm: i_imm12}),
+        (_, opcode::OP_IMM, funct3::SLTIU, _) => Ok(Instruction::SLTIU {rd: rd, rs1: rs1, imm: i_imm12}),
+        (_, opcode::OP_IMM, funct3::XORI, _) => Ok(Instruction::XORI {rd: rd, rs1: rs1, imm: i_imm12}),
+        (_, opcode::OP_IMM, funct3::ORI, _) => Ok(Instruction::ORI {rd: rd, rs1: rs1, imm: i_imm12}),
+        (_, opcode::OP_IMM, funct3::ANDI, _) => Ok(Instruction::ANDI {rd: rd, rs1: rs1, imm: i_imm12}),
+        (false, opcode::OP_IMM, funct3::SLLI, funct7::SLLI) => Ok(Instruction::SLLI {rd: rd, rs1: rs1, shamt: shamt}),
+        (true, opcode::OP_IMM_32, funct3::SLLIW, funct7::SLLIW) => Ok(Instruction::SLLIW {rd: rd, rs1: rs1, shamt: shamt}),
+        (true, opcode::OP_IMM, funct3::SLLI, funct7::SLLI) | (true, opcode::OP_IMM, funct3::SLLI, 0b0000001) => Ok(Instruction::SLLI {rd: rd, rs1: rs1, shamt: shamt64}),
+        (false, opcode::OP_IMM, funct3::SRLI, funct7::SRLI) => Ok(Instruction::SRLI {rd: rd, rs1: rs1, shamt: shamt}),
+        (true, opcode::OP_IMM_32, funct3::SRLIW, funct7::SRLIW) => Ok(Instruction::SRLIW {rd: rd, rs1: rs1, shamt: shamt}),
+        (true, opcode::OP_IMM, funct3::SRLI, funct7::SRLI) | (true, opcode::OP_IMM, funct3::SRLI, 0b0000001) => Ok(Instruction::SRLI {rd: rd, rs1: rs1, shamt: shamt64}),
+        (false, opcode::OP_IMM, funct3::SRAI, funct7::SRAI) => Ok(Instruction::SRAI {rd: rd, rs1: rs1, shamt: shamt}),
+        (true, opcode::OP_IMM_32, funct3::SRAIW, funct7::SRAIW) => Ok(Instruction::SRAIW {rd: rd, rs1: rs1, shamt: shamt}),
+        (true, opcode::OP_IMM, funct3::SRAI, funct7::SRAI) | (true, opcode::OP_IMM, funct3::SRAI, 0b0100001) => Ok(Instruction::SRAI {rd: rd, rs1: rs1, shamt: shamt64}),
+        (_, opcode::OP, funct3::ADD, funct7::ADD) => Ok(Instruction::ADD {rd: rd, rs1: rs1, rs2: rs2}),
+        (true, opcode::OP_32, funct3::ADDW, funct7::ADDW) => Ok(Instruction::ADDW {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, funct3::SUB, funct7::SUB) => Ok(Instruction::SUB {rd: rd, rs1: rs1, rs2: rs2}),
+        (true, opcode::OP_32, funct3::SUBW, funct7::SUBW) => Ok(Instruction::SUBW {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, funct3::SLL, funct7::SLL) => Ok(Instruction::SLL {rd: rd, rs1: rs1, rs2: rs2}),
+        (true, opcode::OP_32, funct3::SLLW, funct7::SLLW) => Ok(Instruction::SLLW {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, funct3::SLT, funct7::SLT) => Ok(Instruction::SLT {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, funct3::SLTU, funct7::SLTU) => Ok(Instruction::SLTU {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, funct3::XOR, funct7::XOR) => Ok(Instruction::XOR {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, funct3::SRL, funct7::SRL) => Ok(Instruction::SRL {rd: rd, rs1: rs1, rs2: rs2}),
+        (true, opcode::OP_32, funct3::SRLW, funct7::SRLW) => Ok(Instruction::SRLW {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, funct3::SRA, funct7::SRA) => Ok(Instruction::SRA {rd: rd, rs1: rs1, rs2: rs2}),
+        (true, opcode::OP_32, funct3::SRAW, funct7::SRAW) => Ok(Instruction::SRAW {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, funct3::OR, funct7::OR) => Ok(Instruction::OR {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, funct3::AND, funct7::AND) => Ok(Instruction::AND {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, mul_div::funct3::MUL, mul_div::funct7::MUL) => Ok(Instruction::MUL {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, mul_div::funct3::MULH, mul_div::funct7::MULH) => Ok(Instruction::MULH {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, mul_div::funct3::MULHSU, mul_div::funct7::MULHSU) => Ok(Instruction::MULHSU {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, mul_div::funct3::MULHU, mul_div::funct7::MULHU) => Ok(Instruction::MULHU {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, mul_div::funct3::DIV, mul_div::funct7::DIV) => Ok(Instruction::DIV {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, mul_div::funct3::DIVU, mul_div::funct7::DIVU) => Ok(Instruction::DIVU {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, mul_div::funct3::REM, mul_div::funct7::REM) => Ok(Instruction::REM {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::OP, mul_div::funct3::REMU, mul_div::funct7::REMU) => Ok(Instruction::REMU {rd: rd, rs1: rs1, rs2: rs2}),
+        (true, opcode::OP_32, mul_div::funct3::MULW, mul_div::funct7::MULW) => Ok(Instruction::MULW {rd: rd, rs1: rs1, rs2: rs2}),
+        (true, opcode::OP_32, mul_div::funct3::DIVW, mul_div::funct7::DIVW) => Ok(Instruction::DIVW {rd: rd, rs1: rs1, rs2: rs2}),
+        (true, opcode::OP_32, mul_div::funct3::DIVUW, mul_div::funct7::DIVUW) => Ok(Instruction::DIVUW {rd: rd, rs1: rs1, rs2: rs2}),
+        (true, opcode::OP_32, mul_div::funct3::REMW, mul_div::funct7::REMW) => Ok(Instruction::REMW {rd: rd, rs1: rs1, rs2: rs2}),
+        (true, opcode::OP_32, mul_div::funct3::REMUW, mul_div::funct7::REMUW) => Ok(Instruction::REMUW {rd: rd, rs1: rs1, rs2: rs2}),
+        (_, opcode::AMO, _, _) => decode_amo(instr, rv64),
+        (_, opcode::LOAD_FP, single_fp::funct3::FLW, _) => Ok(Instruction::FLW {rd: rd, rs1: rs1, imm: i_imm12}),
+        (_, opcode::LOAD_FP, double_fp::funct3::FLD, _) => Ok(Instruction::FLD {rd: rd, rs1: rs1, imm: i_imm12}),
+        (_, opcode::STORE_FP, single_fp::funct3::FSW, _) => Ok(Instruction::FSW {rs1: rs1, rs2: rs2, imm: s_imm12}),
+        (_, opcode::STORE_FP, double_fp::funct3::FSD, _) => Ok(Instruction::FSD {rs1: rs1, rs2: rs2, imm: s_imm12}),
+        (_, opcode::MADD, _, _) | (_, opcode::MSUB, _, _) |
+        (_, opcode::NMSUB, _, _) | (_, opcode::NMADD, _, _) => decode_fma(instr),
+        (_, opcode::OP_FP, _, _) => decode_op_fp(instr, rv64),
+        (_, opcode::MISC_MEM, _, _) => {
+            if rd == 0 && funct3 == 0 && rs1 == 0 && (instr >> 28) == 0 {
+                Ok(Instruction::FENCE {succ: ((instr >> 20) & 0x0F) as u8,
+                    pred: ((instr >> 24) & 0x0F) as u8})
+            } else if rd == 0 && funct3 == 1 && rs1 == 0 && i_imm12 == 0 {
+                Ok(Instruction::FENCE_I)
+            } else if funct3 > 1 {
+                Err(DecodeError::Reserved {word: instr, field: Field::Funct3, value: funct3 as u32})
+            } else {
+                Err(DecodeError::MalformedFence {word: instr})
+            }
+        },
+        (_, opcode::SYSTEM, funct3::ECALL, _) => {
+            if instr == 0x00000073 {
+                Ok(Instruction::ECALL)
+            } else if instr == 0x00100073 {
+                Ok(Instruction::EBREAK)
+            } else if instr == 0x30200073 {
+                Ok(Instruction::MRET)
+            } else if instr == 0x10200073 {
+                Ok(Instruction::SRET)
+            } else if instr == 0x10500073 {
+                Ok(Instruction::WFI)
+            } else if funct7 == 0b0001001 && rd == 0 {
+                Ok(Instruction::SFENCE_VMA {rs1: rs1, rs2: rs2})
+            } else {
+                Err(DecodeError::Reserved {word: instr, field: Field::Funct12, value: instr >> 20})
+            }
+        },
+        (_, opcode::SYSTEM, funct3::CSRRW, _) => Ok(Instruction::CSRRW {rd: rd, rs1: rs1, csr: csr}),
+        (_, opcode::SYSTEM, funct3::CSRRS, _) => Ok(Instruction::CSRRS {rd: rd, rs1: rs1, csr: csr}),
+        (_, opcode::SYSTEM, funct3::CSRRC, _) => Ok(Instruction::CSRRC {rd: rd, rs1: rs1, csr: csr}),
+        (_, opcode::SYSTEM, funct3::CSRRWI, _) => Ok(Instruction::CSRRWI {rd: rd, zimm: rs1, csr: csr}),
+        (_, opcode::SYSTEM, funct3::CSRRSI, _) => Ok(Instruction::CSRRSI {rd: rd, zimm: rs1, csr: csr}),
+        (_, opcode::SYSTEM, funct3::CSRRCI, _) => Ok(Instruction::CSRRCI {rd: rd, zimm: rs1, csr: csr}),
+        _ => Err(base_error(instr, op, funct3, funct7))
+    }
+}
+
+/* decodes a 16-bit compressed instruction for the given XLEN */
+fn decode_halfword(instr : u16, rv64: bool) -> Result<Instruction, DecodeError> {
+    let word     : u32 = instr as u32;
+    let quadrant : u8 = (instr & 0x03) as u8;
+    let funct3   : u8 = ((instr >> 13) & 0x07) as u8;
+    let rd       : u8 = ((instr >> 7) & 0x1F) as u8;
+    let rs2      : u8 = ((instr >> 2) & 0x1F) as u8;
+    let rd_c     : u8 = c_reg(instr, 2);
+    let rs1_c    : u8 = c_reg(instr, 7);
+    let rs2_c    : u8 = c_reg(instr, 2);
+    let bit12    : bool = (instr & 0x1000) != 0;
+    let imm6     : i32 = get_ci_imm6(instr);
+    let shamt    : u8 = (c_bits(instr, 12, 12, 5) | c_bits(instr, 6, 2, 0)) as u8;
+
+    use self::compressed::funct3 as c;
+
+    match (rv64, quadrant, funct3) {
+        (_, compressed::quadrant::C0, c::C_ADDI4SPN) => {
+            let imm = c_bits(instr, 12, 11, 4) | c_bits(instr, 10, 7, 6) |
+                c_bits(instr, 6, 6, 2) | c_bits(instr, 5, 5, 3);
+            /* this also makes the all-zero instruction illegal */
+            if imm == 0 {
+                Err(DecodeError::Reserved {word: word, field: Field::Imm, value: 0})
+            } else {
+                Ok(Instruction::ADDI {rd: rd_c, rs1: 2, imm: imm as i16})
+            }
+        },
+        (_, compressed::quadrant::C0, c::C_FLD) => Ok(Instruction::FLD {rd: rd_c, rs1: rs1_c, imm: get_cld_uimm(instr)}),
+        (_, compressed::quadrant::C0, c::C_LW) => Ok(Instruction::LW {rd: rd_c, rs1: rs1_c, imm: get_clw_uimm(instr)}),
+        (false, compressed::quadrant::C0, c::C_FLW) => Ok(Instruction::FLW {rd: rd_c, rs1: rs1_c, imm: get_clw_uimm(instr)}),
+        (true, compressed::quadrant::C0, c::C_LD) => Ok(Instruction::LD {rd: rd_c, rs1: rs1_c, imm: get_cld_uimm(instr)}),
+        (_, compressed::quadrant::C0, c::C_FSD) => Ok(Instruction::FSD {rs1: rs1_c, rs2: rs2_c, imm: get_cld_uimm(instr)}),
+        (_, compressed::quadrant::C0, c::C_SW) => Ok(Instruction::SW {rs1: rs1_c, rs2: rs2_c, imm: get_clw_uimm(instr)}),
+        (false, compressed::quadrant::C0, c::C_FSW) => Ok(Instruction::FSW {rs1: rs1_c, rs2: rs2_c, imm: get_clw_uimm(instr)}),
+        (true, compressed::quadrant::C0, c::C_SD) => Ok(Instruction::SD {rs1: rs1_c, rs2: rs2_c, imm: get_cld_uimm(instr)}),
+
+        /* C.NOP and the HINTs with rd = 0 or imm = 0 are also expanded */
+        (_, compressed::quadrant::C1, c::C_ADDI) => Ok(Instruction::ADDI {rd: rd, rs1: rd, imm: imm6 as i16}),
+        (false, compressed::quadrant::C1, c::C_JAL) => Ok(Instruction::JAL {rd: 1, imm: get_cj_imm12(instr)}),
+        (true, compressed::quadrant::C1, c::C_ADDIW) if rd != 0 => Ok(Instruction::ADDIW {rd: rd, rs1: rd, imm: imm6 as i16}),
+        (_, compressed::quadrant::C1, c::C_LI) => Ok(Instruction::ADDI {rd: rd, rs1: 0, imm: imm6 as i16}),
+        (_, compressed::quadrant::C1, c::C_ADDI16SP) if rd == 2 => {
+            let imm = c_bits(instr, 12, 12, 9) | c_bits(instr, 6, 6, 4) |
+                c_bits(instr, 5, 5, 6) | c_bits(instr, 4, 3, 7) | c_bits(instr, 2, 2, 5);
+            if imm == 0 {
+                Err(DecodeError::Reserved {word: word, field: Field::Imm, value: 0})
+            } else {
+                Ok(Instruction::ADDI {rd: 2, rs1: 2, imm: sign_extend(imm, 10) as i16})
+            }
+        },
+        (_, compressed::quadrant::C1, c::C_LUI) => {
+            if imm6 == 0 {
+                Err(DecodeError::Reserved {word: word, field: Field::Imm, value: 0})
+            } else {
+                Ok(Instruction::LUI {rd: rd, imm: imm6 << 12})
+            }
+        },
+        (_, compressed::quadrant::C1, c::C_MISC_ALU) => {
+            let funct2 = ((instr >> 10) & 0x03) as u8;
+            let arith = ((instr >> 5) & 0x03) as u8;
+
+            match (rv64, funct2, bit12, arith) {
+                (false, compressed::funct2::C_SRLI, true, _) |
+                (false, compressed::funct2::C_SRAI, true, _) =>
+                    Err(DecodeError::Reserved {word: word, field: Field::Imm, value: shamt as u32}),
+                (_, compressed::funct2::C_SRLI, _, _) => Ok(Instruction::SRLI {rd: rs1_c, rs1: rs1_c, shamt: shamt}),
+                (_, compressed::funct2::C_SRAI, _, _) => Ok(Instruction::SRAI {rd: rs1_c, rs1: rs1_c, shamt: shamt}),
+                (_, compressed::funct2::C_ANDI, _, _) => Ok(Instruction::ANDI {rd: rs1_c, rs1: rs1_c, imm: imm6 as i16}),
+                (_, compressed::funct2::C_ARITH, false, compressed::arith::C_SUB) => Ok(Instruction::SUB {rd: rs1_c, rs1: rs1_c, rs2: rs2_c}),
+                (_, compressed::funct2::C_ARITH, false, compressed::arith::C_XOR) => Ok(Instruction::XOR {rd: rs1_c, rs1: rs1_c, rs2: rs2_c}),
+                (_, compressed::funct2::C_ARITH, false, compressed::arith::C_OR) => Ok(Instruction::OR {rd: rs1_c, rs1: rs1_c, rs2: rs2_c}),
+                (_, compressed::funct2::C_ARITH, false, compressed::arith::C_AND) => Ok(Instruction::AND {rd: rs1_c, rs1: rs1_c, rs2: rs2_c}),
+                (true, compressed::funct2::C_ARITH, true, compressed::arith::C_SUBW) => Ok(Instruction::SUBW {rd: rs1_c, rs1: rs1_c, rs2: rs2_c}),
+                (true, compressed::funct2::C_ARITH, true, compressed::arith::C_ADDW) => Ok(Instruction::ADDW {rd: rs1_c, rs1: rs1_c, rs2: rs2_c}),
+                _ => Err(DecodeError::Reserved {word: word, field: Field::Funct2, value: arith as u32})
+            }
+        },
+        (_, compressed::quadrant::C1, c::C_J) => Ok(Instruction::JAL {rd: 0, imm: get_cj_imm12(instr)}),
+        (_, compressed::quadrant::C1, c::C_BEQZ) => Ok(Instruction::BEQ {rs1: rs1_c, rs2: 0, imm: get_cb_imm9(instr)}),
+        (_, compressed::quadrant::C1, c::C_BNEZ) => Ok(Instruction::BNE {rs1: rs1_c, rs2: 0, imm: get_cb_imm9(instr)}),
+
+        (false, compressed::quadrant::C2, c::C_SLLI) if bit12 =>
+            Err(DecodeError::Reserved {word: word, field: Field::Imm, value: shamt as u32}),
+        (_, compressed::quadrant::C2, c::C_SLLI) => Ok(Instruction::SLLI {rd: rd, rs1: rd, shamt: shamt}),
+        (_, compressed::quadrant::C2, c::C_FLDSP) => Ok(Instruction::FLD {rd: rd, rs1: 2, imm: get_ldsp_uimm(instr)}),
+        (_, compressed::quadrant::C2, c::C_LWSP) if rd != 0 => Ok(Instruction::LW {rd: rd, rs1: 2, imm: get_lwsp_uimm(instr)}),
+        (false, compressed::quadrant::C2, c::C_FLWSP) => Ok(Instruction::FLW {rd: rd, rs1: 2, imm: get_lwsp_uimm(instr)}),
+        (true, compressed::quadrant::C2, c::C_LDSP) if rd != 0 => Ok(Instruction::LD {rd: rd, rs1: 2, imm: get_ldsp_uimm(instr)}),
+        (_, compressed::quadrant::C2, c::C_JR_MV_ADD) => {
+            match (bit12, rd, rs2) {
+                (false, 0, 0) => Err(DecodeError::Reserved {word: word, field: Field::Rd, value: 0}),
+                (false, _, 0) => Ok(Instruction::JALR {rd: 0, rs1: rd, imm: 0}),
+                (false, _, _) => Ok(Instruction::ADD {rd: rd, rs1: 0, rs2: rs2}),
+                (true, 0, 0) => Ok(Instruction::EBREAK),
+                (true, _, 0) => Ok(Instruction::JALR {rd: 1, rs1: rd, imm: 0}),
+                (true, _, _) => Ok(Instruction::ADD {rd: rd, rs1: rd, rs2: rs2}),
+            }
+        },
+        (_, compressed::quadrant::C2, c::C_FSDSP) => Ok(Instruction::FSD {rs1: 2, rs2: rs2, imm: get_sdsp_uimm(instr)}),
+        (_, compressed::quadrant::C2, c::C_SWSP) => Ok(Instruction::SW {rs1: 2, rs2: rs2, imm: get_swsp_uimm(instr)}),
+        (false, compressed::quadrant::C2, c::C_FSWSP) => Ok(Instruction::FSW {rs1: 2, rs2: rs2, imm: get_swsp_uimm(instr)}),
+        (true, compressed::quadrant::C2, c::C_SDSP) => Ok(Instruction::SD {rs1: 2, rs2: rs2, imm: get_sdsp_uimm(instr)}),
+        (_, compressed::quadrant::C0, _) => Err(DecodeError::Reserved {word: word, field: Field::Funct3, value: funct3 as u32}),
+        (_, compressed::quadrant::C1, _) | (_, compressed::quadrant::C2, _) =>
+            Err(DecodeError::Reserved {word: word, field: Field::Rd, value: rd as u32}),
+        _ => Err(DecodeError::NotCompressed {word: word})
+    }
+}
+
 impl Instruction {
     /* decodes an 32-bit sized instruction (RV32I and RV64I) */
-    pub fn decode32(instr : u32, rv64: bool) -> Result<Instruction, ()> {
-        if (instr & 0x03) != 0x03 {
-            return Err(());
+    pub fn decode32(instr : u32, rv64: bool) -> Result<Instruction, DecodeError> {
+        match decode_word(instr, rv64) {
+            Err(_) if !rv64 && decode_word(instr, true).is_ok() => Err(DecodeError::Rv64Only {word: instr}),
+            result => result
+        }
+    }
+
+    /* decodes a 16-bit compressed instruction (RVC) and expands it to the
+     * equivalent 32-bit instruction */
+    pub fn decode16(instr : u16, rv64: bool) -> Result<Instruction, DecodeError> {
+        match decode_halfword(instr, rv64) {
+            Err(_) if !rv64 && decode_halfword(instr, true).is_ok() => Err(DecodeError::Rv64Only {word: instr as u32}),
+            result => result
+        }
+    }
+
+    /* decodes the instruction at the start of bytes (little-endian) and
+     * returns it together with its length in bytes */
+    pub fn decode(bytes: &[u8], rv64: bool) -> Result<(Instruction, usize), DecodeError> {
+        if bytes.len() < 2 {
+            return Err(DecodeError::Truncated {len: bytes.len()});
         }
 
-        let op        : u8 = ((instr >> 2) & 0x1F) as u8;
-        let rd        : u8 = ((instr >> 7) & 0x1F) as u8;
-        let rs1       : u8 = ((instr >> 15) & 0x1F) as u8;
-        let rs2       : u8 = ((instr >> 20) & 0x1F) as u8;
-        let shamt     : u8 = ((instr >> 20) & 0x1F) as u8;
-        let shamt64   : u8 = ((instr >> 20) & 0x3F) as u8;
-        let funct3    : u8 = ((instr >> 12) & 0x07) as u8;
-        let funct7    : u8 = ((instr >> 25) & 0x7F) as u8;
-        let csr       : u16 = ((instr >> 20) & 0x0FFF) as u16;
-        let i_imm12   : i16 = get_i_imm12(instr);
-        let s_imm12   : i16 = get_s_imm12(instr);
-        let sb_imm12  : i16 = get_sb_imm12(instr);
-        let jal_imm20 : i32 = get_jal_imm20(instr);
-
-        /* TODO separate decode32 to decode_rv32i, decode_rv64i, decode_rv32m, 
-         * and so on. decode32 first tries to decode the extensions and then
-         * the base ISA, first 64 bit and then 32 bit. */
-
-        match (rv64, op, funct3, funct7) {
-            (_, opcode::LUI, _, _) => Ok(Instruction::LUI {rd: rd, imm: (instr & 0xFFFFF000) as i32}), 
-            (_, opcode::AUIPC, _, _) => Ok(Instruction::AUIPC {rd: rd, imm: (instr & 0xFFFFF000) as i32}), 
-            (_, opcode::JAL, _, _) => Ok(Instruction::JAL {rd: rd, imm: jal_imm20}), 
-            (_, opcode::JALR, funct3::JALR, _) => Ok(Instruction::JALR {rd: rd, rs1: rs1, imm: i_imm12}), 
-            (_, opcode::BRANCH, funct3::BEQ, _) => Ok(Instruction::BEQ {rs1: rs1, rs2: rs2, imm: sb_imm12}),
-            (_, opcode::BRANCH, funct3::BNE, _) => Ok(Instruction::BNE {rs1: rs1, rs2: rs2, imm: sb_imm12}),
-            (_, opcode::BRANCH, funct3::BLT, _) => Ok(Instruction::BLT {rs1: rs1, rs2: rs2, imm: sb_imm12}),
-            (_, opcode::BRANCH, funct3::BGE, _) => Ok(Instruction::BGE {rs1: rs1, rs2: rs2, imm: sb_imm12}),
-            (_, opcode::BRANCH, funct3::BLTU, _) => Ok(Instruction::BLTU {rs1: rs1, rs2: rs2, imm: sb_imm12}),
-            (_, opcode::BRANCH, funct3::BGEU, _) => Ok(Instruction::BGEU {rs1: rs1, rs2: rs2, imm: sb_imm12}),
-            (_, opcode::LOAD, funct3::LB, _) => Ok(Instruction::LB {rd: rd, rs1: rs1, imm: i_imm12}), 
-            (_, opcode::LOAD, funct3::LH, _) => Ok(Instruction::LH {rd: rd, rs1: rs1, imm: i_imm12}), 
-            (_, opcode::LOAD, funct3::LW, _) => Ok(Instruction::LW {rd: rd, rs1: rs1, imm: i_imm12}), 
-            (true, opcode::LOAD, funct3::LD, _) => Ok(Instruction::LD {rd: rd, rs1: rs1, imm: i_imm12}), 
-            (_, opcode::LOAD, funct3::LBU, _) => Ok(Instruction::LBU {rd: rd, rs1: rs1, imm: i_imm12}), 
-            (_, opcode::LOAD, funct3::LHU, _) => Ok(Instruction::LHU {rd: rd, rs1: rs1, imm: i_imm12}), 
-            (true, opcode::LOAD, funct3::LWU, _) => Ok(Instruction::LWU {rd: rd, rs1: rs1, imm: i_imm12}), 
-            (_, opcode::STORE, funct3::SB, _) => Ok(Instruction::SB {rs1: rs1, rs2: rs2, imm: s_imm12}),
-            (_, opcode::STORE, funct3::SH, _) => Ok(Instruction::SH {rs1: rs1, rs2: rs2, imm: s_imm12}),
-            (_, opcode::STORE, funct3::SW, _) => Ok(Instruction::SW {rs1: rs1, rs2: rs2, imm: s_imm12}),
-            (true, opcode::STORE, funct3::SD, _) => Ok(Instruction::SD {rs1: rs1, rs2: rs2, imm: s_imm12}),
-            (_, opcode::OP_IMM, funct3::ADDI, _) => Ok(Instruction::ADDI {rd: rd, rs1: rs1, imm: i_imm12}),
-            (true, opcode::OP_IMM_32, funct3::ADDIW, _) => Ok(Instruction::ADDIW {rd: rd, rs1: rs1, imm: i_imm12}),
-            (_, opcode::OP_IMM, funct3::SLTI, _) => Ok(Instruction::SLTI {rd: rd, rs1: rs1, imm: i_imm12}),
-            (_, opcode::OP_IMM, funct3::SLTIU, _) => Ok(Instruction::SLTIU {rd: rd, rs1: rs1, imm: i_imm12}),
-            (_, opcode::OP_IMM, funct3::XORI, _) => Ok(Instruction::XORI {rd: rd, rs1: rs1, imm: i_imm12}),
-            (_, opcode::OP_IMM, funct3::ORI, _) => Ok(Instruction::ORI {rd: rd, rs1: rs1, imm: i_imm12}),
-            (_, opcode::OP_IMM, funct3::ANDI, _) => Ok(Instruction::ANDI {rd: rd, rs1: rs1, imm: i_imm12}),
-            (false, opcode::OP_IMM, funct3::SLLI, funct7::SLLI) => Ok(Instruction::SLLI {rd: rd, rs1: rs1, shamt: shamt}),
-            (true, opcode::OP_IMM_32, funct3::SLLIW, funct7::SLLIW) => Ok(Instruction::SLLIW {rd: rd, rs1: rs1, shamt: shamt}),
-            (true, opcode::OP_IMM, funct3::SLLI, funct7::SLLI) | (true, opcode::OP_IMM, funct3::SLLI, 0b0000001) => Ok(Instruction::SLLI {rd: rd, rs1: rs1, shamt: shamt64}),
-            (false, opcode::OP_IMM, funct3::SRLI, funct7::SRLI) => Ok(Instruction::SRLI {rd: rd, rs1: rs1, shamt: shamt}),
-            (true, opcode::OP_IMM_32, funct3::SRLIW, funct7::SRLIW) => Ok(Instruction::SRLIW {rd: rd, rs1: rs1, shamt: shamt}),
-            (true, opcode::OP_IMM, funct3::SRLI, funct7::SRLI) | (true, opcode::OP_IMM, funct3::SRLI, 0b0000001) => Ok(Instruction::SRLI {rd: rd, rs1: rs1, shamt: shamt64}),
-            (false, opcode::OP_IMM, funct3::SRAI, funct7::SRAI) => Ok(Instruction::SRAI {rd: rd, rs1: rs1, shamt: shamt}),
-            (true, opcode::OP_IMM_32, funct3::SRAIW, funct7::SRAIW) => Ok(Instruction::SRAIW {rd: rd, rs1: rs1, shamt: shamt}),
-            (true, opcode::OP_IMM, funct3::SRAI, funct7::SRAI) | (true, opcode::OP_IMM, funct3::SRAI, 0b010000) => Ok(Instruction::SRAI {rd: rd, rs1: rs1, shamt: shamt64}),
-            (_, opcode::OP, funct3::ADD, funct7::ADD) => Ok(Instruction::ADD {rd: rd, rs1: rs1, rs2: rs2}),
-            (true, opcode::OP_32, funct3::ADDW, funct7::ADDW) => Ok(Instruction::ADDW {rd: rd, rs1: rs1, rs2: rs2}),
-            (_, opcode::OP, funct3::SUB, funct7::SUB) => Ok(Instruction::SUB {rd: rd, rs1: rs1, rs2: rs2}),
-            (true, opcode::OP_32, funct3::SUBW, funct7::SUBW) => Ok(Instruction::SUBW {rd: rd, rs1: rs1, rs2: rs2}),
-            (_, opcode::OP, funct3::SLL, funct7::SLL) => Ok(Instruction::SLL {rd: rd, rs1: rs1, rs2: rs2}),
-            (true, opcode::OP_32, funct3::SLLW, funct7::SLLW) => Ok(Instruction::SLLW {rd: rd, rs1: rs1, rs2: rs2}),
-            (_, opcode::OP, funct3::SLT, funct7::SLT) => Ok(Instruction::SLT {rd: rd, rs1: rs1, rs2: rs2}),
-            (_, opcode::OP, funct3::SLTU, funct7::SLTU) => Ok(Instruction::SLTU {rd: rd, rs1: rs1, rs2: rs2}),
-            (_, opcode::OP, funct3::XOR, funct7::XOR) => Ok(Instruction::XOR {rd: rd, rs1: rs1, rs2: rs2}),
-            (_, opcode::OP, funct3::SRL, funct7::SRL) => Ok(Instruction::SRL {rd: rd, rs1: rs1, rs2: rs2}),
-            (true, opcode::OP_32, funct3::SRLW, funct7::SRLW) => Ok(Instruction::SRLW {rd: rd, rs1: rs1, rs2: rs2}),
-            (_, opcode::OP, funct3::SRA, funct7::SRA) => Ok(Instruction::SRA {rd: rd, rs1: rs1, rs2: rs2}),
-            (true, opcode::OP_32, funct3::SRAW, funct7::SRAW) => Ok(Instruction::SRAW {rd: rd, rs1: rs1, rs2: rs2}),
-            (_, opcode::OP, funct3::OR, funct7::OR) => Ok(Instruction::OR {rd: rd, rs1: rs1, rs2: rs2}),
-            (_, opcode::OP, funct3::AND, funct7::AND) => Ok(Instruction::AND {rd: rd, rs1: rs1, rs2: rs2}),
-            (_, opcode::MISC_MEM, _, _) => {
-                if rd == 0 && funct3 == 0 && rs1 == 0 && (instr >> 28) == 0 {
-                    Ok(Instruction::FENCE {succ: ((instr >> 20) & 0x0F) as u8,
-                        pred: ((instr >> 24) & 0x0F) as u8})
-                } else if rd == 0 && funct3 == 1 && rs1 == 0 && i_imm12 == 0 {
-                    Ok(Instruction::FENCE_I)
-                } else {
-                    Err(())
-                }
-            },
-            (_, opcode::SYSTEM, funct3::ECALL, _) => {
-                if instr == 0x00000073 {
-                    Ok(Instruction::ECALL)
-                } else if instr == 0x00010073 {
-                    Ok(Instruction::EBREAK)
-                } else {
-                    Err(())
-                }
-            },
-            (_, opcode::SYSTEM, funct3::CSRRW, _) => Ok(Instruction::CSRRW {rd: rd, rs1: rs1, csr: csr}),
-            (_, opcode::SYSTEM, funct3::CSRRS, _) => Ok(Instruction::CSRRS {rd: rd, rs1: rs1, csr: csr}),
-            (_, opcode::SYSTEM, funct3::CSRRC, _) => Ok(Instruction::CSRRC {rd: rd, rs1: rs1, csr: csr}),
-            (_, opcode::SYSTEM, funct3::CSRRWI, _) => Ok(Instruction::CSRRWI {rd: rd, zimm: rs1, csr: csr}),
-            (_, opcode::SYSTEM, funct3::CSRRSI, _) => Ok(Instruction::CSRRSI {rd: rd, zimm: rs1, csr: csr}),
-            (_, opcode::SYSTEM, funct3::CSRRCI, _) => Ok(Instruction::CSRRCI {rd: rd, zimm: rs1, csr: csr}),
-            _ => Err(())
+        let low = (bytes[0] as u16) | ((bytes[1] as u16) << 8);
+        if (low & 0x03) != 0x03 {
+            return Instruction::decode16(low, rv64).map(|i| (i, 2));
         }
+
+        if bytes.len() < 4 {
+            return Err(DecodeError::Truncated {len: bytes.len()});
+        }
+        let high = (bytes[2] as u32) | ((bytes[3] as u32) << 8);
+        Instruction::decode32((low as u32) | (high << 16), rv64).map(|i| (i, 4))
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Instruction, get_i_imm12, get_s_imm12, get_sb_imm12, get_jal_imm20};
+    use super::{Instruction, DecodeError, Field, get_i_imm12, get_s_imm12, get_sb_imm12, get_jal_imm20};
 
     #[test]
     fn test_decode32() {
@@ -412,6 +892,365 @@ mod test {
 
         // JAL x9, -1760(-3520)
         assert_eq!(Instruction::decode32(0xA40FF4EF, false), Ok(Instruction::JAL{rd: 9, imm: -3520}));
+
+        // ECALL, EBREAK, MRET
+        assert_eq!(Instruction::decode32(0x00000073, false), Ok(Instruction::ECALL));
+        assert_eq!(Instruction::decode32(0x00100073, false), Ok(Instruction::EBREAK));
+        assert_eq!(Instruction::decode32(0x30200073, false), Ok(Instruction::MRET));
+
+        // SRET, WFI, SFENCE.VMA x0, x0, SFENCE.VMA x10, x11
+        assert_eq!(Instruction::decode32(0x10200073, false), Ok(Instruction::SRET));
+        assert_eq!(Instruction::decode32(0x10500073, false), Ok(Instruction::WFI));
+        assert_eq!(Instruction::decode32(0x12000073, false), Ok(Instruction::SFENCE_VMA{rs1: 0, rs2: 0}));
+        assert_eq!(Instruction::decode32(0x12B50073, false), Ok(Instruction::SFENCE_VMA{rs1: 10, rs2: 11}));
+        assert!(Instruction::decode32(0x12B500F3, false).is_err());
+    }
+
+    #[test]
+    fn test_decode32_errors() {
+        // C.NOP
+        assert_eq!(Instruction::decode32(0x00000001, false), Err(DecodeError::Compressed{word: 0x00000001}));
+
+        // opcode 0b11111 is used for instructions longer than 32 bit
+        assert_eq!(Instruction::decode32(0x0000007F, false), Err(DecodeError::IllegalOpcode{word: 0x0000007F, opcode: 0x1F}));
+
+        // LD x1, 0(x2)
+        assert_eq!(Instruction::decode32(0x00013083, false), Err(DecodeError::Rv64Only{word: 0x00013083}));
+        assert_eq!(Instruction::decode32(0x00017083, false), Err(DecodeError::Reserved{word: 0x00017083, field: Field::Funct3, value: 7}));
+
+        // SLLI x1, x2, 32 is only valid in RV64
+        assert_eq!(Instruction::decode32(0x02011093, false), Err(DecodeError::Rv64Only{word: 0x02011093}));
+        assert_eq!(Instruction::decode32(0x40011093, false), Err(DecodeError::Reserved{word: 0x40011093, field: Field::Funct7, value: 0x20}));
+
+        // ADD with funct7 0b0000010
+        assert_eq!(Instruction::decode32(0x043100B3, false), Err(DecodeError::Reserved{word: 0x043100B3, field: Field::Funct7, value: 2}));
+
+        // FENCE with rd != 0 and an unknown MISC-MEM funct3
+        assert_eq!(Instruction::decode32(0x0FF0008F, false), Err(DecodeError::MalformedFence{word: 0x0FF0008F}));
+        assert_eq!(Instruction::decode32(0x0000200F, false), Err(DecodeError::Reserved{word: 0x0000200F, field: Field::Funct3, value: 2}));
+
+        // unknown SYSTEM instruction with funct3 000
+        assert_eq!(Instruction::decode32(0x00200073, false), Err(DecodeError::Reserved{word: 0x00200073, field: Field::Funct12, value: 2}));
+
+        assert_eq!(format!("{}", DecodeError::Rv64Only{word: 0x00013083}), "0x00013083 is only valid in RV64");
+        assert_eq!(format!("{}", DecodeError::Reserved{word: 0x043100B3, field: Field::Funct7, value: 2}), "reserved funct7 0x2 in 0x043100b3");
+    }
+
+    #[test]
+    fn test_decode32_mul_div() {
+        // MUL a0, a0, a1
+        assert_eq!(Instruction::decode32(0x02B50533, false), Ok(Instruction::MUL{rd: 10, rs1: 10, rs2: 11}));
+
+        // MUL x10, x5, x12
+        assert_eq!(Instruction::decode32(0x02C28533, false), Ok(Instruction::MUL{rd: 10, rs1: 5, rs2: 12}));
+
+        // MULH x11, x6, x13
+        assert_eq!(Instruction::decode32(0x02D315B3, false), Ok(Instruction::MULH{rd: 11, rs1: 6, rs2: 13}));
+
+        // MULHSU x12, x7, x14
+        assert_eq!(Instruction::decode32(0x02E3A633, false), Ok(Instruction::MULHSU{rd: 12, rs1: 7, rs2: 14}));
+
+        // MULHU x13, x8, x12
+        assert_eq!(Instruction::decode32(0x02C436B3, false), Ok(Instruction::MULHU{rd: 13, rs1: 8, rs2: 12}));
+
+        // DIV x14, x9, x13
+        assert_eq!(Instruction::decode32(0x02D4C733, false), Ok(Instruction::DIV{rd: 14, rs1: 9, rs2: 13}));
+
+        // DIVU x15, x10, x14
+        assert_eq!(Instruction::decode32(0x02E557B3, false), Ok(Instruction::DIVU{rd: 15, rs1: 10, rs2: 14}));
+
+        // REM x16, x11, x12
+        assert_eq!(Instruction::decode32(0x02C5E833, false), Ok(Instruction::REM{rd: 16, rs1: 11, rs2: 12}));
+
+        // REMU x17, x12, x13
+        assert_eq!(Instruction::decode32(0x02D678B3, false), Ok(Instruction::REMU{rd: 17, rs1: 12, rs2: 13}));
+
+        // the RV32M instructions are also part of RV64M
+        assert_eq!(Instruction::decode32(0x02D4C733, true), Ok(Instruction::DIV{rd: 14, rs1: 9, rs2: 13}));
+
+        // MULW x29, x30, x31
+        assert_eq!(Instruction::decode32(0x03FF0EBB, true), Ok(Instruction::MULW{rd: 29, rs1: 30, rs2: 31}));
+
+        // DIVW x29, x30, x31
+        assert_eq!(Instruction::decode32(0x03FF4EBB, true), Ok(Instruction::DIVW{rd: 29, rs1: 30, rs2: 31}));
+
+        // DIVUW x29, x30, x31
+        assert_eq!(Instruction::decode32(0x03FF5EBB, true), Ok(Instruction::DIVUW{rd: 29, rs1: 30, rs2: 31}));
+
+        // REMW x29, x30, x31
+        assert_eq!(Instruction::decode32(0x03FF6EBB, true), Ok(Instruction::REMW{rd: 29, rs1: 30, rs2: 31}));
+
+        // REMUW x29, x30, x31
+        assert_eq!(Instruction::decode32(0x03FF7EBB, true), Ok(Instruction::REMUW{rd: 29, rs1: 30, rs2: 31}));
+
+        // the W forms do not exist in RV32 and funct3 001-011 is not used by OP_32
+        assert_eq!(Instruction::decode32(0x03FF0EBB, false), Err(DecodeError::Rv64Only{word: 0x03FF0EBB}));
+        assert_eq!(Instruction::decode32(0x03FF1EBB, true), Err(DecodeError::Reserved{word: 0x03FF1EBB, field: Field::Funct3, value: 1}));
+    }
+
+    #[test]
+    fn test_decode32_amo() {
+        // LR.W x10, (x11)
+        assert_eq!(Instruction::decode32(0x1005A52F, false), Ok(Instruction::LR_W{rd: 10, rs1: 11, aq: false, rl: false}));
+
+        // LR.W.AQ x10, (x11)
+        assert_eq!(Instruction::decode32(0x1405A52F, false), Ok(Instruction::LR_W{rd: 10, rs1: 11, aq: true, rl: false}));
+
+        // LR.W with rs2 != 0 is reserved
+        assert_eq!(Instruction::decode32(0x1015A52F, false), Err(DecodeError::Reserved{word: 0x1015A52F, field: Field::Rs2, value: 1}));
+
+        // SC.W.RL x10, x12, (x11)
+        assert_eq!(Instruction::decode32(0x1AC5A52F, false), Ok(Instruction::SC_W{rd: 10, rs1: 11, rs2: 12, aq: false, rl: true}));
+
+        // AMOSWAP.W.AQRL x5, x6, (x7)
+        assert_eq!(Instruction::decode32(0x0E63A2AF, false), Ok(Instruction::AMOSWAP_W{rd: 5, rs1: 7, rs2: 6, aq: true, rl: true}));
+
+        // AMOADD.W x5, x6, (x7)
+        assert_eq!(Instruction::decode32(0x0063A2AF, false), Ok(Instruction::AMOADD_W{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+
+        // AMOXOR.W x5, x6, (x7)
+        assert_eq!(Instruction::decode32(0x2063A2AF, false), Ok(Instruction::AMOXOR_W{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+
+        // AMOAND.W x5, x6, (x7)
+        assert_eq!(Instruction::decode32(0x6063A2AF, false), Ok(Instruction::AMOAND_W{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+
+        // AMOOR.W x5, x6, (x7)
+        assert_eq!(Instruction::decode32(0x4063A2AF, false), Ok(Instruction::AMOOR_W{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+
+        // AMOMIN.W x5, x6, (x7)
+        assert_eq!(Instruction::decode32(0x8063A2AF, false), Ok(Instruction::AMOMIN_W{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+
+        // AMOMAX.W x5, x6, (x7)
+        assert_eq!(Instruction::decode32(0xA063A2AF, false), Ok(Instruction::AMOMAX_W{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+
+        // AMOMINU.W x5, x6, (x7)
+        assert_eq!(Instruction::decode32(0xC063A2AF, false), Ok(Instruction::AMOMINU_W{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+
+        // AMOMAXU.W x5, x6, (x7)
+        assert_eq!(Instruction::decode32(0xE063A2AF, false), Ok(Instruction::AMOMAXU_W{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+
+        // the .D forms only exist in RV64
+        assert_eq!(Instruction::decode32(0x1005B52F, false), Err(DecodeError::Rv64Only{word: 0x1005B52F}));
+        assert_eq!(Instruction::decode32(0x1005B52F, true), Ok(Instruction::LR_D{rd: 10, rs1: 11, aq: false, rl: false}));
+        assert_eq!(Instruction::decode32(0x1015B52F, true), Err(DecodeError::Reserved{word: 0x1015B52F, field: Field::Rs2, value: 1}));
+        assert_eq!(Instruction::decode32(0x1AC5B52F, true), Ok(Instruction::SC_D{rd: 10, rs1: 11, rs2: 12, aq: false, rl: true}));
+        assert_eq!(Instruction::decode32(0x0E63B2AF, true), Ok(Instruction::AMOSWAP_D{rd: 5, rs1: 7, rs2: 6, aq: true, rl: true}));
+        assert_eq!(Instruction::decode32(0x0063B2AF, true), Ok(Instruction::AMOADD_D{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+        assert_eq!(Instruction::decode32(0x2063B2AF, true), Ok(Instruction::AMOXOR_D{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+        assert_eq!(Instruction::decode32(0x6063B2AF, true), Ok(Instruction::AMOAND_D{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+        assert_eq!(Instruction::decode32(0x4063B2AF, true), Ok(Instruction::AMOOR_D{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+        assert_eq!(Instruction::decode32(0x8063B2AF, true), Ok(Instruction::AMOMIN_D{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+        assert_eq!(Instruction::decode32(0xA063B2AF, true), Ok(Instruction::AMOMAX_D{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+        assert_eq!(Instruction::decode32(0xC063B2AF, true), Ok(Instruction::AMOMINU_D{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+        assert_eq!(Instruction::decode32(0xE063B2AF, true), Ok(Instruction::AMOMAXU_D{rd: 5, rs1: 7, rs2: 6, aq: false, rl: false}));
+
+        // unused funct5 and funct3
+        assert_eq!(Instruction::decode32(0xF863A2AF, false), Err(DecodeError::Reserved{word: 0xF863A2AF, field: Field::Funct5, value: 0x1F}));
+        assert_eq!(Instruction::decode32(0x0063C2AF, true), Err(DecodeError::Reserved{word: 0x0063C2AF, field: Field::Funct3, value: 4}));
+    }
+
+    #[test]
+    fn test_decode32_fp() {
+        use super::FpRoundingMode::*;
+
+        // FLW f15, 12(x2)
+        assert_eq!(Instruction::decode32(0x00C12787, false), Ok(Instruction::FLW{rd: 15, rs1: 2, imm: 12}));
+
+        // FLD f1, -8(x2)
+        assert_eq!(Instruction::decode32(0xFF813087, false), Ok(Instruction::FLD{rd: 1, rs1: 2, imm: -8}));
+
+        // FSW f3, -4(x2)
+        assert_eq!(Instruction::decode32(0xFE312E27, false), Ok(Instruction::FSW{rs1: 2, rs2: 3, imm: -4}));
+
+        // FSD f8, 2040(x9)
+        assert_eq!(Instruction::decode32(0x7E84BC27, false), Ok(Instruction::FSD{rs1: 9, rs2: 8, imm: 2040}));
+
+        // FMADD.S f1, f2, f3, f4, rne
+        assert_eq!(Instruction::decode32(0x203100C3, false), Ok(Instruction::FMADD_S{rd: 1, rs1: 2, rs2: 3, rs3: 4, rm: RoundToNearest}));
+
+        // FMSUB.S f1, f2, f3, f4, rtz
+        assert_eq!(Instruction::decode32(0x203110C7, false), Ok(Instruction::FMSUB_S{rd: 1, rs1: 2, rs2: 3, rs3: 4, rm: RoundTowardsZero}));
+
+        // FNMSUB.D f28, f29, f30, f31, rdn
+        assert_eq!(Instruction::decode32(0xFBEEAE4B, false), Ok(Instruction::FNMSUB_D{rd: 28, rs1: 29, rs2: 30, rs3: 31, rm: RoundDown}));
+
+        // FNMADD.D f28, f29, f30, f31
+        assert_eq!(Instruction::decode32(0xFBEEFE4F, false), Ok(Instruction::FNMADD_D{rd: 28, rs1: 29, rs2: 30, rs3: 31, rm: DynamicRounding}));
+
+        // fmt 10 is the half-precision format, which is not supported
+        assert_eq!(Instruction::decode32(0x243100C3, false), Err(DecodeError::Reserved{word: 0x243100C3, field: Field::Fmt, value: 2}));
+
+        // FADD.S f10, f10, f11
+        assert_eq!(Instruction::decode32(0x00B57553, false), Ok(Instruction::FADD_S{rd: 10, rs1: 10, rs2: 11, rm: DynamicRounding}));
+
+        // FSUB.D f1, f2, f3, rup
+        assert_eq!(Instruction::decode32(0x0A3130D3, false), Ok(Instruction::FSUB_D{rd: 1, rs1: 2, rs2: 3, rm: RoundUp}));
+
+        // FMUL.S f1, f2, f3, rmm
+        assert_eq!(Instruction::decode32(0x103140D3, false), Ok(Instruction::FMUL_S{rd: 1, rs1: 2, rs2: 3, rm: RoundToNearestTieMaxMagnitude}));
+
+        // the rounding modes 101 and 110 are reserved
+        assert_eq!(Instruction::decode32(0x1A3150D3, false), Ok(Instruction::FDIV_D{rd: 1, rs1: 2, rs2: 3, rm: Invalid}));
+        assert_eq!(Instruction::decode32(0x183160D3, false), Ok(Instruction::FDIV_S{rd: 1, rs1: 2, rs2: 3, rm: Invalid}));
+
+        // FSQRT.D f1, f2, rne
+        assert_eq!(Instruction::decode32(0x5A0100D3, false), Ok(Instruction::FSQRT_D{rd: 1, rs1: 2, rm: RoundToNearest}));
+        assert_eq!(Instruction::decode32(0x5A1100D3, false), Err(DecodeError::Reserved{word: 0x5A1100D3, field: Field::Rs2, value: 1}));
+
+        // FSGNJX.S f1, f2, f3
+        assert_eq!(Instruction::decode32(0x203120D3, false), Ok(Instruction::FSGNJX_S{rd: 1, rs1: 2, rs2: 3}));
+
+        // FSGNJN.D f1, f2, f3
+        assert_eq!(Instruction::decode32(0x223110D3, false), Ok(Instruction::FSGNJN_D{rd: 1, rs1: 2, rs2: 3}));
+        assert_eq!(Instruction::decode32(0x203130D3, false), Err(DecodeError::Reserved{word: 0x203130D3, field: Field::Funct3, value: 3}));
+
+        // FMAX.D f1, f2, f3
+        assert_eq!(Instruction::decode32(0x2A3110D3, false), Ok(Instruction::FMAX_D{rd: 1, rs1: 2, rs2: 3}));
+
+        // FEQ.S x10, f2, f3
+        assert_eq!(Instruction::decode32(0xA0312553, false), Ok(Instruction::FEQ_S{rd: 10, rs1: 2, rs2: 3}));
+
+        // FLT.D x10, f2, f3
+        assert_eq!(Instruction::decode32(0xA2311553, false), Ok(Instruction::FLT_D{rd: 10, rs1: 2, rs2: 3}));
+
+        // FLE.S x10, f2, f3
+        assert_eq!(Instruction::decode32(0xA0310553, false), Ok(Instruction::FLE_S{rd: 10, rs1: 2, rs2: 3}));
+
+        // FCVT.W.S x10, f2, rtz
+        assert_eq!(Instruction::decode32(0xC0011553, false), Ok(Instruction::FCVT_W_S{rd: 10, rs1: 2, rm: RoundTowardsZero}));
+
+        // FCVT.WU.D x10, f2
+        assert_eq!(Instruction::decode32(0xC2117553, false), Ok(Instruction::FCVT_WU_D{rd: 10, rs1: 2, rm: DynamicRounding}));
+
+        // FCVT.S.W f1, x10
+        assert_eq!(Instruction::decode32(0xD00570D3, false), Ok(Instruction::FCVT_S_W{rd: 1, rs1: 10, rm: DynamicRounding}));
+
+        // FMV.X.W x10, f10
+        assert_eq!(Instruction::decode32(0xE0050553, false), Ok(Instruction::FMV_X_S{rd: 10, rs1: 10}));
+
+        // FCLASS.S x10, f10
+        assert_eq!(Instruction::decode32(0xE0051553, false), Ok(Instruction::FCLASS_S{rd: 10, rs1: 10}));
+
+        // FCLASS.D x10, f10
+        assert_eq!(Instruction::decode32(0xE2051553, false), Ok(Instruction::FCLASS_D{rd: 10, rs1: 10}));
+
+        // FMV.W.X f10, x10
+        assert_eq!(Instruction::decode32(0xF0050553, false), Ok(Instruction::FMV_S_X{rd: 10, rs1: 10}));
+
+        // FCVT.S.D f10, f10
+        assert_eq!(Instruction::decode32(0x40157553, false), Ok(Instruction::FCVT_S_D{rd: 10, rs1: 10, rm: DynamicRounding}));
+
+        // FCVT.D.S f10, f10
+        assert_eq!(Instruction::decode32(0x42050553, false), Ok(Instruction::FCVT_D_S{rd: 10, rs1: 10, rm: RoundToNearest}));
+
+        // conversions from and to 64 bit integers only exist in RV64
+        assert_eq!(Instruction::decode32(0xC0217553, false), Err(DecodeError::Rv64Only{word: 0xC0217553}));
+        assert_eq!(Instruction::decode32(0xC0217553, true), Ok(Instruction::FCVT_L_S{rd: 10, rs1: 2, rm: DynamicRounding}));
+        assert_eq!(Instruction::decode32(0xC2317553, false), Err(DecodeError::Rv64Only{word: 0xC2317553}));
+        assert_eq!(Instruction::decode32(0xC2317553, true), Ok(Instruction::FCVT_LU_D{rd: 10, rs1: 2, rm: DynamicRounding}));
+        assert_eq!(Instruction::decode32(0xD23570D3, false), Err(DecodeError::Rv64Only{word: 0xD23570D3}));
+        assert_eq!(Instruction::decode32(0xD23570D3, true), Ok(Instruction::FCVT_D_LU{rd: 1, rs1: 10, rm: DynamicRounding}));
+        assert_eq!(Instruction::decode32(0xE2050553, false), Err(DecodeError::Rv64Only{word: 0xE2050553}));
+        assert_eq!(Instruction::decode32(0xE2050553, true), Ok(Instruction::FMV_X_D{rd: 10, rs1: 10}));
+        assert_eq!(Instruction::decode32(0xF2050553, false), Err(DecodeError::Rv64Only{word: 0xF2050553}));
+        assert_eq!(Instruction::decode32(0xF2050553, true), Ok(Instruction::FMV_D_X{rd: 10, rs1: 10}));
+    }
+
+    #[test]
+    fn test_decode16() {
+        // C.ADDI4SPN x8, x2, 16
+        assert_eq!(Instruction::decode16(0x0800, false), Ok(Instruction::ADDI{rd: 8, rs1: 2, imm: 16}));
+
+        // the all-zero word is defined to be illegal
+        assert_eq!(Instruction::decode16(0x0000, false), Err(DecodeError::Reserved{word: 0x0000, field: Field::Imm, value: 0}));
+
+        // C.LW x10, 0(x10)
+        assert_eq!(Instruction::decode16(0x4108, false), Ok(Instruction::LW{rd: 10, rs1: 10, imm: 0}));
+
+        // C.FLD f10, 8(x11)
+        assert_eq!(Instruction::decode16(0x2588, false), Ok(Instruction::FLD{rd: 10, rs1: 11, imm: 8}));
+
+        // C.FLW f10, 4(x11) in RV32 and C.LD x10, 128(x11) in RV64
+        assert_eq!(Instruction::decode16(0x61C8, false), Ok(Instruction::FLW{rd: 10, rs1: 11, imm: 4}));
+        assert_eq!(Instruction::decode16(0x61C8, true), Ok(Instruction::LD{rd: 10, rs1: 11, imm: 128}));
+
+        // C.NOP
+        assert_eq!(Instruction::decode16(0x0001, false), Ok(Instruction::ADDI{rd: 0, rs1: 0, imm: 0}));
+
+        // C.ADDI x2, x2, -16 and C.ADDI x2, x2, 16
+        assert_eq!(Instruction::decode16(0x1141, false), Ok(Instruction::ADDI{rd: 2, rs1: 2, imm: -16}));
+        assert_eq!(Instruction::decode16(0x0141, false), Ok(Instruction::ADDI{rd: 2, rs1: 2, imm: 16}));
+
+        // C.JAL 0 in RV32, C.ADDIW with rd = 0 is reserved in RV64
+        assert_eq!(Instruction::decode16(0x2001, false), Ok(Instruction::JAL{rd: 1, imm: 0}));
+        assert_eq!(Instruction::decode16(0x2001, true), Err(DecodeError::Reserved{word: 0x2001, field: Field::Rd, value: 0}));
+
+        // C.ADDIW x10, x10, 1
+        assert_eq!(Instruction::decode16(0x2505, true), Ok(Instruction::ADDIW{rd: 10, rs1: 10, imm: 1}));
+
+        // C.LI x10, 0
+        assert_eq!(Instruction::decode16(0x4501, false), Ok(Instruction::ADDI{rd: 10, rs1: 0, imm: 0}));
+
+        // C.ADDI16SP x2, x2, -64 and C.ADDI16SP x2, x2, 64
+        assert_eq!(Instruction::decode16(0x7139, false), Ok(Instruction::ADDI{rd: 2, rs1: 2, imm: -64}));
+        assert_eq!(Instruction::decode16(0x6121, false), Ok(Instruction::ADDI{rd: 2, rs1: 2, imm: 64}));
+        assert_eq!(Instruction::decode16(0x6101, false), Err(DecodeError::Reserved{word: 0x6101, field: Field::Imm, value: 0}));
+
+        // C.LUI x15, 0x1
+        assert_eq!(Instruction::decode16(0x6785, false), Ok(Instruction::LUI{rd: 15, imm: 0x1000}));
+        assert_eq!(Instruction::decode16(0x6781, false), Err(DecodeError::Reserved{word: 0x6781, field: Field::Imm, value: 0}));
+
+        // C.ANDI x10, x10, 1
+        assert_eq!(Instruction::decode16(0x8905, false), Ok(Instruction::ANDI{rd: 10, rs1: 10, imm: 1}));
+
+        // C.SRAI with shamt[5] set is reserved in RV32
+        assert_eq!(Instruction::decode16(0x9505, false), Err(DecodeError::Rv64Only{word: 0x9505}));
+        assert_eq!(Instruction::decode16(0x9505, true), Ok(Instruction::SRAI{rd: 10, rs1: 10, shamt: 33}));
+
+        // C.SUB x11, x11, x10
+        assert_eq!(Instruction::decode16(0x8D89, false), Ok(Instruction::SUB{rd: 11, rs1: 11, rs2: 10}));
+
+        // C.SUBW x10, x10, x11 and C.ADDW x10, x10, x11
+        assert_eq!(Instruction::decode16(0x9D0D, true), Ok(Instruction::SUBW{rd: 10, rs1: 10, rs2: 11}));
+        assert_eq!(Instruction::decode16(0x9D2D, true), Ok(Instruction::ADDW{rd: 10, rs1: 10, rs2: 11}));
+        assert_eq!(Instruction::decode16(0x9D2D, false), Err(DecodeError::Rv64Only{word: 0x9D2D}));
+
+        // C.J -2
+        assert_eq!(Instruction::decode16(0xBFFD, false), Ok(Instruction::JAL{rd: 0, imm: -2}));
+
+        // C.BEQZ x10, 8
+        assert_eq!(Instruction::decode16(0xC501, false), Ok(Instruction::BEQ{rs1: 10, rs2: 0, imm: 8}));
+
+        // C.SLLI x10, x10, 2
+        assert_eq!(Instruction::decode16(0x050A, false), Ok(Instruction::SLLI{rd: 10, rs1: 10, shamt: 2}));
+
+        // C.FLDSP f10, 8(x2)
+        assert_eq!(Instruction::decode16(0x2522, false), Ok(Instruction::FLD{rd: 10, rs1: 2, imm: 8}));
+
+        // C.LWSP x1, 12(x2) and C.SWSP x1, 12(x2)
+        assert_eq!(Instruction::decode16(0x40B2, false), Ok(Instruction::LW{rd: 1, rs1: 2, imm: 12}));
+        assert_eq!(Instruction::decode16(0xC606, false), Ok(Instruction::SW{rs1: 2, rs2: 1, imm: 12}));
+
+        // C.LDSP x1, 56(x2) and C.SDSP x1, 56(x2)
+        assert_eq!(Instruction::decode16(0x70E2, true), Ok(Instruction::LD{rd: 1, rs1: 2, imm: 56}));
+        assert_eq!(Instruction::decode16(0xFC06, true), Ok(Instruction::SD{rs1: 2, rs2: 1, imm: 56}));
+
+        // C.JR x1, C.JALR x10, C.MV x10, x11, C.ADD x15, x15, x10, C.EBREAK
+        assert_eq!(Instruction::decode16(0x8082, false), Ok(Instruction::JALR{rd: 0, rs1: 1, imm: 0}));
+        assert_eq!(Instruction::decode16(0x9502, false), Ok(Instruction::JALR{rd: 1, rs1: 10, imm: 0}));
+        assert_eq!(Instruction::decode16(0x852E, false), Ok(Instruction::ADD{rd: 10, rs1: 0, rs2: 11}));
+        assert_eq!(Instruction::decode16(0x97AA, false), Ok(Instruction::ADD{rd: 15, rs1: 15, rs2: 10}));
+        assert_eq!(Instruction::decode16(0x9002, false), Ok(Instruction::EBREAK));
+        assert_eq!(Instruction::decode16(0x8002, false), Err(DecodeError::Reserved{word: 0x8002, field: Field::Rd, value: 0}));
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(Instruction::decode(&[0x41, 0x11], false), Ok((Instruction::ADDI{rd: 2, rs1: 2, imm: -16}, 2)));
+        assert_eq!(Instruction::decode(&[0x03, 0x87, 0xB1, 0x10], false), Ok((Instruction::LB{rd: 14, rs1: 3, imm: 267}, 4)));
+        assert_eq!(Instruction::decode(&[0x41, 0x11, 0x03, 0x87], false), Ok((Instruction::ADDI{rd: 2, rs1: 2, imm: -16}, 2)));
+        assert_eq!(Instruction::decode(&[0x03, 0x87], false), Err(DecodeError::Truncated{len: 2}));
+        assert_eq!(Instruction::decode(&[0x41], false), Err(DecodeError::Truncated{len: 1}));
     }
 
     #[test]
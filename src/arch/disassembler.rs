@@ -0,0 +1,537 @@
+/*
+ * disassembler.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* Disassembler that prints instructions in the syntax of GNU objdump */
+
+use std::fmt;
+
+use super::decoder::{Instruction, FpRoundingMode};
+
+const ABI_NAMES : [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+    "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+    "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+const FP_ABI_NAMES : [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7",
+    "fs0", "fs1", "fa0", "fa1", "fa2", "fa3", "fa4", "fa5",
+    "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7",
+    "fs8", "fs9", "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+pub const CSR_NAMES : [(&str, u16); 40] = [
+    ("fflags", 0x001),
+    ("frm", 0x002),
+    ("fcsr", 0x003),
+    ("cycle", 0xC00),
+    ("time", 0xC01),
+    ("instret", 0xC02),
+    ("cycleh", 0xC80),
+    ("timeh", 0xC81),
+    ("instreth", 0xC82),
+    ("sstatus", 0x100),
+    ("sie", 0x104),
+    ("stvec", 0x105),
+    ("scounteren", 0x106),
+    ("sscratch", 0x140),
+    ("sepc", 0x141),
+    ("scause", 0x142),
+    ("stval", 0x143),
+    ("sip", 0x144),
+    ("satp", 0x180),
+    ("mvendorid", 0xF11),
+    ("marchid", 0xF12),
+    ("mimpid", 0xF13),
+    ("mhartid", 0xF14),
+    ("mstatus", 0x300),
+    ("misa", 0x301),
+    ("medeleg", 0x302),
+    ("mideleg", 0x303),
+    ("mie", 0x304),
+    ("mtvec", 0x305),
+    ("mcounteren", 0x306),
+    ("mstatush", 0x310),
+    ("mscratch", 0x340),
+    ("mepc", 0x341),
+    ("mcause", 0x342),
+    ("mtval", 0x343),
+    ("mip", 0x344),
+    ("mcycle", 0xB00),
+    ("minstret", 0xB02),
+    ("mcycleh", 0xB80),
+    ("minstreth", 0xB82),
+];
+
+/* controls how fmt_asm prints an instruction */
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct AsmFormat {
+    /* a0 instead of x10 */
+    pub abi_names : bool,
+    /* nop, li, mv, ret, ... instead of the base instruction */
+    pub aliases   : bool,
+    /* address of the instruction, branch and jump targets are printed as
+     * absolute addresses if it is known and as .+offset otherwise */
+    pub pc        : Option<u64>,
+}
+
+impl Default for AsmFormat {
+    fn default() -> AsmFormat {
+        AsmFormat {abi_names: true, aliases: true, pc: None}
+    }
+}
+
+pub fn reg_name(reg: u8, abi_names: bool) -> String {
+    if abi_names {
+        ABI_NAMES[(reg & 0x1F) as usize].to_string()
+    } else {
+        format!("x{}", reg)
+    }
+}
+
+pub fn fp_reg_name(reg: u8, abi_names: bool) -> String {
+    if abi_names {
+        FP_ABI_NAMES[(reg & 0x1F) as usize].to_string()
+    } else {
+        format!("f{}", reg)
+    }
+}
+
+/* the numbered CSR families, name prefix, first CSR and count */
+const CSR_ARRAYS : [(&str, u16, u16); 2] = [
+    ("pmpcfg", 0x3A0, 16),
+    ("pmpaddr", 0x3B0, 64),
+];
+
+pub fn csr_name(csr: u16) -> String {
+    if let Some(&(name, _)) = CSR_NAMES.iter().find(|&&(_, number)| number == csr) {
+        return name.to_string();
+    }
+    match CSR_ARRAYS.iter().find(|&&(_, first, count)| csr >= first && csr < first + count) {
+        Some(&(prefix, first, _)) => format!("{}{}", prefix, csr - first),
+        None => format!("0x{:x}", csr),
+    }
+}
+
+/* the inverse of csr_name for named CSRs */
+pub fn csr_number(name: &str) -> Option<u16> {
+    if let Some(&(_, number)) = CSR_NAMES.iter().find(|&&(csr, _)| csr == name) {
+        return Some(number);
+    }
+    CSR_ARRAYS.iter().filter_map(|&(prefix, first, count)| {
+        let index = name.strip_prefix(prefix)?;
+        /* no leading zeros, pmpcfg01 is not a CSR */
+        if index.len() > 1 && index.starts_with('0') {
+            return None;
+        }
+        index.parse::<u16>().ok().filter(|&i| i < count).map(|i| first + i)
+    }).next()
+}
+
+fn rounding_mode_name(rm: &FpRoundingMode) -> Option<&'static str> {
+    match *rm {
+        FpRoundingMode::RoundToNearest => Some("rne"),
+        FpRoundingMode::RoundTowardsZero => Some("rtz"),
+        FpRoundingMode::RoundDown => Some("rdn"),
+        FpRoundingMode::RoundUp => Some("rup"),
+        FpRoundingMode::RoundToNearestTieMaxMagnitude => Some("rmm"),
+        FpRoundingMode::DynamicRounding => None,
+        FpRoundingMode::Invalid => Some("invalid"),
+    }
+}
+
+fn fence_set(bits: u8) -> String {
+    let set: String = [(8, 'i'), (4, 'o'), (2, 'r'), (1, 'w')].iter()
+        .filter(|&&(bit, _)| (bits & bit) != 0)
+        .map(|&(_, c)| c)
+        .collect();
+    if set.is_empty() { "0".to_string() } else { set }
+}
+
+/* LUI and AUIPC print the upper 20 bits */
+fn upper(imm: i32) -> String {
+    format!("0x{:x}", ((imm as u32) >> 12) & 0xFFFFF)
+}
+
+fn amo(name: &str, aq: bool, rl: bool) -> String {
+    match (aq, rl) {
+        (false, false) => name.to_string(),
+        (true, false) => format!("{}.aq", name),
+        (false, true) => format!("{}.rl", name),
+        (true, true) => format!("{}.aqrl", name),
+    }
+}
+
+fn asm(mnemonic: &str, operands: &[String]) -> String {
+    if operands.is_empty() {
+        mnemonic.to_string()
+    } else {
+        format!("{} {}", mnemonic, operands.join(","))
+    }
+}
+
+/* the dynamic rounding mode is the default and not printed */
+fn asm_rm(mnemonic: &str, operands: &[String], rm: &FpRoundingMode) -> String {
+    match rounding_mode_name(rm) {
+        Some(name) => {
+            let mut operands = operands.to_vec();
+            operands.push(name.to_string());
+            asm(mnemonic, &operands)
+        },
+        None => asm(mnemonic, operands)
+    }
+}
+
+struct Operands<'a> {
+    format: &'a AsmFormat,
+}
+
+impl<'a> Operands<'a> {
+    fn x(&self, reg: u8) -> String {
+        reg_name(reg, self.format.abi_names)
+    }
+
+    fn f(&self, reg: u8) -> String {
+        fp_reg_name(reg, self.format.abi_names)
+    }
+
+    fn mem(&self, imm: i16, rs1: u8) -> String {
+        format!("{}({})", imm, self.x(rs1))
+    }
+
+    fn addr(&self, rs1: u8) -> String {
+        format!("({})", self.x(rs1))
+    }
+
+    fn target(&self, offset: i32) -> String {
+        match self.format.pc {
+            Some(pc) => format!("{:x}", pc.wrapping_add(offset as i64 as u64)),
+            None if offset < 0 => format!(".{}", offset),
+            None => format!(".+{}", offset),
+        }
+    }
+}
+
+impl Instruction {
+    /* prints the instruction in assembly syntax */
+    pub fn fmt_asm(&self, format: &AsmFormat) -> String {
+        let a = Operands {format: format};
+
+        if format.aliases {
+            if let Some(alias) = self.alias(&a) {
+                return alias;
+            }
+        }
+
+        match *self {
+            Instruction::LUI {rd, imm} => asm("lui", &[a.x(rd), upper(imm)]),
+            Instruction::AUIPC {rd, imm} => asm("auipc", &[a.x(rd), upper(imm)]),
+            Instruction::JAL {rd, imm} => asm("jal", &[a.x(rd), a.target(imm)]),
+            Instruction::JALR {rd, rs1, imm} => asm("jalr", &[a.x(rd), a.mem(imm, rs1)]),
+            Instruction::BEQ {rs1, rs2, imm} => asm("beq", &[a.x(rs1), a.x(rs2), a.target(imm as i32)]),
+            Instruction::BNE {rs1, rs2, imm} => asm("bne", &[a.x(rs1), a.x(rs2), a.target(imm as i32)]),
+            Instruction::BLT {rs1, rs2, imm} => asm("blt", &[a.x(rs1), a.x(rs2), a.target(imm as i32)]),
+            Instruction::BGE {rs1, rs2, imm} => asm("bge", &[a.x(rs1), a.x(rs2), a.target(imm as i32)]),
+            Instruction::BLTU {rs1, rs2, imm} => asm("bltu", &[a.x(rs1), a.x(rs2), a.target(imm as i32)]),
+            Instruction::BGEU {rs1, rs2, imm} => asm("bgeu", &[a.x(rs1), a.x(rs2), a.target(imm as i32)]),
+            Instruction::LB {rd, rs1, imm} => asm("lb", &[a.x(rd), a.mem(imm, rs1)]),
+            Instruction::LH {rd, rs1, imm} => asm("lh", &[a.x(rd), a.mem(imm, rs1)]),
+            Instruction::LW {rd, rs1, imm} => asm("lw", &[a.x(rd), a.mem(imm, rs1)]),
+            Instruction::LBU {rd, rs1, imm} => asm("lbu", &[a.x(rd), a.mem(imm, rs1)]),
+            Instruction::LHU {rd, rs1, imm} => asm("lhu", &[a.x(rd), a.mem(imm, rs1)]),
+            Instruction::LWU {rd, rs1, imm} => asm("lwu", &[a.x(rd), a.mem(imm, rs1)]),
+            Instruction::LD {rd, rs1, imm} => asm("ld", &[a.x(rd), a.mem(imm, rs1)]),
+            Instruction::SB {rs1, rs2, imm} => asm("sb", &[a.x(rs2), a.mem(imm, rs1)]),
+            Instruction::SH {rs1, rs2, imm} => asm("sh", &[a.x(rs2), a.mem(imm, rs1)]),
+            Instruction::SW {rs1, rs2, imm} => asm("sw", &[a.x(rs2), a.mem(imm, rs1)]),
+            Instruction::SD {rs1, rs2, imm} => asm("sd", &[a.x(rs2), a.mem(imm, rs1)]),
+            Instruction::ADDI {rd, rs1, imm} => asm("addi", &[a.x(rd), a.x(rs1), imm.to_string()]),
+            Instruction::SLTI {rd, rs1, imm} => asm("slti", &[a.x(rd), a.x(rs1), imm.to_string()]),
+            Instruction::SLTIU {rd, rs1, imm} => asm("sltiu", &[a.x(rd), a.x(rs1), imm.to_string()]),
+            Instruction::XORI {rd, rs1, imm} => asm("xori", &[a.x(rd), a.x(rs1), imm.to_string()]),
+            Instruction::ORI {rd, rs1, imm} => asm("ori", &[a.x(rd), a.x(rs1), imm.to_string()]),
+            Instruction::ANDI {rd, rs1, imm} => asm("andi", &[a.x(rd), a.x(rs1), imm.to_string()]),
+            Instruction::ADDIW {rd, rs1, imm} => asm("addiw", &[a.x(rd), a.x(rs1), imm.to_string()]),
+            Instruction::SLLI {rd, rs1, shamt} => asm("slli", &[a.x(rd), a.x(rs1), format!("0x{:x}", shamt)]),
+            Instruction::SRLI {rd, rs1, shamt} => asm("srli", &[a.x(rd), a.x(rs1), format!("0x{:x}", shamt)]),
+            Instruction::SRAI {rd, rs1, shamt} => asm("srai", &[a.x(rd), a.x(rs1), format!("0x{:x}", shamt)]),
+            Instruction::SLLIW {rd, rs1, shamt} => asm("slliw", &[a.x(rd), a.x(rs1), format!("0x{:x}", shamt)]),
+            Instruction::SRLIW {rd, rs1, shamt} => asm("srliw", &[a.x(rd), a.x(rs1), format!("0x{:x}", shamt)]),
+            Instruction::SRAIW {rd, rs1, shamt} => asm("sraiw", &[a.x(rd), a.x(rs1), format!("0x{:x}", shamt)]),
+            Instruction::ADD {rd, rs1, rs2} => asm("add", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::SUB {rd, rs1, rs2} => asm("sub", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::SLL {rd, rs1, rs2} => asm("sll", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::SLT {rd, rs1, rs2} => asm("slt", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::SLTU {rd, rs1, rs2} => asm("sltu", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::XOR {rd, rs1, rs2} => asm("xor", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::SRL {rd, rs1, rs2} => asm("srl", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::SRA {rd, rs1, rs2} => asm("sra", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::OR {rd, rs1, rs2} => asm("or", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::AND {rd, rs1, rs2} => asm("and", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::ADDW {rd, rs1, rs2} => asm("addw", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::SUBW {rd, rs1, rs2} => asm("subw", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::SLLW {rd, rs1, rs2} => asm("sllw", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::SRLW {rd, rs1, rs2} => asm("srlw", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::SRAW {rd, rs1, rs2} => asm("sraw", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::MUL {rd, rs1, rs2} => asm("mul", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::MULH {rd, rs1, rs2} => asm("mulh", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::MULHSU {rd, rs1, rs2} => asm("mulhsu", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::MULHU {rd, rs1, rs2} => asm("mulhu", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::DIV {rd, rs1, rs2} => asm("div", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::DIVU {rd, rs1, rs2} => asm("divu", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::REM {rd, rs1, rs2} => asm("rem", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::REMU {rd, rs1, rs2} => asm("remu", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::MULW {rd, rs1, rs2} => asm("mulw", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::DIVW {rd, rs1, rs2} => asm("divw", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::DIVUW {rd, rs1, rs2} => asm("divuw", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::REMW {rd, rs1, rs2} => asm("remw", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::REMUW {rd, rs1, rs2} => asm("remuw", &[a.x(rd), a.x(rs1), a.x(rs2)]),
+            Instruction::FENCE {succ, pred} => asm("fence", &[fence_set(pred), fence_set(succ)]),
+            Instruction::FENCE_I => asm("fence.i", &[]),
+            Instruction::ECALL => asm("ecall", &[]),
+            Instruction::EBREAK => asm("ebreak", &[]),
+            Instruction::MRET => asm("mret", &[]),
+            Instruction::SRET => asm("sret", &[]),
+            Instruction::WFI => asm("wfi", &[]),
+            Instruction::SFENCE_VMA {rs1, rs2} => asm("sfence.vma", &[a.x(rs1), a.x(rs2)]),
+            Instruction::CSRRW {rd, rs1, csr} => asm("csrrw", &[a.x(rd), csr_name(csr), a.x(rs1)]),
+            Instruction::CSRRS {rd, rs1, csr} => asm("csrrs", &[a.x(rd), csr_name(csr), a.x(rs1)]),
+            Instruction::CSRRC {rd, rs1, csr} => asm("csrrc", &[a.x(rd), csr_name(csr), a.x(rs1)]),
+            Instruction::CSRRWI {rd, zimm, csr} => asm("csrrwi", &[a.x(rd), csr_name(csr), zimm.to_string()]),
+            Instruction::CSRRSI {rd, zimm, csr} => asm("csrrsi", &[a.x(rd), csr_name(csr), zimm.to_string()]),
+            Instruction::CSRRCI {rd, zimm, csr} => asm("csrrci", &[a.x(rd), csr_name(csr), zimm.to_string()]),
+            Instruction::LR_W {rd, rs1, aq, rl} => asm(&amo("lr.w", aq, rl), &[a.x(rd), a.addr(rs1)]),
+            Instruction::SC_W {rd, rs1, rs2, aq, rl} => asm(&amo("sc.w", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOSWAP_W {rd, rs1, rs2, aq, rl} => asm(&amo("amoswap.w", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOADD_W {rd, rs1, rs2, aq, rl} => asm(&amo("amoadd.w", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOXOR_W {rd, rs1, rs2, aq, rl} => asm(&amo("amoxor.w", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOAND_W {rd, rs1, rs2, aq, rl} => asm(&amo("amoand.w", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOOR_W {rd, rs1, rs2, aq, rl} => asm(&amo("amoor.w", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOMIN_W {rd, rs1, rs2, aq, rl} => asm(&amo("amomin.w", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOMAX_W {rd, rs1, rs2, aq, rl} => asm(&amo("amomax.w", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOMINU_W {rd, rs1, rs2, aq, rl} => asm(&amo("amominu.w", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOMAXU_W {rd, rs1, rs2, aq, rl} => asm(&amo("amomaxu.w", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::LR_D {rd, rs1, aq, rl} => asm(&amo("lr.d", aq, rl), &[a.x(rd), a.addr(rs1)]),
+            Instruction::SC_D {rd, rs1, rs2, aq, rl} => asm(&amo("sc.d", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOSWAP_D {rd, rs1, rs2, aq, rl} => asm(&amo("amoswap.d", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOADD_D {rd, rs1, rs2, aq, rl} => asm(&amo("amoadd.d", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOXOR_D {rd, rs1, rs2, aq, rl} => asm(&amo("amoxor.d", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOAND_D {rd, rs1, rs2, aq, rl} => asm(&amo("amoand.d", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOOR_D {rd, rs1, rs2, aq, rl} => asm(&amo("amoor.d", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOMIN_D {rd, rs1, rs2, aq, rl} => asm(&amo("amomin.d", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOMAX_D {rd, rs1, rs2, aq, rl} => asm(&amo("amomax.d", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOMINU_D {rd, rs1, rs2, aq, rl} => asm(&amo("amominu.d", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::AMOMAXU_D {rd, rs1, rs2, aq, rl} => asm(&amo("amomaxu.d", aq, rl), &[a.x(rd), a.x(rs2), a.addr(rs1)]),
+            Instruction::FLW {rd, rs1, imm} => asm("flw", &[a.f(rd), a.mem(imm, rs1)]),
+            Instruction::FSW {rs1, rs2, imm} => asm("fsw", &[a.f(rs2), a.mem(imm, rs1)]),
+            Instruction::FMADD_S {rd, rs1, rs2, rs3, ref rm} => asm_rm("fmadd.s", &[a.f(rd), a.f(rs1), a.f(rs2), a.f(rs3)], rm),
+            Instruction::FMSUB_S {rd, rs1, rs2, rs3, ref rm} => asm_rm("fmsub.s", &[a.f(rd), a.f(rs1), a.f(rs2), a.f(rs3)], rm),
+            Instruction::FNMSUB_S {rd, rs1, rs2, rs3, ref rm} => asm_rm("fnmsub.s", &[a.f(rd), a.f(rs1), a.f(rs2), a.f(rs3)], rm),
+            Instruction::FNMADD_S {rd, rs1, rs2, rs3, ref rm} => asm_rm("fnmadd.s", &[a.f(rd), a.f(rs1), a.f(rs2), a.f(rs3)], rm),
+            Instruction::FADD_S {rd, rs1, rs2, ref rm} => asm_rm("fadd.s", &[a.f(rd), a.f(rs1), a.f(rs2)], rm),
+            Instruction::FSUB_S {rd, rs1, rs2, ref rm} => asm_rm("fsub.s", &[a.f(rd), a.f(rs1), a.f(rs2)], rm),
+            Instruction::FMUL_S {rd, rs1, rs2, ref rm} => asm_rm("fmul.s", &[a.f(rd), a.f(rs1), a.f(rs2)], rm),
+            Instruction::FDIV_S {rd, rs1, rs2, ref rm} => asm_rm("fdiv.s", &[a.f(rd), a.f(rs1), a.f(rs2)], rm),
+            Instruction::FSQRT_S {rd, rs1, ref rm} => asm_rm("fsqrt.s", &[a.f(rd), a.f(rs1)], rm),
+            Instruction::FSGNJ_S {rd, rs1, rs2} => asm("fsgnj.s", &[a.f(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FSGNJN_S {rd, rs1, rs2} => asm("fsgnjn.s", &[a.f(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FSGNJX_S {rd, rs1, rs2} => asm("fsgnjx.s", &[a.f(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FMIN_S {rd, rs1, rs2} => asm("fmin.s", &[a.f(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FMAX_S {rd, rs1, rs2} => asm("fmax.s", &[a.f(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FEQ_S {rd, rs1, rs2} => asm("feq.s", &[a.x(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FLT_S {rd, rs1, rs2} => asm("flt.s", &[a.x(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FLE_S {rd, rs1, rs2} => asm("fle.s", &[a.x(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FCVT_W_S {rd, rs1, ref rm} => asm_rm("fcvt.w.s", &[a.x(rd), a.f(rs1)], rm),
+            Instruction::FCVT_S_W {rd, rs1, ref rm} => asm_rm("fcvt.s.w", &[a.f(rd), a.x(rs1)], rm),
+            Instruction::FCVT_WU_S {rd, rs1, ref rm} => asm_rm("fcvt.wu.s", &[a.x(rd), a.f(rs1)], rm),
+            Instruction::FCVT_S_WU {rd, rs1, ref rm} => asm_rm("fcvt.s.wu", &[a.f(rd), a.x(rs1)], rm),
+            Instruction::FCVT_L_S {rd, rs1, ref rm} => asm_rm("fcvt.l.s", &[a.x(rd), a.f(rs1)], rm),
+            Instruction::FCVT_S_L {rd, rs1, ref rm} => asm_rm("fcvt.s.l", &[a.f(rd), a.x(rs1)], rm),
+            Instruction::FCVT_LU_S {rd, rs1, ref rm} => asm_rm("fcvt.lu.s", &[a.x(rd), a.f(rs1)], rm),
+            Instruction::FCVT_S_LU {rd, rs1, ref rm} => asm_rm("fcvt.s.lu", &[a.f(rd), a.x(rs1)], rm),
+            Instruction::FMV_X_S {rd, rs1} => asm("fmv.x.w", &[a.x(rd), a.f(rs1)]),
+            Instruction::FMV_S_X {rd, rs1} => asm("fmv.w.x", &[a.f(rd), a.x(rs1)]),
+            Instruction::FCLASS_S {rd, rs1} => asm("fclass.s", &[a.x(rd), a.f(rs1)]),
+            Instruction::FLD {rd, rs1, imm} => asm("fld", &[a.f(rd), a.mem(imm, rs1)]),
+            Instruction::FSD {rs1, rs2, imm} => asm("fsd", &[a.f(rs2), a.mem(imm, rs1)]),
+            Instruction::FMADD_D {rd, rs1, rs2, rs3, ref rm} => asm_rm("fmadd.d", &[a.f(rd), a.f(rs1), a.f(rs2), a.f(rs3)], rm),
+            Instruction::FMSUB_D {rd, rs1, rs2, rs3, ref rm} => asm_rm("fmsub.d", &[a.f(rd), a.f(rs1), a.f(rs2), a.f(rs3)], rm),
+            Instruction::FNMSUB_D {rd, rs1, rs2, rs3, ref rm} => asm_rm("fnmsub.d", &[a.f(rd), a.f(rs1), a.f(rs2), a.f(rs3)], rm),
+            Instruction::FNMADD_D {rd, rs1, rs2, rs3, ref rm} => asm_rm("fnmadd.d", &[a.f(rd), a.f(rs1), a.f(rs2), a.f(rs3)], rm),
+            Instruction::FADD_D {rd, rs1, rs2, ref rm} => asm_rm("fadd.d", &[a.f(rd), a.f(rs1), a.f(rs2)], rm),
+            Instruction::FSUB_D {rd, rs1, rs2, ref rm} => asm_rm("fsub.d", &[a.f(rd), a.f(rs1), a.f(rs2)], rm),
+            Instruction::FMUL_D {rd, rs1, rs2, ref rm} => asm_rm("fmul.d", &[a.f(rd), a.f(rs1), a.f(rs2)], rm),
+            Instruction::FDIV_D {rd, rs1, rs2, ref rm} => asm_rm("fdiv.d", &[a.f(rd), a.f(rs1), a.f(rs2)], rm),
+            Instruction::FSQRT_D {rd, rs1, ref rm} => asm_rm("fsqrt.d", &[a.f(rd), a.f(rs1)], rm),
+            Instruction::FSGNJ_D {rd, rs1, rs2} => asm("fsgnj.d", &[a.f(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FSGNJN_D {rd, rs1, rs2} => asm("fsgnjn.d", &[a.f(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FSGNJX_D {rd, rs1, rs2} => asm("fsgnjx.d", &[a.f(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FMIN_D {rd, rs1, rs2} => asm("fmin.d", &[a.f(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FMAX_D {rd, rs1, rs2} => asm("fmax.d", &[a.f(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FEQ_D {rd, rs1, rs2} => asm("feq.d", &[a.x(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FLT_D {rd, rs1, rs2} => asm("flt.d", &[a.x(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FLE_D {rd, rs1, rs2} => asm("fle.d", &[a.x(rd), a.f(rs1), a.f(rs2)]),
+            Instruction::FCVT_W_D {rd, rs1, ref rm} => asm_rm("fcvt.w.d", &[a.x(rd), a.f(rs1)], rm),
+            Instruction::FCVT_D_W {rd, rs1, ref rm} => asm_rm("fcvt.d.w", &[a.f(rd), a.x(rs1)], rm),
+            Instruction::FCVT_WU_D {rd, rs1, ref rm} => asm_rm("fcvt.wu.d", &[a.x(rd), a.f(rs1)], rm),
+            Instruction::FCVT_D_WU {rd, rs1, ref rm} => asm_rm("fcvt.d.wu", &[a.f(rd), a.x(rs1)], rm),
+            Instruction::FCVT_L_D {rd, rs1, ref rm} => asm_rm("fcvt.l.d", &[a.x(rd), a.f(rs1)], rm),
+            Instruction::FCVT_D_L {rd, rs1, ref rm} => asm_rm("fcvt.d.l", &[a.f(rd), a.x(rs1)], rm),
+            Instruction::FCVT_LU_D {rd, rs1, ref rm} => asm_rm("fcvt.lu.d", &[a.x(rd), a.f(rs1)], rm),
+            Instruction::FCVT_D_LU {rd, rs1, ref rm} => asm_rm("fcvt.d.lu", &[a.f(rd), a.x(rs1)], rm),
+            Instruction::FMV_X_D {rd, rs1} => asm("fmv.x.d", &[a.x(rd), a.f(rs1)]),
+            Instruction::FMV_D_X {rd, rs1} => asm("fmv.d.x", &[a.f(rd), a.x(rs1)]),
+            Instruction::FCLASS_D {rd, rs1} => asm("fclass.d", &[a.x(rd), a.f(rs1)]),
+            Instruction::FCVT_S_D {rd, rs1, ref rm} => asm_rm("fcvt.s.d", &[a.f(rd), a.f(rs1)], rm),
+            Instruction::FCVT_D_S {rd, rs1, ref rm} => asm_rm("fcvt.d.s", &[a.f(rd), a.f(rs1)], rm),
+        }
+    }
+
+    /* the pseudo-instructions that objdump prints */
+    fn alias(&self, a: &Operands) -> Option<String> {
+        let alias = match *self {
+            Instruction::ADDI {rd: 0, rs1: 0, imm: 0} => asm("nop", &[]),
+            Instruction::ADDI {rd, rs1: 0, imm} => asm("li", &[a.x(rd), imm.to_string()]),
+            Instruction::ADDI {rd, rs1, imm: 0} => asm("mv", &[a.x(rd), a.x(rs1)]),
+            Instruction::ADDIW {rd, rs1, imm: 0} => asm("sext.w", &[a.x(rd), a.x(rs1)]),
+            Instruction::XORI {rd, rs1, imm: -1} => asm("not", &[a.x(rd), a.x(rs1)]),
+            Instruction::SUB {rd, rs1: 0, rs2} => asm("neg", &[a.x(rd), a.x(rs2)]),
+            Instruction::SUBW {rd, rs1: 0, rs2} => asm("negw", &[a.x(rd), a.x(rs2)]),
+            Instruction::SLTIU {rd, rs1, imm: 1} => asm("seqz", &[a.x(rd), a.x(rs1)]),
+            Instruction::SLTU {rd, rs1: 0, rs2} => asm("snez", &[a.x(rd), a.x(rs2)]),
+            Instruction::SLT {rd, rs1, rs2: 0} => asm("sltz", &[a.x(rd), a.x(rs1)]),
+            Instruction::SLT {rd, rs1: 0, rs2} => asm("sgtz", &[a.x(rd), a.x(rs2)]),
+            Instruction::BEQ {rs1, rs2: 0, imm} => asm("beqz", &[a.x(rs1), a.target(imm as i32)]),
+            Instruction::BNE {rs1, rs2: 0, imm} => asm("bnez", &[a.x(rs1), a.target(imm as i32)]),
+            Instruction::BGE {rs1: 0, rs2, imm} => asm("blez", &[a.x(rs2), a.target(imm as i32)]),
+            Instruction::BGE {rs1, rs2: 0, imm} => asm("bgez", &[a.x(rs1), a.target(imm as i32)]),
+            Instruction::BLT {rs1, rs2: 0, imm} => asm("bltz", &[a.x(rs1), a.target(imm as i32)]),
+            Instruction::BLT {rs1: 0, rs2, imm} => asm("bgtz", &[a.x(rs2), a.target(imm as i32)]),
+            Instruction::JAL {rd: 0, imm} => asm("j", &[a.target(imm)]),
+            Instruction::JAL {rd: 1, imm} => asm("jal", &[a.target(imm)]),
+            Instruction::JALR {rd: 0, rs1: 1, imm: 0} => asm("ret", &[]),
+            Instruction::JALR {rd: 0, rs1, imm: 0} => asm("jr", &[a.x(rs1)]),
+            Instruction::JALR {rd: 1, rs1, imm: 0} => asm("jalr", &[a.x(rs1)]),
+            Instruction::FENCE {succ: 0x0F, pred: 0x0F} => asm("fence", &[]),
+            Instruction::SFENCE_VMA {rs1: 0, rs2: 0} => asm("sfence.vma", &[]),
+            Instruction::SFENCE_VMA {rs1, rs2: 0} => asm("sfence.vma", &[a.x(rs1)]),
+            Instruction::CSRRS {rd, rs1: 0, csr: 0xC00} => asm("rdcycle", &[a.x(rd)]),
+            Instruction::CSRRS {rd, rs1: 0, csr: 0xC01} => asm("rdtime", &[a.x(rd)]),
+            Instruction::CSRRS {rd, rs1: 0, csr: 0xC02} => asm("rdinstret", &[a.x(rd)]),
+            Instruction::CSRRS {rd, rs1: 0, csr} => asm("csrr", &[a.x(rd), csr_name(csr)]),
+            Instruction::CSRRW {rd: 0, rs1, csr} => asm("csrw", &[csr_name(csr), a.x(rs1)]),
+            Instruction::CSRRS {rd: 0, rs1, csr} => asm("csrs", &[csr_name(csr), a.x(rs1)]),
+            Instruction::CSRRC {rd: 0, rs1, csr} => asm("csrc", &[csr_name(csr), a.x(rs1)]),
+            Instruction::CSRRWI {rd: 0, zimm, csr} => asm("csrwi", &[csr_name(csr), zimm.to_string()]),
+            Instruction::CSRRSI {rd: 0, zimm, csr} => asm("csrsi", &[csr_name(csr), zimm.to_string()]),
+            Instruction::CSRRCI {rd: 0, zimm, csr} => asm("csrci", &[csr_name(csr), zimm.to_string()]),
+            Instruction::FSGNJ_S {rd, rs1, rs2} if rs1 == rs2 => asm("fmv.s", &[a.f(rd), a.f(rs1)]),
+            Instruction::FSGNJX_S {rd, rs1, rs2} if rs1 == rs2 => asm("fabs.s", &[a.f(rd), a.f(rs1)]),
+            Instruction::FSGNJN_S {rd, rs1, rs2} if rs1 == rs2 => asm("fneg.s", &[a.f(rd), a.f(rs1)]),
+            Instruction::FSGNJ_D {rd, rs1, rs2} if rs1 == rs2 => asm("fmv.d", &[a.f(rd), a.f(rs1)]),
+            Instruction::FSGNJX_D {rd, rs1, rs2} if rs1 == rs2 => asm("fabs.d", &[a.f(rd), a.f(rs1)]),
+            Instruction::FSGNJN_D {rd, rs1, rs2} if rs1 == rs2 => asm("fneg.d", &[a.f(rd), a.f(rs1)]),
+            _ => return None
+        };
+        Some(alias)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.fmt_asm(&AsmFormat::default()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AsmFormat, csr_number};
+    use super::super::decoder::{Instruction, FpRoundingMode};
+
+    fn dis(word: u32) -> String {
+        format!("{}", Instruction::decode32(word, true).unwrap())
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(dis(0x10B18703), "lb a4,267(gp)");
+        assert_eq!(dis(0xBF4A0F13), "addi t5,s4,-1036");
+        assert_eq!(dis(0x99230523), "sb s2,-1654(t1)");
+        assert_eq!(dis(0x12345137), "lui sp,0x12345");
+        assert_eq!(dis(0x4040D313), "srai t1,ra,0x4");
+        assert_eq!(dis(0x02B50533), "mul a0,a0,a1");
+        assert_eq!(dis(0x0E63A2AF), "amoswap.w.aqrl t0,t1,(t2)");
+        assert_eq!(dis(0x1005A52F), "lr.w a0,(a1)");
+        assert_eq!(dis(0x1AC5A52F), "sc.w.rl a0,a2,(a1)");
+        assert_eq!(dis(0x00C12787), "flw fa5,12(sp)");
+        assert_eq!(dis(0xFE312E27), "fsw ft3,-4(sp)");
+        assert_eq!(dis(0x00B57553), "fadd.s fa0,fa0,fa1");
+        assert_eq!(dis(0xC0011553), "fcvt.w.s a0,ft2,rtz");
+        assert_eq!(dis(0x203100C3), "fmadd.s ft1,ft2,ft3,ft4,rne");
+        assert_eq!(dis(0xE0050553), "fmv.x.w a0,fa0");
+        assert_eq!(dis(0x0330000F), "fence rw,rw");
+        assert_eq!(dis(0x00000073), "ecall");
+        assert_eq!(dis(0x10500073), "wfi");
+        assert_eq!(dis(0x12000073), "sfence.vma");
+        assert_eq!(dis(0x12B50073), "sfence.vma a0,a1");
+        assert_eq!(dis(0x30529073), "csrw mtvec,t0");
+        assert_eq!(dis(0x34202573), "csrr a0,mcause");
+        assert_eq!(dis(0x7C0510F3), "csrrw ra,0x7c0,a0");
+        assert_eq!(dis(0x3B351073), "csrw pmpaddr3,a0");
+        assert_eq!(dis(0x3A202573), "csrr a0,pmpcfg2");
+        assert_eq!(csr_number("pmpaddr63"), Some(0x3EF));
+        assert_eq!(csr_number("pmpaddr64"), None);
+        assert_eq!(csr_number("pmpcfg01"), None);
+    }
+
+    #[test]
+    fn test_aliases() {
+        assert_eq!(dis(0x00000013), "nop");
+        assert_eq!(dis(0xFFF00513), "li a0,-1");
+        assert_eq!(dis(0x00058513), "mv a0,a1");
+        assert_eq!(dis(0x00008067), "ret");
+        assert_eq!(dis(0x000780E7), "jalr a5");
+        assert_eq!(dis(0x0000006F), "j .+0");
+        assert_eq!(dis(0xC00020F3), "rdcycle ra");
+        assert_eq!(dis(0x0FF0000F), "fence");
+        assert_eq!(dis(0x20A50553), "fmv.s fa0,fa0");
+
+        let format = AsmFormat {aliases: false, .. AsmFormat::default()};
+        assert_eq!(Instruction::ADDI{rd: 0, rs1: 0, imm: 0}.fmt_asm(&format), "addi zero,zero,0");
+        assert_eq!(Instruction::JALR{rd: 0, rs1: 1, imm: 0}.fmt_asm(&format), "jalr zero,0(ra)");
+    }
+
+    #[test]
+    fn test_format() {
+        let numeric = AsmFormat {abi_names: false, .. AsmFormat::default()};
+        assert_eq!(Instruction::LB{rd: 14, rs1: 3, imm: 267}.fmt_asm(&numeric), "lb x14,267(x3)");
+        assert_eq!(Instruction::FADD_D{rd: 1, rs1: 2, rs2: 3, rm: FpRoundingMode::RoundUp}.fmt_asm(&numeric), "fadd.d f1,f2,f3,rup");
+
+        let branch = Instruction::BLTU{rs1: 17, rs2: 16, imm: -1770};
+        assert_eq!(branch.fmt_asm(&AsmFormat::default()), "bltu a7,a6,.-1770");
+        let at = AsmFormat {pc: Some(0x80001000), .. AsmFormat::default()};
+        assert_eq!(branch.fmt_asm(&at), "bltu a7,a6,80000916");
+        assert_eq!(Instruction::JAL{rd: 1, imm: 0x100}.fmt_asm(&at), "jal 80001100");
+        assert_eq!(Instruction::JAL{rd: 5, imm: -4}.fmt_asm(&at), "jal t0,80000ffc");
+    }
+}
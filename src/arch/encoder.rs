@@ -0,0 +1,441 @@
+/*
+ * encoder.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::error::Error;
+use std::fmt;
+
+use super::constants::*;
+use super::decoder::{Instruction, FpRoundingMode};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum EncodeError {
+    InvalidRegister {reg: u8},
+    ImmediateOutOfRange {imm: i32},
+    /* branch and jump offsets must be a multiple of two */
+    MisalignedImmediate {imm: i32},
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EncodeError::InvalidRegister {reg} => write!(f, "invalid register x{}", reg),
+            EncodeError::ImmediateOutOfRange {imm} => write!(f, "immediate {} out of range", imm),
+            EncodeError::MisalignedImmediate {imm} => write!(f, "immediate {} is not a multiple of two", imm),
+        }
+    }
+}
+
+impl Error for EncodeError {}
+
+fn op(opcode: u8) -> u32 {
+    ((opcode as u32) << 2) | 0x03
+}
+
+fn reg(r: u8) -> Result<u32, EncodeError> {
+    if r < 32 {
+        Ok(r as u32)
+    } else {
+        Err(EncodeError::InvalidRegister {reg: r})
+    }
+}
+
+/* checks that imm is a signed value with the given number of bits */
+fn simm(imm: i32, bits: u32) -> Result<u32, EncodeError> {
+    let min = -(1i32 << (bits - 1));
+    let max = (1i32 << (bits - 1)) - 1;
+    if imm < min || imm > max {
+        Err(EncodeError::ImmediateOutOfRange {imm: imm})
+    } else {
+        Ok((imm as u32) & ((1u32 << bits) - 1))
+    }
+}
+
+/* checks that imm is an unsigned value below limit */
+fn uimm(imm: u8, limit: u32) -> Result<u32, EncodeError> {
+    if (imm as u32) < limit {
+        Ok(imm as u32)
+    } else {
+        Err(EncodeError::ImmediateOutOfRange {imm: imm as i32})
+    }
+}
+
+fn rounding_mode(rm: &FpRoundingMode) -> u8 {
+    match *rm {
+        FpRoundingMode::RoundToNearest => rounding_mode::RNE,
+        FpRoundingMode::RoundTowardsZero => rounding_mode::RTZ,
+        FpRoundingMode::RoundDown => rounding_mode::RDN,
+        FpRoundingMode::RoundUp => rounding_mode::RUP,
+        FpRoundingMode::RoundToNearestTieMaxMagnitude => rounding_mode::RMM,
+        FpRoundingMode::DynamicRounding => rounding_mode::DYN,
+        /* the first of the two reserved encodings */
+        FpRoundingMode::Invalid => 0b101,
+    }
+}
+
+fn r_type(funct7: u8, rs2: u8, rs1: u8, funct3: u8, rd: u8, opcode: u8) -> Result<u32, EncodeError> {
+    Ok(((funct7 as u32) << 25) | (reg(rs2)? << 20) | (reg(rs1)? << 15) |
+       ((funct3 as u32) << 12) | (reg(rd)? << 7) | op(opcode))
+}
+
+fn r4_type(rs3: u8, fmt: u8, rs2: u8, rs1: u8, rm: &FpRoundingMode, rd: u8, opcode: u8) -> Result<u32, EncodeError> {
+    Ok((reg(rs3)? << 27) | ((fmt as u32) << 25) | r_type(0, rs2, rs1, rounding_mode(rm), rd, opcode)?)
+}
+
+fn i_type(imm: i16, rs1: u8, funct3: u8, rd: u8, opcode: u8) -> Result<u32, EncodeError> {
+    Ok((simm(imm as i32, 12)? << 20) | (reg(rs1)? << 15) | ((funct3 as u32) << 12) |
+       (reg(rd)? << 7) | op(opcode))
+}
+
+fn s_type(imm: i16, rs2: u8, rs1: u8, funct3: u8, opcode: u8) -> Result<u32, EncodeError> {
+    let imm = simm(imm as i32, 12)?;
+    Ok(((imm >> 5) << 25) | (reg(rs2)? << 20) | (reg(rs1)? << 15) |
+       ((funct3 as u32) << 12) | ((imm & 0x1F) << 7) | op(opcode))
+}
+
+fn b_type(imm: i16, rs2: u8, rs1: u8, funct3: u8) -> Result<u32, EncodeError> {
+    if (imm & 0x01) != 0 {
+        return Err(EncodeError::MisalignedImmediate {imm: imm as i32});
+    }
+    let imm = simm(imm as i32, 13)?;
+    let imm12 = (imm >> 12) & 0x01;
+    let imm11 = (imm >> 11) & 0x01;
+    let imm10 = (imm >> 5) & 0x3F;
+    let imm4 = (imm >> 1) & 0x0F;
+    Ok((imm12 << 31) | (imm10 << 25) | (reg(rs2)? << 20) | (reg(rs1)? << 15) |
+       ((funct3 as u32) << 12) | (imm4 << 8) | (imm11 << 7) | op(opcode::BRANCH))
+}
+
+fn u_type(imm: i32, rd: u8, opcode: u8) -> Result<u32, EncodeError> {
+    if (imm & 0x0FFF) != 0 {
+        return Err(EncodeError::ImmediateOutOfRange {imm: imm});
+    }
+    Ok((imm as u32) | (reg(rd)? << 7) | op(opcode))
+}
+
+fn j_type(imm: i32, rd: u8) -> Result<u32, EncodeError> {
+    if (imm & 0x01) != 0 {
+        return Err(EncodeError::MisalignedImmediate {imm: imm});
+    }
+    let imm = simm(imm, 21)?;
+    let imm20 = (imm >> 20) & 0x01;
+    let imm19 = (imm >> 12) & 0xFF;
+    let imm11 = (imm >> 11) & 0x01;
+    let imm10 = (imm >> 1) & 0x03FF;
+    Ok((imm20 << 31) | (imm10 << 21) | (imm11 << 20) | (imm19 << 12) |
+       (reg(rd)? << 7) | op(opcode::JAL))
+}
+
+/* shamt[5] of the RV64 shifts overlaps with the lowest bit of funct7 */
+fn shift(funct7: u8, shamt: u8, limit: u32, rs1: u8, funct3: u8, rd: u8, opcode: u8) -> Result<u32, EncodeError> {
+    let shamt = uimm(shamt, limit)?;
+    Ok(((funct7 as u32) << 25) | (shamt << 20) | (reg(rs1)? << 15) |
+       ((funct3 as u32) << 12) | (reg(rd)? << 7) | op(opcode))
+}
+
+/* the source is either rs1 or the 5-bit zimm */
+fn csr_type(csr: u16, source: u32, funct3: u8, rd: u8) -> Result<u32, EncodeError> {
+    if csr > 0x0FFF {
+        return Err(EncodeError::ImmediateOutOfRange {imm: csr as i32});
+    }
+    Ok(((csr as u32) << 20) | (source << 15) | ((funct3 as u32) << 12) |
+       (reg(rd)? << 7) | op(opcode::SYSTEM))
+}
+
+fn amo_type(funct5: u8, aq: bool, rl: bool, rs2: u8, rs1: u8, funct3: u8, rd: u8) -> Result<u32, EncodeError> {
+    let funct7 = (funct5 << 2) | ((aq as u8) << 1) | (rl as u8);
+    r_type(funct7, rs2, rs1, funct3, rd, opcode::AMO)
+}
+
+impl Instruction {
+    /* encodes the instruction as 32-bit word, the inverse of decode32 */
+    pub fn encode32(&self) -> Result<u32, EncodeError> {
+        match *self {
+            Instruction::LUI {rd, imm} => u_type(imm, rd, opcode::LUI),
+            Instruction::AUIPC {rd, imm} => u_type(imm, rd, opcode::AUIPC),
+            Instruction::JAL {rd, imm} => j_type(imm, rd),
+            Instruction::JALR {rd, rs1, imm} => i_type(imm, rs1, funct3::JALR, rd, opcode::JALR),
+            Instruction::BEQ {rs1, rs2, imm} => b_type(imm, rs2, rs1, funct3::BEQ),
+            Instruction::BNE {rs1, rs2, imm} => b_type(imm, rs2, rs1, funct3::BNE),
+            Instruction::BLT {rs1, rs2, imm} => b_type(imm, rs2, rs1, funct3::BLT),
+            Instruction::BGE {rs1, rs2, imm} => b_type(imm, rs2, rs1, funct3::BGE),
+            Instruction::BLTU {rs1, rs2, imm} => b_type(imm, rs2, rs1, funct3::BLTU),
+            Instruction::BGEU {rs1, rs2, imm} => b_type(imm, rs2, rs1, funct3::BGEU),
+            Instruction::LB {rd, rs1, imm} => i_type(imm, rs1, funct3::LB, rd, opcode::LOAD),
+            Instruction::LH {rd, rs1, imm} => i_type(imm, rs1, funct3::LH, rd, opcode::LOAD),
+            Instruction::LW {rd, rs1, imm} => i_type(imm, rs1, funct3::LW, rd, opcode::LOAD),
+            Instruction::LBU {rd, rs1, imm} => i_type(imm, rs1, funct3::LBU, rd, opcode::LOAD),
+            Instruction::LHU {rd, rs1, imm} => i_type(imm, rs1, funct3::LHU, rd, opcode::LOAD),
+            Instruction::LWU {rd, rs1, imm} => i_type(imm, rs1, funct3::LWU, rd, opcode::LOAD),
+            Instruction::LD {rd, rs1, imm} => i_type(imm, rs1, funct3::LD, rd, opcode::LOAD),
+            Instruction::SB {rs1, rs2, imm} => s_type(imm, rs2, rs1, funct3::SB, opcode::STORE),
+            Instruction::SH {rs1, rs2, imm} => s_type(imm, rs2, rs1, funct3::SH, opcode::STORE),
+            Instruction::SW {rs1, rs2, imm} => s_type(imm, rs2, rs1, funct3::SW, opcode::STORE),
+            Instruction::SD {rs1, rs2, imm} => s_type(imm, rs2, rs1, funct3::SD, opcode::STORE),
+            Instruction::ADDI {rd, rs1, imm} => i_type(imm, rs1, funct3::ADDI, rd, opcode::OP_IMM),
+            Instruction::SLTI {rd, rs1, imm} => i_type(imm, rs1, funct3::SLTI, rd, opcode::OP_IMM),
+            Instruction::SLTIU {rd, rs1, imm} => i_type(imm, rs1, funct3::SLTIU, rd, opcode::OP_IMM),
+            Instruction::XORI {rd, rs1, imm} => i_type(imm, rs1, funct3::XORI, rd, opcode::OP_IMM),
+            Instruction::ORI {rd, rs1, imm} => i_type(imm, rs1, funct3::ORI, rd, opcode::OP_IMM),
+            Instruction::ANDI {rd, rs1, imm} => i_type(imm, rs1, funct3::ANDI, rd, opcode::OP_IMM),
+            Instruction::ADDIW {rd, rs1, imm} => i_type(imm, rs1, funct3::ADDIW, rd, opcode::OP_IMM_32),
+            Instruction::SLLI {rd, rs1, shamt} => shift(funct7::SLLI, shamt, 64, rs1, funct3::SLLI, rd, opcode::OP_IMM),
+            Instruction::SRLI {rd, rs1, shamt} => shift(funct7::SRLI, shamt, 64, rs1, funct3::SRLI, rd, opcode::OP_IMM),
+            Instruction::SRAI {rd, rs1, shamt} => shift(funct7::SRAI, shamt, 64, rs1, funct3::SRAI, rd, opcode::OP_IMM),
+            Instruction::SLLIW {rd, rs1, shamt} => shift(funct7::SLLIW, shamt, 32, rs1, funct3::SLLIW, rd, opcode::OP_IMM_32),
+            Instruction::SRLIW {rd, rs1, shamt} => shift(funct7::SRLIW, shamt, 32, rs1, funct3::SRLIW, rd, opcode::OP_IMM_32),
+            Instruction::SRAIW {rd, rs1, shamt} => shift(funct7::SRAIW, shamt, 32, rs1, funct3::SRAIW, rd, opcode::OP_IMM_32),
+            Instruction::ADD {rd, rs1, rs2} => r_type(funct7::ADD, rs2, rs1, funct3::ADD, rd, opcode::OP),
+            Instruction::SUB {rd, rs1, rs2} => r_type(funct7::SUB, rs2, rs1, funct3::SUB, rd, opcode::OP),
+            Instruction::SLL {rd, rs1, rs2} => r_type(funct7::SLL, rs2, rs1, funct3::SLL, rd, opcode::OP),
+            Instruction::SLT {rd, rs1, rs2} => r_type(funct7::SLT, rs2, rs1, funct3::SLT, rd, opcode::OP),
+            Instruction::SLTU {rd, rs1, rs2} => r_type(funct7::SLTU, rs2, rs1, funct3::SLTU, rd, opcode::OP),
+            Instruction::XOR {rd, rs1, rs2} => r_type(funct7::XOR, rs2, rs1, funct3::XOR, rd, opcode::OP),
+            Instruction::SRL {rd, rs1, rs2} => r_type(funct7::SRL, rs2, rs1, funct3::SRL, rd, opcode::OP),
+            Instruction::SRA {rd, rs1, rs2} => r_type(funct7::SRA, rs2, rs1, funct3::SRA, rd, opcode::OP),
+            Instruction::OR {rd, rs1, rs2} => r_type(funct7::OR, rs2, rs1, funct3::OR, rd, opcode::OP),
+            Instruction::AND {rd, rs1, rs2} => r_type(funct7::AND, rs2, rs1, funct3::AND, rd, opcode::OP),
+            Instruction::ADDW {rd, rs1, rs2} => r_type(funct7::ADDW, rs2, rs1, funct3::ADDW, rd, opcode::OP_32),
+            Instruction::SUBW {rd, rs1, rs2} => r_type(funct7::SUBW, rs2, rs1, funct3::SUBW, rd, opcode::OP_32),
+            Instruction::SLLW {rd, rs1, rs2} => r_type(funct7::SLLW, rs2, rs1, funct3::SLLW, rd, opcode::OP_32),
+            Instruction::SRLW {rd, rs1, rs2} => r_type(funct7::SRLW, rs2, rs1, funct3::SRLW, rd, opcode::OP_32),
+            Instruction::SRAW {rd, rs1, rs2} => r_type(funct7::SRAW, rs2, rs1, funct3::SRAW, rd, opcode::OP_32),
+            Instruction::FENCE {succ, pred} => {
+                if succ > 0x0F || pred > 0x0F {
+                    return Err(EncodeError::ImmediateOutOfRange {imm: ((pred as i32) << 4) | succ as i32});
+                }
+                Ok(((pred as u32) << 24) | ((succ as u32) << 20) | op(opcode::MISC_MEM))
+            },
+            Instruction::FENCE_I => Ok(((funct3::FENCE_I as u32) << 12) | op(opcode::MISC_MEM)),
+            Instruction::ECALL => Ok(0x00000073),
+            Instruction::EBREAK => Ok(0x00100073),
+            Instruction::MRET => Ok(0x30200073),
+            Instruction::SRET => Ok(0x10200073),
+            Instruction::WFI => Ok(0x10500073),
+            Instruction::SFENCE_VMA {rs1, rs2} => r_type(0b0001001, rs2, rs1, funct3::PRIV, 0, opcode::SYSTEM),
+            Instruction::CSRRW {rd, rs1, csr} => csr_type(csr, reg(rs1)?, funct3::CSRRW, rd),
+            Instruction::CSRRS {rd, rs1, csr} => csr_type(csr, reg(rs1)?, funct3::CSRRS, rd),
+            Instruction::CSRRC {rd, rs1, csr} => csr_type(csr, reg(rs1)?, funct3::CSRRC, rd),
+            Instruction::CSRRWI {rd, zimm, csr} => csr_type(csr, uimm(zimm, 32)?, funct3::CSRRWI, rd),
+            Instruction::CSRRSI {rd, zimm, csr} => csr_type(csr, uimm(zimm, 32)?, funct3::CSRRSI, rd),
+            Instruction::CSRRCI {rd, zimm, csr} => csr_type(csr, uimm(zimm, 32)?, funct3::CSRRCI, rd),
+            Instruction::MUL {rd, rs1, rs2} => r_type(mul_div::funct7::MUL, rs2, rs1, mul_div::funct3::MUL, rd, opcode::OP),
+            Instruction::MULH {rd, rs1, rs2} => r_type(mul_div::funct7::MULH, rs2, rs1, mul_div::funct3::MULH, rd, opcode::OP),
+            Instruction::MULHSU {rd, rs1, rs2} => r_type(mul_div::funct7::MULHSU, rs2, rs1, mul_div::funct3::MULHSU, rd, opcode::OP),
+            Instruction::MULHU {rd, rs1, rs2} => r_type(mul_div::funct7::MULHU, rs2, rs1, mul_div::funct3::MULHU, rd, opcode::OP),
+            Instruction::DIV {rd, rs1, rs2} => r_type(mul_div::funct7::DIV, rs2, rs1, mul_div::funct3::DIV, rd, opcode::OP),
+            Instruction::DIVU {rd, rs1, rs2} => r_type(mul_div::funct7::DIVU, rs2, rs1, mul_div::funct3::DIVU, rd, opcode::OP),
+            Instruction::REM {rd, rs1, rs2} => r_type(mul_div::funct7::REM, rs2, rs1, mul_div::funct3::REM, rd, opcode::OP),
+            Instruction::REMU {rd, rs1, rs2} => r_type(mul_div::funct7::REMU, rs2, rs1, mul_div::funct3::REMU, rd, opcode::OP),
+            Instruction::MULW {rd, rs1, rs2} => r_type(mul_div::funct7::MULW, rs2, rs1, mul_div::funct3::MULW, rd, opcode::OP_32),
+            Instruction::DIVW {rd, rs1, rs2} => r_type(mul_div::funct7::DIVW, rs2, rs1, mul_div::funct3::DIVW, rd, opcode::OP_32),
+            Instruction::DIVUW {rd, rs1, rs2} => r_type(mul_div::funct7::DIVUW, rs2, rs1, mul_div::funct3::DIVUW, rd, opcode::OP_32),
+            Instruction::REMW {rd, rs1, rs2} => r_type(mul_div::funct7::REMW, rs2, rs1, mul_div::funct3::REMW, rd, opcode::OP_32),
+            Instruction::REMUW {rd, rs1, rs2} => r_type(mul_div::funct7::REMUW, rs2, rs1, mul_div::funct3::REMUW, rd, opcode::OP_32),
+            Instruction::LR_W {rd, rs1, aq, rl} => amo_type(amo::funct5::LR_W, aq, rl, 0, rs1, amo::funct3::LR_W, rd),
+            Instruction::SC_W {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::SC_W, aq, rl, rs2, rs1, amo::funct3::SC_W, rd),
+            Instruction::AMOSWAP_W {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOSWAP_W, aq, rl, rs2, rs1, amo::funct3::AMOSWAP_W, rd),
+            Instruction::AMOADD_W {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOADD_W, aq, rl, rs2, rs1, amo::funct3::AMOADD_W, rd),
+            Instruction::AMOXOR_W {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOXOR_W, aq, rl, rs2, rs1, amo::funct3::AMOXOR_W, rd),
+            Instruction::AMOAND_W {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOAND_W, aq, rl, rs2, rs1, amo::funct3::AMOAND_W, rd),
+            Instruction::AMOOR_W {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOOR_W, aq, rl, rs2, rs1, amo::funct3::AMOOR_W, rd),
+            Instruction::AMOMIN_W {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOMIN_W, aq, rl, rs2, rs1, amo::funct3::AMOMIN_W, rd),
+            Instruction::AMOMAX_W {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOMAX_W, aq, rl, rs2, rs1, amo::funct3::AMOMAX_W, rd),
+            Instruction::AMOMINU_W {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOMINU_W, aq, rl, rs2, rs1, amo::funct3::AMOMINU_W, rd),
+            Instruction::AMOMAXU_W {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOMAXU_W, aq, rl, rs2, rs1, amo::funct3::AMOMAXU_W, rd),
+            Instruction::LR_D {rd, rs1, aq, rl} => amo_type(amo::funct5::LR_D, aq, rl, 0, rs1, amo::funct3::LR_D, rd),
+            Instruction::SC_D {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::SC_D, aq, rl, rs2, rs1, amo::funct3::SC_D, rd),
+            Instruction::AMOSWAP_D {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOSWAP_D, aq, rl, rs2, rs1, amo::funct3::AMOSWAP_D, rd),
+            Instruction::AMOADD_D {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOADD_D, aq, rl, rs2, rs1, amo::funct3::AMOADD_D, rd),
+            Instruction::AMOXOR_D {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOXOR_D, aq, rl, rs2, rs1, amo::funct3::AMOXOR_D, rd),
+            Instruction::AMOAND_D {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOAND_D, aq, rl, rs2, rs1, amo::funct3::AMOAND_D, rd),
+            Instruction::AMOOR_D {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOOR_D, aq, rl, rs2, rs1, amo::funct3::AMOOR_D, rd),
+            Instruction::AMOMIN_D {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOMIN_D, aq, rl, rs2, rs1, amo::funct3::AMOMIN_D, rd),
+            Instruction::AMOMAX_D {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOMAX_D, aq, rl, rs2, rs1, amo::funct3::AMOMAX_D, rd),
+            Instruction::AMOMINU_D {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOMINU_D, aq, rl, rs2, rs1, amo::funct3::AMOMINU_D, rd),
+            Instruction::AMOMAXU_D {rd, rs1, rs2, aq, rl} => amo_type(amo::funct5::AMOMAXU_D, aq, rl, rs2, rs1, amo::funct3::AMOMAXU_D, rd),
+            Instruction::FLW {rd, rs1, imm} => i_type(imm, rs1, single_fp::funct3::FLW, rd, opcode::LOAD_FP),
+            Instruction::FSW {rs1, rs2, imm} => s_type(imm, rs2, rs1, single_fp::funct3::FSW, opcode::STORE_FP),
+            Instruction::FMADD_S {rd, rs1, rs2, rs3, ref rm} => r4_type(rs3, fp_fmt::S, rs2, rs1, rm, rd, opcode::MADD),
+            Instruction::FMSUB_S {rd, rs1, rs2, rs3, ref rm} => r4_type(rs3, fp_fmt::S, rs2, rs1, rm, rd, opcode::MSUB),
+            Instruction::FNMSUB_S {rd, rs1, rs2, rs3, ref rm} => r4_type(rs3, fp_fmt::S, rs2, rs1, rm, rd, opcode::NMSUB),
+            Instruction::FNMADD_S {rd, rs1, rs2, rs3, ref rm} => r4_type(rs3, fp_fmt::S, rs2, rs1, rm, rd, opcode::NMADD),
+            Instruction::FADD_S {rd, rs1, rs2, ref rm} => r_type(single_fp::funct7::FADD_S, rs2, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FSUB_S {rd, rs1, rs2, ref rm} => r_type(single_fp::funct7::FSUB_S, rs2, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FMUL_S {rd, rs1, rs2, ref rm} => r_type(single_fp::funct7::FMUL_S, rs2, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FDIV_S {rd, rs1, rs2, ref rm} => r_type(single_fp::funct7::FDIV_S, rs2, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FSQRT_S {rd, rs1, ref rm} => r_type(single_fp::funct7::FSQRT_S, single_fp::rs2::FSQRT_S, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FSGNJ_S {rd, rs1, rs2} => r_type(single_fp::funct7::FSGNJ_S, rs2, rs1, single_fp::funct3::FSGNJ_S, rd, opcode::OP_FP),
+            Instruction::FSGNJN_S {rd, rs1, rs2} => r_type(single_fp::funct7::FSGNJN_S, rs2, rs1, single_fp::funct3::FSGNJN_S, rd, opcode::OP_FP),
+            Instruction::FSGNJX_S {rd, rs1, rs2} => r_type(single_fp::funct7::FSGNJX_S, rs2, rs1, single_fp::funct3::FSGNJX_S, rd, opcode::OP_FP),
+            Instruction::FMIN_S {rd, rs1, rs2} => r_type(single_fp::funct7::FMIN_S, rs2, rs1, single_fp::funct3::FMIN_S, rd, opcode::OP_FP),
+            Instruction::FMAX_S {rd, rs1, rs2} => r_type(single_fp::funct7::FMAX_S, rs2, rs1, single_fp::funct3::FMAX_S, rd, opcode::OP_FP),
+            Instruction::FEQ_S {rd, rs1, rs2} => r_type(single_fp::funct7::FEQ_S, rs2, rs1, single_fp::funct3::FEQ_S, rd, opcode::OP_FP),
+            Instruction::FLT_S {rd, rs1, rs2} => r_type(single_fp::funct7::FLT_S, rs2, rs1, single_fp::funct3::FLT_S, rd, opcode::OP_FP),
+            Instruction::FLE_S {rd, rs1, rs2} => r_type(single_fp::funct7::FLE_S, rs2, rs1, single_fp::funct3::FLE_S, rd, opcode::OP_FP),
+            Instruction::FCVT_W_S {rd, rs1, ref rm} => r_type(single_fp::funct7::FCVT_W_S, single_fp::rs2::FCVT_W_S, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_S_W {rd, rs1, ref rm} => r_type(single_fp::funct7::FCVT_S_W, single_fp::rs2::FCVT_S_W, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_WU_S {rd, rs1, ref rm} => r_type(single_fp::funct7::FCVT_WU_S, single_fp::rs2::FCVT_WU_S, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_S_WU {rd, rs1, ref rm} => r_type(single_fp::funct7::FCVT_S_WU, single_fp::rs2::FCVT_S_WU, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_L_S {rd, rs1, ref rm} => r_type(single_fp::funct7::FCVT_L_S, single_fp::rs2::FCVT_L_S, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_S_L {rd, rs1, ref rm} => r_type(single_fp::funct7::FCVT_S_L, single_fp::rs2::FCVT_S_L, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_LU_S {rd, rs1, ref rm} => r_type(single_fp::funct7::FCVT_LU_S, single_fp::rs2::FCVT_LU_S, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_S_LU {rd, rs1, ref rm} => r_type(single_fp::funct7::FCVT_S_LU, single_fp::rs2::FCVT_S_LU, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FMV_X_S {rd, rs1} => r_type(single_fp::funct7::FMV_X_S, single_fp::rs2::FMV_X_S, rs1, single_fp::funct3::FMV_X_S, rd, opcode::OP_FP),
+            Instruction::FMV_S_X {rd, rs1} => r_type(single_fp::funct7::FMV_S_X, single_fp::rs2::FMV_S_X, rs1, single_fp::funct3::FMV_S_X, rd, opcode::OP_FP),
+            Instruction::FCLASS_S {rd, rs1} => r_type(single_fp::funct7::FCLASS_S, single_fp::rs2::FCLASS_S, rs1, single_fp::funct3::FCLASS_S, rd, opcode::OP_FP),
+            Instruction::FLD {rd, rs1, imm} => i_type(imm, rs1, double_fp::funct3::FLD, rd, opcode::LOAD_FP),
+            Instruction::FSD {rs1, rs2, imm} => s_type(imm, rs2, rs1, double_fp::funct3::FSD, opcode::STORE_FP),
+            Instruction::FMADD_D {rd, rs1, rs2, rs3, ref rm} => r4_type(rs3, fp_fmt::D, rs2, rs1, rm, rd, opcode::MADD),
+            Instruction::FMSUB_D {rd, rs1, rs2, rs3, ref rm} => r4_type(rs3, fp_fmt::D, rs2, rs1, rm, rd, opcode::MSUB),
+            Instruction::FNMSUB_D {rd, rs1, rs2, rs3, ref rm} => r4_type(rs3, fp_fmt::D, rs2, rs1, rm, rd, opcode::NMSUB),
+            Instruction::FNMADD_D {rd, rs1, rs2, rs3, ref rm} => r4_type(rs3, fp_fmt::D, rs2, rs1, rm, rd, opcode::NMADD),
+            Instruction::FADD_D {rd, rs1, rs2, ref rm} => r_type(double_fp::funct7::FADD_D, rs2, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FSUB_D {rd, rs1, rs2, ref rm} => r_type(double_fp::funct7::FSUB_D, rs2, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FMUL_D {rd, rs1, rs2, ref rm} => r_type(double_fp::funct7::FMUL_D, rs2, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FDIV_D {rd, rs1, rs2, ref rm} => r_type(double_fp::funct7::FDIV_D, rs2, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FSQRT_D {rd, rs1, ref rm} => r_type(double_fp::funct7::FSQRT_D, double_fp::rs2::FSQRT_D, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FSGNJ_D {rd, rs1, rs2} => r_type(double_fp::funct7::FSGNJ_D, rs2, rs1, double_fp::funct3::FSGNJ_D, rd, opcode::OP_FP),
+            Instruction::FSGNJN_D {rd, rs1, rs2} => r_type(double_fp::funct7::FSGNJN_D, rs2, rs1, double_fp::funct3::FSGNJN_D, rd, opcode::OP_FP),
+            Instruction::FSGNJX_D {rd, rs1, rs2} => r_type(double_fp::funct7::FSGNJX_D, rs2, rs1, double_fp::funct3::FSGNJX_D, rd, opcode::OP_FP),
+            Instruction::FMIN_D {rd, rs1, rs2} => r_type(double_fp::funct7::FMIN_D, rs2, rs1, double_fp::funct3::FMIN_D, rd, opcode::OP_FP),
+            Instruction::FMAX_D {rd, rs1, rs2} => r_type(double_fp::funct7::FMAX_D, rs2, rs1, double_fp::funct3::FMAX_D, rd, opcode::OP_FP),
+            Instruction::FEQ_D {rd, rs1, rs2} => r_type(double_fp::funct7::FEQ_D, rs2, rs1, double_fp::funct3::FEQ_D, rd, opcode::OP_FP),
+            Instruction::FLT_D {rd, rs1, rs2} => r_type(double_fp::funct7::FLT_D, rs2, rs1, double_fp::funct3::FLT_D, rd, opcode::OP_FP),
+            Instruction::FLE_D {rd, rs1, rs2} => r_type(double_fp::funct7::FLE_D, rs2, rs1, double_fp::funct3::FLE_D, rd, opcode::OP_FP),
+            Instruction::FCVT_W_D {rd, rs1, ref rm} => r_type(double_fp::funct7::FCVT_W_D, double_fp::rs2::FCVT_W_D, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_D_W {rd, rs1, ref rm} => r_type(double_fp::funct7::FCVT_D_W, double_fp::rs2::FCVT_D_W, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_WU_D {rd, rs1, ref rm} => r_type(double_fp::funct7::FCVT_WU_D, double_fp::rs2::FCVT_WU_D, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_D_WU {rd, rs1, ref rm} => r_type(double_fp::funct7::FCVT_D_WU, double_fp::rs2::FCVT_D_WU, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_L_D {rd, rs1, ref rm} => r_type(double_fp::funct7::FCVT_L_D, double_fp::rs2::FCVT_L_D, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_D_L {rd, rs1, ref rm} => r_type(double_fp::funct7::FCVT_D_L, double_fp::rs2::FCVT_D_L, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_LU_D {rd, rs1, ref rm} => r_type(double_fp::funct7::FCVT_LU_D, double_fp::rs2::FCVT_LU_D, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_D_LU {rd, rs1, ref rm} => r_type(double_fp::funct7::FCVT_D_LU, double_fp::rs2::FCVT_D_LU, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FMV_X_D {rd, rs1} => r_type(double_fp::funct7::FMV_X_D, double_fp::rs2::FMV_X_D, rs1, double_fp::funct3::FMV_X_D, rd, opcode::OP_FP),
+            Instruction::FMV_D_X {rd, rs1} => r_type(double_fp::funct7::FMV_D_X, double_fp::rs2::FMV_D_X, rs1, double_fp::funct3::FMV_D_X, rd, opcode::OP_FP),
+            Instruction::FCLASS_D {rd, rs1} => r_type(double_fp::funct7::FCLASS_D, double_fp::rs2::FCLASS_D, rs1, double_fp::funct3::FCLASS_D, rd, opcode::OP_FP),
+            Instruction::FCVT_S_D {rd, rs1, ref rm} => r_type(double_fp::funct7::FCVT_S_D, double_fp::rs2::FCVT_S_D, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+            Instruction::FCVT_D_S {rd, rs1, ref rm} => r_type(double_fp::funct7::FCVT_D_S, double_fp::rs2::FCVT_D_S, rs1, rounding_mode(rm), rd, opcode::OP_FP),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EncodeError;
+    use super::super::decoder::{Instruction, FpRoundingMode};
+
+    /* xorshift, so that the test is deterministic without extra crates */
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_encode32() {
+        // LB x14, 267(x3)
+        assert_eq!(Instruction::LB{rd: 14, rs1: 3, imm: 267}.encode32(), Ok(0x10B18703));
+
+        // SW x5, 1951(x8)
+        assert_eq!(Instruction::SW{rs1: 8, rs2: 5, imm: 1951}.encode32(), Ok(0x78542FA3));
+
+        // BLTU x17, x16, -1770
+        assert_eq!(Instruction::BLTU{rs1: 17, rs2: 16, imm: -1770}.encode32(), Ok(0x9108EBE3));
+
+        // JAL x9, -3520
+        assert_eq!(Instruction::JAL{rd: 9, imm: -3520}.encode32(), Ok(0xA40FF4EF));
+
+        // MUL x10, x10, x11
+        assert_eq!(Instruction::MUL{rd: 10, rs1: 10, rs2: 11}.encode32(), Ok(0x02B50533));
+
+        // FMADD.S f1, f2, f3, f4, rne
+        assert_eq!(Instruction::FMADD_S{rd: 1, rs1: 2, rs2: 3, rs3: 4, rm: FpRoundingMode::RoundToNearest}.encode32(), Ok(0x203100C3));
+
+        // SRAI x1, x2, 63
+        assert_eq!(Instruction::SRAI{rd: 1, rs1: 2, shamt: 63}.encode32(), Ok(0x43F15093));
+        assert_eq!(Instruction::decode32(0x43F15093, true), Ok(Instruction::SRAI{rd: 1, rs1: 2, shamt: 63}));
+    }
+
+    #[test]
+    fn test_encode32_errors() {
+        assert_eq!(Instruction::ADDI{rd: 32, rs1: 0, imm: 0}.encode32(), Err(EncodeError::InvalidRegister{reg: 32}));
+        assert_eq!(Instruction::ADDI{rd: 1, rs1: 0, imm: 2048}.encode32(), Err(EncodeError::ImmediateOutOfRange{imm: 2048}));
+        assert_eq!(Instruction::ADDI{rd: 1, rs1: 0, imm: -2049}.encode32(), Err(EncodeError::ImmediateOutOfRange{imm: -2049}));
+        assert_eq!(Instruction::BEQ{rs1: 1, rs2: 2, imm: 3}.encode32(), Err(EncodeError::MisalignedImmediate{imm: 3}));
+        assert_eq!(Instruction::BEQ{rs1: 1, rs2: 2, imm: 4096}.encode32(), Err(EncodeError::ImmediateOutOfRange{imm: 4096}));
+        assert_eq!(Instruction::BEQ{rs1: 1, rs2: 2, imm: -4096}.encode32(), Ok(0x80208063));
+        assert_eq!(Instruction::JAL{rd: 1, imm: 1 << 20}.encode32(), Err(EncodeError::ImmediateOutOfRange{imm: 1 << 20}));
+        assert_eq!(Instruction::LUI{rd: 1, imm: 0x1001}.encode32(), Err(EncodeError::ImmediateOutOfRange{imm: 0x1001}));
+        assert_eq!(Instruction::SLLIW{rd: 1, rs1: 1, shamt: 32}.encode32(), Err(EncodeError::ImmediateOutOfRange{imm: 32}));
+        assert_eq!(Instruction::CSRRWI{rd: 1, zimm: 32, csr: 0}.encode32(), Err(EncodeError::ImmediateOutOfRange{imm: 32}));
+        assert_eq!(Instruction::CSRRW{rd: 1, rs1: 1, csr: 0x1000}.encode32(), Err(EncodeError::ImmediateOutOfRange{imm: 0x1000}));
+    }
+
+    /* decode32(encode32(i)) == i for every instruction that random words
+     * decode to */
+    #[test]
+    fn test_roundtrip() {
+        let mut rng = Rng(0x2545F491);
+        let mut decoded = 0;
+
+        for _ in 0..200000 {
+            let word = rng.next() | 0x03;
+            for rv64 in &[false, true] {
+                if let Ok(instr) = Instruction::decode32(word, *rv64) {
+                    let encoded = instr.encode32().unwrap();
+                    assert_eq!(Instruction::decode32(encoded, *rv64), Ok(instr));
+                    decoded += 1;
+                }
+            }
+        }
+
+        assert!(decoded > 100000);
+    }
+
+    /* the instructions with fixed encodings, which random words almost
+     * never hit */
+    #[test]
+    fn test_roundtrip_fixed() {
+        let fixed = [
+            (Instruction::ECALL, 0x00000073),
+            (Instruction::EBREAK, 0x00100073),
+            (Instruction::FENCE_I, 0x0000100F),
+            (Instruction::FENCE {succ: 0x3, pred: 0xF}, 0x0F30000F),
+            (Instruction::MRET, 0x30200073),
+            (Instruction::SRET, 0x10200073),
+            (Instruction::WFI, 0x10500073),
+            (Instruction::SFENCE_VMA {rs1: 0, rs2: 0}, 0x12000073),
+            (Instruction::SFENCE_VMA {rs1: 10, rs2: 11}, 0x12B50073),
+        ];
+        for &(ref instr, word) in fixed.iter() {
+            assert_eq!(instr.encode32(), Ok(word));
+            for rv64 in &[false, true] {
+                assert_eq!(Instruction::decode32(word, *rv64).as_ref(), Ok(instr));
+            }
+        }
+    }
+}
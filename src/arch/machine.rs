@@ -0,0 +1,372 @@
+/*
+ * machine.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* A system of several harts sharing one bus. Only one hart runs at a time,
+ * the schedule decides which one executes the next instruction, so a run
+ * is reproducible for a given seed. Stores are snooped on the way to the
+ * bus to invalidate the LR/SC reservations of the other harts. Before a
+ * hart runs, the lines of the CLINT and the PLIC are copied into its mip,
+ * afterwards mtime advances by one. */
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::clint::{Clint, CLINT_BASE, CLINT_SIZE};
+use super::core::{Core, Exception};
+use super::csr::irq;
+use super::plic::{Plic, Source, PLIC_BASE, PLIC_SIZE};
+use super::memory::{AccessFault, Bus, Memory};
+
+/* how the harts are interleaved */
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Schedule {
+    /* one instruction per hart in turn */
+    RoundRobin,
+    /* every hart runs the given number of instructions in turn */
+    Quantum(u64),
+    /* the next hart and its quantum of 1 to max instructions are drawn
+     * from a generator seeded with seed */
+    Random {seed: u64, max: u64},
+}
+
+/* splitmix64, every seed including zero gives a good sequence */
+fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/* forwards accesses and records the address and size of every store */
+struct Snoop<'a, M: Memory + ?Sized + 'a> {
+    mem    : &'a mut M,
+    stores : &'a mut Vec<(u64, u64)>,
+}
+
+impl<'a, M: Memory + ?Sized> Snoop<'a, M> {
+    fn record<T>(&mut self, addr: u64, size: u64, result: Result<T, AccessFault>) -> Result<T, AccessFault> {
+        if result.is_ok() {
+            self.stores.push((addr, size));
+        }
+        result
+    }
+}
+
+impl<'a, M: Memory + ?Sized> Memory for Snoop<'a, M> {
+    fn load8(&mut self, addr: u64) -> Result<u8, AccessFault> {
+        self.mem.load8(addr)
+    }
+
+    fn load16(&mut self, addr: u64) -> Result<u16, AccessFault> {
+        self.mem.load16(addr)
+    }
+
+    fn load32(&mut self, addr: u64) -> Result<u32, AccessFault> {
+        self.mem.load32(addr)
+    }
+
+    fn load64(&mut self, addr: u64) -> Result<u64, AccessFault> {
+        self.mem.load64(addr)
+    }
+
+    fn store8(&mut self, addr: u64, value: u8) -> Result<(), AccessFault> {
+        let result = self.mem.store8(addr, value);
+        self.record(addr, 1, result)
+    }
+
+    fn store16(&mut self, addr: u64, value: u16) -> Result<(), AccessFault> {
+        let result = self.mem.store16(addr, value);
+        self.record(addr, 2, result)
+    }
+
+    fn store32(&mut self, addr: u64, value: u32) -> Result<(), AccessFault> {
+        let result = self.mem.store32(addr, value);
+        self.record(addr, 4, result)
+    }
+
+    fn store64(&mut self, addr: u64, value: u64) -> Result<(), AccessFault> {
+        let result = self.mem.store64(addr, value);
+        self.record(addr, 8, result)
+    }
+
+    fn writable(&mut self, addr: u64, len: u64) -> bool {
+        self.mem.writable(addr, len)
+    }
+}
+
+pub struct Machine {
+    pub harts : Vec<Core>,
+    pub bus   : Bus,
+    pub clint : Rc<RefCell<Clint>>,
+    pub plic  : Rc<RefCell<Plic>>,
+    schedule  : Schedule,
+    random    : u64,
+    current   : usize,
+    remaining : u64, // instructions left in the quantum of the current hart
+    stores    : Vec<(u64, u64)>,
+    sources   : Vec<(u32, Rc<RefCell<dyn Source>>)>,
+}
+
+impl Machine {
+    /* n harts with the hart IDs 0 to n - 1 and the ISA given by misa
+     * starting at pc and a bus with only the CLINT and the PLIC mapped */
+    pub fn new(harts: usize, pc: u64, misa: u64, schedule: Schedule) -> Machine {
+        assert!(harts > 0, "a machine needs at least one hart");
+        let clint = Rc::new(RefCell::new(Clint::new(harts)));
+        let plic = Rc::new(RefCell::new(Plic::new(harts)));
+        let mut bus = Bus::new();
+        bus.map(CLINT_BASE, CLINT_SIZE, Box::new(clint.clone())).expect("the bus is empty");
+        bus.map(PLIC_BASE, PLIC_SIZE, Box::new(plic.clone())).expect("the PLIC does not overlap the CLINT");
+
+        let harts = (0..harts).map(|i| {
+            let mut core = Core::new(pc, (misa >> 62) == 2);
+            core.csr.misa = misa;
+            core.csr.mhartid = i as u64;
+            core
+        }).collect::<Vec<_>>();
+        let random = match schedule {
+            Schedule::Random {seed, ..} => seed,
+            _ => 0,
+        };
+        Machine {current: harts.len() - 1, harts: harts, bus: bus, clint: clint, plic: plic, schedule: schedule,
+                 random: random, remaining: 0, stores: Vec::new(), sources: Vec::new()}
+    }
+
+    /* connects the interrupt line of a device to a source of the PLIC,
+     * the device is polled before every instruction */
+    pub fn connect(&mut self, source: u32, device: Rc<RefCell<dyn Source>>) {
+        self.sources.push((source, device));
+    }
+
+    /* the hart that executes the next instruction */
+    fn next_hart(&mut self) -> usize {
+        if self.remaining == 0 {
+            let n = self.harts.len();
+            let (hart, quantum) = match self.schedule {
+                Schedule::RoundRobin => ((self.current + 1) % n, 1),
+                Schedule::Quantum(quantum) => ((self.current + 1) % n, quantum),
+                Schedule::Random {max, ..} => {
+                    let hart = (next_random(&mut self.random) % n as u64) as usize;
+                    (hart, 1 + next_random(&mut self.random) % max.max(1))
+                },
+            };
+            self.current = hart;
+            self.remaining = quantum.max(1);
+        }
+        self.remaining -= 1;
+        self.current
+    }
+
+    /* Steps the next hart like Core::step, so an exception has already
+     * entered the trap handler when it is returned. Returns the hart as
+     * well. */
+    pub fn step(&mut self) -> (usize, Result<(), Exception>) {
+        let hart = self.next_hart();
+        for &(source, ref device) in &self.sources {
+            self.plic.borrow_mut().set_level(source, device.borrow_mut().level());
+        }
+        let core = &mut self.harts[hart];
+        {
+            let clint = self.clint.borrow();
+            let plic = self.plic.borrow();
+            let line = |irq: u64, high: bool| (high as u64) << irq;
+            core.csr.external = line(irq::MSI, clint.msip(hart)) | line(irq::MTI, clint.mtip(hart))
+                | line(irq::MEI, plic.interrupt(2 * hart)) | line(irq::SEI, plic.interrupt(2 * hart + 1));
+            core.csr.time = Some(clint.mtime());
+        }
+
+        self.stores.clear();
+        let result = core.step(&mut Snoop {mem: &mut self.bus, stores: &mut self.stores});
+        self.clint.borrow_mut().tick();
+        for (i, other) in self.harts.iter_mut().enumerate() {
+            if i != hart {
+                for &(addr, size) in &self.stores {
+                    other.snoop(addr, size);
+                }
+            }
+        }
+        (hart, result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Machine, Schedule};
+    use super::super::assembler::assemble_into;
+    use super::super::csr::{misa, MIP};
+    use super::super::memory::Memory;
+
+    fn machine(source: &str, harts: usize, schedule: Schedule) -> Machine {
+        let isa = misa::parse("rv64imac").unwrap();
+        let mut machine = Machine::new(harts, 0x8000_0000, isa, schedule);
+        machine.bus.map_ram(0x8000_0000, 0x1_0000).unwrap();
+        assemble_into(&mut machine.bus, 0x8000_0000, source, true);
+        machine
+    }
+
+    fn order(machine: &mut Machine, steps: usize) -> Vec<usize> {
+        (0..steps).map(|_| machine.step().0).collect()
+    }
+
+    #[test]
+    fn test_schedule() {
+        let mut rr = machine("j 0", 3, Schedule::RoundRobin);
+        assert_eq!(order(&mut rr, 7), vec![0, 1, 2, 0, 1, 2, 0]);
+        assert_eq!(rr.harts[2].csr.mhartid, 2);
+        let mut quantum = machine("j 0", 2, Schedule::Quantum(3));
+        assert_eq!(order(&mut quantum, 8), vec![0, 0, 0, 1, 1, 1, 0, 0]);
+
+        let random = Schedule::Random {seed: 42, max: 4};
+        let a = order(&mut machine("j 0", 4, random), 200);
+        let b = order(&mut machine("j 0", 4, random), 200);
+        assert_eq!(a, b);
+        assert!((0..4).all(|hart| a.contains(&hart)));
+        let c = order(&mut machine("j 0", 4, Schedule::Random {seed: 43, max: 4}), 200);
+        assert!(a != c);
+    }
+
+    /* hart 0 takes a lock with LR/SC and sends an IPI to hart 1, which
+     * waits for it with the software interrupt enabled */
+    #[test]
+    fn test_ipi() {
+        let source = "
+            csrr t0, mhartid
+            bnez t0, secondary
+            li a0, 0x80001000
+            li t1, 1
+        retry:
+            lr.w t2, (a0)
+            bnez t2, retry
+            sc.w t2, t1, (a0)
+            bnez t2, retry
+            li t3, 0x2000004
+            sw t1, 0(t3)
+        done:
+            j done
+        secondary:
+            la t1, handler
+            csrw mtvec, t1
+            li t1, 8
+            csrw mie, t1
+            csrsi mstatus, 8
+        wait:
+            j wait
+        handler:
+            csrr a1, mcause
+            li t3, 0x2000004
+            sw zero, 0(t3)
+            j done
+        ";
+        let mut machine = machine(source, 2, Schedule::RoundRobin);
+        for _ in 0..100 {
+            assert_eq!(machine.step().1, Ok(()));
+        }
+        assert_eq!(machine.bus.load32(0x8000_1000), Ok(1));
+        assert_eq!(machine.harts[1].reg[11], (1 << 63) | 3);
+        assert!(!machine.clint.borrow().msip(1));
+        assert_eq!(machine.harts[0].reg[11], 0);
+    }
+
+    /* hart 0 sleeps until the timer fires, then claims the external
+     * interrupt of source 3 that the PLIC routes to it */
+    #[test]
+    fn test_timer_and_external() {
+        let source = "
+            la t0, handler
+            csrw mtvec, t0
+            li t0, 0x2004000
+            li t1, 30
+            sd t1, 0(t0)
+            li t0, 0xC00000C
+            li t1, 1
+            sw t1, 0(t0)
+            li t0, 0xC002000
+            li t1, 8
+            sw t1, 0(t0)
+            li t1, 0x880
+            csrw mie, t1
+            csrsi mstatus, 8
+        wait:
+            j wait
+        handler:
+            csrr t1, mcause
+            bltz t1, interrupt
+        done:
+            j done
+        interrupt:
+            slli t1, t1, 1
+            srli t1, t1, 1
+            li t2, 7
+            beq t1, t2, timer
+            li t0, 0xC200004
+            lw a1, 0(t0)
+            sw a1, 0(t0)
+            li t1, 0x800
+            csrc mie, t1
+            mret
+        timer:
+            rdtime a0
+            li t1, 0x80
+            csrc mie, t1
+            mret
+        ";
+        let mut machine = machine(source, 1, Schedule::RoundRobin);
+        for _ in 0..60 {
+            assert_eq!(machine.step().1, Ok(()));
+        }
+        assert!(machine.harts[0].reg[10] >= 30);
+        assert_eq!(machine.harts[0].reg[11], 0);
+
+        machine.plic.borrow_mut().set_level(3, true);
+        for _ in 0..30 {
+            assert_eq!(machine.step().1, Ok(()));
+        }
+        assert_eq!(machine.harts[0].reg[11], 3);
+        assert_eq!(machine.harts[0].csr.mie, 0);
+        assert_eq!(machine.harts[0].csr.read(MIP), Some((1 << 7) | (1 << 11)));
+    }
+
+    #[test]
+    fn test_reservation() {
+        let source = "
+            li a0, 0x80001000
+            csrr t0, mhartid
+            bnez t0, store
+            lr.w t1, (a0)
+            nop
+            sc.w t2, t0, (a0)
+        done:
+            j done
+        store:
+            nop
+            sw t0, 4(a0)
+            j done
+        ";
+        let mut machine = machine(source, 2, Schedule::RoundRobin);
+        for _ in 0..40 {
+            assert_eq!(machine.step().1, Ok(()));
+        }
+        assert_eq!(machine.harts[0].reg[7], 1);
+        assert_eq!(machine.bus.load32(0x8000_1004), Ok(1));
+    }
+}
@@ -0,0 +1,535 @@
+/*
+ * memory.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ *
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+/* returned by a memory when nothing is mapped at the accessed address */
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct AccessFault;
+
+/* returned when a region would overlap an already mapped region */
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct RegionOverlap {
+    pub base : u64,
+    pub size : u64,
+}
+
+impl fmt::Display for RegionOverlap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "region {:#x}+{:#x} overlaps a mapped region", self.base, self.size)
+    }
+}
+
+impl Error for RegionOverlap {}
+
+/* Little-endian physical memory as seen by the core. Only the byte accesses
+ * must be implemented, the wider accesses are composed out of them. Loads
+ * take &mut self because reading a device register may have side effects. */
+pub trait Memory {
+    fn load8(&mut self, addr: u64) -> Result<u8, AccessFault>;
+    fn store8(&mut self, addr: u64, value: u8) -> Result<(), AccessFault>;
+
+    fn load16(&mut self, addr: u64) -> Result<u16, AccessFault> {
+        let lo = self.load8(addr)? as u16;
+        let hi = self.load8(addr.wrapping_add(1))? as u16;
+        Ok(lo | (hi << 8))
+    }
+
+    fn load32(&mut self, addr: u64) -> Result<u32, AccessFault> {
+        let lo = self.load16(addr)? as u32;
+        let hi = self.load16(addr.wrapping_add(2))? as u32;
+        Ok(lo | (hi << 16))
+    }
+
+    fn load64(&mut self, addr: u64) -> Result<u64, AccessFault> {
+        let lo = self.load32(addr)? as u64;
+        let hi = self.load32(addr.wrapping_add(4))? as u64;
+        Ok(lo | (hi << 32))
+    }
+
+    fn store16(&mut self, addr: u64, value: u16) -> Result<(), AccessFault> {
+        self.store8(addr, value as u8)?;
+        self.store8(addr.wrapping_add(1), (value >> 8) as u8)
+    }
+
+    fn store32(&mut self, addr: u64, value: u32) -> Result<(), AccessFault> {
+        self.store16(addr, value as u16)?;
+        self.store16(addr.wrapping_add(2), (value >> 16) as u16)
+    }
+
+    fn store64(&mut self, addr: u64, value: u64) -> Result<(), AccessFault> {
+        self.store32(addr, value as u32)?;
+        self.store32(addr.wrapping_add(4), (value >> 32) as u32)
+    }
+
+    /* whether a store of len bytes at addr would succeed, so a store that
+     * is split into several accesses can fail before it writes anything.
+     * Devices accept every address unless they say otherwise. */
+    fn writable(&mut self, _addr: u64, _len: u64) -> bool {
+        true
+    }
+
+    /* copies a whole block into memory, e.g. a program image */
+    fn write_bytes(&mut self, addr: u64, data: &[u8]) -> Result<(), AccessFault> {
+        for (i, b) in data.iter().enumerate() {
+            self.store8(addr.wrapping_add(i as u64), *b)?;
+        }
+        Ok(())
+    }
+}
+
+/* A device shared between the bus and its owner, e.g. an interrupt
+ * controller whose state the machine passes on to the harts. Every access
+ * is forwarded with its width. */
+impl<T: Memory> Memory for Rc<RefCell<T>> {
+    fn load8(&mut self, addr: u64) -> Result<u8, AccessFault> {
+        self.borrow_mut().load8(addr)
+    }
+
+    fn store8(&mut self, addr: u64, value: u8) -> Result<(), AccessFault> {
+        self.borrow_mut().store8(addr, value)
+    }
+
+    fn load16(&mut self, addr: u64) -> Result<u16, AccessFault> {
+        self.borrow_mut().load16(addr)
+    }
+
+    fn load32(&mut self, addr: u64) -> Result<u32, AccessFault> {
+        self.borrow_mut().load32(addr)
+    }
+
+    fn load64(&mut self, addr: u64) -> Result<u64, AccessFault> {
+        self.borrow_mut().load64(addr)
+    }
+
+    fn store16(&mut self, addr: u64, value: u16) -> Result<(), AccessFault> {
+        self.borrow_mut().store16(addr, value)
+    }
+
+    fn store32(&mut self, addr: u64, value: u32) -> Result<(), AccessFault> {
+        self.borrow_mut().store32(addr, value)
+    }
+
+    fn store64(&mut self, addr: u64, value: u64) -> Result<(), AccessFault> {
+        self.borrow_mut().store64(addr, value)
+    }
+
+    fn writable(&mut self, addr: u64, len: u64) -> bool {
+        self.borrow_mut().writable(addr, len)
+    }
+}
+
+/* stores all bytes or none of them, the wide stores of RAM are made of
+ * byte stores */
+fn store_bytes<M: Memory + ?Sized>(mem: &mut M, addr: u64, bytes: &[u8]) -> Result<(), AccessFault> {
+    if !mem.writable(addr, bytes.len() as u64) {
+        return Err(AccessFault);
+    }
+    for (i, &byte) in bytes.iter().enumerate() {
+        mem.store8(addr + i as u64, byte)?;
+    }
+    Ok(())
+}
+
+/* a contiguous block of RAM starting at base */
+pub struct Ram {
+    base : u64,
+    data : Vec<u8>,
+}
+
+impl Ram {
+    pub fn new(base: u64, size: usize) -> Ram {
+        Ram {base: base, data: vec![0; size]}
+    }
+
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    fn offset(&self, addr: u64) -> Result<usize, AccessFault> {
+        match addr.checked_sub(self.base) {
+            Some(off) if off < self.data.len() as u64 => Ok(off as usize),
+            _ => Err(AccessFault)
+        }
+    }
+}
+
+impl Memory for Ram {
+    fn load8(&mut self, addr: u64) -> Result<u8, AccessFault> {
+        let off = self.offset(addr)?;
+        Ok(self.data[off])
+    }
+
+    fn writable(&mut self, addr: u64, len: u64) -> bool {
+        let last = addr.checked_add(len.saturating_sub(1));
+        self.offset(addr).is_ok() && last.is_some_and(|last| self.offset(last).is_ok())
+    }
+
+    fn store8(&mut self, addr: u64, value: u8) -> Result<(), AccessFault> {
+        let off = self.offset(addr)?;
+        self.data[off] = value;
+        Ok(())
+    }
+
+    fn store16(&mut self, addr: u64, value: u16) -> Result<(), AccessFault> {
+        store_bytes(self, addr, &value.to_le_bytes())
+    }
+
+    fn store32(&mut self, addr: u64, value: u32) -> Result<(), AccessFault> {
+        store_bytes(self, addr, &value.to_le_bytes())
+    }
+
+    fn store64(&mut self, addr: u64, value: u64) -> Result<(), AccessFault> {
+        store_bytes(self, addr, &value.to_le_bytes())
+    }
+}
+
+/* read-only memory, stores raise an access fault */
+pub struct Rom {
+    base : u64,
+    data : Vec<u8>,
+}
+
+impl Rom {
+    pub fn new(base: u64, data: Vec<u8>) -> Rom {
+        Rom {base: base, data: data}
+    }
+}
+
+impl Memory for Rom {
+    fn load8(&mut self, addr: u64) -> Result<u8, AccessFault> {
+        match addr.checked_sub(self.base) {
+            Some(off) if off < self.data.len() as u64 => Ok(self.data[off as usize]),
+            _ => Err(AccessFault)
+        }
+    }
+
+    fn store8(&mut self, _addr: u64, _value: u8) -> Result<(), AccessFault> {
+        Err(AccessFault)
+    }
+
+    fn writable(&mut self, _addr: u64, _len: u64) -> bool {
+        false
+    }
+}
+
+const PAGE_SIZE : u64 = 4096;
+
+/* RAM whose pages are only allocated when they are first written, so large
+ * address spaces cost nothing until used. Unwritten memory reads as zero. */
+pub struct SparseRam {
+    base  : u64,
+    size  : u64,
+    pages : HashMap<u64, Box<[u8]>>,
+}
+
+impl SparseRam {
+    pub fn new(base: u64, size: u64) -> SparseRam {
+        SparseRam {base: base, size: size, pages: HashMap::new()}
+    }
+
+    fn offset(&self, addr: u64) -> Result<u64, AccessFault> {
+        match addr.checked_sub(self.base) {
+            Some(off) if off < self.size => Ok(off),
+            _ => Err(AccessFault)
+        }
+    }
+}
+
+impl Memory for SparseRam {
+    fn load8(&mut self, addr: u64) -> Result<u8, AccessFault> {
+        let off = self.offset(addr)?;
+        match self.pages.get(&(off / PAGE_SIZE)) {
+            Some(page) => Ok(page[(off % PAGE_SIZE) as usize]),
+            None => Ok(0)
+        }
+    }
+
+    fn store8(&mut self, addr: u64, value: u8) -> Result<(), AccessFault> {
+        let off = self.offset(addr)?;
+        let page = self.pages.entry(off / PAGE_SIZE)
+            .or_insert_with(|| vec![0; PAGE_SIZE as usize].into_boxed_slice());
+        page[(off % PAGE_SIZE) as usize] = value;
+        Ok(())
+    }
+
+    fn writable(&mut self, addr: u64, len: u64) -> bool {
+        let last = addr.checked_add(len.saturating_sub(1));
+        self.offset(addr).is_ok() && last.is_some_and(|last| self.offset(last).is_ok())
+    }
+
+    fn store16(&mut self, addr: u64, value: u16) -> Result<(), AccessFault> {
+        store_bytes(self, addr, &value.to_le_bytes())
+    }
+
+    fn store32(&mut self, addr: u64, value: u32) -> Result<(), AccessFault> {
+        store_bytes(self, addr, &value.to_le_bytes())
+    }
+
+    fn store64(&mut self, addr: u64, value: u64) -> Result<(), AccessFault> {
+        store_bytes(self, addr, &value.to_le_bytes())
+    }
+}
+
+struct Region {
+    base   : u64,
+    size   : u64,
+    memory : Box<dyn Memory>,
+}
+
+/* The physical address space. Each region is backed by RAM, ROM or a
+ * memory mapped device and receives the offset into the region as address.
+ * Accesses are forwarded with their original width, so devices can react
+ * to e.g. 32-bit register accesses. Unmapped addresses and accesses that
+ * cross the end of a region raise an access fault. */
+#[derive(Default)]
+pub struct Bus {
+    regions : Vec<Region>,
+}
+
+impl Bus {
+    pub fn new() -> Bus {
+        Bus {regions: Vec::new()}
+    }
+
+    pub fn map(&mut self, base: u64, size: u64, memory: Box<dyn Memory>) -> Result<(), RegionOverlap> {
+        let overlap = RegionOverlap {base: base, size: size};
+        let end = base.checked_add(size).ok_or(overlap)?;
+        if size == 0 || self.regions.iter().any(|r| base < r.base + r.size && r.base < end) {
+            return Err(overlap);
+        }
+        let pos = self.regions.iter().position(|r| r.base > base).unwrap_or(self.regions.len());
+        self.regions.insert(pos, Region {base: base, size: size, memory: memory});
+        Ok(())
+    }
+
+    pub fn map_ram(&mut self, base: u64, size: u64) -> Result<(), RegionOverlap> {
+        self.map(base, size, Box::new(SparseRam::new(0, size)))
+    }
+
+    /* maps RAM into every part of [base, base + size) that is not mapped
+     * yet, so devices can be placed inside a large RAM area */
+    pub fn map_ram_gaps(&mut self, base: u64, size: u64) -> Result<(), RegionOverlap> {
+        let end = base.checked_add(size).ok_or(RegionOverlap {base: base, size: size})?;
+        let mut gaps = Vec::new();
+        let mut start = base;
+        for region in self.regions.iter().filter(|r| r.base < end && base < r.base + r.size) {
+            if region.base > start {
+                gaps.push((start, region.base - start));
+            }
+            start = start.max(region.base + region.size);
+        }
+        if start < end {
+            gaps.push((start, end - start));
+        }
+        for (base, size) in gaps {
+            self.map_ram(base, size)?;
+        }
+        Ok(())
+    }
+
+    pub fn map_rom(&mut self, base: u64, data: Vec<u8>) -> Result<(), RegionOverlap> {
+        let size = data.len() as u64;
+        self.map(base, size, Box::new(Rom::new(0, data)))
+    }
+
+    /* the region holding all len bytes at addr and the offset into it */
+    fn region(&mut self, addr: u64, len: u64) -> Result<(&mut dyn Memory, u64), AccessFault> {
+        for region in self.regions.iter_mut() {
+            if addr >= region.base && addr - region.base < region.size {
+                let off = addr - region.base;
+                if region.size - off < len {
+                    return Err(AccessFault);
+                }
+                return Ok((&mut *region.memory, off));
+            }
+        }
+        Err(AccessFault)
+    }
+}
+
+impl Memory for Bus {
+    fn load8(&mut self, addr: u64) -> Result<u8, AccessFault> {
+        let (mem, off) = self.region(addr, 1)?;
+        mem.load8(off)
+    }
+
+    fn load16(&mut self, addr: u64) -> Result<u16, AccessFault> {
+        let (mem, off) = self.region(addr, 2)?;
+        mem.load16(off)
+    }
+
+    fn load32(&mut self, addr: u64) -> Result<u32, AccessFault> {
+        let (mem, off) = self.region(addr, 4)?;
+        mem.load32(off)
+    }
+
+    fn load64(&mut self, addr: u64) -> Result<u64, AccessFault> {
+        let (mem, off) = self.region(addr, 8)?;
+        mem.load64(off)
+    }
+
+    fn store8(&mut self, addr: u64, value: u8) -> Result<(), AccessFault> {
+        let (mem, off) = self.region(addr, 1)?;
+        mem.store8(off, value)
+    }
+
+    fn store16(&mut self, addr: u64, value: u16) -> Result<(), AccessFault> {
+        let (mem, off) = self.region(addr, 2)?;
+        mem.store16(off, value)
+    }
+
+    fn store32(&mut self, addr: u64, value: u32) -> Result<(), AccessFault> {
+        let (mem, off) = self.region(addr, 4)?;
+        mem.store32(off, value)
+    }
+
+    fn store64(&mut self, addr: u64, value: u64) -> Result<(), AccessFault> {
+        let (mem, off) = self.region(addr, 8)?;
+        mem.store64(off, value)
+    }
+
+    fn writable(&mut self, addr: u64, len: u64) -> bool {
+        match self.region(addr, len) {
+            Ok((mem, off)) => mem.writable(off, len),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::{Memory, Ram, SparseRam, Bus, AccessFault, RegionOverlap};
+
+    #[test]
+    fn test_ram() {
+        let mut ram = Ram::new(0x1000, 16);
+        assert_eq!(ram.store32(0x1000, 0x12345678), Ok(()));
+        assert_eq!(ram.load8(0x1000), Ok(0x78));
+        assert_eq!(ram.load16(0x1002), Ok(0x1234));
+        assert_eq!(ram.load32(0x1001), Ok(0x00123456));
+        assert_eq!(ram.load8(0x0FFF), Err(AccessFault));
+        assert_eq!(ram.load32(0x100E), Err(AccessFault));
+        assert_eq!(ram.store8(0x1010, 0), Err(AccessFault));
+    }
+
+    #[test]
+    fn test_wide_access() {
+        let mut ram = Ram::new(0, 16);
+        assert_eq!(ram.store64(4, 0x0123456789ABCDEF), Ok(()));
+        assert_eq!(ram.load32(4), Ok(0x89ABCDEF));
+        assert_eq!(ram.load64(4), Ok(0x0123456789ABCDEF));
+        assert_eq!(ram.load64(12), Err(AccessFault));
+
+        /* a store crossing the end writes nothing */
+        assert_eq!(ram.store64(12, !0), Err(AccessFault));
+        assert_eq!(ram.load32(12), Ok(0));
+        let mut sparse = SparseRam::new(0, 16);
+        assert_eq!(sparse.store32(14, !0), Err(AccessFault));
+        assert_eq!(sparse.load16(14), Ok(0));
+    }
+
+    #[test]
+    fn test_sparse_ram() {
+        let mut ram = SparseRam::new(0x80000000, 1 << 40);
+        assert_eq!(ram.load64(0x80000000 + (1 << 39)), Ok(0));
+        assert_eq!(ram.store32(0x80000FFE, 0xDEADBEEF), Ok(()));
+        assert_eq!(ram.load32(0x80000FFE), Ok(0xDEADBEEF));
+        assert_eq!(ram.pages.len(), 2);
+        assert_eq!(ram.load8(0x7FFFFFFF), Err(AccessFault));
+        assert_eq!(ram.store8(0x80000000 + (1 << 40), 0), Err(AccessFault));
+    }
+
+    /* records the width of every access */
+    struct Device {
+        log : Rc<RefCell<Vec<(u64, u32)>>>,
+    }
+
+    impl Memory for Device {
+        fn load8(&mut self, addr: u64) -> Result<u8, AccessFault> {
+            self.log.borrow_mut().push((addr, 1));
+            Ok(0x11)
+        }
+
+        fn store8(&mut self, addr: u64, _value: u8) -> Result<(), AccessFault> {
+            self.log.borrow_mut().push((addr, 1));
+            Ok(())
+        }
+
+        fn load32(&mut self, addr: u64) -> Result<u32, AccessFault> {
+            self.log.borrow_mut().push((addr, 4));
+            Ok(0x44444444)
+        }
+
+        fn store32(&mut self, addr: u64, _value: u32) -> Result<(), AccessFault> {
+            self.log.borrow_mut().push((addr, 4));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_bus() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut bus = Bus::new();
+        bus.map_ram(0x80000000, 0x1000).unwrap();
+        bus.map_rom(0x1000, vec![1, 2, 3, 4]).unwrap();
+        bus.map(0x10000000, 0x100, Box::new(Device {log: log.clone()})).unwrap();
+
+        assert_eq!(bus.map_ram(0x80000800, 0x1000), Err(RegionOverlap {base: 0x80000800, size: 0x1000}));
+        assert_eq!(bus.map_ram(0xFFF, 2), Err(RegionOverlap {base: 0xFFF, size: 2}));
+        assert_eq!(bus.map_ram(0xFFFFFFFFFFFFF000, 0x2000), Err(RegionOverlap {base: 0xFFFFFFFFFFFFF000, size: 0x2000}));
+
+        assert_eq!(bus.store64(0x80000008, 0x1122334455667788), Ok(()));
+        assert_eq!(bus.load16(0x8000000E), Ok(0x1122));
+        assert_eq!(bus.load64(0x80000FFC), Err(AccessFault));
+
+        assert_eq!(bus.load32(0x1000), Ok(0x04030201));
+        assert_eq!(bus.store8(0x1000, 0), Err(AccessFault));
+        assert_eq!(bus.load8(0x1004), Err(AccessFault));
+
+        assert_eq!(bus.load32(0x10000010), Ok(0x44444444));
+        assert_eq!(bus.store8(0x10000004, 0), Ok(()));
+        assert_eq!(bus.load16(0x100000FE), Ok(0x1111));
+        assert_eq!(*log.borrow(), vec![(0x10, 4), (0x04, 1), (0xFE, 1), (0xFF, 1)]);
+
+        assert_eq!(bus.load8(0), Err(AccessFault));
+
+        /* RAM around the mapped regions */
+        bus.map_ram_gaps(0x800, 0x10000200 - 0x800).unwrap();
+        assert_eq!(bus.load8(0x800), Ok(0));
+        assert_eq!(bus.load32(0x1000), Ok(0x04030201));
+        assert_eq!(bus.store8(0x1004, 5), Ok(()));
+        assert_eq!(bus.load8(0x100001FF), Ok(0));
+        assert_eq!(bus.load32(0x10000010), Ok(0x44444444));
+        assert_eq!(bus.load8(0x10000200), Err(AccessFault));
+    }
+}
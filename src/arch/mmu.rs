@@ -0,0 +1,408 @@
+/*
+ * mmu.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* Virtual memory translation with the Sv32, Sv39 and Sv48 page table
+ * formats. Translations are cached in a direct-mapped TLB which is only
+ * flushed by SFENCE.VMA and writes to satp. The A and D bits are updated by
+ * the page table walk. */
+
+use super::core::Exception;
+use super::csr::{CsrFile, Privilege, mstatus};
+use super::memory::Memory;
+
+const PAGE_SHIFT : u32 = 12;
+const PAGE_SIZE : u64 = 1 << PAGE_SHIFT;
+const TLB_SIZE : usize = 64;
+
+/* page table entry bits */
+pub mod pte {
+    pub const V : u64 = 1 << 0;
+    pub const R : u64 = 1 << 1;
+    pub const W : u64 = 1 << 2;
+    pub const X : u64 = 1 << 3;
+    pub const U : u64 = 1 << 4;
+    pub const G : u64 = 1 << 5;
+    pub const A : u64 = 1 << 6;
+    pub const D : u64 = 1 << 7;
+}
+
+/* satp.MODE values */
+pub mod satp_mode {
+    pub const BARE : u64 = 0;
+    pub const SV32 : u64 = 1;
+    pub const SV39 : u64 = 8;
+    pub const SV48 : u64 = 9;
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum AccessType {
+    Fetch,
+    Load,
+    Store,
+}
+
+impl AccessType {
+    pub fn page_fault(self, addr: u64) -> Exception {
+        match self {
+            AccessType::Fetch => Exception::InstructionPageFault(addr),
+            AccessType::Load => Exception::LoadPageFault(addr),
+            AccessType::Store => Exception::StorePageFault(addr),
+        }
+    }
+
+    pub fn access_fault(self, addr: u64) -> Exception {
+        match self {
+            AccessType::Fetch => Exception::InstructionAccessFault(addr),
+            AccessType::Load => Exception::LoadAccessFault(addr),
+            AccessType::Store => Exception::StoreAccessFault(addr),
+        }
+    }
+}
+
+/* the parameters of a page table format */
+struct Scheme {
+    levels   : u32,
+    pte_size : u64,
+    vpn_bits : u32,
+    va_bits  : u32,
+}
+
+const SV32 : Scheme = Scheme {levels: 2, pte_size: 4, vpn_bits: 10, va_bits: 32};
+const SV39 : Scheme = Scheme {levels: 3, pte_size: 8, vpn_bits: 9, va_bits: 39};
+const SV48 : Scheme = Scheme {levels: 4, pte_size: 8, vpn_bits: 9, va_bits: 48};
+
+/* the fields of satp, the MODE is only valid if written by CsrFile::write */
+fn satp_fields(csr: &CsrFile) -> (u64, u64, u64) {
+    let satp = csr.satp;
+    if csr.rv64() {
+        (satp >> 60, (satp >> 44) & 0xFFFF, satp & 0x0FFF_FFFF_FFFF)
+    } else {
+        ((satp >> 31) & 1, (satp >> 22) & 0x1FF, satp & 0x3F_FFFF)
+    }
+}
+
+/* a translation of a single 4 KiB page, which may be part of a superpage */
+#[derive(PartialEq, Debug, Clone, Copy)]
+struct TlbEntry {
+    vpn  : u64,
+    asid : u64,
+    mode : u64,
+    ppn  : u64,
+    pte  : u64, // only the permission bits
+    span : u64, // the VPN bits within the leaf page, zero for a 4 KiB page
+}
+
+pub struct Mmu {
+    tlb : [Option<TlbEntry>; TLB_SIZE],
+}
+
+impl Default for Mmu {
+    fn default() -> Mmu {
+        Mmu::new()
+    }
+}
+
+/* whether the permission bits of a leaf PTE allow the access */
+fn permitted(pte: u64, access: AccessType, privilege: Privilege, status: u64) -> bool {
+    let user = (pte & pte::U) != 0;
+    match privilege {
+        Privilege::User if !user => return false,
+        /* S-mode may only read and write user pages with SUM set */
+        Privilege::Supervisor if user && (access == AccessType::Fetch || (status & mstatus::SUM) == 0) => return false,
+        _ => ()
+    }
+    match access {
+        AccessType::Fetch => (pte & pte::X) != 0,
+        AccessType::Load => (pte & pte::R) != 0 || ((status & mstatus::MXR) != 0 && (pte & pte::X) != 0),
+        AccessType::Store => (pte & pte::W) != 0,
+    }
+}
+
+impl Mmu {
+    pub fn new() -> Mmu {
+        Mmu {tlb: [None; TLB_SIZE]}
+    }
+
+    /* Drops cached translations. Without an address all pages are flushed,
+     * without an ASID the entries of all address spaces including global
+     * mappings. An address flushes all pages of the superpage it lies in. */
+    pub fn flush(&mut self, vaddr: Option<u64>, asid: Option<u64>) {
+        for slot in self.tlb.iter_mut() {
+            if let Some(entry) = *slot {
+                let page = vaddr.is_none_or(|addr| ((addr >> PAGE_SHIFT) | entry.span) == (entry.vpn | entry.span));
+                let space = asid.is_none_or(|asid| asid == entry.asid && (entry.pte & pte::G) == 0);
+                if page && space {
+                    *slot = None;
+                }
+            }
+        }
+    }
+
+    /* translates a virtual to a physical address */
+    pub fn translate<M: Memory + ?Sized>(&mut self, vaddr: u64, access: AccessType, csr: &CsrFile, mem: &mut M)
+        -> Result<u64, Exception> {
+        let privilege = if access == AccessType::Fetch { csr.privilege } else { csr.data_privilege() };
+
+        let (mode, asid, root) = satp_fields(csr);
+        let scheme = match mode {
+            _ if privilege == Privilege::Machine => return Ok(vaddr),
+            satp_mode::SV32 if !csr.rv64() => SV32,
+            satp_mode::SV39 => SV39,
+            satp_mode::SV48 => SV48,
+            _ => return Ok(vaddr)
+        };
+
+        /* the upper bits must be copies of the highest address bit */
+        let shift = 64 - scheme.va_bits;
+        if csr.rv64() && (((vaddr << shift) as i64) >> shift) as u64 != vaddr {
+            return Err(access.page_fault(vaddr));
+        }
+
+        let vpn = (vaddr & csr.xlen_mask()) >> PAGE_SHIFT;
+        let offset = vaddr & (PAGE_SIZE - 1);
+        let slot = (vpn as usize) % TLB_SIZE;
+        if let Some(entry) = self.tlb[slot] {
+            let hit = entry.vpn == vpn && entry.mode == mode &&
+                (entry.asid == asid || (entry.pte & pte::G) != 0);
+            /* the first store to a clean page must set D in memory */
+            let clean = access == AccessType::Store && (entry.pte & pte::D) == 0;
+            if hit && !clean {
+                if !permitted(entry.pte, access, privilege, csr.mstatus) {
+                    return Err(access.page_fault(vaddr));
+                }
+                return Ok((entry.ppn << PAGE_SHIFT) | offset);
+            }
+        }
+
+        let (ppn, flags, span) = self.walk(&scheme, root, vaddr, access, privilege, csr, mem)?;
+        self.tlb[slot] = Some(TlbEntry {vpn: vpn, asid: asid, mode: mode, ppn: ppn, pte: flags, span: span});
+        Ok((ppn << PAGE_SHIFT) | offset)
+    }
+
+    /* walks the page table, returns the physical page number of the 4 KiB
+     * page, the flags of the leaf PTE and the VPN bits within the leaf */
+    #[allow(clippy::too_many_arguments)]
+    fn walk<M: Memory + ?Sized>(&mut self, scheme: &Scheme, root: u64, vaddr: u64, access: AccessType,
+                                privilege: Privilege, csr: &CsrFile, mem: &mut M) -> Result<(u64, u64, u64), Exception> {
+        let fault = access.page_fault(vaddr);
+        let vpn_mask = (1 << scheme.vpn_bits) - 1;
+        let mut table = root << PAGE_SHIFT;
+        let mut level = scheme.levels;
+
+        loop {
+            if level == 0 {
+                return Err(fault);
+            }
+            level -= 1;
+
+            let index = (vaddr >> (PAGE_SHIFT + level * scheme.vpn_bits)) & vpn_mask;
+            let addr = table + index * scheme.pte_size;
+            /* the walk is checked by PMP as S-mode accesses */
+            if !csr.pmp.check(addr, scheme.pte_size, AccessType::Load, Privilege::Supervisor) {
+                return Err(access.access_fault(vaddr));
+            }
+            let entry = if scheme.pte_size == 4 {
+                mem.load32(addr).map(|e| e as u64)
+            } else {
+                mem.load64(addr)
+            }.map_err(|_| access.access_fault(vaddr))?;
+
+            /* the upper ten bits of a Sv39/Sv48 PTE are reserved */
+            if (entry & pte::V) == 0 || ((entry & pte::R) == 0 && (entry & pte::W) != 0) || (entry >> 54) != 0 {
+                return Err(fault);
+            }
+            let ppn = if scheme.pte_size == 4 { entry >> 10 } else { (entry >> 10) & 0x0FFF_FFFF_FFFF };
+            if (entry & (pte::R | pte::X)) == 0 {
+                table = ppn << PAGE_SHIFT;
+                continue;
+            }
+
+            if !permitted(entry, access, privilege, csr.mstatus) {
+                return Err(fault);
+            }
+            /* the lower PPN fields of a superpage must be zero */
+            let page_mask = (1 << (level * scheme.vpn_bits)) - 1;
+            if (ppn & page_mask) != 0 {
+                return Err(fault);
+            }
+
+            let mut updated = entry | pte::A;
+            if access == AccessType::Store {
+                updated |= pte::D;
+            }
+            if updated != entry {
+                if !csr.pmp.check(addr, scheme.pte_size, AccessType::Store, Privilege::Supervisor) {
+                    return Err(access.access_fault(vaddr));
+                }
+                if scheme.pte_size == 4 {
+                    mem.store32(addr, updated as u32)
+                } else {
+                    mem.store64(addr, updated)
+                }.map_err(|_| access.access_fault(vaddr))?;
+            }
+
+            let vpn = (vaddr >> PAGE_SHIFT) & page_mask;
+            return Ok((ppn | vpn, updated & 0xFF, page_mask));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Mmu, AccessType, pte};
+    use super::super::core::Exception;
+    use super::super::csr::{CsrFile, Privilege, mstatus};
+    use super::super::memory::{Memory, Ram};
+
+    const RWX : u64 = pte::V | pte::R | pte::W | pte::X;
+
+    /* a Sv39 table at 0x1000 with a 4 KiB page at 0x40000000 through the
+     * table at 0x2000 and 0x3000, and a 1 GiB page at 0x80000000 */
+    fn sv39() -> (Ram, CsrFile) {
+        let mut ram = Ram::new(0, 0x10000);
+        ram.store64(0x1000 + 8, (0x2 << 10) | pte::V).unwrap();
+        ram.store64(0x1000 + 16, (0x80000 << 10) | RWX | pte::A | pte::D | pte::G).unwrap();
+        ram.store64(0x2000, (0x3 << 10) | pte::V).unwrap();
+        ram.store64(0x3000, (0x8 << 10) | pte::V | pte::R | pte::W | pte::U).unwrap();
+        ram.store64(0x3000 + 8, (0x9 << 10) | pte::V | pte::X).unwrap();
+        ram.store64(0x3000 + 16, (0x9 << 10) | pte::V | pte::W).unwrap();
+        let mut csr = CsrFile::new(true, 0);
+        csr.satp = (8 << 60) | (1 << 44) | 1;
+        csr.privilege = Privilege::Supervisor;
+        (ram, csr)
+    }
+
+    #[test]
+    fn test_sv39() {
+        let (mut ram, mut csr) = sv39();
+        let mut mmu = Mmu::new();
+
+        assert_eq!(mmu.translate(0x80123456, AccessType::Load, &csr, &mut ram), Ok(0x80123456));
+        assert_eq!(mmu.translate(0x40001FFC, AccessType::Fetch, &csr, &mut ram), Ok(0x9FFC));
+        assert_eq!(ram.load64(0x3008), Ok((0x9 << 10) | pte::V | pte::X | pte::A));
+        assert_eq!(mmu.translate(0x40001000, AccessType::Load, &csr, &mut ram), Err(Exception::LoadPageFault(0x40001000)));
+        csr.mstatus |= mstatus::MXR;
+        assert_eq!(mmu.translate(0x40001000, AccessType::Load, &csr, &mut ram), Ok(0x9000));
+
+        /* W without R is reserved */
+        assert_eq!(mmu.translate(0x40002000, AccessType::Store, &csr, &mut ram), Err(Exception::StorePageFault(0x40002000)));
+        /* unmapped and non-canonical */
+        assert_eq!(mmu.translate(0x4000, AccessType::Load, &csr, &mut ram), Err(Exception::LoadPageFault(0x4000)));
+        assert_eq!(mmu.translate(0x8000000000, AccessType::Load, &csr, &mut ram), Err(Exception::LoadPageFault(0x8000000000)));
+
+        /* M-mode is not translated unless MPRV is set */
+        csr.privilege = Privilege::Machine;
+        assert_eq!(mmu.translate(0x4000, AccessType::Load, &csr, &mut ram), Ok(0x4000));
+        csr.mstatus = (csr.mstatus & !mstatus::MPP) | mstatus::MPRV | (1 << mstatus::MPP_SHIFT);
+        assert_eq!(mmu.translate(0x4000, AccessType::Fetch, &csr, &mut ram), Ok(0x4000));
+        assert_eq!(mmu.translate(0x4000, AccessType::Load, &csr, &mut ram), Err(Exception::LoadPageFault(0x4000)));
+    }
+
+    #[test]
+    fn test_user_pages() {
+        let (mut ram, mut csr) = sv39();
+        let mut mmu = Mmu::new();
+
+        assert_eq!(mmu.translate(0x40000010, AccessType::Load, &csr, &mut ram), Err(Exception::LoadPageFault(0x40000010)));
+        csr.mstatus |= mstatus::SUM;
+        assert_eq!(mmu.translate(0x40000010, AccessType::Load, &csr, &mut ram), Ok(0x8010));
+        assert_eq!(mmu.translate(0x40000010, AccessType::Fetch, &csr, &mut ram), Err(Exception::InstructionPageFault(0x40000010)));
+
+        csr.privilege = Privilege::User;
+        assert_eq!(mmu.translate(0x40000010, AccessType::Store, &csr, &mut ram), Ok(0x8010));
+        assert_eq!(ram.load64(0x3000), Ok((0x8 << 10) | pte::V | pte::R | pte::W | pte::U | pte::A | pte::D));
+        assert_eq!(mmu.translate(0x80000000, AccessType::Load, &csr, &mut ram), Err(Exception::LoadPageFault(0x80000000)));
+    }
+
+    #[test]
+    fn test_tlb() {
+        let (mut ram, csr) = sv39();
+        let mut mmu = Mmu::new();
+
+        assert_eq!(mmu.translate(0x40000000, AccessType::Fetch, &csr, &mut ram), Err(Exception::InstructionPageFault(0x40000000)));
+        assert_eq!(mmu.translate(0x80000000, AccessType::Load, &csr, &mut ram), Ok(0x80000000));
+        assert_eq!(mmu.translate(0x40001000, AccessType::Fetch, &csr, &mut ram), Ok(0x9000));
+
+        /* stale translations are used until they are flushed */
+        ram.store64(0x3008, (0xA << 10) | pte::V | pte::X | pte::A).unwrap();
+        assert_eq!(mmu.translate(0x40001000, AccessType::Fetch, &csr, &mut ram), Ok(0x9000));
+        mmu.flush(Some(0x40001000), None);
+        assert_eq!(mmu.translate(0x40001000, AccessType::Fetch, &csr, &mut ram), Ok(0xA000));
+
+        /* global mappings survive an ASID flush */
+        ram.store64(0x1000 + 16, 0).unwrap();
+        ram.store64(0x3008, 0).unwrap();
+        mmu.flush(None, Some(1));
+        assert_eq!(mmu.translate(0x80000000, AccessType::Load, &csr, &mut ram), Ok(0x80000000));
+        assert_eq!(mmu.translate(0x40001000, AccessType::Fetch, &csr, &mut ram), Err(Exception::InstructionPageFault(0x40001000)));
+        mmu.flush(None, None);
+        assert_eq!(mmu.translate(0x80000000, AccessType::Load, &csr, &mut ram), Err(Exception::LoadPageFault(0x80000000)));
+    }
+
+    #[test]
+    fn test_superpage_flush() {
+        let (mut ram, csr) = sv39();
+        let mut mmu = Mmu::new();
+
+        /* a 2 MiB page at 0x40200000 is cached as separate 4 KiB pages */
+        ram.store64(0x2000 + 8, (0x200 << 10) | RWX | pte::A | pte::D).unwrap();
+        assert_eq!(mmu.translate(0x40200010, AccessType::Load, &csr, &mut ram), Ok(0x200010));
+        assert_eq!(mmu.translate(0x40345678, AccessType::Load, &csr, &mut ram), Ok(0x345678));
+        assert_eq!(mmu.translate(0x40001000, AccessType::Fetch, &csr, &mut ram), Ok(0x9000));
+
+        /* flushing one address of the superpage drops all of its pages */
+        ram.store64(0x2000 + 8, (0x400 << 10) | RWX | pte::A | pte::D).unwrap();
+        ram.store64(0x3008, (0xA << 10) | pte::V | pte::X | pte::A).unwrap();
+        mmu.flush(Some(0x40200000), None);
+        assert_eq!(mmu.translate(0x40200010, AccessType::Load, &csr, &mut ram), Ok(0x400010));
+        assert_eq!(mmu.translate(0x40345678, AccessType::Load, &csr, &mut ram), Ok(0x545678));
+        assert_eq!(mmu.translate(0x40001000, AccessType::Fetch, &csr, &mut ram), Ok(0x9000));
+    }
+
+    #[test]
+    fn test_sv32_sv48() {
+        let mut ram = Ram::new(0, 0x10000);
+        /* Sv32: a 4 MiB megapage at 0x00400000 and a misaligned one */
+        ram.store32(0x1000 + 4 * 0x200, ((0x400 << 10) | RWX) as u32).unwrap();
+        ram.store32(0x1000 + 4 * 0x201, ((0x401 << 10) | RWX) as u32).unwrap();
+        let mut csr = CsrFile::new(false, 0);
+        csr.satp = (1 << 31) | 1;
+        csr.privilege = Privilege::Supervisor;
+        let mut mmu = Mmu::new();
+        assert_eq!(mmu.translate(0x80012345, AccessType::Store, &csr, &mut ram), Ok(0x00412345));
+        assert_eq!(ram.load32(0x1800), Ok(((0x400 << 10) | RWX | pte::A | pte::D) as u32));
+        assert_eq!(mmu.translate(0x80400000, AccessType::Load, &csr, &mut ram), Err(Exception::LoadPageFault(0x80400000)));
+
+        /* Sv48: four levels down to a 4 KiB page */
+        let mut ram = Ram::new(0, 0x10000);
+        ram.store64(0x1000 + 8 * 0x100, (0x2 << 10) | pte::V).unwrap();
+        ram.store64(0x2000, (0x3 << 10) | pte::V).unwrap();
+        ram.store64(0x3000, (0x4 << 10) | pte::V).unwrap();
+        ram.store64(0x4000 + 8 * 5, (0x7 << 10) | RWX).unwrap();
+        let mut csr = CsrFile::new(true, 0);
+        csr.satp = (9 << 60) | 1;
+        csr.privilege = Privilege::Supervisor;
+        let mut mmu = Mmu::new();
+        assert_eq!(mmu.translate(0xFFFF800000005ABC, AccessType::Load, &csr, &mut ram), Ok(0x7ABC));
+        assert_eq!(mmu.translate(0xFFFF000000005ABC, AccessType::Load, &csr, &mut ram), Err(Exception::LoadPageFault(0xFFFF000000005ABC)));
+    }
+}
@@ -19,6 +19,17 @@
  * You should have received a copy of the GNU General Public License
  * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
  */
+pub mod assembler;
+pub mod clint;
 pub mod decoder;
+pub mod disassembler;
+pub mod encoder;
 mod constants;
-mod core;
+pub mod core;
+pub mod csr;
+pub mod machine;
+pub mod memory;
+pub mod mmu;
+pub mod plic;
+pub mod pmp;
+pub mod softfloat;
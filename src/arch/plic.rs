@@ -0,0 +1,330 @@
+/*
+ * plic.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* The platform-level interrupt controller in the SiFive layout with 31
+ * interrupt sources and two contexts per hart, 2 * hart for M-mode and
+ * 2 * hart + 1 for S-mode. The devices drive level-sensitive lines, a
+ * source with a high line becomes pending unless it has been claimed and
+ * not completed yet. A context requests an interrupt while an enabled
+ * source is pending whose priority is above the threshold of the context.
+ * The machine copies the requests into mip.MEIP and mip.SEIP of the
+ * harts. */
+
+use super::memory::{AccessFault, Memory};
+
+pub const PLIC_BASE : u64 = 0x0C00_0000;
+pub const PLIC_SIZE : u64 = 0x400_0000;
+
+pub const SOURCES : u32 = 32; // including the reserved source 0
+
+/* register offsets */
+const PRIORITY : u64 = 0x00_0000;
+const PENDING  : u64 = 0x00_1000;
+const ENABLE   : u64 = 0x00_2000;
+const CONTEXT  : u64 = 0x20_0000;
+
+const ENABLE_STRIDE  : u64 = 0x80;
+const CONTEXT_STRIDE : u64 = 0x1000;
+const PRIORITY_MASK  : u32 = 7;
+
+/* a device that drives an interrupt line of the PLIC */
+pub trait Source {
+    /* polls the device and returns the level of its line */
+    fn level(&mut self) -> bool;
+}
+
+pub struct Plic {
+    priority  : [u32; SOURCES as usize],
+    level     : u32, // the lines of the devices, one bit per source
+    pending   : u32,
+    claimed   : u32,
+    enable    : Vec<u32>,
+    threshold : Vec<u32>,
+}
+
+impl Plic {
+    pub fn new(harts: usize) -> Plic {
+        Plic {priority: [0; SOURCES as usize], level: 0, pending: 0, claimed: 0,
+              enable: vec![0; 2 * harts], threshold: vec![0; 2 * harts]}
+    }
+
+    /* sets the line of a source, it stays pending if it is lowered before
+     * it is claimed */
+    pub fn set_level(&mut self, source: u32, high: bool) {
+        assert!(source > 0 && source < SOURCES, "invalid interrupt source {}", source);
+        if high {
+            self.level |= 1 << source;
+        } else {
+            self.level &= !(1 << source);
+        }
+        self.update();
+    }
+
+    /* whether the context requests an interrupt */
+    pub fn interrupt(&self, context: usize) -> bool {
+        self.best(context) != 0
+    }
+
+    fn update(&mut self) {
+        self.pending |= self.level & !self.claimed;
+    }
+
+    /* the pending source with the highest priority above the threshold of
+     * the context, the lowest ID wins a tie, or 0 if there is none */
+    fn best(&self, context: usize) -> u32 {
+        let candidates = self.pending & self.enable[context];
+        (1..SOURCES)
+            .filter(|&s| (candidates & (1 << s)) != 0 && self.priority[s as usize] > self.threshold[context])
+            .fold(0, |best, s| if best == 0 || self.priority[s as usize] > self.priority[best as usize] { s } else { best })
+    }
+
+    fn claim(&mut self, context: usize) -> u32 {
+        let source = self.best(context);
+        if source != 0 {
+            self.pending &= !(1 << source);
+            self.claimed |= 1 << source;
+        }
+        source
+    }
+
+    /* completing a source that is not enabled for the context is ignored */
+    fn complete(&mut self, context: usize, source: u32) {
+        if source < SOURCES && (self.enable[context] & (1 << source)) != 0 {
+            self.claimed &= !(1 << source);
+            self.update();
+        }
+    }
+
+    /* the context and the offset into its enable bits or its threshold
+     * and claim registers */
+    fn context(&self, offset: u64, base: u64, stride: u64) -> Option<(usize, u64)> {
+        let context = (offset.checked_sub(base)? / stride) as usize;
+        if context < self.enable.len() { Some((context, offset % stride)) } else { None }
+    }
+}
+
+/* only 32-bit accesses are supported, the claim register must not be read
+ * in parts */
+impl Memory for Plic {
+    fn load8(&mut self, _addr: u64) -> Result<u8, AccessFault> {
+        Err(AccessFault)
+    }
+
+    fn store8(&mut self, _addr: u64, _value: u8) -> Result<(), AccessFault> {
+        Err(AccessFault)
+    }
+
+    fn load16(&mut self, _addr: u64) -> Result<u16, AccessFault> {
+        Err(AccessFault)
+    }
+
+    fn store16(&mut self, _addr: u64, _value: u16) -> Result<(), AccessFault> {
+        Err(AccessFault)
+    }
+
+    fn writable(&mut self, addr: u64, len: u64) -> bool {
+        len == 4 && (addr & 3) == 0
+    }
+
+    fn load32(&mut self, addr: u64) -> Result<u32, AccessFault> {
+        if (addr & 3) != 0 {
+            return Err(AccessFault);
+        }
+        if addr < PRIORITY + 4 * SOURCES as u64 {
+            return Ok(self.priority[((addr - PRIORITY) / 4) as usize]);
+        }
+        if addr == PENDING {
+            return Ok(self.pending);
+        }
+        if let Some((context, offset)) = self.context(addr, ENABLE, ENABLE_STRIDE) {
+            return Ok(if offset == 0 { self.enable[context] } else { 0 });
+        }
+        match self.context(addr, CONTEXT, CONTEXT_STRIDE) {
+            Some((context, 0)) => Ok(self.threshold[context]),
+            Some((context, 4)) => Ok(self.claim(context)),
+            _ => Err(AccessFault),
+        }
+    }
+
+    fn store32(&mut self, addr: u64, value: u32) -> Result<(), AccessFault> {
+        if (addr & 3) != 0 {
+            return Err(AccessFault);
+        }
+        if addr < PRIORITY + 4 * SOURCES as u64 {
+            /* source 0 does not exist, its priority is hardwired to zero */
+            if addr != PRIORITY {
+                self.priority[((addr - PRIORITY) / 4) as usize] = value & PRIORITY_MASK;
+            }
+            return Ok(());
+        }
+        /* the pending bits are read-only */
+        if addr == PENDING {
+            return Ok(());
+        }
+        if let Some((context, offset)) = self.context(addr, ENABLE, ENABLE_STRIDE) {
+            if offset == 0 {
+                self.enable[context] = value & !1;
+            }
+            return Ok(());
+        }
+        match self.context(addr, CONTEXT, CONTEXT_STRIDE) {
+            Some((context, 0)) => self.threshold[context] = value & PRIORITY_MASK,
+            Some((context, 4)) => self.complete(context, value),
+            _ => return Err(AccessFault),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Plic;
+    use super::super::memory::{AccessFault, Memory};
+
+    #[test]
+    fn test_claim() {
+        let mut plic = Plic::new(1);
+        plic.store32(4 * 3, 1).unwrap();
+        plic.store32(4 * 5, 2).unwrap();
+        plic.store32(0x2000, 0xFFFF_FFFF).unwrap();
+        assert_eq!(plic.load32(0x2000), Ok(0xFFFF_FFFE));
+        plic.set_level(3, true);
+        plic.set_level(5, true);
+        assert_eq!(plic.load32(0x1000), Ok((1 << 3) | (1 << 5)));
+        assert!(plic.interrupt(0) && !plic.interrupt(1));
+
+        /* the higher priority first, a claimed source is not pending until
+         * it is completed */
+        assert_eq!(plic.load32(0x20_0004), Ok(5));
+        assert_eq!(plic.load32(0x20_0004), Ok(3));
+        assert_eq!(plic.load32(0x20_0004), Ok(0));
+        assert!(!plic.interrupt(0));
+        plic.set_level(3, false);
+        plic.store32(0x20_0004, 3).unwrap();
+        plic.store32(0x20_0004, 5).unwrap();
+        assert_eq!(plic.load32(0x1000), Ok(1 << 5));
+
+        /* the threshold masks priorities up to its value */
+        plic.store32(0x20_0000, 2).unwrap();
+        assert!(!plic.interrupt(0));
+        plic.store32(0x20_0000, 1).unwrap();
+        assert!(plic.interrupt(0));
+
+        /* a source only interrupts the contexts that enable it */
+        plic.store32(0x2000, 0).unwrap();
+        plic.store32(0x2080, 1 << 5).unwrap();
+        assert!(!plic.interrupt(0) && plic.interrupt(1));
+        assert_eq!(plic.load32(0x20_1004), Ok(5));
+
+        assert_eq!(plic.load32(0x2100), Err(AccessFault));
+        assert_eq!(plic.load32(0x20_2000), Err(AccessFault));
+        assert_eq!(plic.load8(0), Err(AccessFault));
+    }
+
+    #[test]
+    fn test_priority() {
+        let mut plic = Plic::new(1);
+        for &(source, priority) in &[(2, 3), (4, 3), (6, 3), (7, 1)] {
+            plic.store32(4 * source, priority).unwrap();
+            plic.set_level(source as u32, true);
+        }
+        plic.store32(0, 7).unwrap();
+        assert_eq!(plic.load32(0), Ok(0));
+        assert_eq!(plic.load32(4 * 2), Ok(3));
+        plic.store32(4 * 9, 0xF).unwrap();
+        assert_eq!(plic.load32(4 * 9), Ok(7));
+        plic.store32(4 * 9, 0).unwrap();
+        plic.store32(0x2000, (1 << 2) | (1 << 4) | (1 << 6) | (1 << 7)).unwrap();
+
+        /* the lowest ID wins a tie */
+        assert_eq!(plic.load32(0x20_0004), Ok(2));
+        assert_eq!(plic.load32(0x20_0004), Ok(4));
+
+        /* a source whose priority equals the threshold is masked */
+        plic.store32(0x20_0000, 3).unwrap();
+        assert_eq!(plic.load32(0x20_0000), Ok(3));
+        assert!(!plic.interrupt(0));
+        assert_eq!(plic.load32(0x20_0004), Ok(0));
+        assert_eq!(plic.load32(0x1000), Ok((1 << 6) | (1 << 7)));
+        plic.store32(0x20_0000, 2).unwrap();
+        assert_eq!(plic.load32(0x20_0004), Ok(6));
+        plic.store32(0x20_0000, 0).unwrap();
+        assert_eq!(plic.load32(0x20_0004), Ok(7));
+
+        /* a source with priority 0 never interrupts */
+        plic.store32(0x2000, 1 << 9).unwrap();
+        plic.set_level(9, true);
+        assert!(!plic.interrupt(0));
+    }
+
+    #[test]
+    fn test_contexts() {
+        let mut plic = Plic::new(2);
+        plic.store32(4 * 3, 1).unwrap();
+        plic.set_level(3, true);
+
+        /* the S-mode context of hart 1 */
+        plic.store32(0x2000 + 3 * 0x80, 1 << 3).unwrap();
+        assert!(!plic.interrupt(0) && !plic.interrupt(1) && !plic.interrupt(2) && plic.interrupt(3));
+        plic.store32(0x20_0000 + 3 * 0x1000, 1).unwrap();
+        assert!(!plic.interrupt(3));
+        assert_eq!(plic.load32(0x20_3004), Ok(0));
+        plic.store32(0x20_3000, 0).unwrap();
+        assert_eq!(plic.load32(0x20_3004), Ok(3));
+        assert!(!plic.interrupt(3));
+
+        /* a complete from a context that does not enable the source is
+         * ignored */
+        plic.store32(0x20_0004, 3).unwrap();
+        plic.store32(0x20_2004, 3).unwrap();
+        assert_eq!(plic.load32(0x1000), Ok(0));
+        plic.store32(0x20_3004, 3).unwrap();
+        assert_eq!(plic.load32(0x1000), Ok(1 << 3));
+        assert!(plic.interrupt(3));
+
+        assert_eq!(plic.load32(0x2000 + 4 * 0x80), Err(AccessFault));
+        assert_eq!(plic.load32(0x20_4000), Err(AccessFault));
+    }
+
+    #[test]
+    fn test_levels() {
+        let mut plic = Plic::new(1);
+        plic.store32(4 * 3, 1).unwrap();
+        plic.store32(0x2000, 1 << 3).unwrap();
+
+        /* a source that is lowered before it is claimed stays pending */
+        plic.set_level(3, true);
+        plic.set_level(3, false);
+        assert_eq!(plic.load32(0x1000), Ok(1 << 3));
+        assert_eq!(plic.load32(0x20_0004), Ok(3));
+        plic.store32(0x20_0004, 3).unwrap();
+        assert_eq!(plic.load32(0x1000), Ok(0));
+        assert!(!plic.interrupt(0));
+
+        /* a line that is still high becomes pending again on completion */
+        plic.set_level(3, true);
+        assert_eq!(plic.load32(0x20_0004), Ok(3));
+        assert_eq!(plic.load32(0x1000), Ok(0));
+        plic.store32(0x20_0004, 3).unwrap();
+        assert_eq!(plic.load32(0x1000), Ok(1 << 3));
+    }
+}
@@ -0,0 +1,241 @@
+/*
+ * pmp.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* Physical memory protection with 64 entries and a granularity of four
+ * bytes. Each entry is configured by one byte of the pmpcfg CSRs and an
+ * address register that holds bits 55:2 (RV64) or 33:2 (RV32) of a
+ * physical address. */
+
+use super::csr::Privilege;
+use super::mmu::AccessType;
+
+pub const ENTRIES : usize = 64;
+
+/* fields of a configuration byte */
+pub mod pmpcfg {
+    pub const R       : u8 = 1 << 0;
+    pub const W       : u8 = 1 << 1;
+    pub const X       : u8 = 1 << 2;
+    pub const A_SHIFT : u8 = 3;
+    pub const A       : u8 = 0b11 << A_SHIFT;
+    pub const L       : u8 = 1 << 7;
+
+    /* address matching modes */
+    pub const OFF   : u8 = 0;
+    pub const TOR   : u8 = 1;
+    pub const NA4   : u8 = 2;
+    pub const NAPOT : u8 = 3;
+}
+
+pub struct Pmp {
+    cfg  : [u8; ENTRIES],
+    addr : [u64; ENTRIES],
+}
+
+impl Default for Pmp {
+    fn default() -> Pmp {
+        Pmp::new()
+    }
+}
+
+impl Pmp {
+    /* Like Spike the first entry grants access to all of memory at reset,
+     * so that software which is unaware of PMP can use S and U-mode. */
+    pub fn new() -> Pmp {
+        let mut pmp = Pmp {cfg: [0; ENTRIES], addr: [0; ENTRIES]};
+        pmp.cfg[0] = (pmpcfg::NAPOT << pmpcfg::A_SHIFT) | pmpcfg::R | pmpcfg::W | pmpcfg::X;
+        pmp.addr[0] = (1 << 54) - 1;
+        pmp
+    }
+
+    fn mode(&self, i: usize) -> u8 {
+        (self.cfg[i] & pmpcfg::A) >> pmpcfg::A_SHIFT
+    }
+
+    fn locked(&self, i: usize) -> bool {
+        (self.cfg[i] & pmpcfg::L) != 0
+    }
+
+    /* pmpcfgN holds the entries 4N to 4N+3 on RV32 and 4N to 4N+7 on RV64,
+     * where only the even registers exist */
+    pub fn read_cfg(&self, n: usize, rv64: bool) -> u64 {
+        let count = if rv64 { 8 } else { 4 };
+        (0..count).fold(0, |value, i| value | ((self.cfg[4 * n + i] as u64) << (8 * i)))
+    }
+
+    /* Locked entries are not changed. W without R is reserved and the
+     * write of W is ignored then. */
+    pub fn write_cfg(&mut self, n: usize, value: u64, rv64: bool) {
+        let count = if rv64 { 8 } else { 4 };
+        for i in 0..count {
+            let entry = 4 * n + i;
+            if self.locked(entry) {
+                continue;
+            }
+            let mut cfg = (value >> (8 * i)) as u8 & !0x60;
+            if (cfg & pmpcfg::R) == 0 {
+                cfg &= !pmpcfg::W;
+            }
+            self.cfg[entry] = cfg;
+        }
+    }
+
+    pub fn read_addr(&self, i: usize) -> u64 {
+        self.addr[i]
+    }
+
+    /* The address of a locked entry is read-only, as is the address below
+     * a locked TOR entry. */
+    pub fn write_addr(&mut self, i: usize, value: u64, rv64: bool) {
+        let tor = i + 1 < ENTRIES && self.locked(i + 1) && self.mode(i + 1) == pmpcfg::TOR;
+        if self.locked(i) || tor {
+            return;
+        }
+        self.addr[i] = value & if rv64 { (1 << 54) - 1 } else { 0xFFFF_FFFF };
+    }
+
+    /* the byte range [start, end) matched by an entry, None if it is off */
+    fn range(&self, i: usize) -> Option<(u128, u128)> {
+        let addr = self.addr[i] as u128;
+        match self.mode(i) {
+            pmpcfg::TOR => {
+                let start = if i == 0 { 0 } else { (self.addr[i - 1] as u128) << 2 };
+                Some((start, addr << 2))
+            },
+            pmpcfg::NA4 => Some((addr << 2, (addr << 2) + 4)),
+            pmpcfg::NAPOT => {
+                /* the number of trailing ones encodes the size */
+                let ones = (!self.addr[i]).trailing_zeros();
+                let start = (addr & !((1 << ones) - 1)) << 2;
+                Some((start, start + (1 << (ones + 3))))
+            },
+            _ => None
+        }
+    }
+
+    /* Whether an access of size bytes at a physical address is allowed.
+     * The entry with the lowest number that matches any byte decides and it
+     * has to match all of them. M-mode is only restricted by locked entries
+     * while S and U-mode accesses fail if no entry matches. */
+    pub fn check(&self, addr: u64, size: u64, access: AccessType, privilege: Privilege) -> bool {
+        let start = addr as u128;
+        let end = start + size as u128;
+        for i in 0..ENTRIES {
+            let (low, high) = match self.range(i) {
+                Some(range) => range,
+                None => continue
+            };
+            if end <= low || start >= high {
+                continue;
+            }
+            if start < low || end > high {
+                return false;
+            }
+            if privilege == Privilege::Machine && !self.locked(i) {
+                return true;
+            }
+            let bit = match access {
+                AccessType::Fetch => pmpcfg::X,
+                AccessType::Load => pmpcfg::R,
+                AccessType::Store => pmpcfg::W,
+            };
+            return (self.cfg[i] & bit) != 0;
+        }
+        privilege == Privilege::Machine
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Pmp, pmpcfg};
+    use super::super::csr::Privilege;
+    use super::super::mmu::AccessType;
+
+    const TOR : u64 = (pmpcfg::TOR << pmpcfg::A_SHIFT) as u64;
+    const NA4 : u64 = (pmpcfg::NA4 << pmpcfg::A_SHIFT) as u64;
+    const NAPOT : u64 = (pmpcfg::NAPOT << pmpcfg::A_SHIFT) as u64;
+
+    #[test]
+    fn test_reset() {
+        let pmp = Pmp::new();
+        assert!(pmp.check(0, 8, AccessType::Store, Privilege::User));
+        assert!(pmp.check(0xFFFFFFFFFFFFF8, 8, AccessType::Fetch, Privilege::Supervisor));
+        assert_eq!(pmp.read_cfg(0, true), 0x1F);
+        assert_eq!(pmp.read_addr(0), 0x3FFFFFFFFFFFFF);
+    }
+
+    #[test]
+    fn test_matching() {
+        let mut pmp = Pmp::new();
+        /* 0: NA4 at 0x1000 read-only, 1: TOR [0x1000, 0x2000) RW,
+         * 2: NAPOT 0x4000 with 4 KiB X */
+        pmp.write_addr(0, 0x1000 >> 2, false);
+        pmp.write_addr(1, 0x2000 >> 2, false);
+        pmp.write_addr(2, (0x4000 >> 2) | 0x1FF, false);
+        pmp.write_cfg(0, NA4 | 1 | ((TOR | 3) << 8) | ((NAPOT | 4) << 16), false);
+        assert_eq!(pmp.read_cfg(0, false), 0x1C0B11);
+
+        let user = Privilege::User;
+        assert!(pmp.check(0x1000, 4, AccessType::Load, user));
+        assert!(!pmp.check(0x1000, 4, AccessType::Store, user));
+        /* a partial match of entry 0 fails even though entry 1 covers it */
+        assert!(!pmp.check(0x1002, 4, AccessType::Load, user));
+        assert!(pmp.check(0x1004, 4, AccessType::Store, user));
+        assert!(pmp.check(0x1FF8, 8, AccessType::Store, user));
+        assert!(!pmp.check(0x1FFC, 8, AccessType::Load, user));
+        assert!(pmp.check(0x4FFE, 2, AccessType::Fetch, user));
+        assert!(!pmp.check(0x4000, 4, AccessType::Load, user));
+        assert!(!pmp.check(0x5000, 4, AccessType::Load, user));
+
+        /* M-mode is not restricted by unlocked entries */
+        assert!(pmp.check(0x1000, 4, AccessType::Store, Privilege::Machine));
+        assert!(pmp.check(0x5000, 4, AccessType::Store, Privilege::Machine));
+
+        /* W without R is reserved */
+        pmp.write_cfg(0, NA4 | 2, false);
+        assert_eq!(pmp.read_cfg(0, false), 0x10);
+    }
+
+    #[test]
+    fn test_lock() {
+        let mut pmp = Pmp::new();
+        pmp.write_addr(0, 0x1000 >> 2, true);
+        pmp.write_addr(1, 0x2000 >> 2, true);
+        pmp.write_cfg(0, NAPOT | 0x80 | ((TOR | 0x80 | 1) << 8), true);
+        assert!(!pmp.check(0x1000, 4, AccessType::Load, Privilege::Machine));
+        assert!(pmp.check(0x1800, 4, AccessType::Load, Privilege::Machine));
+        assert!(!pmp.check(0x1800, 4, AccessType::Store, Privilege::Machine));
+
+        /* neither the configuration nor the addresses can be changed */
+        pmp.write_cfg(0, 0, true);
+        pmp.write_addr(0, 0, true);
+        pmp.write_addr(1, 0, true);
+        assert_eq!(pmp.read_cfg(0, true), 0x8998);
+        assert_eq!(pmp.read_addr(0), 0x400);
+        assert_eq!(pmp.read_addr(1), 0x800);
+
+        /* RV64 pmpcfg2 holds the entries 8 to 15 */
+        pmp.write_cfg(2, 0x1F << 56, true);
+        assert_eq!(pmp.read_cfg(2, true), 0x1F << 56);
+        assert_eq!(pmp.read_cfg(3, false), 0x1F000000);
+    }
+}
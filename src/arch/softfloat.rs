@@ -0,0 +1,784 @@
+/*
+ * softfloat.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* IEEE-754 binary32 and binary64 arithmetic in software, bit-exact with
+ * the RISC-V flavour of Berkeley SoftFloat: tininess is detected after
+ * rounding and every NaN result is the canonical NaN. Values are passed as
+ * raw bit patterns in the lower bits of a u64. Internally a finite value
+ * is a sign, an integer significand and an exponent, so every operation
+ * computes the exact result (or enough of it plus a sticky bit) and rounds
+ * it once. */
+
+use std::cmp::Ordering;
+
+/* accrued exception flags, in the layout of fflags */
+pub mod flags {
+    pub const NX : u8 = 1 << 0; // inexact
+    pub const UF : u8 = 1 << 1; // underflow
+    pub const OF : u8 = 1 << 2; // overflow
+    pub const DZ : u8 = 1 << 3; // divide by zero
+    pub const NV : u8 = 1 << 4; // invalid operation
+}
+
+/* the rounding modes in the encoding of frm */
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Rounding {
+    NearestEven = 0,
+    TowardZero = 1,
+    Down = 2,
+    Up = 3,
+    NearestMaxMagnitude = 4,
+}
+
+impl Rounding {
+    /* the values 5 to 7 are reserved */
+    pub fn from_bits(bits: u64) -> Option<Rounding> {
+        match bits {
+            0 => Some(Rounding::NearestEven),
+            1 => Some(Rounding::TowardZero),
+            2 => Some(Rounding::Down),
+            3 => Some(Rounding::Up),
+            4 => Some(Rounding::NearestMaxMagnitude),
+            _ => None
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Format {
+    exp_bits  : u32,
+    frac_bits : u32,
+}
+
+pub const F32 : Format = Format {exp_bits: 8, frac_bits: 23};
+pub const F64 : Format = Format {exp_bits: 11, frac_bits: 52};
+
+impl Format {
+    fn bias(self) -> i32 {
+        (1 << (self.exp_bits - 1)) - 1
+    }
+
+    /* significand bits including the implicit one */
+    fn precision(self) -> i32 {
+        self.frac_bits as i32 + 1
+    }
+
+    /* exponent of the smallest normal number */
+    fn emin(self) -> i32 {
+        1 - self.bias()
+    }
+
+    fn max_exp(self) -> u64 {
+        (1 << self.exp_bits) - 1
+    }
+
+    fn frac_mask(self) -> u64 {
+        (1 << self.frac_bits) - 1
+    }
+
+    pub fn sign_bit(self) -> u64 {
+        1 << (self.exp_bits + self.frac_bits)
+    }
+
+    fn exp(self, a: u64) -> u64 {
+        (a >> self.frac_bits) & self.max_exp()
+    }
+
+    pub fn sign(self, a: u64) -> bool {
+        (a & self.sign_bit()) != 0
+    }
+
+    pub fn canonical_nan(self) -> u64 {
+        (self.max_exp() << self.frac_bits) | (1 << (self.frac_bits - 1))
+    }
+
+    fn zero(self, sign: bool) -> u64 {
+        if sign { self.sign_bit() } else { 0 }
+    }
+
+    fn infinity(self, sign: bool) -> u64 {
+        self.zero(sign) | (self.max_exp() << self.frac_bits)
+    }
+
+    fn largest(self, sign: bool) -> u64 {
+        self.infinity(sign) - 1
+    }
+
+    pub fn is_nan(self, a: u64) -> bool {
+        self.exp(a) == self.max_exp() && (a & self.frac_mask()) != 0
+    }
+
+    pub fn is_signaling(self, a: u64) -> bool {
+        self.is_nan(a) && (a & (1 << (self.frac_bits - 1))) == 0
+    }
+
+    pub fn is_infinite(self, a: u64) -> bool {
+        self.exp(a) == self.max_exp() && (a & self.frac_mask()) == 0
+    }
+
+    pub fn is_zero(self, a: u64) -> bool {
+        (a & !self.sign_bit()) == 0
+    }
+
+    /* sign, significand and exponent of a finite value */
+    fn unpack(self, a: u64) -> (bool, u128, i32) {
+        let frac = (a & self.frac_mask()) as u128;
+        let exp = self.exp(a) as i32;
+        if exp == 0 {
+            (self.sign(a), frac, self.emin() - self.frac_bits as i32)
+        } else {
+            (self.sign(a), frac | (1 << self.frac_bits), exp - self.bias() - self.frac_bits as i32)
+        }
+    }
+
+    /* the result of fclass, a single bit from -infinity (0) to quiet
+     * NaN (9) */
+    pub fn classify(self, a: u64) -> u64 {
+        let sign = self.sign(a);
+        let bit = if self.is_signaling(a) {
+            8
+        } else if self.is_nan(a) {
+            9
+        } else if self.is_infinite(a) {
+            if sign { 0 } else { 7 }
+        } else if self.exp(a) != 0 {
+            if sign { 1 } else { 6 }
+        } else if !self.is_zero(a) {
+            if sign { 2 } else { 5 }
+        } else if sign {
+            3
+        } else {
+            4
+        };
+        1 << bit
+    }
+}
+
+fn bit_length(sig: u128) -> i32 {
+    128 - sig.leading_zeros() as i32
+}
+
+fn isqrt(n: u128) -> u128 {
+    if n == 0 {
+        return 0;
+    }
+    let mut n = n;
+    let mut root = 0;
+    let mut bit = 1 << ((bit_length(n) - 1) & !1);
+    while bit != 0 {
+        if n >= root + bit {
+            n -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+/* The rounding mode and the flags accrued by the operations, which only
+ * ever set flags. The operations take and return raw bit patterns. */
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Env {
+    pub rm    : Rounding,
+    pub flags : u8,
+}
+
+impl Env {
+    pub fn new(rm: Rounding) -> Env {
+        Env {rm: rm, flags: 0}
+    }
+
+    /* shifts a significand right by at least one bit and rounds it to an
+     * integer, returns the rounded value, which may have carried into a
+     * new bit, and whether it is inexact */
+    fn shift_round(&self, sign: bool, sig: u128, shift: u32) -> (u128, bool) {
+        let (kept, half, inexact) = if shift > 128 {
+            (0, Ordering::Less, sig != 0)
+        } else {
+            let kept = sig.checked_shr(shift).unwrap_or(0);
+            let rem = sig & (!0u128 >> (128 - shift));
+            (kept, rem.cmp(&(1 << (shift - 1))), rem != 0)
+        };
+        let up = match self.rm {
+            Rounding::NearestEven => half == Ordering::Greater || (half == Ordering::Equal && (kept & 1) != 0),
+            Rounding::NearestMaxMagnitude => half != Ordering::Less,
+            Rounding::TowardZero => false,
+            Rounding::Down => inexact && sign,
+            Rounding::Up => inexact && !sign,
+        };
+        (kept + up as u128, inexact)
+    }
+
+    /* rounds sig * 2^exp to the format, raising overflow, underflow and
+     * inexact as needed */
+    fn round(&mut self, fmt: Format, sign: bool, sig: u128, exp: i32) -> u64 {
+        if sig == 0 {
+            return fmt.zero(sign);
+        }
+        let p = fmt.precision();
+        let top = exp + bit_length(sig) - 1;
+        /* exponent of the last significand bit, subnormals have fewer bits */
+        let mut lsb = (top - p + 1).max(fmt.emin() - p + 1);
+        let (mut kept, inexact) = if lsb <= exp {
+            (sig << (exp - lsb), false)
+        } else {
+            self.shift_round(sign, sig, (lsb - exp) as u32)
+        };
+        if (kept >> p) != 0 {
+            kept >>= 1;
+            lsb += 1;
+        }
+
+        if inexact {
+            self.flags |= flags::NX;
+            /* tiny if the result rounded to an unbounded exponent range is
+             * below the smallest normal number */
+            let tiny = top < fmt.emin() - 1 || (top == fmt.emin() - 1 && {
+                let shift = top - p + 1 - exp;
+                shift <= 0 || (self.shift_round(sign, sig, shift as u32).0 >> p) == 0
+            });
+            if tiny {
+                self.flags |= flags::UF;
+            }
+        }
+
+        if (kept >> (p - 1)) == 0 {
+            return fmt.zero(sign) | kept as u64;
+        }
+        let exp = lsb + p - 1;
+        if exp > fmt.bias() {
+            self.flags |= flags::OF | flags::NX;
+            let infinite = match self.rm {
+                Rounding::NearestEven | Rounding::NearestMaxMagnitude => true,
+                Rounding::TowardZero => false,
+                Rounding::Down => sign,
+                Rounding::Up => !sign,
+            };
+            return if infinite { fmt.infinity(sign) } else { fmt.largest(sign) };
+        }
+        fmt.zero(sign) | (((exp + fmt.bias()) as u64) << fmt.frac_bits) | (kept as u64 & fmt.frac_mask())
+    }
+
+    /* the canonical NaN, invalid if any operand is a signaling NaN */
+    fn nan(&mut self, fmt: Format, operands: &[u64]) -> u64 {
+        if operands.iter().any(|&a| fmt.is_signaling(a)) {
+            self.flags |= flags::NV;
+        }
+        fmt.canonical_nan()
+    }
+
+    fn invalid(&mut self, fmt: Format) -> u64 {
+        self.flags |= flags::NV;
+        fmt.canonical_nan()
+    }
+
+    /* the zero of an exact sum of two operands with opposite signs */
+    fn cancelled(&self, fmt: Format) -> u64 {
+        fmt.zero(self.rm == Rounding::Down)
+    }
+
+    /* Rounds the sum of two finite values. The larger one is placed at bit
+     * 125 and the smaller one is shifted next to it, bits that fall off
+     * are collected in a sticky bit. Bits are only lost if the values are
+     * far apart, so there is no cancellation and the sticky bit is well
+     * below the rounding position. The significands can have up to 106
+     * bits for the product of a fused multiply-add. */
+    fn add_values(&mut self, fmt: Format, a: (bool, u128, i32), b: (bool, u128, i32)) -> u64 {
+        let ((sa, ma, ea), (sb, mb, eb)) = (a, b);
+        if ma == 0 && mb == 0 {
+            return if sa == sb { fmt.zero(sa) } else { self.cancelled(fmt) };
+        } else if ma == 0 {
+            return self.round(fmt, sb, mb, eb);
+        } else if mb == 0 {
+            return self.round(fmt, sa, ma, ea);
+        }
+
+        let (top_a, top_b) = (ea + bit_length(ma) - 1, eb + bit_length(mb) - 1);
+        let ((sa, ma, ea), (sb, mb, eb)) = if top_a >= top_b { (a, b) } else { (b, a) };
+        let lsb = top_a.max(top_b) - 125;
+        let x = ma << (ea - lsb);
+        let y = if eb >= lsb {
+            mb << (eb - lsb)
+        } else {
+            let shift = (lsb - eb) as u32;
+            let lost = shift >= 128 || (mb & (!0u128 >> (128 - shift))) != 0;
+            mb.checked_shr(shift).unwrap_or(0) | lost as u128
+        };
+
+        let (sign, sig) = if sa == sb {
+            (sa, x + y)
+        } else if x >= y {
+            (sa, x - y)
+        } else {
+            (sb, y - x)
+        };
+        if sig == 0 {
+            return self.cancelled(fmt);
+        }
+        self.round(fmt, sign, sig, lsb)
+    }
+
+    pub fn add(&mut self, fmt: Format, a: u64, b: u64) -> u64 {
+        if fmt.is_nan(a) || fmt.is_nan(b) {
+            return self.nan(fmt, &[a, b]);
+        }
+        if fmt.is_infinite(a) {
+            if fmt.is_infinite(b) && fmt.sign(a) != fmt.sign(b) {
+                return self.invalid(fmt);
+            }
+            return a;
+        } else if fmt.is_infinite(b) {
+            return b;
+        }
+        self.add_values(fmt, fmt.unpack(a), fmt.unpack(b))
+    }
+
+    pub fn sub(&mut self, fmt: Format, a: u64, b: u64) -> u64 {
+        self.add(fmt, a, b ^ fmt.sign_bit())
+    }
+
+    pub fn mul(&mut self, fmt: Format, a: u64, b: u64) -> u64 {
+        if fmt.is_nan(a) || fmt.is_nan(b) {
+            return self.nan(fmt, &[a, b]);
+        }
+        let sign = fmt.sign(a) != fmt.sign(b);
+        if fmt.is_infinite(a) || fmt.is_infinite(b) {
+            if fmt.is_zero(a) || fmt.is_zero(b) {
+                return self.invalid(fmt);
+            }
+            return fmt.infinity(sign);
+        }
+        let ((_, ma, ea), (_, mb, eb)) = (fmt.unpack(a), fmt.unpack(b));
+        self.round(fmt, sign, ma * mb, ea + eb)
+    }
+
+    /* The quotient is computed with at least two bits more than the
+     * precision and a sticky bit for a non-zero remainder. */
+    pub fn div(&mut self, fmt: Format, a: u64, b: u64) -> u64 {
+        if fmt.is_nan(a) || fmt.is_nan(b) {
+            return self.nan(fmt, &[a, b]);
+        }
+        let sign = fmt.sign(a) != fmt.sign(b);
+        if fmt.is_infinite(a) {
+            if fmt.is_infinite(b) {
+                return self.invalid(fmt);
+            }
+            return fmt.infinity(sign);
+        } else if fmt.is_infinite(b) {
+            return fmt.zero(sign);
+        } else if fmt.is_zero(b) {
+            if fmt.is_zero(a) {
+                return self.invalid(fmt);
+            }
+            self.flags |= flags::DZ;
+            return fmt.infinity(sign);
+        } else if fmt.is_zero(a) {
+            return fmt.zero(sign);
+        }
+
+        let ((_, ma, ea), (_, mb, eb)) = (fmt.unpack(a), fmt.unpack(b));
+        let shift = bit_length(mb) - bit_length(ma) + fmt.precision() + 2;
+        let num = ma << shift;
+        let sig = ((num / mb) << 1) | !num.is_multiple_of(mb) as u128;
+        self.round(fmt, sign, sig, ea - eb - shift - 1)
+    }
+
+    pub fn sqrt(&mut self, fmt: Format, a: u64) -> u64 {
+        if fmt.is_nan(a) {
+            return self.nan(fmt, &[a]);
+        }
+        if fmt.is_zero(a) {
+            return a;
+        } else if fmt.sign(a) {
+            return self.invalid(fmt);
+        } else if fmt.is_infinite(a) {
+            return a;
+        }
+
+        /* an even exponent and at least 2 * (precision + 2) bits, so the
+         * root has two bits more than the precision */
+        let (_, mut sig, mut exp) = fmt.unpack(a);
+        if (exp & 1) != 0 {
+            sig <<= 1;
+            exp -= 1;
+        }
+        let shift = (2 * (fmt.precision() + 2) - bit_length(sig) + 1).max(0) & !1;
+        let sig = sig << shift;
+        let root = isqrt(sig);
+        let root = (root << 1) | (root * root != sig) as u128;
+        self.round(fmt, false, root, (exp - shift) / 2 - 1)
+    }
+
+    /* Fused multiply-add, a * b + c with a single rounding. FMSUB, FNMSUB
+     * and FNMADD negate the product and/or the addend. The product of
+     * infinity and zero is invalid even if c is a quiet NaN. */
+    pub fn mul_add(&mut self, fmt: Format, a: u64, b: u64, c: u64, negate_product: bool, negate_addend: bool) -> u64 {
+        if fmt.is_nan(a) || fmt.is_nan(b) {
+            return self.nan(fmt, &[a, b, c]);
+        }
+        let (a_inf, b_inf) = (fmt.is_infinite(a), fmt.is_infinite(b));
+        if (a_inf && fmt.is_zero(b)) || (b_inf && fmt.is_zero(a)) {
+            self.nan(fmt, &[c]);
+            return self.invalid(fmt);
+        }
+        if fmt.is_nan(c) {
+            return self.nan(fmt, &[c]);
+        }
+
+        let sign = (fmt.sign(a) != fmt.sign(b)) != negate_product;
+        let c = if negate_addend { c ^ fmt.sign_bit() } else { c };
+        if a_inf || b_inf {
+            if fmt.is_infinite(c) && fmt.sign(c) != sign {
+                return self.invalid(fmt);
+            }
+            return fmt.infinity(sign);
+        } else if fmt.is_infinite(c) {
+            return c;
+        }
+
+        let ((_, ma, ea), (_, mb, eb)) = (fmt.unpack(a), fmt.unpack(b));
+        self.add_values(fmt, (sign, ma * mb, ea + eb), fmt.unpack(c))
+    }
+
+    /* the minimum or maximum, with -0 below +0 and NaNs only returned if
+     * both operands are NaN */
+    pub fn min_max(&mut self, fmt: Format, a: u64, b: u64, max: bool) -> u64 {
+        match (fmt.is_nan(a), fmt.is_nan(b)) {
+            (true, true) => return self.nan(fmt, &[a, b]),
+            (true, false) => {
+                self.nan(fmt, &[a]);
+                return b;
+            },
+            (false, true) => {
+                self.nan(fmt, &[b]);
+                return a;
+            },
+            (false, false) => (),
+        }
+        let a_less = match (fmt.sign(a), fmt.sign(b)) {
+            (true, false) => true,
+            (false, true) => false,
+            (true, true) => a > b,
+            (false, false) => a < b,
+        };
+        if a_less != max { a } else { b }
+    }
+
+    pub fn min(&mut self, fmt: Format, a: u64, b: u64) -> u64 {
+        self.min_max(fmt, a, b, false)
+    }
+
+    pub fn max(&mut self, fmt: Format, a: u64, b: u64) -> u64 {
+        self.min_max(fmt, a, b, true)
+    }
+
+    /* orders two values that are not NaN */
+    fn compare(fmt: Format, a: u64, b: u64) -> Ordering {
+        if fmt.is_zero(a) && fmt.is_zero(b) {
+            return Ordering::Equal;
+        }
+        match (fmt.sign(a), fmt.sign(b)) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (true, true) => b.cmp(&a),
+            (false, false) => a.cmp(&b),
+        }
+    }
+
+    /* the quiet comparison, only signaling NaNs are invalid */
+    pub fn equal(&mut self, fmt: Format, a: u64, b: u64) -> bool {
+        if fmt.is_nan(a) || fmt.is_nan(b) {
+            self.nan(fmt, &[a, b]);
+            return false;
+        }
+        Env::compare(fmt, a, b) == Ordering::Equal
+    }
+
+    /* the signaling comparisons, every NaN is invalid */
+    pub fn less(&mut self, fmt: Format, a: u64, b: u64) -> bool {
+        if fmt.is_nan(a) || fmt.is_nan(b) {
+            self.flags |= flags::NV;
+            return false;
+        }
+        Env::compare(fmt, a, b) == Ordering::Less
+    }
+
+    pub fn less_equal(&mut self, fmt: Format, a: u64, b: u64) -> bool {
+        if fmt.is_nan(a) || fmt.is_nan(b) {
+            self.flags |= flags::NV;
+            return false;
+        }
+        Env::compare(fmt, a, b) != Ordering::Greater
+    }
+
+    /* Converts to a signed or unsigned integer of the given width, the
+     * result is returned in the lower bits. Out of range values and NaNs
+     * are invalid and saturate, NaNs to the largest integer. */
+    pub fn to_int(&mut self, fmt: Format, a: u64, signed: bool, bits: u32) -> u64 {
+        let (min, max) = if signed {
+            (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+        } else {
+            (0, (1i128 << bits) - 1)
+        };
+        if fmt.is_nan(a) {
+            self.flags |= flags::NV;
+            return max as u64;
+        }
+
+        let (sign, sig, exp) = fmt.unpack(a);
+        let value = if fmt.is_infinite(a) || exp > 64 {
+            None
+        } else if exp >= 0 {
+            Some((sig << exp, false))
+        } else {
+            Some(self.shift_round(sign, sig, (-exp) as u32))
+        };
+        match value {
+            Some((mag, inexact)) => {
+                let value = if sign { -(mag as i128) } else { mag as i128 };
+                if value < min || value > max {
+                    self.flags |= flags::NV;
+                    return if sign { min as u64 } else { max as u64 };
+                }
+                if inexact {
+                    self.flags |= flags::NX;
+                }
+                value as u64
+            },
+            None => {
+                self.flags |= flags::NV;
+                if sign { min as u64 } else { max as u64 }
+            }
+        }
+    }
+
+    /* converts an integer, a signed value is passed sign-extended */
+    pub fn from_int(&mut self, fmt: Format, value: u64, signed: bool) -> u64 {
+        if signed && (value as i64) < 0 {
+            self.round(fmt, true, (value as i64).unsigned_abs() as u128, 0)
+        } else {
+            self.round(fmt, false, value as u128, 0)
+        }
+    }
+
+    /* converts between the formats */
+    pub fn convert(&mut self, from: Format, to: Format, a: u64) -> u64 {
+        if from.is_nan(a) {
+            self.nan(from, &[a]);
+            return to.canonical_nan();
+        }
+        let sign = from.sign(a);
+        if from.is_infinite(a) {
+            return to.infinity(sign);
+        }
+        let (_, sig, exp) = from.unpack(a);
+        self.round(to, sign, sig, exp)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Env, Rounding, F32, F64, flags};
+
+    /* xorshift, to compare against the host FPU in round to nearest */
+    fn random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    /* operands with interesting exponents, from subnormals to overflow */
+    fn operand64(state: &mut u64) -> u64 {
+        let r = random(state);
+        let exp = match r % 8 {
+            0 => 0,
+            1 => 1 + (r >> 8) % 4,
+            2 => 2046 - (r >> 8) % 4,
+            3 => 0x3FF + ((r >> 8) % 64) - 32,
+            _ => (r >> 8) % 0x7FF,
+        };
+        (random(state) & 0x800F_FFFF_FFFF_FFFF) | (exp << 52)
+    }
+
+    fn operand32(state: &mut u64) -> u64 {
+        let r = random(state);
+        let exp = match r % 8 {
+            0 => 0,
+            1 => 1 + (r >> 8) % 4,
+            2 => 254 - (r >> 8) % 4,
+            3 => 0x7F + ((r >> 8) % 32) - 16,
+            _ => (r >> 8) % 0xFF,
+        };
+        (random(state) & 0x807F_FFFF) | (exp << 23)
+    }
+
+    #[test]
+    fn test_against_host() {
+        let mut state = 0x1234_5678_9ABC_DEF1;
+        for _ in 0..20000 {
+            let (a, b, c) = (operand64(&mut state), operand64(&mut state), operand64(&mut state));
+            let (x, y, z) = (f64::from_bits(a), f64::from_bits(b), f64::from_bits(c));
+            let mut env = Env::new(Rounding::NearestEven);
+            assert_eq!(env.add(F64, a, b), (x + y).to_bits(), "{:x} + {:x}", a, b);
+            assert_eq!(env.sub(F64, a, b), (x - y).to_bits(), "{:x} - {:x}", a, b);
+            assert_eq!(env.mul(F64, a, b), (x * y).to_bits(), "{:x} * {:x}", a, b);
+            assert_eq!(env.div(F64, a, b), (x / y).to_bits(), "{:x} / {:x}", a, b);
+            assert_eq!(env.mul_add(F64, a, b, c, false, false), x.mul_add(y, z).to_bits(), "fma {:x} {:x} {:x}", a, b, c);
+            assert_eq!(env.sqrt(F64, a & !(1 << 63)), f64::from_bits(a & !(1 << 63)).sqrt().to_bits(), "sqrt {:x}", a);
+            assert_eq!(env.convert(F64, F32, a) as u32, (x as f32).to_bits(), "f32 {:x}", a);
+
+            let (a, b, c) = (operand32(&mut state), operand32(&mut state), operand32(&mut state));
+            let (x, y, z) = (f32::from_bits(a as u32), f32::from_bits(b as u32), f32::from_bits(c as u32));
+            assert_eq!(env.add(F32, a, b) as u32, (x + y).to_bits(), "{:x} + {:x}", a, b);
+            assert_eq!(env.mul(F32, a, b) as u32, (x * y).to_bits(), "{:x} * {:x}", a, b);
+            assert_eq!(env.div(F32, a, b) as u32, (x / y).to_bits(), "{:x} / {:x}", a, b);
+            assert_eq!(env.mul_add(F32, a, b, c, false, false) as u32, x.mul_add(y, z).to_bits(), "fma {:x} {:x} {:x}", a, b, c);
+            assert_eq!(env.sqrt(F32, a & 0x7FFF_FFFF) as u32, f32::from_bits(a as u32 & 0x7FFF_FFFF).sqrt().to_bits(), "sqrt {:x}", a);
+            assert_eq!(env.convert(F32, F64, a), (x as f64).to_bits(), "f64 {:x}", a);
+        }
+    }
+
+    #[test]
+    fn test_flags() {
+        let one = 0x3F80_0000;
+        let three = 0x4040_0000;
+        let max = 0x7F7F_FFFF;
+        let min_normal = 0x0080_0000;
+        let cases = [
+            /* 1/3 is inexact, rounds up to nearest and down toward zero */
+            (Rounding::NearestEven, Env::div as fn(&mut Env, _, _, _) -> u64, one, three, 0x3EAA_AAAB, flags::NX),
+            (Rounding::TowardZero, Env::div, one, three, 0x3EAA_AAAA, flags::NX),
+            (Rounding::Up, Env::div, one | 1 << 31, three, 0xBEAA_AAAA, flags::NX),
+            (Rounding::Down, Env::div, one | 1 << 31, three, 0xBEAA_AAAB, flags::NX),
+            (Rounding::NearestEven, Env::div, one, 0, 0x7F80_0000, flags::DZ),
+            (Rounding::NearestEven, Env::div, 0, 0, 0x7FC0_0000, flags::NV),
+            /* overflow is infinite or the largest number */
+            (Rounding::NearestEven, Env::mul, max, three, 0x7F80_0000, flags::OF | flags::NX),
+            (Rounding::TowardZero, Env::mul, max, three, max, flags::OF | flags::NX),
+            (Rounding::Down, Env::mul, max, three, max, flags::OF | flags::NX),
+            (Rounding::Up, Env::mul, max | 1 << 31, three, max | 1 << 31, flags::OF | flags::NX),
+            /* exact subnormal results do not underflow */
+            (Rounding::NearestEven, Env::mul, min_normal, 0x3F00_0000, 0x0040_0000, 0),
+            (Rounding::NearestEven, Env::mul, 0x0000_0003, 0x3F00_0000, 0x0000_0002, flags::UF | flags::NX),
+            /* (2^47 - 1) * 2^-173 rounds up to the smallest normal number
+             * with an unbounded exponent too, so it is not tiny */
+            (Rounding::NearestEven, Env::mul, 0x3F21_E58F, 0x00CA_6691, min_normal, flags::NX),
+            /* (2^24 - 1) * 2^-150 is tiny, although the result is normal */
+            (Rounding::NearestEven, Env::mul, 0x00FF_FFFF, 0x3F00_0000, min_normal, flags::UF | flags::NX),
+            /* the tie goes to the larger magnitude */
+            (Rounding::NearestMaxMagnitude, Env::add, 0x4B00_0000, 0x3F00_0000, 0x4B00_0001, flags::NX),
+            (Rounding::NearestEven, Env::add, 0x4B00_0000, 0x3F00_0000, 0x4B00_0000, flags::NX),
+            /* exact cancellation is -0 only when rounding down */
+            (Rounding::NearestEven, Env::sub, one, one, 0, 0),
+            (Rounding::Down, Env::sub, one, one, 0x8000_0000, 0),
+            /* infinity - infinity and signaling NaNs are invalid */
+            (Rounding::NearestEven, Env::sub, 0x7F80_0000, 0x7F80_0000, 0x7FC0_0000, flags::NV),
+            (Rounding::NearestEven, Env::add, 0x7F80_0001, one, 0x7FC0_0000, flags::NV),
+            (Rounding::NearestEven, Env::add, 0xFFC1_2345, one, 0x7FC0_0000, 0),
+        ];
+        for (i, &(rm, op, a, b, result, fflags)) in cases.iter().enumerate() {
+            let mut env = Env::new(rm);
+            assert_eq!((op(&mut env, F32, a, b), env.flags), (result, fflags), "case {}", i);
+        }
+    }
+
+    #[test]
+    fn test_special() {
+        let mut env = Env::new(Rounding::NearestEven);
+        /* infinity * 0 is invalid even with a quiet NaN addend */
+        assert_eq!(env.mul_add(F64, 0x7FF0 << 48, 0, 0x7FF8 << 48, false, false), F64.canonical_nan());
+        assert_eq!(env.flags, flags::NV);
+        /* the fused result is not rounded twice */
+        let mut env = Env::new(Rounding::NearestEven);
+        let a = 0x3F80_0002; // 1 + 2^-22
+        assert_eq!(env.mul_add(F32, a, a, 0xBF80_0000, false, false), 0x3500_0001);
+        assert_eq!(env.flags, 0);
+        /* -(0 * 1) - 0 */
+        let mut env = Env::new(Rounding::NearestEven);
+        assert_eq!(env.mul_add(F32, 0, 0x3F80_0000, 0, true, true), 0x8000_0000);
+        assert_eq!(env.sqrt(F32, 0x8000_0000), 0x8000_0000);
+        assert_eq!(env.sqrt(F32, 0xBF80_0000), 0x7FC0_0000);
+        assert_eq!(env.flags, flags::NV);
+
+        let mut env = Env::new(Rounding::NearestEven);
+        assert_eq!(env.min(F32, 0x8000_0000, 0), 0x8000_0000);
+        assert_eq!(env.max(F32, 0x8000_0000, 0), 0);
+        assert_eq!(env.max(F32, 0x7FC0_0000, 0x3F80_0000), 0x3F80_0000);
+        assert_eq!(env.flags, 0);
+        assert_eq!(env.min(F32, 0x7F80_0001, 0x7FC0_0000), 0x7FC0_0000);
+        assert_eq!(env.flags, flags::NV);
+
+        let mut env = Env::new(Rounding::NearestEven);
+        assert!(env.equal(F32, 0x8000_0000, 0) && env.less_equal(F32, 0, 0x8000_0000));
+        assert!(!env.equal(F32, 0x7FC0_0000, 0x7FC0_0000));
+        assert_eq!(env.flags, 0);
+        assert!(!env.less(F32, 0x7FC0_0000, 0));
+        assert_eq!(env.flags, flags::NV);
+
+        assert_eq!(F32.classify(0xFF80_0000), 1 << 0);
+        assert_eq!(F32.classify(0x8000_0001), 1 << 2);
+        assert_eq!(F32.classify(0), 1 << 4);
+        assert_eq!(F64.classify(0x7FF0_0000_0000_0001), 1 << 8);
+        assert_eq!(F64.classify(F64.canonical_nan()), 1 << 9);
+    }
+
+    #[test]
+    fn test_conversions() {
+        let mut env = Env::new(Rounding::NearestEven);
+        assert_eq!(env.to_int(F32, 0x3FC0_0000, true, 32), 2); // 1.5
+        assert_eq!(env.to_int(F32, 0x4020_0000, true, 32), 2); // 2.5
+        assert_eq!(env.to_int(F32, 0xBFC0_0000, true, 32) as u32, -2i32 as u32);
+        assert_eq!(env.flags, flags::NX);
+
+        let mut env = Env::new(Rounding::TowardZero);
+        assert_eq!(env.to_int(F32, 0xBF00_0000, false, 32), 0); // -0.5
+        assert_eq!(env.flags, flags::NX);
+        let mut env = Env::new(Rounding::NearestEven);
+        assert_eq!(env.to_int(F32, 0xBF80_0000, false, 32), 0); // -1.0
+        assert_eq!(env.to_int(F32, 0x7FC0_0000, true, 32), 0x7FFF_FFFF);
+        assert_eq!(env.to_int(F64, 0x7FF0 << 48, false, 64), !0);
+        assert_eq!(env.to_int(F64, 0xFFF0 << 48, true, 64), 1 << 63);
+        assert_eq!(env.to_int(F32, 0x4F00_0000, true, 32), 0x7FFF_FFFF); // 2^31
+        assert_eq!(env.flags, flags::NV);
+
+        let mut env = Env::new(Rounding::NearestEven);
+        assert_eq!(env.to_int(F32, 0xCF00_0000, true, 32) as u32, 0x8000_0000); // -2^31
+        assert_eq!(env.to_int(F64, 0x43E0 << 48, false, 64), 1 << 63); // 2^63
+        assert_eq!(env.flags, 0);
+
+        assert_eq!(env.from_int(F32, -1i64 as u64, true), 0xBF80_0000);
+        assert_eq!(env.from_int(F64, 1 << 63, true), 0xC3E0 << 48);
+        assert_eq!(env.flags, 0);
+        /* 2^24 + 1 is not a single */
+        assert_eq!(env.from_int(F32, 0x0100_0001, false), 0x4B80_0000);
+        assert_eq!(env.flags, flags::NX);
+
+        let mut env = Env::new(Rounding::NearestEven);
+        assert_eq!(env.convert(F32, F64, 0x7F80_0001), F64.canonical_nan());
+        assert_eq!(env.flags, flags::NV);
+        assert_eq!(Rounding::from_bits(4), Some(Rounding::NearestMaxMagnitude));
+        assert_eq!(Rounding::from_bits(5), None);
+    }
+}
@@ -0,0 +1,464 @@
+/*
+ * elf.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* Loader for statically linked RISC-V ELF executables. */
+
+use std::error::Error;
+use std::fmt;
+
+use arch::core::Core;
+use arch::memory::Memory;
+
+const ELF_MAGIC : [u8; 4] = [0x7F, b'E', b'L', b'F'];
+const ELFCLASS32 : u8 = 1;
+const ELFCLASS64 : u8 = 2;
+const ELFDATA2LSB : u8 = 1;
+const ET_EXEC : u16 = 2;
+const ET_DYN : u16 = 3;
+const EM_RISCV : u16 = 243;
+const PT_LOAD : u32 = 1;
+const SHT_RISCV_ATTRIBUTES : u32 = 0x7000_0003;
+const TAG_FILE : u8 = 1;
+const TAG_RISCV_ARCH : u64 = 5;
+
+/* e_flags */
+pub const EF_RISCV_RVC : u32 = 0x0001;
+pub const EF_RISCV_FLOAT_ABI : u32 = 0x0006;
+pub const EF_RISCV_RVE : u32 = 0x0008;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ElfError {
+    Truncated,
+    BadMagic,
+    UnsupportedClass {class: u8},
+    UnsupportedEncoding {data: u8},
+    UnsupportedType {kind: u16},
+    WrongMachine {machine: u16},
+    SegmentOutOfFile {index: usize},
+    MalformedAttributes,
+    AccessFault {addr: u64},
+    ClassMismatch {rv64: bool},
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ElfError::Truncated => write!(f, "file is truncated"),
+            ElfError::BadMagic => write!(f, "not an ELF file"),
+            ElfError::UnsupportedClass {class} => write!(f, "unsupported ELF class {}", class),
+            ElfError::UnsupportedEncoding {data} => write!(f, "unsupported data encoding {}", data),
+            ElfError::UnsupportedType {kind} => write!(f, "unsupported object file type {}", kind),
+            ElfError::WrongMachine {machine} => write!(f, "machine {} is not RISC-V", machine),
+            ElfError::SegmentOutOfFile {index} => write!(f, "segment {} exceeds the file", index),
+            ElfError::MalformedAttributes => write!(f, "malformed .riscv.attributes section"),
+            ElfError::AccessFault {addr} => write!(f, "no memory at {:#x}", addr),
+            ElfError::ClassMismatch {rv64} => write!(f, "ELF{} file does not match the XLEN of the hart",
+                                                     if rv64 { 64 } else { 32 }),
+        }
+    }
+}
+
+impl Error for ElfError {}
+
+/* a PT_LOAD segment */
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Segment {
+    pub addr   : u64,
+    pub offset : u64,
+    pub filesz : u64,
+    pub memsz  : u64,
+    pub flags  : u32,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ElfInfo {
+    pub rv64     : bool,
+    pub entry    : u64,
+    pub flags    : u32,
+    pub isa      : Option<String>,
+    pub segments : Vec<Segment>,
+}
+
+/* little-endian reads of 32-bit or 64-bit ELF structures */
+struct Reader<'a> {
+    data : &'a [u8],
+    rv64 : bool,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, off: u64, len: u64) -> Result<&'a [u8], ElfError> {
+        let end = off.checked_add(len).ok_or(ElfError::Truncated)?;
+        if end > self.data.len() as u64 {
+            return Err(ElfError::Truncated);
+        }
+        Ok(&self.data[off as usize..end as usize])
+    }
+
+    fn uint(&self, off: u64, len: u64) -> Result<u64, ElfError> {
+        let bytes = self.bytes(off, len)?;
+        Ok(bytes.iter().rev().fold(0, |acc, b| (acc << 8) | *b as u64))
+    }
+
+    fn u16(&self, off: u64) -> Result<u16, ElfError> {
+        Ok(self.uint(off, 2)? as u16)
+    }
+
+    fn u32(&self, off: u64) -> Result<u32, ElfError> {
+        Ok(self.uint(off, 4)? as u32)
+    }
+
+    /* Elf32_Addr/Elf32_Off or Elf64_Addr/Elf64_Off */
+    fn word(&self, off: u64) -> Result<u64, ElfError> {
+        self.uint(off, if self.rv64 { 8 } else { 4 })
+    }
+}
+
+fn uleb128(data: &[u8], pos: &mut usize) -> Result<u64, ElfError> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let b = *data.get(*pos).ok_or(ElfError::MalformedAttributes)?;
+        *pos += 1;
+        if shift < 64 {
+            value |= ((b & 0x7F) as u64) << shift;
+        }
+        shift += 7;
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn ntbs<'a>(data: &'a [u8], pos: &mut usize) -> Result<&'a [u8], ElfError> {
+    let rest = data.get(*pos..).ok_or(ElfError::MalformedAttributes)?;
+    let len = rest.iter().position(|b| *b == 0).ok_or(ElfError::MalformedAttributes)?;
+    *pos += len + 1;
+    Ok(&rest[..len])
+}
+
+/* Extracts Tag_RISCV_arch out of the build attributes. The section holds
+ * vendor subsections, of which only "riscv" is interpreted. Attributes with
+ * an odd tag are strings, the others are ULEB128 numbers. */
+fn parse_attributes(data: &[u8]) -> Result<Option<String>, ElfError> {
+    let reader = Reader {data: data, rv64: false};
+    if data.first() != Some(&b'A') {
+        return Err(ElfError::MalformedAttributes);
+    }
+    let mut off = 1;
+    while off < data.len() as u64 {
+        let len = reader.u32(off).map_err(|_| ElfError::MalformedAttributes)? as u64;
+        let sub = reader.bytes(off, len).map_err(|_| ElfError::MalformedAttributes)?;
+        off += len;
+        if len < 4 {
+            return Err(ElfError::MalformedAttributes);
+        }
+
+        let mut pos = 4;
+        if ntbs(sub, &mut pos)? != b"riscv" {
+            continue;
+        }
+        while pos < sub.len() {
+            let tag = sub[pos];
+            let sub_reader = Reader {data: sub, rv64: false};
+            let len = sub_reader.u32(pos as u64 + 1).map_err(|_| ElfError::MalformedAttributes)? as usize;
+            let end = pos.checked_add(len).filter(|end| *end <= sub.len() && len > 5)
+                .ok_or(ElfError::MalformedAttributes)?;
+            if tag == TAG_FILE {
+                let attrs = &sub[..end];
+                let mut p = pos + 5;
+                while p < end {
+                    let attr = uleb128(attrs, &mut p)?;
+                    if attr & 1 == 1 {
+                        let value = ntbs(attrs, &mut p)?;
+                        if attr == TAG_RISCV_ARCH {
+                            return Ok(Some(String::from_utf8_lossy(value).into_owned()));
+                        }
+                    } else {
+                        uleb128(attrs, &mut p)?;
+                    }
+                }
+            }
+            pos = end;
+        }
+    }
+    Ok(None)
+}
+
+/* parses the headers without touching any memory */
+pub fn parse(data: &[u8]) -> Result<ElfInfo, ElfError> {
+    if data.len() < 16 {
+        return Err(ElfError::Truncated);
+    }
+    if data[0..4] != ELF_MAGIC {
+        return Err(ElfError::BadMagic);
+    }
+    let rv64 = match data[4] {
+        ELFCLASS32 => false,
+        ELFCLASS64 => true,
+        class => return Err(ElfError::UnsupportedClass {class: class})
+    };
+    if data[5] != ELFDATA2LSB {
+        return Err(ElfError::UnsupportedEncoding {data: data[5]});
+    }
+
+    let r = Reader {data: data, rv64: rv64};
+    let word = if rv64 { 8 } else { 4 };
+    let kind = r.u16(16)?;
+    if kind != ET_EXEC && kind != ET_DYN {
+        return Err(ElfError::UnsupportedType {kind: kind});
+    }
+    let machine = r.u16(18)?;
+    if machine != EM_RISCV {
+        return Err(ElfError::WrongMachine {machine: machine});
+    }
+    let entry = r.word(24)?;
+    let phoff = r.word(24 + word)?;
+    let shoff = r.word(24 + 2 * word)?;
+    let flags = r.u32(24 + 3 * word)?;
+    let phentsize = r.u16(24 + 3 * word + 6)? as u64;
+    let phnum = r.u16(24 + 3 * word + 8)? as u64;
+    let shentsize = r.u16(24 + 3 * word + 10)? as u64;
+    let shnum = r.u16(24 + 3 * word + 12)? as u64;
+
+    let mut segments = Vec::new();
+    for i in 0..phnum {
+        /* the whole header must be in the file, so the field offsets cannot
+         * overflow */
+        let ph = i.checked_mul(phentsize).and_then(|off| off.checked_add(phoff)).ok_or(ElfError::Truncated)?;
+        r.bytes(ph, if rv64 { 56 } else { 32 })?;
+        if r.u32(ph)? != PT_LOAD {
+            continue;
+        }
+        /* Elf64_Phdr moves p_flags right after p_type */
+        let segment = if rv64 {
+            Segment {
+                flags: r.u32(ph + 4)?,
+                offset: r.word(ph + 8)?,
+                addr: r.word(ph + 24)?,
+                filesz: r.word(ph + 32)?,
+                memsz: r.word(ph + 40)?,
+            }
+        } else {
+            Segment {
+                offset: r.word(ph + 4)?,
+                addr: r.word(ph + 12)?,
+                filesz: r.word(ph + 16)?,
+                memsz: r.word(ph + 20)?,
+                flags: r.u32(ph + 24)?,
+            }
+        };
+        if r.bytes(segment.offset, segment.filesz).is_err() || segment.filesz > segment.memsz {
+            return Err(ElfError::SegmentOutOfFile {index: i as usize});
+        }
+        segments.push(segment);
+    }
+
+    let mut isa = None;
+    for i in 0..shnum {
+        let sh = i.checked_mul(shentsize).and_then(|off| off.checked_add(shoff)).ok_or(ElfError::Truncated)?;
+        r.bytes(sh, 10 * word)?;
+        if r.u32(sh + 4)? != SHT_RISCV_ATTRIBUTES {
+            continue;
+        }
+        let offset = r.word(sh + 8 + 2 * word)?;
+        let size = r.word(sh + 8 + 3 * word)?;
+        isa = parse_attributes(r.bytes(offset, size)?)?;
+    }
+
+    Ok(ElfInfo {rv64: rv64, entry: entry, flags: flags, isa: isa, segments: segments})
+}
+
+/* Copies the PT_LOAD segments to their physical addresses, zeroes the part
+ * of each segment that is not backed by the file (.bss) and points the core
+ * at the entry. */
+pub fn load<M: Memory + ?Sized>(data: &[u8], mem: &mut M, core: &mut Core) -> Result<ElfInfo, ElfError> {
+    let info = parse(data)?;
+    if info.rv64 != core.rv64() {
+        return Err(ElfError::ClassMismatch {rv64: info.rv64});
+    }
+    for segment in &info.segments {
+        let start = segment.offset as usize;
+        let bytes = &data[start..start + segment.filesz as usize];
+        for (i, b) in bytes.iter().enumerate() {
+            let addr = segment.addr.wrapping_add(i as u64);
+            mem.store8(addr, *b).map_err(|_| ElfError::AccessFault {addr: addr})?;
+        }
+        for i in segment.filesz..segment.memsz {
+            let addr = segment.addr.wrapping_add(i);
+            mem.store8(addr, 0).map_err(|_| ElfError::AccessFault {addr: addr})?;
+        }
+    }
+    core.pc = info.entry;
+    Ok(info)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, load, ElfError, Segment};
+    use arch::core::Core;
+    use arch::memory::{Memory, Ram};
+
+    fn put(buf: &mut Vec<u8>, off: usize, value: u64, len: usize) {
+        if buf.len() < off + len {
+            buf.resize(off + len, 0);
+        }
+        for i in 0..len {
+            buf[off + i] = (value >> (8 * i)) as u8;
+        }
+    }
+
+    /* an executable with one segment of 8 bytes code and 8 bytes .bss at
+     * 0x80000000 and a .riscv.attributes section */
+    fn build(rv64: bool) -> Vec<u8> {
+        let w = if rv64 { 8 } else { 4 };
+        let ehsize = 16 + 2 + 2 + 4 + 3 * w + 4 + 6 * 2;
+        let phentsize = if rv64 { 56 } else { 32 };
+        let shentsize = 10 * w;
+        let code = ehsize + phentsize;
+        let attrs = code + 8;
+
+        let arch = b"rv64i2p1_m2p0_c2p0\0";
+        let mut section = vec![b'A'];
+        let sublen = 4 + 6 + 1 + 4 + 3 + arch.len();
+        put(&mut section, 1, sublen as u64, 4);
+        section.extend_from_slice(b"riscv\0");
+        section.push(1);
+        put(&mut section, 12, (1 + 4 + 3 + arch.len()) as u64, 4);
+        section.extend_from_slice(&[4, 16, 5]);
+        section.extend_from_slice(arch);
+        let shoff = attrs + section.len();
+
+        let mut buf = vec![0x7F, b'E', b'L', b'F', if rv64 { 2 } else { 1 }, 1, 1];
+        put(&mut buf, 16, 2, 2);
+        put(&mut buf, 18, 243, 2);
+        put(&mut buf, 20, 1, 4);
+        put(&mut buf, 24, 0x80000004, w);
+        put(&mut buf, 24 + w, ehsize as u64, w);
+        put(&mut buf, 24 + 2 * w, shoff as u64, w);
+        put(&mut buf, 24 + 3 * w, 0x0005, 4);
+        put(&mut buf, 24 + 3 * w + 4, ehsize as u64, 2);
+        put(&mut buf, 24 + 3 * w + 6, phentsize as u64, 2);
+        put(&mut buf, 24 + 3 * w + 8, 1, 2);
+        put(&mut buf, 24 + 3 * w + 10, shentsize as u64, 2);
+        put(&mut buf, 24 + 3 * w + 12, 2, 2);
+
+        put(&mut buf, ehsize, 1, 4);
+        if rv64 {
+            put(&mut buf, ehsize + 4, 5, 4);
+            put(&mut buf, ehsize + 8, code as u64, 8);
+            put(&mut buf, ehsize + 16, 0x80000000, 8);
+            put(&mut buf, ehsize + 24, 0x80000000, 8);
+            put(&mut buf, ehsize + 32, 8, 8);
+            put(&mut buf, ehsize + 40, 16, 8);
+        } else {
+            put(&mut buf, ehsize + 4, code as u64, 4);
+            put(&mut buf, ehsize + 8, 0x80000000, 4);
+            put(&mut buf, ehsize + 12, 0x80000000, 4);
+            put(&mut buf, ehsize + 16, 8, 4);
+            put(&mut buf, ehsize + 20, 16, 4);
+            put(&mut buf, ehsize + 24, 5, 4);
+        }
+        /* addi a0, zero, 1; ebreak */
+        put(&mut buf, code, 0x00100513, 4);
+        put(&mut buf, code + 4, 0x00100073, 4);
+        buf.extend_from_slice(&section);
+
+        /* a null section header followed by .riscv.attributes */
+        buf.resize(shoff + 2 * shentsize, 0);
+        let sh = shoff + shentsize;
+        put(&mut buf, sh + 4, 0x70000003, 4);
+        put(&mut buf, sh + 8 + 2 * w, attrs as u64, w);
+        put(&mut buf, sh + 8 + 3 * w, section.len() as u64, w);
+        buf
+    }
+
+    #[test]
+    fn test_parse() {
+        for rv64 in [false, true].iter() {
+            let info = parse(&build(*rv64)).unwrap();
+            assert_eq!(info.rv64, *rv64);
+            assert_eq!(info.entry, 0x80000004);
+            assert_eq!(info.flags, 0x0005);
+            assert_eq!(info.isa, Some("rv64i2p1_m2p0_c2p0".to_string()));
+            assert_eq!(info.segments, vec![Segment {
+                addr: 0x80000000, offset: if *rv64 { 120 } else { 84 }, filesz: 8, memsz: 16, flags: 5
+            }]);
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let mut ram = Ram::new(0x80000000, 0x20);
+        ram.write_bytes(0x80000000, &[0xFF; 0x20]).unwrap();
+        let mut core = Core::new(0, false);
+        load(&build(false), &mut ram, &mut core).unwrap();
+        assert_eq!(core.pc, 0x80000004);
+        assert_eq!(ram.load32(0x80000000), Ok(0x00100513));
+        assert_eq!(ram.load32(0x80000004), Ok(0x00100073));
+        assert_eq!(ram.load32(0x80000008), Ok(0));
+        assert_eq!(ram.load32(0x8000000C), Ok(0));
+        assert_eq!(ram.load32(0x80000010), Ok(0xFFFFFFFF));
+
+        let mut small = Ram::new(0x80000000, 12);
+        assert_eq!(load(&build(false), &mut small, &mut core), Err(ElfError::AccessFault {addr: 0x8000000C}));
+
+        let mut core = Core::new(0, true);
+        assert_eq!(load(&build(false), &mut ram, &mut core), Err(ElfError::ClassMismatch {rv64: false}));
+    }
+
+    #[test]
+    fn test_errors() {
+        let elf = build(false);
+        assert_eq!(parse(&elf[..10]), Err(ElfError::Truncated));
+        assert_eq!(parse(&elf[..40]), Err(ElfError::Truncated));
+
+        let mut bad = elf.clone();
+        bad[1] = b'e';
+        assert_eq!(parse(&bad), Err(ElfError::BadMagic));
+
+        let mut bad = elf.clone();
+        bad[4] = 3;
+        assert_eq!(parse(&bad), Err(ElfError::UnsupportedClass {class: 3}));
+
+        let mut bad = elf.clone();
+        bad[5] = 2;
+        assert_eq!(parse(&bad), Err(ElfError::UnsupportedEncoding {data: 2}));
+
+        let mut bad = elf.clone();
+        bad[18] = 62;
+        assert_eq!(parse(&bad), Err(ElfError::WrongMachine {machine: 62}));
+
+        let mut bad = elf.clone();
+        bad[16] = 1;
+        assert_eq!(parse(&bad), Err(ElfError::UnsupportedType {kind: 1}));
+
+        /* header tables at the very end of the address space */
+        for field in [28, 32].iter() {
+            let mut bad = elf.clone();
+            put(&mut bad, *field, 0xFFFF_FFFF, 4);
+            assert_eq!(parse(&bad), Err(ElfError::Truncated));
+        }
+        let mut bad = build(true);
+        put(&mut bad, 40, 0xFFFF_FFFF_FFFF_FFFC, 8);
+        assert_eq!(parse(&bad), Err(ElfError::Truncated));
+    }
+}
@@ -0,0 +1,807 @@
+/*
+ * gdb.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* A stub for the GDB remote serial protocol. GDB connects over TCP or a
+ * Unix socket and can read and write registers and memory, set software
+ * breakpoints, which are EBREAKs patched into memory while the target runs,
+ * hardware breakpoints and watchpoints, and step or continue the core. */
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use arch::core::{Commit, Core, Exception};
+use arch::csr::{misa, Privilege, FCSR, FFLAGS, FRM, SATP};
+use arch::disassembler::{CSR_NAMES, csr_name, fp_reg_name, reg_name};
+use arch::memory::{AccessFault, Memory};
+use syscall::Proxy;
+
+const PACKET_SIZE : usize = 4096;
+/* instructions between two checks for a ^C of GDB */
+const POLL_INTERVAL : u64 = 4096;
+
+/* GDB numbers the CSRs after the 32 floating-point registers */
+const PC_REGNUM : usize = 32;
+const FIRST_FP_REGNUM : usize = 33;
+const FIRST_CSR_REGNUM : usize = 65;
+const PRIV_REGNUM : usize = FIRST_CSR_REGNUM + 4096;
+
+const SIGINT : u8 = 2;
+const SIGILL : u8 = 4;
+const SIGTRAP : u8 = 5;
+const SIGBUS : u8 = 10;
+const SIGSEGV : u8 = 11;
+
+const EBREAK : u32 = 0x0010_0073;
+const C_EBREAK : u16 = 0x9002;
+
+/* a byte stream to GDB that can be polled for an interrupt request */
+pub trait Connection: Read + Write {
+    /* returns true if GDB sent a ^C, must not block */
+    fn interrupted(&mut self) -> io::Result<bool>;
+}
+
+/* reads a single byte without blocking */
+macro_rules! poll_byte {
+    ($stream:expr) => {{
+        $stream.set_nonblocking(true)?;
+        let mut byte = [0];
+        let result = $stream.read(&mut byte);
+        $stream.set_nonblocking(false)?;
+        match result {
+            Ok(1) => Ok(byte[0] == 0x03),
+            Ok(_) => Ok(false),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }};
+}
+
+impl Connection for TcpStream {
+    fn interrupted(&mut self) -> io::Result<bool> {
+        poll_byte!(self)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for UnixStream {
+    fn interrupted(&mut self) -> io::Result<bool> {
+        poll_byte!(self)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum WatchKind {
+    Write,
+    Read,
+    Access,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+struct Watchpoint {
+    kind : WatchKind,
+    addr : u64,
+    len  : u64,
+}
+
+/* a software breakpoint and the instruction it replaces */
+#[derive(PartialEq, Debug, Clone, Copy)]
+struct Breakpoint {
+    addr     : u64,
+    len      : u64,
+    original : u32,
+}
+
+/* why the target stopped */
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Stop {
+    Signal(u8),
+    Watch(WatchKind, u64),
+    Exited(i32),
+}
+
+/* The first data access of a retired instruction that hits a watchpoint.
+ * The accesses are taken from the commit log, which only holds the loads
+ * and stores of the program, not the fetches or the page walks. */
+fn watch_hit(points: &[Watchpoint], commit: &Commit) -> Option<(WatchKind, u64)> {
+    let loads = commit.loads.iter().map(|&(addr, len)| (addr, len, false));
+    let stores = commit.stores.iter().map(|&(addr, len, _)| (addr, len, true));
+    for (addr, len, write) in loads.chain(stores) {
+        for point in points {
+            let kind = match point.kind {
+                WatchKind::Write if write => WatchKind::Write,
+                WatchKind::Read if !write => WatchKind::Read,
+                WatchKind::Access => WatchKind::Access,
+                _ => continue
+            };
+            if addr < point.addr.wrapping_add(point.len) && point.addr < addr.wrapping_add(len) {
+                return Some((kind, point.addr));
+            }
+        }
+    }
+    None
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+fn hex_bytes(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex(text: &str) -> Option<u64> {
+    u64::from_str_radix(text, 16).ok()
+}
+
+fn parse_hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/* escapes the bytes that have a meaning in the packet framing */
+fn escape(data: &str) -> String {
+    let mut escaped = String::with_capacity(data.len());
+    for c in data.chars() {
+        if matches!(c, '$' | '#' | '}' | '*') {
+            escaped.push('}');
+            escaped.push(((c as u8) ^ 0x20) as char);
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/* the CSRs that GDB expects in the floating-point feature */
+const FP_CSRS : [u16; 3] = [FFLAGS, FRM, FCSR];
+
+/* the size of the floating-point registers in bytes, None without F */
+fn flen(core: &Core) -> Option<usize> {
+    if core.csr.has(misa::D) {
+        Some(8)
+    } else if core.csr.has(misa::F) {
+        Some(4)
+    } else {
+        None
+    }
+}
+
+/* the registers of the g packet in the order of their numbers */
+fn g_registers(core: &Core) -> Vec<usize> {
+    let mut regs: Vec<usize> = (0..=PC_REGNUM).collect();
+    if flen(core).is_some() {
+        regs.extend(FIRST_FP_REGNUM..FIRST_CSR_REGNUM);
+        regs.extend(FP_CSRS.iter().map(|&csr| FIRST_CSR_REGNUM + csr as usize));
+    }
+    regs
+}
+
+/* the target description with the integer registers, the pc, the
+ * floating-point registers and the CSRs of the hart */
+fn target_xml(core: &Core) -> String {
+    let xlen = if core.rv64() { 64 } else { 32 };
+    let mut xml = String::from("<?xml version=\"1.0\"?>\n<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n<target version=\"1.0\">\n");
+    xml += &format!("<architecture>riscv:rv{}</architecture>\n", xlen);
+    xml += "<feature name=\"org.gnu.gdb.riscv.cpu\">\n";
+    for i in 0..32 {
+        let kind = match i {
+            1 => "code_ptr",
+            2 | 8 => "data_ptr",
+            _ => "int",
+        };
+        xml += &format!("<reg name=\"{}\" bitsize=\"{}\" type=\"{}\" regnum=\"{}\"/>\n", reg_name(i, true), xlen, kind, i);
+    }
+    xml += &format!("<reg name=\"pc\" bitsize=\"{}\" type=\"code_ptr\" regnum=\"{}\"/>\n", xlen, PC_REGNUM);
+    if let Some(flen) = flen(core) {
+        let kind = if flen == 8 { "ieee_double" } else { "ieee_single" };
+        xml += "</feature>\n<feature name=\"org.gnu.gdb.riscv.fpu\">\n";
+        for i in 0..32 {
+            xml += &format!("<reg name=\"{}\" bitsize=\"{}\" type=\"{}\" regnum=\"{}\"/>\n",
+                            fp_reg_name(i, true), 8 * flen, kind, FIRST_FP_REGNUM + i as usize);
+        }
+        for &csr in FP_CSRS.iter() {
+            xml += &format!("<reg name=\"{}\" bitsize=\"{}\" regnum=\"{}\"/>\n", csr_name(csr), xlen, FIRST_CSR_REGNUM + csr as usize);
+        }
+    }
+    xml += "</feature>\n<feature name=\"org.gnu.gdb.riscv.csr\">\n";
+    for &(name, csr) in CSR_NAMES.iter() {
+        if !FP_CSRS.contains(&csr) && core.csr.read(csr).is_some() {
+            xml += &format!("<reg name=\"{}\" bitsize=\"{}\" regnum=\"{}\"/>\n", name, xlen, FIRST_CSR_REGNUM + csr as usize);
+        }
+    }
+    xml += "</feature>\n<feature name=\"org.gnu.gdb.riscv.virtual\">\n";
+    xml += &format!("<reg name=\"priv\" bitsize=\"{}\" regnum=\"{}\"/>\n", xlen, PRIV_REGNUM);
+    xml += "</feature>\n</target>\n";
+    xml
+}
+
+pub struct GdbStub<C: Connection> {
+    conn        : C,
+    no_ack      : bool,
+    last        : Vec<u8>,
+    breakpoints : Vec<Breakpoint>,
+    hardware    : Vec<u64>,
+    watchpoints : Vec<Watchpoint>,
+}
+
+/* accepts a single connection from GDB */
+pub fn accept_tcp(addr: &str) -> io::Result<TcpStream> {
+    let listener = ::std::net::TcpListener::bind(addr)?;
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+#[cfg(unix)]
+pub fn accept_unix(path: &str) -> io::Result<UnixStream> {
+    let listener = ::std::os::unix::net::UnixListener::bind(path)?;
+    let (stream, _) = listener.accept()?;
+    Ok(stream)
+}
+
+impl<C: Connection> GdbStub<C> {
+    pub fn new(conn: C) -> GdbStub<C> {
+        GdbStub {conn: conn, no_ack: false, last: Vec::new(), breakpoints: Vec::new(),
+                 hardware: Vec::new(), watchpoints: Vec::new()}
+    }
+
+    /* Handles packets until GDB detaches, kills the target or closes the
+     * connection. With a proxy the program runs in user mode with emulated
+     * system calls and the exit code is returned when it exits. */
+    pub fn serve<M: Memory + ?Sized>(&mut self, core: &mut Core, mem: &mut M, mut proxy: Option<&mut Proxy>)
+        -> io::Result<Option<i32>> {
+        while let Some(packet) = self.receive()? {
+            let reply = match packet.chars().next() {
+                Some('?') => "S05".to_string(),
+                Some('g') => self.read_registers(core),
+                Some('G') => self.write_registers(core, &packet[1..]),
+                Some('p') => self.read_register(core, &packet[1..]),
+                Some('P') => self.write_register(core, &packet[1..]),
+                Some('m') => self.read_memory(mem, &packet[1..]),
+                Some('M') => self.write_memory(mem, &packet[1..]),
+                Some('Z') => self.insert_point(mem, &packet[1..]),
+                Some('z') => self.remove_point(&packet[1..]),
+                Some(c) if c == 'c' || c == 's' => {
+                    if let Some(addr) = parse_hex(&packet[1..]) {
+                        core.pc = addr & core.csr.xlen_mask();
+                    }
+                    match self.resume(core, mem, proxy.as_deref_mut(), c == 's')? {
+                        Stop::Exited(code) => {
+                            self.send(&format!("W{:02x}", code as u8))?;
+                            return Ok(Some(code));
+                        },
+                        Stop::Signal(signal) => format!("S{:02x}", signal),
+                        Stop::Watch(kind, addr) => {
+                            let name = match kind {
+                                WatchKind::Write => "watch",
+                                WatchKind::Read => "rwatch",
+                                WatchKind::Access => "awatch",
+                            };
+                            format!("T{:02x}{}:{:x};", SIGTRAP, name, addr)
+                        },
+                    }
+                },
+                Some('k') => return Ok(None),
+                Some('D') => {
+                    self.send("OK")?;
+                    return Ok(None);
+                },
+                Some('H') => "OK".to_string(),
+                _ => self.query(core, &packet),
+            };
+            self.send(&reply)?;
+        }
+        Ok(None)
+    }
+
+    /* the general queries, unsupported packets get an empty reply */
+    fn query(&mut self, core: &Core, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return format!("PacketSize={:x};qXfer:features:read+;QStartNoAckMode+;swbreak+;hwbreak+", PACKET_SIZE);
+        }
+        if packet == "QStartNoAckMode" {
+            /* the acknowledgment of this packet is still sent */
+            self.no_ack = true;
+            return "OK".to_string();
+        }
+        if let Some(args) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let mut parts = args.split(',');
+            let (offset, len) = match (parts.next().and_then(parse_hex), parts.next().and_then(parse_hex)) {
+                (Some(offset), Some(len)) => (offset as usize, len as usize),
+                _ => return "E01".to_string()
+            };
+            let xml = target_xml(core);
+            let start = offset.min(xml.len());
+            let end = start.saturating_add(len).min(xml.len());
+            let marker = if end == xml.len() { 'l' } else { 'm' };
+            return format!("{}{}", marker, escape(&xml[start..end]));
+        }
+        match packet {
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new()
+        }
+    }
+
+    /* the size of a register in bytes */
+    fn reg_size(&self, core: &Core, r: usize) -> usize {
+        match flen(core) {
+            Some(flen) if (FIRST_FP_REGNUM..FIRST_CSR_REGNUM).contains(&r) => flen,
+            _ => if core.rv64() { 8 } else { 4 },
+        }
+    }
+
+    fn reg_hex(&self, core: &Core, r: usize, value: u64) -> String {
+        hex_bytes(&value.to_le_bytes()[..self.reg_size(core, r)])
+    }
+
+    fn get_register(&self, core: &Core, r: usize) -> Option<u64> {
+        Some(match r {
+            0..=31 => core.reg[r],
+            PC_REGNUM => core.pc,
+            FIRST_FP_REGNUM..FIRST_CSR_REGNUM if flen(core).is_some() => core.freg[r - FIRST_FP_REGNUM],
+            PRIV_REGNUM => core.csr.privilege as u64,
+            FIRST_CSR_REGNUM..PRIV_REGNUM => core.csr.read((r - FIRST_CSR_REGNUM) as u16)?,
+            _ => return None
+        })
+    }
+
+    fn read_registers(&self, core: &Core) -> String {
+        g_registers(core).into_iter()
+            .map(|r| self.reg_hex(core, r, self.get_register(core, r).unwrap_or(0)))
+            .collect()
+    }
+
+    fn write_registers(&self, core: &mut Core, data: &str) -> String {
+        let mut offset = 0;
+        for r in g_registers(core) {
+            let len = 2 * self.reg_size(core, r);
+            match data.get(offset..offset + len).and_then(parse_hex_bytes) {
+                Some(bytes) => self.set_register(core, r, &bytes),
+                None => return "E01".to_string()
+            };
+            offset += len;
+        }
+        "OK".to_string()
+    }
+
+    fn read_register(&self, core: &Core, data: &str) -> String {
+        match parse_hex(data).map(|r| r as usize) {
+            Some(r) => match self.get_register(core, r) {
+                Some(value) => self.reg_hex(core, r, value),
+                None => "E01".to_string()
+            },
+            None => "E01".to_string()
+        }
+    }
+
+    fn write_register(&self, core: &mut Core, data: &str) -> String {
+        let mut parts = data.splitn(2, '=');
+        match (parts.next().and_then(parse_hex), parts.next().and_then(parse_hex_bytes)) {
+            (Some(r), Some(bytes)) if self.set_register(core, r as usize, &bytes) => "OK".to_string(),
+            _ => "E01".to_string()
+        }
+    }
+
+    /* sets a register from its little-endian bytes, singles are NaN-boxed */
+    fn set_register(&self, core: &mut Core, r: usize, bytes: &[u8]) -> bool {
+        let raw = bytes.iter().rev().fold(0, |v, &b| (v << 8) | b as u64);
+        let value = raw & core.csr.xlen_mask();
+        match r {
+            0 => (),
+            1..=31 => core.reg[r] = value,
+            PC_REGNUM => core.pc = value,
+            FIRST_FP_REGNUM..FIRST_CSR_REGNUM if flen(core).is_some() => {
+                core.freg[r - FIRST_FP_REGNUM] = if flen(core) == Some(4) { raw | 0xFFFF_FFFF_0000_0000 } else { raw };
+            },
+            PRIV_REGNUM => match Privilege::from_bits(value) {
+                Some(privilege) => core.csr.privilege = privilege,
+                None => return false
+            },
+            FIRST_CSR_REGNUM..PRIV_REGNUM => {
+                let csr = (r - FIRST_CSR_REGNUM) as u16;
+                if core.csr.read(csr).is_none() {
+                    return false;
+                }
+                core.csr.write(csr, value);
+                if csr == SATP {
+                    core.mmu.flush(None, None);
+                }
+            },
+            _ => return false
+        }
+        true
+    }
+
+    fn read_memory<M: Memory + ?Sized>(&self, mem: &mut M, args: &str) -> String {
+        let mut parts = args.split(',');
+        let (addr, len) = match (parts.next().and_then(parse_hex), parts.next().and_then(parse_hex)) {
+            (Some(addr), Some(len)) => (addr, len.min(PACKET_SIZE as u64 / 2)),
+            _ => return "E01".to_string()
+        };
+        let data: Result<Vec<u8>, AccessFault> = (0..len).map(|i| mem.load8(addr.wrapping_add(i))).collect();
+        match data {
+            Ok(data) => hex_bytes(&data),
+            Err(_) => "E14".to_string()
+        }
+    }
+
+    fn write_memory<M: Memory + ?Sized>(&self, mem: &mut M, args: &str) -> String {
+        let mut parts = args.splitn(2, ':');
+        let mut range = parts.next().unwrap_or("").split(',');
+        let addr = range.next().and_then(parse_hex);
+        match (addr, parts.next().and_then(parse_hex_bytes)) {
+            (Some(addr), Some(data)) => match mem.write_bytes(addr, &data) {
+                Ok(()) => "OK".to_string(),
+                Err(_) => "E14".to_string()
+            },
+            _ => "E01".to_string()
+        }
+    }
+
+    /* Z0 software breakpoint, Z1 hardware breakpoint, Z2 write, Z3 read and
+     * Z4 access watchpoint. The kind of a breakpoint is its size. */
+    fn insert_point<M: Memory + ?Sized>(&mut self, mem: &mut M, args: &str) -> String {
+        let fields: Vec<Option<u64>> = args.split(',').map(parse_hex).collect();
+        let (kind, addr, len) = match fields[..] {
+            [Some(kind), Some(addr), Some(len)] => (kind, addr, len),
+            _ => return "E01".to_string()
+        };
+        match kind {
+            0 => {
+                let original = match len {
+                    2 => mem.load16(addr).map(|i| i as u32),
+                    4 => mem.load32(addr),
+                    _ => return "E01".to_string()
+                };
+                match original {
+                    Ok(original) => {
+                        self.breakpoints.retain(|b| b.addr != addr);
+                        self.breakpoints.push(Breakpoint {addr: addr, len: len, original: original});
+                    },
+                    Err(_) => return "E14".to_string()
+                }
+            },
+            1 => self.hardware.push(addr),
+            2..=4 => {
+                let kind = match kind {
+                    2 => WatchKind::Write,
+                    3 => WatchKind::Read,
+                    _ => WatchKind::Access,
+                };
+                self.watchpoints.push(Watchpoint {kind: kind, addr: addr, len: len});
+            },
+            _ => return String::new()
+        }
+        "OK".to_string()
+    }
+
+    fn remove_point(&mut self, args: &str) -> String {
+        let fields: Vec<Option<u64>> = args.split(',').map(parse_hex).collect();
+        let (kind, addr) = match fields[..] {
+            [Some(kind), Some(addr), _] => (kind, addr),
+            _ => return "E01".to_string()
+        };
+        match kind {
+            0 => self.breakpoints.retain(|b| b.addr != addr),
+            1 => self.hardware.retain(|&a| a != addr),
+            2..=4 => self.watchpoints.retain(|w| w.addr != addr),
+            _ => return String::new()
+        }
+        "OK".to_string()
+    }
+
+    /* patches EBREAKs over the breakpoints or restores the instructions */
+    fn patch_breakpoints<M: Memory + ?Sized>(&self, mem: &mut M, insert: bool) {
+        for b in &self.breakpoints {
+            /* the breakpoint was readable, so a write can only fail for ROM */
+            let _ = match (b.len, insert) {
+                (2, true) => mem.store16(b.addr, C_EBREAK),
+                (2, false) => mem.store16(b.addr, b.original as u16),
+                (_, true) => mem.store32(b.addr, EBREAK),
+                (_, false) => mem.store32(b.addr, b.original),
+            };
+        }
+    }
+
+    /* Runs until a breakpoint, a watchpoint, an interrupt from GDB or the
+     * exit of the program, or for a single instruction. The instruction at
+     * pc is executed before the breakpoints are inserted, so the target can
+     * continue from a breakpoint. */
+    fn resume<M: Memory + ?Sized>(&mut self, core: &mut Core, mem: &mut M, mut proxy: Option<&mut Proxy>, step: bool)
+        -> io::Result<Stop> {
+        /* the watchpoints are checked against the commit log */
+        let logging = core.log.is_some();
+        if !logging {
+            core.log = Some(Commit::new());
+        }
+        let mut count = 0;
+        let stop = loop {
+            if count > 0 && self.hardware.contains(&core.pc) {
+                break Stop::Signal(SIGTRAP);
+            }
+            if count == 1 {
+                self.patch_breakpoints(mem, true);
+            }
+
+            let result = match proxy {
+                Some(ref mut proxy) => proxy.step(core, mem).map(|exit| exit.map(Stop::Exited)),
+                None if core.take_interrupt() => Ok(None),
+                None => match core.try_step(mem) {
+                    Err(Exception::Breakpoint(addr)) => Err(Exception::Breakpoint(addr)),
+                    Err(e) => {
+                        core.trap(e);
+                        Ok(None)
+                    },
+                    Ok(()) => Ok(None)
+                },
+            };
+            let hit = core.log.as_ref().filter(|log| log.retired).and_then(|log| watch_hit(&self.watchpoints, log));
+
+            match result {
+                Ok(Some(stop)) => break stop,
+                Err(Exception::Breakpoint(_)) => break Stop::Signal(SIGTRAP),
+                Err(Exception::IllegalInstruction(_)) => break Stop::Signal(SIGILL),
+                Err(Exception::LoadAddressMisaligned(_)) |
+                Err(Exception::StoreAddressMisaligned(_)) => break Stop::Signal(SIGBUS),
+                Err(_) => break Stop::Signal(SIGSEGV),
+                Ok(None) => ()
+            }
+            if let Some((kind, addr)) = hit {
+                break Stop::Watch(kind, addr);
+            }
+            if step {
+                break Stop::Signal(SIGTRAP);
+            }
+            count += 1;
+            if count % POLL_INTERVAL == 0 && self.conn.interrupted()? {
+                break Stop::Signal(SIGINT);
+            }
+        };
+        if count >= 1 {
+            self.patch_breakpoints(mem, false);
+        }
+        if !logging {
+            core.log = None;
+        }
+        Ok(stop)
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.conn.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0]))
+        }
+    }
+
+    /* Receives the next packet, None if the connection is closed. Packets
+     * with a wrong checksum are rejected and GDB sends them again. */
+    fn receive(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => (),
+                /* GDB asks for the last reply again */
+                Some(b'-') if !self.last.is_empty() => {
+                    let last = self.last.clone();
+                    self.conn.write_all(&last)?;
+                    continue;
+                },
+                Some(_) => continue
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+            let mut sum = [0; 2];
+            self.conn.read_exact(&mut sum)?;
+            let valid = ::std::str::from_utf8(&sum).ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok()) == Some(checksum(&data));
+            if !self.no_ack {
+                self.conn.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+        self.conn.write_all(packet.as_bytes())?;
+        self.conn.flush()?;
+        self.last = packet.into_bytes();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Cursor, Read, Write};
+
+    use super::{Connection, GdbStub, checksum, target_xml};
+    use arch::assembler::assemble_into;
+    use arch::core::Core;
+    use arch::csr::{misa, FCSR};
+    use arch::memory::{Memory, Ram};
+    use syscall::{Proxy, FdTable, FilePolicy};
+
+    /* replays the packets of GDB and records the replies */
+    struct Script {
+        input     : Cursor<Vec<u8>>,
+        output    : Vec<u8>,
+        interrupt : bool,
+    }
+
+    impl Read for Script {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Script {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Connection for Script {
+        fn interrupted(&mut self) -> io::Result<bool> {
+            Ok(self.interrupt)
+        }
+    }
+
+    fn script(packets: &[&str], interrupt: bool) -> Script {
+        let mut input = b"+".to_vec();
+        for packet in packets {
+            input.extend(format!("${}#{:02x}", packet, checksum(packet.as_bytes())).bytes());
+        }
+        Script {input: Cursor::new(input), output: Vec::new(), interrupt: interrupt}
+    }
+
+    /* the payloads of the replies without the acknowledgments */
+    fn replies(output: &[u8]) -> Vec<String> {
+        let text = String::from_utf8_lossy(output);
+        text.split('$').skip(1).map(|p| p.rsplit_once('#').unwrap().0.to_string()).collect()
+    }
+
+    fn program(source: &str) -> Ram {
+        let mut ram = Ram::new(0, 0x2000);
+        assemble_into(&mut ram, 0, source, true);
+        ram
+    }
+
+    #[test]
+    fn test_session() {
+        let mut ram = program("
+            li a0, 5
+            lui t0, 1
+            sd a0, 0(t0)
+            addi a0, a0, 1
+            li a7, 93
+            ecall
+        ");
+        let mut core = Core::new(0, true);
+        let mut proxy = Proxy::new(FdTable::new(FilePolicy::Deny), 0x2000, 0x2000);
+        let mut stub = GdbStub::new(script(&["qSupported:swbreak+", "QStartNoAckMode", "?", "p20", "Z2,1000,8", "Z0,10,4",
+                                             "c", "z2,1000,8", "m10,4", "c", "p20", "pa", "m1000,8", "M1000,1:ff",
+                                             "Pa=2a00000000000000", "s", "p20", "c"], false));
+        assert_eq!(stub.serve(&mut core, &mut ram, Some(&mut proxy)).unwrap(), Some(42));
+
+        let output = stub.conn.output.clone();
+        assert!(output.starts_with(b"+$PacketSize"));
+        /* only the first two packets are acknowledged */
+        assert_eq!(output.windows(2).filter(|w| w == b"+$").count(), 2);
+        assert_eq!(replies(&output)[1..], ["OK", "S05", "0000000000000000", "OK", "OK", "T05watch:1000;",
+                                           "OK", "9308d005", "S05", "1000000000000000", "0600000000000000",
+                                           "0500000000000000", "OK", "OK", "S05", "1400000000000000", "W2a"]);
+        assert_eq!(ram.load32(16), Ok(0x05D00893));
+        assert_eq!(ram.load8(0x1000), Ok(0xFF));
+    }
+
+    /* fetches do not hit watchpoints, only the loads and stores do */
+    #[test]
+    fn test_watch_data_only() {
+        let mut ram = program("
+            lui t0, 1
+            ld a0, 0(t0)
+            li a0, 7
+            li a7, 93
+            ecall
+        ");
+        let mut core = Core::new(0, true);
+        let mut proxy = Proxy::new(FdTable::new(FilePolicy::Deny), 0x2000, 0x2000);
+        let mut stub = GdbStub::new(script(&["QStartNoAckMode", "Z4,0,20", "Z3,1000,8", "c", "p20", "c"], false));
+        assert_eq!(stub.serve(&mut core, &mut ram, Some(&mut proxy)).unwrap(), Some(7));
+        assert_eq!(replies(&stub.conn.output)[1..], ["OK", "OK", "T05rwatch:1000;", "0800000000000000", "W07"]);
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut ram = program("
+            ebreak
+            j .
+        ");
+        let mut core = Core::new(0, false);
+        core.reg[1] = 0x12345678;
+        let g = format!("00000000{}{}", "78563412", "00000000".repeat(31));
+        let mut stub = GdbStub::new(script(&["g", &format!("G{}", "01000000".repeat(33)), "p20", "p1", "p1041",
+                                             "P1041=00000000", "p1041", "p382", "p7c0", "P20=00000000", "c", "P20=04000000",
+                                             "Z1,4,2", "c", "c", "z1,4,2", "c",
+                                             "qXfer:features:read:target.xml:0,20", "k"], true));
+        assert_eq!(stub.serve(&mut core, &mut ram, None).unwrap(), None);
+        assert_eq!(core.csr.privilege as u64, 0);
+
+        let replies = replies(&stub.conn.output);
+        assert_eq!(replies[..13], [g.as_str(), "OK", "01000000", "01000000", "03000000", "OK", "00000000",
+                                   "00000000", "E01", "OK", "S05", "OK", "OK"]);
+        /* the hardware breakpoint is skipped when continuing from it */
+        assert_eq!(replies[13..17], ["S05", "S05", "OK", "S02"]);
+        assert_eq!(replies[17], "m<?xml version=\"1.0\"?>\n<!DOCTYPE ");
+    }
+
+    #[test]
+    fn test_fp_registers() {
+        let mut ram = program("ebreak");
+        let mut core = Core::new(0, true);
+        core.csr.misa |= misa::F | misa::D;
+        core.freg[1] = 0x400921FB54442D18;
+        core.csr.write(FCSR, 0x41);
+        let xml = target_xml(&core);
+        assert!(xml.contains("<feature name=\"org.gnu.gdb.riscv.fpu\">\n<reg name=\"ft0\" bitsize=\"64\" type=\"ieee_double\" regnum=\"33\"/>"));
+        assert_eq!(xml.matches("name=\"fcsr\"").count(), 1);
+
+        /* x0 to x31, pc, f0 to f31, fflags, frm and fcsr */
+        let g = format!("{}182d4454fb210940{}010000000000000002000000000000004100000000000000",
+                        "0000000000000000".repeat(34), "0000000000000000".repeat(30));
+        let mut stub = GdbStub::new(script(&["g", "p22", "p44", "P21=0000000000000840", "P44=e000000000000000",
+                                             &format!("G{}", "0100000000000000".repeat(68)), "k"], false));
+        assert_eq!(stub.serve(&mut core, &mut ram, None).unwrap(), None);
+        assert_eq!(replies(&stub.conn.output), [g.as_str(), "182d4454fb210940", "4100000000000000", "OK", "OK", "OK"]);
+        assert_eq!(core.freg[0], 1);
+        assert_eq!(core.csr.read(FCSR), Some(1));
+
+        /* singles are NaN-boxed */
+        let mut core = Core::new(0, false);
+        core.csr.misa |= misa::F;
+        let mut stub = GdbStub::new(script(&["P21=0000803f", "p21", "p3f", "k"], false));
+        assert_eq!(stub.serve(&mut core, &mut ram, None).unwrap(), None);
+        assert_eq!(replies(&stub.conn.output), ["OK", "0000803f", "00000000"]);
+        assert_eq!(core.freg[0], 0xFFFF_FFFF_3F80_0000);
+        assert!(target_xml(&core).contains("bitsize=\"32\" type=\"ieee_single\""));
+    }
+}
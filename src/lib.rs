@@ -1,4 +1,12 @@
+#![allow(clippy::redundant_field_names)]
+#![allow(clippy::identity_op)]
+
 pub mod arch;
+pub mod elf;
+pub mod gdb;
+pub mod syscall;
+pub mod trace;
+pub mod uart;
 
 #[cfg(test)]
 mod tests {
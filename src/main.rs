@@ -0,0 +1,507 @@
+/*
+ * main.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* The ruscy-v simulator. Loads an ELF executable or a raw binary and runs
+ * it on a single hart with emulated Linux system calls, or bare on a
+ * machine with one or more harts. */
+
+extern crate ruscy_v;
+
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io::{self, LineWriter};
+use std::process;
+use std::rc::Rc;
+
+use ruscy_v::arch::core::{Commit, Core, Exception, DEFAULT_GRANULE};
+use ruscy_v::arch::csr::misa;
+use ruscy_v::arch::disassembler::reg_name;
+use ruscy_v::arch::machine::{Machine, Schedule};
+use ruscy_v::arch::memory::{AccessFault, Bus, Memory};
+use ruscy_v::elf::{self, ElfInfo};
+use ruscy_v::gdb::{self, GdbStub};
+use ruscy_v::syscall::{FdTable, FilePolicy, Proxy};
+use ruscy_v::trace::{Disassembly, SpikeLog, Tracer};
+use ruscy_v::uart::{Backend, Stream, Uart, UART_BASE, UART_IRQ, UART_SIZE};
+
+const USAGE : &str = "usage: ruscy-v [options] <file> [args...]
+
+Runs a RISC-V ELF executable or raw binary.
+
+options:
+  --isa <isa>       ISA of the hart, e.g. rv32imafdc or rv64gc. The default
+                    is taken from the ELF file or is rv64gc.
+  --base <addr>     load address and entry point of a raw binary
+                    (default 0x80000000)
+  --limit <n>       stop after n instructions
+  --granule <n>     LR/SC reservation granule in bytes, a power of two of
+                    at least 8 (default 64)
+  --trace           print every executed instruction to stderr
+  --log-commits     print a commit log in the format of Spike to stderr
+  --bare            run without system call emulation, exceptions enter the
+                    trap handler of the program and EBREAK ends the run
+                    with the exit code in a0. The CLINT is mapped at
+                    0x2000000, the PLIC at 0xC000000, the UART at
+                    0x10000000 with interrupt 10, RAM everywhere else.
+  --harts <n>       number of harts sharing the memory, needs --bare
+                    (default 1)
+  --quantum <n>     instructions a hart runs before the next one takes
+                    over (default 1)
+  --seed <n>        interleave the harts randomly with quanta of up to
+                    --quantum instructions, reproducible for each seed
+  --uart <backend>  connects the UART in bare mode to stdio, unix:<path>
+                    or none (default stdio)
+  --sandbox <dir>   only allow the program to open files below dir
+  --gdb <addr>      wait for GDB on a TCP host:port or a Unix socket path,
+                    not with --bare
+  -h, --help        print this help
+
+The exit status is the exit code of the program, or 1 after an unhandled
+trap or when the instruction limit is reached.";
+
+const DEFAULT_BASE : u64 = 0x8000_0000;
+/* the stack of user-mode programs grows down from here, the program break
+ * may grow up to its lower end */
+const STACK_TOP : u64 = 0x7FFF_F000;
+const STACK_SIZE : u64 = 8 << 20;
+
+#[derive(Debug)]
+struct Options {
+    isa         : Option<String>,
+    base        : u64,
+    limit       : Option<u64>,
+    granule     : u64,
+    trace       : bool,
+    log_commits : bool,
+    bare        : bool,
+    harts       : usize,
+    quantum     : u64,
+    seed        : Option<u64>,
+    uart        : String,
+    sandbox     : Option<String>,
+    gdb         : Option<String>,
+    help        : bool,
+    /* the file followed by the arguments of the program */
+    args        : Vec<String>,
+}
+
+/* how a run ended */
+enum Outcome {
+    Exit(i32),
+    Trap(Exception),
+    Limit,
+    Detached,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("ruscy-v: {}", message);
+    process::exit(2);
+}
+
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/* parses the command line without the program name, --help only sets
+ * help and skips the other checks */
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {isa: None, base: DEFAULT_BASE, limit: None, granule: DEFAULT_GRANULE,
+                               trace: false, log_commits: false, bare: false,
+                               harts: 1, quantum: 1, seed: None, uart: "stdio".to_string(),
+                               sandbox: None, gdb: None, help: false, args: Vec::new()};
+    while let Some(arg) = args.next() {
+        if !options.args.is_empty() {
+            options.args.push(arg);
+            continue;
+        }
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--isa" => options.isa = Some(value("--isa")?),
+            "--base" => options.base = parse_number(&value("--base")?).ok_or("invalid base address")?,
+            "--limit" => options.limit = Some(parse_number(&value("--limit")?).ok_or("invalid limit")?),
+            "--granule" => options.granule = parse_number(&value("--granule")?)
+                .filter(|g| g.is_power_of_two() && *g >= 8)
+                .ok_or("invalid reservation granule")?,
+            "--trace" => options.trace = true,
+            "--log-commits" => options.log_commits = true,
+            "--bare" => options.bare = true,
+            "--harts" => options.harts = parse_number(&value("--harts")?)
+                .filter(|&n| n > 0 && n <= 1024)
+                .ok_or("invalid number of harts")? as usize,
+            "--quantum" => options.quantum = parse_number(&value("--quantum")?)
+                .filter(|&n| n > 0)
+                .ok_or("invalid quantum")?,
+            "--seed" => options.seed = Some(parse_number(&value("--seed")?).ok_or("invalid seed")?),
+            "--uart" => options.uart = value("--uart")?,
+            "--sandbox" => options.sandbox = Some(value("--sandbox")?),
+            "--gdb" => options.gdb = Some(value("--gdb")?),
+            "-h" | "--help" => {
+                options.help = true;
+                return Ok(options);
+            },
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}\n\n{}", arg, USAGE)),
+            _ => options.args.push(arg),
+        }
+    }
+    if options.args.is_empty() {
+        return Err(format!("no file given\n\n{}", USAGE));
+    }
+    if options.harts > 1 && !options.bare {
+        return Err("several harts need --bare".to_string());
+    }
+    /* the stub drives a single core without the timer and the devices of
+     * the machine */
+    if options.bare && options.gdb.is_some() {
+        return Err("GDB cannot debug a bare machine".to_string());
+    }
+    Ok(options)
+}
+
+/* the misa of the ISA given by --isa, the ELF file or the default, which
+ * has to match the class of the ELF file */
+fn isa(options: &Options, info: Option<&ElfInfo>) -> Result<u64, String> {
+    let isa = match (&options.isa, info) {
+        (Some(isa), _) => isa.clone(),
+        (None, Some(info)) => info.isa.clone()
+            .unwrap_or_else(|| if info.rv64 { "rv64gc".to_string() } else { "rv32gc".to_string() }),
+        (None, None) => "rv64gc".to_string(),
+    };
+    let misa = misa::parse(&isa).ok_or_else(|| format!("invalid ISA {}", isa))?;
+    let rv64 = (misa >> 62) == 2;
+    if info.is_some_and(|info| info.rv64 != rv64) {
+        return Err(format!("the ISA {} does not match the ELF class", isa));
+    }
+    Ok(misa)
+}
+
+/* opens the backend of the UART, None leaves it unmapped */
+fn uart_backend(name: &str) -> io::Result<Option<Box<dyn Backend>>> {
+    Ok(Some(match name {
+        "none" => return Ok(None),
+        "stdio" => Box::new(Stream::stdio()),
+        _ => match name.strip_prefix("unix:") {
+            Some(path) => {
+                eprintln!("ruscy-v: waiting for the UART connection on {}", path);
+                Box::new(Stream::unix(path)?)
+            },
+            None => fail(&format!("unknown UART backend {}", name)),
+        },
+    }))
+}
+
+/* Lays out argc, argv, an empty environment and an empty auxiliary vector
+ * at the top of the stack like Linux does. */
+fn setup_stack(core: &mut Core, mem: &mut Bus, args: &[String]) -> Result<(), AccessFault> {
+    let word = if core.rv64() { 8 } else { 4 };
+    let mut sp = STACK_TOP;
+    let mut pointers = Vec::new();
+    for arg in args {
+        sp -= arg.len() as u64 + 1;
+        mem.write_bytes(sp, arg.as_bytes())?;
+        mem.store8(sp + arg.len() as u64, 0)?;
+        pointers.push(sp);
+    }
+
+    /* argc, argv, NULL, envp NULL, AT_NULL */
+    let mut words = vec![args.len() as u64];
+    words.extend(pointers);
+    words.extend(&[0, 0, 0, 0]);
+    sp = (sp - words.len() as u64 * word) & !0xF;
+    for (i, value) in words.iter().enumerate() {
+        let addr = sp + i as u64 * word;
+        if word == 8 { mem.store64(addr, *value)? } else { mem.store32(addr, *value as u32)? }
+    }
+    core.reg[2] = sp;
+    Ok(())
+}
+
+/* loads an ELF file or a raw binary at --base and returns the end of the
+ * image */
+fn load(data: &[u8], elf: bool, mem: &mut Bus, core: &mut Core, options: &Options) -> u64 {
+    if elf {
+        let info = elf::load(data, mem, core).unwrap_or_else(|e| fail(&e.to_string()));
+        info.segments.iter().map(|s| s.addr + s.memsz).max().unwrap_or(0)
+    } else {
+        mem.write_bytes(options.base, data).unwrap_or_else(|_| fail("the binary does not fit into memory"));
+        options.base + data.len() as u64
+    }
+}
+
+fn dump_registers(core: &Core) {
+    let width = if core.rv64() { 16 } else { 8 };
+    eprintln!("pc   0x{:0w$x}", core.pc, w = width);
+    for row in 0..8 {
+        let line: Vec<String> = (0..4).map(|col| {
+            let reg = row * 4 + col;
+            format!("{:<4} 0x{:0w$x}", reg_name(reg, true), core.reg[reg as usize], w = width)
+        }).collect();
+        eprintln!("{}", line.join("  "));
+    }
+}
+
+/* the tracers selected by the options, line buffered so the traces stay
+ * in order with the program output */
+fn tracers(options: &Options) -> Vec<Box<dyn Tracer>> {
+    let mut tracers: Vec<Box<dyn Tracer>> = Vec::new();
+    if options.trace {
+        tracers.push(Box::new(Disassembly::new(LineWriter::new(io::stderr()))));
+    }
+    if options.log_commits {
+        tracers.push(Box::new(SpikeLog::new(LineWriter::new(io::stderr()))));
+    }
+    tracers
+}
+
+fn retire(tracers: &mut [Box<dyn Tracer>], core: &Core) {
+    if let Some(ref commit) = core.log {
+        if commit.retired {
+            for tracer in tracers.iter_mut() {
+                let _ = tracer.retire(core, commit);
+            }
+        }
+    }
+}
+
+/* runs a user-mode program with system call emulation */
+fn run(core: &mut Core, mem: &mut Bus, proxy: &mut Proxy, options: &Options) -> Outcome {
+    let mut tracers = tracers(options);
+    if !tracers.is_empty() {
+        core.log = Some(Commit::new());
+    }
+
+    let mut count = 0;
+    loop {
+        if options.limit == Some(count) {
+            return Outcome::Limit;
+        }
+        count += 1;
+
+        match proxy.step(core, mem) {
+            Ok(Some(code)) => return Outcome::Exit(code),
+            Ok(None) => (),
+            Err(e) => return Outcome::Trap(e),
+        }
+        retire(&mut tracers, core);
+    }
+}
+
+/* runs bare until a hart executes EBREAK */
+fn run_machine(machine: &mut Machine, options: &Options) -> Outcome {
+    let mut tracers = tracers(options);
+    if !tracers.is_empty() {
+        for hart in machine.harts.iter_mut() {
+            hart.log = Some(Commit::new());
+        }
+    }
+
+    let mut count = 0;
+    loop {
+        if options.limit == Some(count) {
+            return Outcome::Limit;
+        }
+        count += 1;
+
+        let (hart, result) = machine.step();
+        let core = &machine.harts[hart];
+        if let Err(Exception::Breakpoint(_)) = result {
+            return Outcome::Exit(core.reg[10] as i32);
+        }
+        retire(&mut tracers, core);
+    }
+}
+
+fn debug(addr: &str, core: &mut Core, mem: &mut Bus, proxy: &mut Proxy) -> io::Result<Outcome> {
+    eprintln!("ruscy-v: waiting for GDB on {}", addr);
+    let exit = if addr.contains(':') {
+        GdbStub::new(gdb::accept_tcp(addr)?).serve(core, mem, Some(proxy))?
+    } else {
+        unix_debug(addr, core, mem, proxy)?
+    };
+    Ok(exit.map_or(Outcome::Detached, Outcome::Exit))
+}
+
+#[cfg(unix)]
+fn unix_debug(path: &str, core: &mut Core, mem: &mut Bus, proxy: &mut Proxy) -> io::Result<Option<i32>> {
+    GdbStub::new(gdb::accept_unix(path)?).serve(core, mem, Some(proxy))
+}
+
+#[cfg(not(unix))]
+fn unix_debug(_path: &str, _core: &mut Core, _mem: &mut Bus, _proxy: &mut Proxy) -> io::Result<Option<i32>> {
+    Err(io::Error::new(io::ErrorKind::Other, "Unix sockets are not supported"))
+}
+
+fn main() {
+    let options = parse_options(env::args().skip(1)).unwrap_or_else(|e| fail(&e));
+    if options.help {
+        println!("{}", USAGE);
+        process::exit(0);
+    }
+    let data = fs::read(&options.args[0])
+        .unwrap_or_else(|e| fail(&format!("cannot read {}: {}", options.args[0], e)));
+
+    let info = if data.starts_with(b"\x7FELF") {
+        Some(elf::parse(&data).unwrap_or_else(|e| fail(&e.to_string())))
+    } else {
+        None
+    };
+    let misa = isa(&options, info.as_ref()).unwrap_or_else(|e| fail(&e));
+    let rv64 = (misa >> 62) == 2;
+
+    let memory = if rv64 { 1 << 40 } else { 1 << 32 };
+    let elf = info.is_some();
+    let (outcome, harts) = if options.bare {
+        let schedule = match options.seed {
+            Some(seed) => Schedule::Random {seed, max: options.quantum},
+            None if options.quantum > 1 => Schedule::Quantum(options.quantum),
+            None => Schedule::RoundRobin,
+        };
+        let isa = misa | misa::S | misa::U;
+        let mut machine = Machine::new(options.harts, options.base, isa, schedule);
+        let backend = uart_backend(&options.uart).unwrap_or_else(|e| fail(&format!("cannot open the UART: {}", e)));
+        if let Some(backend) = backend {
+            let uart = Rc::new(RefCell::new(Uart::new(backend)));
+            machine.bus.map(UART_BASE, UART_SIZE, Box::new(uart.clone())).expect("the UART does not overlap");
+            machine.connect(UART_IRQ, uart);
+        }
+        machine.bus.map_ram_gaps(0, memory).expect("RAM is only mapped into the gaps");
+        load(&data, elf, &mut machine.bus, &mut machine.harts[0], &options);
+        let pc = machine.harts[0].pc;
+        for hart in machine.harts.iter_mut() {
+            hart.pc = pc;
+            hart.granule = options.granule;
+        }
+
+        let outcome = run_machine(&mut machine, &options);
+        (outcome, machine.harts)
+    } else {
+        let mut core = Core::new(options.base, rv64);
+        core.csr.misa = misa | misa::S | misa::U;
+        core.granule = options.granule;
+        let mut mem = Bus::new();
+        mem.map_ram(0, memory).expect("the bus is empty");
+        let end = load(&data, elf, &mut mem, &mut core, &options);
+
+        setup_stack(&mut core, &mut mem, &options.args).unwrap_or_else(|_| fail("cannot set up the stack"));
+        let policy = match options.sandbox {
+            Some(ref dir) => FilePolicy::Directory(dir.into()),
+            None => FilePolicy::Host,
+        };
+        let brk = (end + 0xFFF) & !0xFFF;
+        let mut proxy = Proxy::new(FdTable::stdio(policy), brk, STACK_TOP - STACK_SIZE);
+
+        let outcome = match options.gdb {
+            Some(ref addr) => debug(addr, &mut core, &mut mem, &mut proxy)
+                .unwrap_or_else(|e| fail(&format!("GDB connection failed: {}", e))),
+            None => run(&mut core, &mut mem, &mut proxy, &options),
+        };
+        (outcome, vec![core])
+    };
+
+    let status = match outcome {
+        Outcome::Exit(code) => {
+            eprintln!("exit code {}", code);
+            code
+        },
+        Outcome::Trap(e) => {
+            eprintln!("unhandled trap {:?} at pc 0x{:x}", e, harts[0].pc);
+            1
+        },
+        Outcome::Limit => {
+            eprintln!("instruction limit reached");
+            1
+        },
+        Outcome::Detached => {
+            eprintln!("GDB detached");
+            0
+        },
+    };
+    for hart in &harts {
+        if harts.len() > 1 {
+            eprintln!("hart {}", hart.csr.mhartid);
+        }
+        dump_registers(hart);
+    }
+    process::exit(status);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{isa, parse_options, Options};
+    use ruscy_v::arch::csr::misa;
+    use ruscy_v::elf::ElfInfo;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_options(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn info(rv64: bool, isa: Option<&str>) -> ElfInfo {
+        ElfInfo {rv64, entry: 0, flags: 0, isa: isa.map(|isa| isa.to_string()), segments: Vec::new()}
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = parse(&["--bare", "--harts", "4", "--granule", "0x10", "prog", "--trace"]).unwrap();
+        assert_eq!(options.harts, 4);
+        assert_eq!(options.granule, 16);
+        assert!(!options.trace);
+        assert_eq!(options.args, vec!["prog", "--trace"]);
+
+        assert!(parse(&["--help", "--bogus"]).unwrap().help);
+        assert!(parse(&[]).unwrap_err().starts_with("no file given"));
+        assert!(parse(&["--bogus", "prog"]).unwrap_err().starts_with("unknown option --bogus"));
+        assert_eq!(parse(&["prog", "--harts"]).unwrap().args, vec!["prog", "--harts"]);
+        assert_eq!(parse(&["--harts"]).unwrap_err(), "--harts needs a value");
+    }
+
+    #[test]
+    fn test_invalid_options() {
+        assert_eq!(parse(&["--harts", "2", "prog"]).unwrap_err(), "several harts need --bare");
+        assert_eq!(parse(&["--harts", "0", "--bare", "prog"]).unwrap_err(), "invalid number of harts");
+        assert_eq!(parse(&["--bare", "--gdb", "localhost:1234", "prog"]).unwrap_err(),
+                   "GDB cannot debug a bare machine");
+        assert!(parse(&["--gdb", "localhost:1234", "prog"]).is_ok());
+        for granule in &["4", "24", "x"] {
+            assert_eq!(parse(&["--granule", granule, "prog"]).unwrap_err(), "invalid reservation granule");
+        }
+    }
+
+    #[test]
+    fn test_isa() {
+        let options = parse(&["prog"]).unwrap();
+        assert_eq!(isa(&options, None), Ok(misa::parse("rv64gc").unwrap()));
+        assert_eq!(isa(&options, Some(&info(false, None))), Ok(misa::parse("rv32gc").unwrap()));
+        assert_eq!(isa(&options, Some(&info(false, Some("rv32imac")))), Ok(misa::parse("rv32imac").unwrap()));
+
+        let options = parse(&["--isa", "rv32imc", "prog"]).unwrap();
+        assert_eq!(isa(&options, None), Ok(misa::parse("rv32imc").unwrap()));
+        assert_eq!(isa(&options, Some(&info(false, Some("rv32gc")))), Ok(misa::parse("rv32imc").unwrap()));
+        assert_eq!(isa(&options, Some(&info(true, None))),
+                   Err("the ISA rv32imc does not match the ELF class".to_string()));
+
+        let options = parse(&["--isa", "rv64xyz", "prog"]).unwrap();
+        assert_eq!(isa(&options, None), Err("invalid ISA rv64xyz".to_string()));
+    }
+}
@@ -0,0 +1,566 @@
+/*
+ * syscall.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* Emulation of the Linux system call interface for user-mode programs like
+ * newlib or riscv-pk binaries. An ECALL passes the number in a7 and the
+ * arguments in a0 to a5, the result or a negated errno is returned in a0.
+ * Pointers are physical addresses as user-mode programs run without address
+ * translation. */
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use arch::core::{Core, Exception};
+use arch::memory::Memory;
+
+/* system call numbers of the generic Linux ABI */
+pub mod nr {
+    pub const OPENAT       : u64 = 56;
+    pub const CLOSE        : u64 = 57;
+    pub const LSEEK        : u64 = 62;
+    pub const READ         : u64 = 63;
+    pub const WRITE        : u64 = 64;
+    pub const FSTAT        : u64 = 80;
+    pub const EXIT         : u64 = 93;
+    pub const EXIT_GROUP   : u64 = 94;
+    pub const GETTIMEOFDAY : u64 = 169;
+    pub const BRK          : u64 = 214;
+}
+
+/* error numbers, returned negated */
+pub mod errno {
+    pub const ENOENT       : i64 = 2;
+    pub const EIO          : i64 = 5;
+    pub const EBADF        : i64 = 9;
+    pub const EACCES       : i64 = 13;
+    pub const EFAULT       : i64 = 14;
+    pub const EEXIST       : i64 = 17;
+    pub const EINVAL       : i64 = 22;
+    pub const EMFILE       : i64 = 24;
+    pub const ESPIPE       : i64 = 29;
+    pub const ENAMETOOLONG : i64 = 36;
+    pub const ENOSYS       : i64 = 38;
+}
+
+const ECALL : u32 = 0x0000_0073;
+const AT_FDCWD : i64 = -100;
+const O_ACCMODE : u64 = 0o3;
+const O_WRONLY : u64 = 0o1;
+const O_RDWR : u64 = 0o2;
+const O_CREAT : u64 = 0o100;
+const O_EXCL : u64 = 0o200;
+const O_TRUNC : u64 = 0o1000;
+const O_APPEND : u64 = 0o2000;
+
+const S_IFCHR : u32 = 0o020000;
+const S_IFDIR : u32 = 0o040000;
+const S_IFREG : u32 = 0o100000;
+
+const MAX_FDS : usize = 256;
+const PATH_MAX : u64 = 4096;
+/* larger reads and writes are done partially */
+const MAX_TRANSFER : u64 = 1 << 20;
+
+/* which host files openat may access */
+#[derive(PartialEq, Debug, Clone)]
+pub enum FilePolicy {
+    /* every open fails with EACCES */
+    Deny,
+    /* paths are relative to this directory and may not leave it */
+    Directory(PathBuf),
+    /* paths are passed to the host unchanged */
+    Host,
+}
+
+/* the object behind a file descriptor of the guest */
+pub enum Descriptor {
+    Reader(Box<dyn Read>),
+    Writer(Box<dyn Write>),
+    File(File),
+}
+
+pub struct FdTable {
+    files  : Vec<Option<Descriptor>>,
+    policy : FilePolicy,
+}
+
+impl FdTable {
+    /* a table without any open descriptors */
+    pub fn new(policy: FilePolicy) -> FdTable {
+        FdTable {files: Vec::new(), policy: policy}
+    }
+
+    /* a table with the standard streams of the host as 0, 1 and 2 */
+    pub fn stdio(policy: FilePolicy) -> FdTable {
+        let mut table = FdTable::new(policy);
+        table.set(0, Descriptor::Reader(Box::new(io::stdin())));
+        table.set(1, Descriptor::Writer(Box::new(io::stdout())));
+        table.set(2, Descriptor::Writer(Box::new(io::stderr())));
+        table
+    }
+
+    /* installs a descriptor under the given number, replacing an old one */
+    pub fn set(&mut self, fd: usize, file: Descriptor) {
+        if fd >= self.files.len() {
+            self.files.resize_with(fd + 1, || None);
+        }
+        self.files[fd] = Some(file);
+    }
+
+    /* installs a descriptor under the lowest free number */
+    pub fn insert(&mut self, file: Descriptor) -> Option<usize> {
+        let fd = self.files.iter().position(|f| f.is_none()).unwrap_or(self.files.len());
+        if fd >= MAX_FDS {
+            return None;
+        }
+        self.set(fd, file);
+        Some(fd)
+    }
+
+    pub fn remove(&mut self, fd: usize) -> Option<Descriptor> {
+        self.files.get_mut(fd).and_then(|f| f.take())
+    }
+
+    fn get(&mut self, fd: u64) -> Result<&mut Descriptor, i64> {
+        match self.files.get_mut(fd as usize) {
+            Some(&mut Some(ref mut file)) => Ok(file),
+            _ => Err(errno::EBADF)
+        }
+    }
+
+    /* maps a guest path to a host path according to the policy */
+    fn resolve(&self, path: &str) -> Result<PathBuf, i64> {
+        match self.policy {
+            FilePolicy::Deny => Err(errno::EACCES),
+            FilePolicy::Host => Ok(PathBuf::from(path)),
+            FilePolicy::Directory(ref root) => {
+                let mut resolved = root.clone();
+                for component in Path::new(path).components() {
+                    match component {
+                        Component::Normal(name) => resolved.push(name),
+                        Component::RootDir | Component::CurDir => (),
+                        _ => return Err(errno::EACCES)
+                    }
+                }
+                /* symbolic links may point out of the root, so the host path
+                 * must stay below it once they are resolved. A file that does
+                 * not exist yet is checked by its directory, a dangling link
+                 * is not followed. */
+                let root = root.canonicalize().map_err(|e| host_errno(&e))?;
+                let host = match resolved.canonicalize() {
+                    Ok(host) => host,
+                    Err(ref e) if e.kind() == io::ErrorKind::NotFound && resolved.symlink_metadata().is_err() => {
+                        let name = resolved.file_name().ok_or(errno::ENOENT)?;
+                        let parent = resolved.parent().ok_or(errno::ENOENT)?;
+                        parent.canonicalize().map_err(|e| host_errno(&e))?.join(name)
+                    },
+                    Err(e) => return Err(host_errno(&e)),
+                };
+                if host.starts_with(&root) { Ok(host) } else { Err(errno::EACCES) }
+            }
+        }
+    }
+}
+
+fn host_errno(error: &io::Error) -> i64 {
+    match error.kind() {
+        io::ErrorKind::NotFound => errno::ENOENT,
+        io::ErrorKind::PermissionDenied => errno::EACCES,
+        io::ErrorKind::AlreadyExists => errno::EEXIST,
+        io::ErrorKind::InvalidInput => errno::EINVAL,
+        _ => error.raw_os_error().map_or(errno::EIO, |e| e as i64)
+    }
+}
+
+fn read_guest<M: Memory + ?Sized>(mem: &mut M, addr: u64, len: u64) -> Result<Vec<u8>, i64> {
+    (0..len).map(|i| mem.load8(addr.wrapping_add(i)).map_err(|_| errno::EFAULT)).collect()
+}
+
+/* reads a NUL terminated string */
+fn read_string<M: Memory + ?Sized>(mem: &mut M, addr: u64) -> Result<String, i64> {
+    let mut bytes = Vec::new();
+    loop {
+        if bytes.len() as u64 >= PATH_MAX {
+            return Err(errno::ENAMETOOLONG);
+        }
+        match mem.load8(addr.wrapping_add(bytes.len() as u64)).map_err(|_| errno::EFAULT)? {
+            0 => break,
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| errno::ENOENT)
+}
+
+/* Intercepts the ECALLs of a program. The program break starts at the end
+ * of the loaded image and may only grow up to a limit. */
+pub struct Proxy {
+    pub fds   : FdTable,
+    brk_start : u64,
+    brk_limit : u64,
+    brk       : u64,
+}
+
+impl Proxy {
+    pub fn new(fds: FdTable, brk_start: u64, brk_limit: u64) -> Proxy {
+        Proxy {fds: fds, brk_start: brk_start, brk_limit: brk_limit, brk: brk_start}
+    }
+
+    /* Executes one instruction and handles an ECALL, which retires like
+     * any other instruction with a0 as its result. Returns the exit code
+     * once the program exits, other exceptions are not handled and the
+     * core is left at the faulting instruction. */
+    pub fn step<M: Memory + ?Sized>(&mut self, core: &mut Core, mem: &mut M) -> Result<Option<i32>, Exception> {
+        match core.try_step(mem) {
+            Ok(()) => Ok(None),
+            Err(Exception::EnvironmentCall(_)) => {
+                let exit = self.syscall(core, mem);
+                if exit.is_none() {
+                    core.retire_emulated(ECALL, 4, &[10]);
+                }
+                Ok(exit)
+            },
+            Err(e) => Err(e)
+        }
+    }
+
+    /* Executes the system call requested by the registers and writes the
+     * result to a0. Returns the exit code for exit and exit_group. */
+    pub fn syscall<M: Memory + ?Sized>(&mut self, core: &mut Core, mem: &mut M) -> Option<i32> {
+        let a = |i: usize| core.reg[10 + i];
+        let signed = |i: usize| if core.rv64() { a(i) as i64 } else { a(i) as i32 as i64 };
+        let result = match core.reg[17] {
+            nr::EXIT | nr::EXIT_GROUP => return Some(a(0) as i32),
+            nr::READ => self.read(mem, a(0), a(1), a(2)),
+            nr::WRITE => self.write(mem, a(0), a(1), a(2)),
+            nr::OPENAT => self.openat(mem, signed(0), a(1), a(2)),
+            nr::CLOSE => self.fds.remove(a(0) as usize).map(|_| 0).ok_or(errno::EBADF),
+            nr::LSEEK => self.lseek(a(0), signed(1), a(2)),
+            nr::FSTAT => self.fstat(mem, a(0), a(1)),
+            nr::BRK => Ok(self.brk(a(0))),
+            nr::GETTIMEOFDAY => self.gettimeofday(core.rv64(), mem, a(0)),
+            _ => Err(errno::ENOSYS)
+        };
+        let value = match result {
+            Ok(value) => value,
+            Err(errno) => (-errno) as u64,
+        };
+        core.reg[10] = value & core.csr.xlen_mask();
+        None
+    }
+
+    fn read<M: Memory + ?Sized>(&mut self, mem: &mut M, fd: u64, buf: u64, count: u64) -> Result<u64, i64> {
+        let mut data = vec![0; count.min(MAX_TRANSFER) as usize];
+        let len = match *self.fds.get(fd)? {
+            Descriptor::Reader(ref mut reader) => reader.read(&mut data),
+            Descriptor::File(ref mut file) => file.read(&mut data),
+            Descriptor::Writer(_) => return Err(errno::EBADF),
+        }.map_err(|e| host_errno(&e))?;
+        mem.write_bytes(buf, &data[..len]).map_err(|_| errno::EFAULT)?;
+        Ok(len as u64)
+    }
+
+    fn write<M: Memory + ?Sized>(&mut self, mem: &mut M, fd: u64, buf: u64, count: u64) -> Result<u64, i64> {
+        let data = read_guest(mem, buf, count.min(MAX_TRANSFER))?;
+        let len = match *self.fds.get(fd)? {
+            Descriptor::Writer(ref mut writer) => writer.write(&data).and_then(|len| writer.flush().map(|_| len)),
+            Descriptor::File(ref mut file) => file.write(&data),
+            Descriptor::Reader(_) => return Err(errno::EBADF),
+        }.map_err(|e| host_errno(&e))?;
+        Ok(len as u64)
+    }
+
+    fn openat<M: Memory + ?Sized>(&mut self, mem: &mut M, dirfd: i64, path: u64, flags: u64) -> Result<u64, i64> {
+        let path = read_string(mem, path)?;
+        /* relative paths are only supported from the working directory */
+        if dirfd != AT_FDCWD && !path.starts_with('/') {
+            return Err(errno::EBADF);
+        }
+        let host = self.fds.resolve(&path)?;
+
+        let mut options = OpenOptions::new();
+        match flags & O_ACCMODE {
+            O_WRONLY => options.write(true),
+            O_RDWR => options.read(true).write(true),
+            _ => options.read(true),
+        };
+        options.append((flags & O_APPEND) != 0).truncate((flags & O_TRUNC) != 0);
+        if (flags & O_CREAT) != 0 {
+            /* the host API can only create files that are opened for writing */
+            options.write(true);
+            if (flags & O_EXCL) != 0 {
+                options.create_new(true);
+            } else {
+                options.create(true);
+            }
+        }
+        let file = options.open(host).map_err(|e| host_errno(&e))?;
+        self.fds.insert(Descriptor::File(file)).map(|fd| fd as u64).ok_or(errno::EMFILE)
+    }
+
+    fn lseek(&mut self, fd: u64, offset: i64, whence: u64) -> Result<u64, i64> {
+        let pos = match whence {
+            0 if offset >= 0 => SeekFrom::Start(offset as u64),
+            1 => SeekFrom::Current(offset),
+            2 => SeekFrom::End(offset),
+            _ => return Err(errno::EINVAL)
+        };
+        match *self.fds.get(fd)? {
+            Descriptor::File(ref mut file) => file.seek(pos).map_err(|e| host_errno(&e)),
+            _ => Err(errno::ESPIPE)
+        }
+    }
+
+    /* The stat structure of riscv-pk and newlib has the same layout for
+     * RV32 and RV64. Only the type, permissions and size are filled in. */
+    fn fstat<M: Memory + ?Sized>(&mut self, mem: &mut M, fd: u64, buf: u64) -> Result<u64, i64> {
+        let (mode, size) = match *self.fds.get(fd)? {
+            Descriptor::File(ref file) => {
+                let meta = file.metadata().map_err(|e| host_errno(&e))?;
+                let kind = if meta.is_dir() { S_IFDIR } else { S_IFREG };
+                let perm = if meta.permissions().readonly() { 0o444 } else { 0o644 };
+                (kind | perm, meta.len())
+            },
+            _ => (S_IFCHR | 0o620, 0)
+        };
+        let mut stat = [0u8; 128];
+        stat[16..20].copy_from_slice(&mode.to_le_bytes());
+        stat[20..24].copy_from_slice(&1u32.to_le_bytes());
+        stat[48..56].copy_from_slice(&size.to_le_bytes());
+        stat[56..60].copy_from_slice(&4096u32.to_le_bytes());
+        stat[64..72].copy_from_slice(&size.div_ceil(512).to_le_bytes());
+        mem.write_bytes(buf, &stat).map_err(|_| errno::EFAULT)?;
+        Ok(0)
+    }
+
+    /* returns the new break, which is the old one if the request fails */
+    fn brk(&mut self, addr: u64) -> u64 {
+        if addr >= self.brk_start && addr <= self.brk_limit {
+            self.brk = addr;
+        }
+        self.brk
+    }
+
+    /* struct timeval consists of two longs */
+    fn gettimeofday<M: Memory + ?Sized>(&mut self, rv64: bool, mem: &mut M, tv: u64) -> Result<u64, i64> {
+        if tv == 0 {
+            return Ok(0);
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let usec = now.subsec_micros() as u64;
+        if rv64 {
+            mem.store64(tv, now.as_secs()).and_then(|_| mem.store64(tv + 8, usec))
+        } else {
+            mem.store32(tv, now.as_secs() as u32).and_then(|_| mem.store32(tv + 4, usec as u32))
+        }.map_err(|_| errno::EFAULT)?;
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::env;
+    use std::fs;
+    use std::io::{self, Cursor, Write};
+    use std::rc::Rc;
+
+    use super::{Proxy, FdTable, FilePolicy, Descriptor, nr, errno, S_IFREG};
+    use arch::assembler::assemble_into;
+    use arch::core::{Commit, Core, Exception};
+    use arch::memory::{Memory, Ram};
+
+    /* a writer whose output can be inspected */
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn call(proxy: &mut Proxy, core: &mut Core, ram: &mut Ram, number: u64, args: &[u64]) -> i64 {
+        core.reg[17] = number;
+        for (i, arg) in args.iter().enumerate() {
+            core.reg[10 + i] = *arg;
+        }
+        assert_eq!(proxy.syscall(core, ram), None);
+        core.reg[10] as i64
+    }
+
+    #[test]
+    fn test_step() {
+        let mut ram = Ram::new(0, 0x2000);
+        assemble_into(&mut ram, 0, "
+            li a0, 1
+            li a1, 0x1000
+            li a2, 6
+            li a7, 64
+            ecall
+            mv s0, a0
+            li a0, 0
+            li a7, 63
+            ecall
+            mv s1, a0
+            li a0, 3
+            li a7, 93
+            ecall
+        ", false);
+        ram.write_bytes(0x1000, b"hello\n").unwrap();
+
+        let out = Shared::default();
+        let mut fds = FdTable::new(FilePolicy::Deny);
+        fds.set(1, Descriptor::Writer(Box::new(out.clone())));
+        let mut proxy = Proxy::new(fds, 0x2000, 0x2000);
+        let mut core = Core::new(0, false);
+        core.log = Some(Commit::new());
+        let mut exit = None;
+        let mut ecalls = Vec::new();
+        for _ in 0..100 {
+            exit = proxy.step(&mut core, &mut ram).unwrap();
+            if exit.is_some() {
+                break;
+            }
+            let log = core.log.as_ref().unwrap();
+            if log.retired && log.word == 0x00000073 {
+                ecalls.push(log.writes.clone());
+            }
+        }
+        assert_eq!(exit, Some(3));
+        assert_eq!(ecalls, vec![vec![(10, 6)], vec![(10, (-errno::EBADF) as u32 as u64)]]);
+        assert_eq!(core.csr.instret, 12);
+        assert_eq!(&out.0.borrow()[..], b"hello\n");
+        assert_eq!(core.reg[8], 6);
+        assert_eq!(core.reg[9], (-errno::EBADF) as u32 as u64);
+        assert_eq!(core.pc, 48);
+
+        /* other exceptions are not handled */
+        ram.store32(52, 0).unwrap();
+        core.pc = 52;
+        assert_eq!(proxy.step(&mut core, &mut ram), Err(Exception::IllegalInstruction(0)));
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = env::temp_dir().join(format!("ruscy-v-syscall-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut proxy = Proxy::new(FdTable::new(FilePolicy::Directory(dir.clone())), 0, 0);
+        let mut core = Core::new(0, true);
+        let mut ram = Ram::new(0, 0x1000);
+        ram.write_bytes(0x100, b"/test.txt\0../test.txt\0hello\0").unwrap();
+        let at_fdcwd = -100i64 as u64;
+
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::OPENAT, &[at_fdcwd, 0x10A, 0, 0]), -errno::EACCES);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::OPENAT, &[at_fdcwd, 0x100, 0, 0]), -errno::ENOENT);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::OPENAT, &[at_fdcwd, 0x100, 0o1101, 0o644]), 0);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::WRITE, &[0, 0x116, 5]), 5);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::LSEEK, &[0, 1, 0]), 1);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::CLOSE, &[0]), 0);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::CLOSE, &[0]), -errno::EBADF);
+        assert_eq!(fs::read(dir.join("test.txt")).unwrap(), b"hello");
+
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::OPENAT, &[at_fdcwd, 0x100, 0o300, 0]), -errno::EEXIST);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::OPENAT, &[at_fdcwd, 0x100, 0, 0]), 0);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::FSTAT, &[0, 0x200]), 0);
+        assert_eq!(ram.load32(0x210), Ok(S_IFREG | 0o644));
+        assert_eq!(ram.load64(0x230), Ok(5));
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::LSEEK, &[0, -2i64 as u64, 2]), 3);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::READ, &[0, 0x300, 100]), 2);
+        assert_eq!(ram.load16(0x300), Ok(0x6F6C));
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::READ, &[0, 0xFFF, 100]), 0);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::LSEEK, &[0, 0, 0]), 0);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::READ, &[0, 0xFFF, 100]), -errno::EFAULT);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sandbox_links() {
+        use std::os::unix::fs::symlink;
+
+        let base = env::temp_dir().join(format!("ruscy-v-links-{}", std::process::id()));
+        let (dir, outside) = (base.join("root"), base.join("outside"));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("secret.txt"), b"secret").unwrap();
+        fs::write(dir.join("sub/test.txt"), b"test").unwrap();
+        symlink(&outside, dir.join("out")).unwrap();
+        symlink(outside.join("secret.txt"), dir.join("secret")).unwrap();
+        symlink(outside.join("new.txt"), dir.join("dangling")).unwrap();
+        symlink(dir.join("sub"), dir.join("inside")).unwrap();
+
+        let mut proxy = Proxy::new(FdTable::new(FilePolicy::Directory(dir.clone())), 0, 0);
+        let mut core = Core::new(0, true);
+        let mut ram = Ram::new(0, 0x1000);
+        ram.write_bytes(0x100, b"/out/secret.txt\0/secret\0/dangling\0/out/new.txt\0/inside/test.txt\0").unwrap();
+        let at_fdcwd = -100i64 as u64;
+
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::OPENAT, &[at_fdcwd, 0x100, 0, 0]), -errno::EACCES);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::OPENAT, &[at_fdcwd, 0x110, 0, 0]), -errno::EACCES);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::OPENAT, &[at_fdcwd, 0x118, 0o101, 0o644]), -errno::ENOENT);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::OPENAT, &[at_fdcwd, 0x122, 0o101, 0o644]), -errno::EACCES);
+        assert!(!outside.join("new.txt").exists());
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::OPENAT, &[at_fdcwd, 0x12F, 0, 0]), 0);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::READ, &[0, 0x200, 100]), 4);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_misc() {
+        let mut fds = FdTable::new(FilePolicy::Deny);
+        fds.set(0, Descriptor::Reader(Box::new(Cursor::new(b"input".to_vec()))));
+        let mut proxy = Proxy::new(fds, 0x4000, 0x8000);
+        let mut core = Core::new(0, false);
+        let mut ram = Ram::new(0, 0x1000);
+        ram.write_bytes(0x100, b"/etc/passwd\0").unwrap();
+
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::OPENAT, &[0xFFFFFF9C, 0x100, 0, 0]), 0xFFFFFFF3);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::READ, &[0, 0x200, 2]), 2);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::READ, &[0, 0x200, 8]), 3);
+        assert_eq!(ram.load32(0x200), Ok(0x00747570));
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::LSEEK, &[0, 0, 0]), (-errno::ESPIPE) as u32 as i64);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::FSTAT, &[0, 0x400]), 0);
+        assert_eq!(ram.load32(0x410), Ok(0o020620));
+
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::BRK, &[0]), 0x4000);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::BRK, &[0x5000]), 0x5000);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::BRK, &[0x9000]), 0x5000);
+
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, nr::GETTIMEOFDAY, &[0x500, 0]), 0);
+        assert!(ram.load32(0x500).unwrap() > 1_500_000_000);
+        assert!(ram.load32(0x504).unwrap() < 1_000_000);
+        assert_eq!(call(&mut proxy, &mut core, &mut ram, 1234, &[]), (-errno::ENOSYS) as u32 as i64);
+
+        core.reg[17] = nr::EXIT_GROUP;
+        core.reg[10] = 0xFFFFFFFF;
+        assert_eq!(proxy.syscall(&mut core, &mut ram), Some(-1));
+    }
+}
@@ -0,0 +1,160 @@
+/*
+ * trace.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* Tracers receive every retired instruction from the execution loop. The
+ * disassembly trace is meant for reading, the commit log follows Spike's
+ * --log-commits format so traces can be compared line by line. */
+
+use std::io::{self, Write};
+
+use arch::core::{Commit, Core};
+use arch::csr::misa;
+use arch::decoder::Instruction;
+use arch::disassembler::AsmFormat;
+
+pub trait Tracer {
+    /* called after an instruction retired, the log of the core is enabled */
+    fn retire(&mut self, core: &Core, commit: &Commit) -> io::Result<()>;
+}
+
+/* prints the value with as many hex digits as the width in bits needs */
+fn hex(value: u64, bits: u64) -> String {
+    format!("0x{:0w$x}", value, w = (bits / 4) as usize)
+}
+
+fn xlen(core: &Core) -> u64 {
+    if core.rv64() { 64 } else { 32 }
+}
+
+/* one line with address, instruction word and disassembly per instruction */
+pub struct Disassembly<W: Write> {
+    out : W,
+}
+
+impl<W: Write> Disassembly<W> {
+    pub fn new(out: W) -> Disassembly<W> {
+        Disassembly {out: out}
+    }
+}
+
+impl<W: Write> Tracer for Disassembly<W> {
+    fn retire(&mut self, core: &Core, commit: &Commit) -> io::Result<()> {
+        let instr = if commit.len == 2 {
+            Instruction::decode16(commit.word as u16, core.rv64())
+        } else {
+            Instruction::decode32(commit.word, core.rv64())
+        };
+        let format = AsmFormat {pc: Some(commit.pc), .. AsmFormat::default()};
+        let text = instr.map(|i| i.fmt_asm(&format)).unwrap_or_default();
+        writeln!(self.out, "{} ({}) {}", hex(commit.pc, xlen(core)), hex(commit.word as u64, 8 * commit.len), text)
+    }
+}
+
+/* Spike's commit log, e.g.
+ * core   0: 3 0x0000000080000000 (0x00000297) x5  0x0000000080000000
+ * with the register writes, the addresses of loads and the address and data
+ * of stores following the instruction. */
+pub struct SpikeLog<W: Write> {
+    out : W,
+}
+
+impl<W: Write> SpikeLog<W> {
+    pub fn new(out: W) -> SpikeLog<W> {
+        SpikeLog {out: out}
+    }
+}
+
+impl<W: Write> Tracer for SpikeLog<W> {
+    fn retire(&mut self, core: &Core, commit: &Commit) -> io::Result<()> {
+        let xlen = xlen(core);
+        let mut line = format!("core{:4}: {} {} ({})", core.csr.mhartid, commit.privilege as u8,
+                               hex(commit.pc, xlen), hex(commit.word as u64, 8 * commit.len));
+        for &(rd, value) in &commit.writes {
+            line += &format!(" x{:<2} {}", rd, hex(value, xlen));
+        }
+        let flen = if core.csr.has(misa::D) { 64 } else { 32 };
+        for &(rd, value) in &commit.fwrites {
+            line += &format!(" f{:<2} {}", rd, hex(value, flen));
+        }
+        for &(addr, _) in &commit.loads {
+            line += &format!(" mem {}", hex(addr, xlen));
+        }
+        for &(addr, size, value) in &commit.stores {
+            line += &format!(" mem {} {}", hex(addr, xlen), hex(value, 8 * size));
+        }
+        writeln!(self.out, "{}", line)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Tracer, Disassembly, SpikeLog};
+    use arch::assembler::assemble_into;
+    use arch::core::{Commit, Core};
+    use arch::memory::{Memory, Ram};
+
+    /* runs a program with both tracers until the first exception */
+    fn trace(source: &str, rv64: bool) -> (String, String) {
+        let mut ram = Ram::new(0, 0x1000);
+        assemble_into(&mut ram, 0, source, rv64);
+        ram.store16(0x100, 0x0505).unwrap(); // c.addi a0, 1
+        let mut core = Core::new(0, rv64);
+        core.log = Some(Commit::new());
+        let mut disassembly = Disassembly::new(Vec::new());
+        let mut spike = SpikeLog::new(Vec::new());
+        while core.try_step(&mut ram).is_ok() {
+            let commit = core.log.clone().unwrap();
+            assert!(commit.retired);
+            disassembly.retire(&core, &commit).unwrap();
+            spike.retire(&core, &commit).unwrap();
+        }
+        (String::from_utf8(disassembly.out).unwrap(), String::from_utf8(spike.out).unwrap())
+    }
+
+    #[test]
+    fn test_spike_log() {
+        let source = "
+            li a0, 0x100
+            sd a0, 8(a0)
+            lw a1, 8(a0)
+            sb zero, 16(a0)
+            nop
+            j 0x100
+        ";
+        let (_, log) = trace(source, true);
+        assert_eq!(log, "core   0: 3 0x0000000000000000 (0x10000513) x10 0x0000000000000100\n\
+                         core   0: 3 0x0000000000000004 (0x00a53423) mem 0x0000000000000108 0x0000000000000100\n\
+                         core   0: 3 0x0000000000000008 (0x00852583) x11 0x0000000000000100 mem 0x0000000000000108\n\
+                         core   0: 3 0x000000000000000c (0x00050823) mem 0x0000000000000110 0x00\n\
+                         core   0: 3 0x0000000000000010 (0x00000013)\n\
+                         core   0: 3 0x0000000000000014 (0x0ec0006f)\n\
+                         core   0: 3 0x0000000000000100 (0x0505) x10 0x0000000000000101\n");
+
+        let (disassembly, log) = trace("li ra, -1\nj 0x100", false);
+        assert_eq!(log, "core   0: 3 0x00000000 (0xfff00093) x1  0xffffffff\n\
+                         core   0: 3 0x00000004 (0x0fc0006f)\n\
+                         core   0: 3 0x00000100 (0x0505) x10 0x00000001\n");
+        assert_eq!(disassembly, "0x00000000 (0xfff00093) li ra,-1\n\
+                                 0x00000004 (0x0fc0006f) j 100\n\
+                                 0x00000100 (0x0505) addi a0,a0,1\n");
+    }
+}
@@ -0,0 +1,477 @@
+/*
+ * uart.rs
+ * Author: Fabjan Sukalia <fsukalia@gmail.com>
+ * Date: 2026-10-18
+ * Copyright 2016-2026 Fabjan Sukalia
+ *
+ * This file is part of ruscy-v
+ *
+ * ruscy-v is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * any later version.
+ * 
+ * ruscy-v is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ * 
+ * You should have received a copy of the GNU General Public License
+ * along with ruscy-v.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* An NS16550A compatible UART with byte-wide registers. A transmitted
+ * byte is handed to the backend at once, so the transmitter is always
+ * empty, and received bytes are fetched from the backend into the 16 byte
+ * FIFO when the program reads RBR, IIR or LSR or the machine polls the
+ * interrupt line. The divisor latch is kept for the drivers that program
+ * it, but has no effect. The modem status lines report a connected
+ * terminal, in loopback mode they follow the modem control register and
+ * transmitted bytes are received again. */
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use arch::memory::{AccessFault, Memory};
+use arch::plic::Source;
+
+pub const UART_BASE : u64 = 0x1000_0000;
+pub const UART_SIZE : u64 = 0x100;
+/* the PLIC source of the interrupt line */
+pub const UART_IRQ  : u32 = 10;
+
+/* register offsets, the divisor latch replaces RBR/THR and IER while
+ * LCR.DLAB is set */
+const RBR : u64 = 0; // read
+const THR : u64 = 0; // write
+const IER : u64 = 1;
+const IIR : u64 = 2; // read
+const FCR : u64 = 2; // write
+const LCR : u64 = 3;
+const MCR : u64 = 4;
+const LSR : u64 = 5;
+const MSR : u64 = 6;
+const SCR : u64 = 7;
+
+mod ier {
+    pub const RX   : u8 = 0x01;
+    pub const THRE : u8 = 0x02;
+    pub const MASK : u8 = 0x0F;
+}
+
+mod iir {
+    pub const NONE : u8 = 0x01;
+    pub const THRE : u8 = 0x02;
+    pub const RX   : u8 = 0x04;
+    pub const FIFO : u8 = 0xC0;
+}
+
+mod fcr {
+    pub const ENABLE   : u8 = 0x01;
+    pub const CLEAR_RX : u8 = 0x02;
+}
+
+mod lsr {
+    pub const DR   : u8 = 0x01;
+    pub const THRE : u8 = 0x20;
+    pub const TEMT : u8 = 0x40;
+}
+
+const LCR_DLAB : u8 = 0x80;
+const MCR_LOOP : u8 = 0x10;
+const MCR_MASK : u8 = 0x1F;
+/* DCD, DSR and CTS */
+const MSR_CONNECTED : u8 = 0xB0;
+const FIFO_SIZE : usize = 16;
+
+/* where the bytes of the UART come from and go to */
+pub trait Backend {
+    /* a received byte if there is one, must not block */
+    fn receive(&mut self) -> Option<u8>;
+    fn transmit(&mut self, byte: u8) -> io::Result<()>;
+}
+
+/* an in-memory backend, mainly for tests */
+#[derive(Default)]
+pub struct Buffer {
+    pub input  : VecDeque<u8>,
+    pub output : Vec<u8>,
+}
+
+impl Backend for Buffer {
+    fn receive(&mut self) -> Option<u8> {
+        self.input.pop_front()
+    }
+
+    fn transmit(&mut self, byte: u8) -> io::Result<()> {
+        self.output.push(byte);
+        Ok(())
+    }
+}
+
+/* A host byte stream. A thread reads it, so receiving does not block,
+ * and ends at the end of the stream or at the first error. */
+pub struct Stream {
+    input  : Receiver<u8>,
+    output : Box<dyn Write>,
+}
+
+impl Stream {
+    pub fn new<R: Read + Send + 'static, W: Write + 'static>(mut reader: R, writer: W) -> Stream {
+        let (sender, input) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 256];
+            loop {
+                match reader.read(&mut buffer) {
+                    Ok(0) => return,
+                    Ok(n) => if buffer[..n].iter().any(|&byte| sender.send(byte).is_err()) {
+                        return;
+                    },
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(_) => return,
+                }
+            }
+        });
+        Stream {input: input, output: Box::new(writer)}
+    }
+
+    /* the standard input and output of the simulator */
+    pub fn stdio() -> Stream {
+        Stream::new(io::stdin(), io::stdout())
+    }
+
+    /* waits for a connection on the Unix socket at path */
+    #[cfg(unix)]
+    pub fn unix(path: &str) -> io::Result<Stream> {
+        let listener = ::std::os::unix::net::UnixListener::bind(path)?;
+        let (stream, _) = listener.accept()?;
+        Ok(Stream::new(stream.try_clone()?, stream))
+    }
+
+    #[cfg(not(unix))]
+    pub fn unix(_path: &str) -> io::Result<Stream> {
+        Err(io::Error::new(io::ErrorKind::Other, "Unix sockets are not supported"))
+    }
+}
+
+impl Backend for Stream {
+    fn receive(&mut self) -> Option<u8> {
+        self.input.try_recv().ok()
+    }
+
+    fn transmit(&mut self, byte: u8) -> io::Result<()> {
+        self.output.write_all(&[byte])?;
+        self.output.flush()
+    }
+}
+
+impl<B: Backend + ?Sized> Backend for Box<B> {
+    fn receive(&mut self) -> Option<u8> {
+        (**self).receive()
+    }
+
+    fn transmit(&mut self, byte: u8) -> io::Result<()> {
+        (**self).transmit(byte)
+    }
+}
+
+pub struct Uart<B: Backend> {
+    pub backend : B,
+    rx          : VecDeque<u8>,
+    fifo        : bool,
+    divisor     : u16,
+    ier         : u8,
+    lcr         : u8,
+    mcr         : u8,
+    scr         : u8,
+    /* the THR empty interrupt, cleared by reading it from IIR or writing
+     * THR */
+    thre        : bool,
+}
+
+impl<B: Backend> Uart<B> {
+    pub fn new(backend: B) -> Uart<B> {
+        Uart {backend: backend, rx: VecDeque::new(), fifo: false, divisor: 0, ier: 0, lcr: 0, mcr: 0,
+              scr: 0, thre: false}
+    }
+
+    fn dlab(&self) -> bool {
+        (self.lcr & LCR_DLAB) != 0
+    }
+
+    fn loopback(&self) -> bool {
+        (self.mcr & MCR_LOOP) != 0
+    }
+
+    fn capacity(&self) -> usize {
+        if self.fifo { FIFO_SIZE } else { 1 }
+    }
+
+    /* fills the receiver from the backend, which is disconnected in
+     * loopback mode */
+    fn poll(&mut self) {
+        while !self.loopback() && self.rx.len() < self.capacity() {
+            match self.backend.receive() {
+                Some(byte) => self.rx.push_back(byte),
+                None => break,
+            }
+        }
+    }
+
+    fn transmit(&mut self, byte: u8) {
+        if self.loopback() {
+            if self.rx.len() < self.capacity() {
+                self.rx.push_back(byte);
+            }
+        } else {
+            /* the program cannot do anything about a failing host */
+            let _ = self.backend.transmit(byte);
+        }
+        self.thre = true;
+    }
+
+    /* the pending interrupt with the highest priority */
+    fn iir(&self) -> u8 {
+        if (self.ier & ier::RX) != 0 && !self.rx.is_empty() {
+            iir::RX
+        } else if (self.ier & ier::THRE) != 0 && self.thre {
+            iir::THRE
+        } else {
+            iir::NONE
+        }
+    }
+
+    fn msr(&self) -> u8 {
+        if self.loopback() {
+            /* RTS to CTS, DTR to DSR, OUT1 to RI and OUT2 to DCD */
+            (self.mcr & 0x0F) << 4
+        } else {
+            MSR_CONNECTED
+        }
+    }
+}
+
+impl<B: Backend> Memory for Uart<B> {
+    fn load8(&mut self, addr: u64) -> Result<u8, AccessFault> {
+        Ok(match addr {
+            RBR if self.dlab() => self.divisor as u8,
+            RBR => {
+                self.poll();
+                self.rx.pop_front().unwrap_or(0)
+            },
+            IER if self.dlab() => (self.divisor >> 8) as u8,
+            IER => self.ier,
+            IIR => {
+                self.poll();
+                let iir = self.iir();
+                if iir == iir::THRE {
+                    self.thre = false;
+                }
+                if self.fifo { iir | iir::FIFO } else { iir }
+            },
+            LCR => self.lcr,
+            MCR => self.mcr,
+            LSR => {
+                self.poll();
+                let ready = if self.rx.is_empty() { 0 } else { lsr::DR };
+                ready | lsr::THRE | lsr::TEMT
+            },
+            MSR => self.msr(),
+            SCR => self.scr,
+            _ => return Err(AccessFault),
+        })
+    }
+
+    fn store8(&mut self, addr: u64, value: u8) -> Result<(), AccessFault> {
+        match addr {
+            THR if self.dlab() => self.divisor = (self.divisor & 0xFF00) | value as u16,
+            THR => self.transmit(value),
+            IER if self.dlab() => self.divisor = (self.divisor & 0x00FF) | ((value as u16) << 8),
+            IER => {
+                /* enabling the interrupt of the empty transmitter raises it */
+                if (value & !self.ier & ier::THRE) != 0 {
+                    self.thre = true;
+                }
+                self.ier = value & ier::MASK;
+            },
+            FCR => {
+                let fifo = (value & fcr::ENABLE) != 0;
+                if fifo != self.fifo || (value & fcr::CLEAR_RX) != 0 {
+                    self.rx.clear();
+                }
+                self.fifo = fifo;
+            },
+            LCR => self.lcr = value,
+            MCR => self.mcr = value & MCR_MASK,
+            /* LSR and MSR are read-only */
+            LSR | MSR => (),
+            SCR => self.scr = value,
+            _ => return Err(AccessFault),
+        }
+        Ok(())
+    }
+
+    fn writable(&mut self, addr: u64, len: u64) -> bool {
+        addr.checked_add(len).is_some_and(|end| end <= SCR + 1)
+    }
+}
+
+impl<B: Backend> Source for Uart<B> {
+    fn level(&mut self) -> bool {
+        self.poll();
+        self.iir() != iir::NONE
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{Buffer, Stream, Uart, UART_BASE, UART_IRQ, UART_SIZE};
+    use arch::assembler::assemble_into;
+    use arch::csr::misa;
+    use arch::machine::{Machine, Schedule};
+    use arch::memory::{AccessFault, Memory};
+    use arch::plic::Source;
+
+    #[test]
+    fn test_registers() {
+        let mut uart = Uart::new(Buffer::default());
+        uart.backend.input.extend(b"ab");
+        assert_eq!(uart.load8(5), Ok(0x61));
+        assert_eq!(uart.load8(0), Ok(b'a'));
+        assert_eq!(uart.load8(0), Ok(b'b'));
+        assert_eq!(uart.load8(5), Ok(0x60));
+        uart.store8(0, b'x').unwrap();
+        assert_eq!(uart.backend.output, b"x");
+
+        /* the divisor latch hides THR and IER */
+        uart.store8(3, 0x83).unwrap();
+        uart.store8(0, 0x01).unwrap();
+        uart.store8(1, 0x02).unwrap();
+        assert_eq!(uart.load8(0), Ok(0x01));
+        assert_eq!(uart.load8(1), Ok(0x02));
+        uart.store8(3, 0x03).unwrap();
+        assert_eq!(uart.load8(1), Ok(0));
+        assert_eq!(uart.backend.output, b"x");
+
+        /* in loopback mode the bytes come back and the modem lines follow
+         * MCR */
+        uart.store8(2, 0x07).unwrap();
+        uart.store8(4, 0x1A).unwrap();
+        assert_eq!(uart.load8(6), Ok(0xA0));
+        uart.store8(0, b'y').unwrap();
+        assert_eq!(uart.load8(0), Ok(b'y'));
+        assert_eq!(uart.backend.output, b"x");
+        assert_eq!(uart.load8(7), Ok(0));
+        assert_eq!(uart.load8(8), Err(AccessFault));
+    }
+
+    #[test]
+    fn test_interrupts() {
+        let mut uart = Uart::new(Buffer::default());
+        assert!(!uart.level());
+        assert_eq!(uart.load8(2), Ok(0x01));
+
+        /* enabling the THR empty interrupt raises it, reading IIR clears it */
+        uart.store8(2, 0x01).unwrap();
+        uart.store8(1, 0x02).unwrap();
+        assert!(uart.level());
+        assert_eq!(uart.load8(2), Ok(0xC2));
+        assert!(!uart.level());
+        uart.store8(0, b'z').unwrap();
+        assert!(uart.level());
+
+        /* received data takes precedence */
+        uart.store8(1, 0x03).unwrap();
+        uart.backend.input.push_back(b'q');
+        assert_eq!(uart.load8(2), Ok(0xC4));
+        assert_eq!(uart.load8(0), Ok(b'q'));
+        assert_eq!(uart.load8(2), Ok(0xC2));
+        assert_eq!(uart.load8(2), Ok(0xC1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stream() {
+        use std::io::{Read, Write};
+        use std::os::unix::net::UnixStream;
+        use std::thread;
+        use std::time::Duration;
+
+        let (a, mut b) = UnixStream::pair().unwrap();
+        let mut uart = Uart::new(Stream::new(a.try_clone().unwrap(), a));
+        assert_eq!(uart.load8(5), Ok(0x60));
+        b.write_all(b"ok").unwrap();
+
+        /* the reader thread delivers the bytes in the background */
+        let mut received = Vec::new();
+        for _ in 0..1000 {
+            if received.len() == 2 {
+                break;
+            }
+            if uart.load8(5) == Ok(0x61) {
+                received.push(uart.load8(0).unwrap());
+            } else {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        assert_eq!(received, b"ok");
+
+        uart.store8(0, b'!').unwrap();
+        let mut byte = [0];
+        b.read_exact(&mut byte).unwrap();
+        assert_eq!(&byte, b"!");
+    }
+
+    /* the program echoes a byte from its external interrupt handler */
+    #[test]
+    fn test_machine() {
+        let source = "
+            la t0, handler
+            csrw mtvec, t0
+            li t0, 0xC000028
+            li t1, 1
+            sw t1, 0(t0)
+            li t0, 0xC002000
+            li t1, 0x400
+            sw t1, 0(t0)
+            li t0, 0x10000000
+            li t1, 1
+            sb t1, 1(t0)
+            li t1, 0x800
+            csrw mie, t1
+            csrsi mstatus, 8
+        wait:
+            j wait
+        handler:
+            li t0, 0x10000000
+            lbu a0, 0(t0)
+            sb a0, 0(t0)
+            li t0, 0xC200004
+            lw t1, 0(t0)
+            sw t1, 0(t0)
+            mret
+        ";
+        let isa = misa::parse("rv64imac").unwrap();
+        let mut machine = Machine::new(1, 0x8000_0000, isa, Schedule::RoundRobin);
+        machine.bus.map_ram(0x8000_0000, 0x1_0000).unwrap();
+        assemble_into(&mut machine.bus, 0x8000_0000, source, true);
+        let uart = Rc::new(RefCell::new(Uart::new(Buffer::default())));
+        machine.bus.map(UART_BASE, UART_SIZE, Box::new(uart.clone())).unwrap();
+        machine.connect(UART_IRQ, uart.clone());
+
+        for _ in 0..40 {
+            assert_eq!(machine.step().1, Ok(()));
+        }
+        uart.borrow_mut().backend.input.extend(b"hi");
+        for _ in 0..60 {
+            assert_eq!(machine.step().1, Ok(()));
+        }
+        assert_eq!(uart.borrow().backend.output, b"hi");
+        assert_eq!(machine.harts[0].reg[10], b'i' as u64);
+    }
+}